@@ -19,7 +19,7 @@ CATEGORIES="control constant operator math list dict string io file system meta
 # Return the category a keyword belongs to, or "other" if it is unknown.
 category_of() {
   case "$1" in
-  IF | ELSE | REPEAT | UNTIL | TIMES | FOR | EACH | IN | RETURN | PROCEDURE | CLASS | IMPORT | TRY | CATCH)
+  IF | ELSE | MATCH | CASE | REPEAT | UNTIL | WHILE | TIMES | BREAK | CONTINUE | FOR | EACH | IN | FROM | TO | STEP | RETURN | PROCEDURE | CLASS | IMPORT | AS | EXPORT | PRIVATE | TRY | CATCH | FINALLY | THROW)
     printf 'control'
     ;;
   TRUE | FALSE | NULL | NAN)
@@ -0,0 +1,140 @@
+//! `fpli check`: find the syntax errors in a program without running it.
+//!
+//! Every file named is lexed and parsed with [`parser::parse_all`], which reports
+//! every error in a file rather than the first, and every file those IMPORT is
+//! found the way a run would find it and checked in turn. Nothing is evaluated,
+//! so checking a program that deletes files, reads INPUT or loops forever is as
+//! safe as checking one that prints a greeting -- which is the point, for an
+//! editor that checks on every save or a pre-commit hook.
+
+use crate::error::{PSLError, Span};
+use crate::interpreter::find_import;
+use crate::lexer::Lexer;
+use crate::parser;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// What checking a program found.
+#[derive(Default)]
+pub struct CheckReport {
+    /// Every file read, the named ones first and then what they import, each once.
+    pub files: Vec<PathBuf>,
+    /// Every error, each carrying the text and the name of its file so that
+    /// `error.format("")` renders it in place.
+    pub errors: Vec<PSLError>,
+}
+
+/// Check `paths` and everything they import.
+///
+/// A file imported by several others, or by itself through a cycle, is checked
+/// once: a run imports each file once too, so an import cycle is no error here.
+pub fn check_files(paths: &[PathBuf], debug: bool) -> CheckReport {
+    let mut report = CheckReport::default();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    // Files still to read: where the file is, the name to report it by, and the
+    // IMPORT that led to it, to blame when it cannot be read.
+    let mut pending: Vec<(PathBuf, String, Option<PSLError>)> = paths
+        .iter()
+        .rev()
+        .map(|path| (path.clone(), path.display().to_string(), None))
+        .collect();
+
+    while let Some((path, name, imported_by)) = pending.pop() {
+        let key = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if !seen.insert(key) {
+            continue;
+        }
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                let message = format!("Could not read {}: {}", name, e);
+                report.errors.push(match imported_by {
+                    Some(import) => PSLError { message, ..import },
+                    None => PSLError::new(message),
+                });
+                continue;
+            }
+        };
+        report.files.push(path.clone());
+
+        let parsed = parser::parse_all(Lexer::new(&source).tokenize(), debug);
+        let source: Rc<str> = Rc::from(source.as_str());
+        let in_file = |mut error: PSLError| {
+            error.span = error.span.map(|span| at_end_if_unplaced(span, &source));
+            error.source = Some(Rc::clone(&source));
+            error.origin = Some(name.clone());
+            error
+        };
+        report.errors.extend(parsed.errors.into_iter().map(in_file));
+
+        // Pushed in reverse so that imports are checked in the order they appear.
+        for (import, span) in parsed.imports.into_iter().rev() {
+            match find_import(&import, Some(&path)) {
+                Ok(resolved) => {
+                    let shown = resolved.display().to_string();
+                    let blame = in_file(PSLError::syntax("", span));
+                    pending.push((resolved, shown, Some(blame)));
+                }
+                Err(message) => report.errors.push(in_file(PSLError::syntax(message, span))),
+            }
+        }
+    }
+
+    // Found depth first, but read best in file order and then line order.
+    report.errors.sort_by_key(|error| {
+        let file = error.origin.as_ref().and_then(|origin| {
+            report
+                .files
+                .iter()
+                .position(|f| f.display().to_string() == *origin)
+        });
+        (file, error.span.map(|span| span.start))
+    });
+    report
+}
+
+/// The parser reports running out of input with an empty span at offset 0, which
+/// would put an unclosed block's error on line 1. Move it to where the file ends.
+fn at_end_if_unplaced(span: Span, source: &str) -> Span {
+    if span.end == 0 {
+        let end = source.trim_end().len();
+        Span::new(end, end)
+    } else {
+        span
+    }
+}
+
+/// Check the files named on the command line, print what was found, and return
+/// the exit status: 0 when every file parsed, 1 otherwise.
+pub fn run(paths: &[String], debug: bool) -> i32 {
+    if let Some(bad) = paths.iter().find(|path| !path.ends_with(".psl")) {
+        eprintln!("Error: Input file must have .psl extension, got: {}", bad);
+        return 1;
+    }
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    let report = check_files(&paths, debug);
+    for error in &report.errors {
+        eprintln!("Error: {}", error.format(""));
+    }
+    let files = plural(report.files.len(), "file");
+    if report.errors.is_empty() {
+        println!("Checked {}: no errors", files);
+        0
+    } else {
+        eprintln!(
+            "Checked {}: found {}",
+            files,
+            plural(report.errors.len(), "error")
+        );
+        1
+    }
+}
+
+pub(crate) fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
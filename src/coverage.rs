@@ -0,0 +1,167 @@
+//! `fpli run --coverage` and `fpli test --coverage`: which lines of a program
+//! ran and how often, written out as an LCOV tracefile once the run is over.
+//!
+//! A line counts if a statement starts on it, the same statements a debugger
+//! can stop at. Each one that never ran shows as a line with no hits. That
+//! includes the statements of a procedure nobody called, and of a branch or a
+//! loop body never taken. Comments, blank lines and the lines a statement runs
+//! on to after its first are not counted at all.
+//!
+//! Every file with a statement that ran is reported: the program itself and
+//! each file it imported, whose top level runs when it is imported. The lines
+//! are counted against the text the run read, which is kept for each file in
+//! `ModuleState::sources`. That way a file edited while the program ran is
+//! still reported by the lines it was run with. Source with no file, from
+//! EVAL, is left out: a report has nowhere to put it.
+//!
+//! ```text
+//! TN:
+//! SF:/home/ada/maths/lib.psl
+//! DA:1,1
+//! DA:2,3
+//! DA:4,0
+//! LF:3
+//! LH:2
+//! end_of_record
+//! ```
+//!
+//! `genhtml`, and the coverage views of editors and CI services, read this as
+//! it is. Under `fpli test` one report covers every test run, so a line any of
+//! them reached counts as covered.
+
+use crate::error::{Span, resolve_span};
+use crate::interpreter::{Observer, Paused};
+use crate::lexer::Lexer;
+use crate::parser::{self, AstNode, Spanned};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// A file with a statement that ran.
+struct Covered {
+    path: PathBuf,
+    /// The text the run read, which the spans index.
+    source: String,
+    /// How many times the statement with each span started.
+    hits: HashMap<(usize, usize), u64>,
+}
+
+pub struct Coverage {
+    output: Box<dyn Write>,
+    files: Vec<Covered>,
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl Coverage {
+    /// Coverage that writes its LCOV report to `output` when the run ends.
+    pub fn new(output: Box<dyn Write>) -> Coverage {
+        Coverage {
+            output,
+            files: Vec::new(),
+            by_path: HashMap::new(),
+        }
+    }
+
+    /// The report, one record per file, in the order the files first ran.
+    pub fn lcov(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+            // A file the run read cannot fail to parse the same way again. Were it
+            // to, the lines that ran are still worth reporting.
+            if let Ok(program) =
+                parser::parse_with_source(Lexer::new(&file.source).tokenize(), &file.source, false)
+            {
+                let mut spans = Vec::new();
+                statements(&program, &mut spans);
+                for span in spans {
+                    let (line, _, _) = resolve_span(&file.source, &span);
+                    lines.entry(line).or_insert(0);
+                }
+            }
+            for (&(start, end), &hits) in &file.hits {
+                let (line, _, _) = resolve_span(&file.source, &Span { start, end });
+                *lines.entry(line).or_insert(0) += hits;
+            }
+            out.push_str("TN:\n");
+            out.push_str(&format!("SF:{}\n", file.path.display()));
+            for (line, hits) in &lines {
+                out.push_str(&format!("DA:{},{}\n", line, hits));
+            }
+            out.push_str(&format!("LF:{}\n", lines.len()));
+            let hit = lines.values().filter(|&&hits| hits > 0).count();
+            out.push_str(&format!("LH:{}\n", hit));
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+}
+
+/// The spans of every statement in `node`, at any depth: each one
+/// [`Observer::before_statement`] is told about when it runs.
+fn statements(node: &Spanned, found: &mut Vec<Span>) {
+    match &node.node {
+        AstNode::Program(items) | AstNode::Block(items) => {
+            for item in items {
+                let runs = match &item.node {
+                    AstNode::Comment => false,
+                    AstNode::Block(inner) => !inner.is_empty(),
+                    _ => true,
+                };
+                if runs {
+                    found.push(item.span);
+                }
+                statements(item, found);
+            }
+        }
+        // A class body is a list of methods, declared together rather than run one
+        // by one: only what is inside the methods runs as statements.
+        AstNode::ClassDecl(_, body) => match &body.node {
+            AstNode::Block(methods) => {
+                for method in methods {
+                    for child in method.node.children() {
+                        statements(child, found);
+                    }
+                }
+            }
+            _ => statements(body, found),
+        },
+        other => {
+            for child in other.children() {
+                statements(child, found);
+            }
+        }
+    }
+}
+
+impl Observer for Coverage {
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+        // Source with no file is not reported; either way the run goes on.
+        let path = paused.file()?;
+        let index = match self.by_path.get(path) {
+            Some(&index) => index,
+            None => {
+                self.files.push(Covered {
+                    path: path.to_path_buf(),
+                    source: paused.source().to_string(),
+                    hits: HashMap::new(),
+                });
+                self.by_path
+                    .insert(path.to_path_buf(), self.files.len() - 1);
+                self.files.len() - 1
+            }
+        };
+        let span = paused.span();
+        *self.files[index]
+            .hits
+            .entry((span.start, span.end))
+            .or_insert(0) += 1;
+        None
+    }
+
+    fn ended(&mut self) {
+        let report = self.lcov();
+        let _ = self.output.write_all(report.as_bytes());
+        let _ = self.output.flush();
+    }
+}
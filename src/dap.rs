@@ -0,0 +1,647 @@
+//! `fpli dap`: a debug adapter, so an editor can run a program a statement at a
+//! time.
+//!
+//! The adapter speaks the Debug Adapter Protocol over standard input and output,
+//! framed the way the language server's messages are. It launches one program
+//! and offers:
+//!
+//! - breakpoints by line, in the program and in any file it imports;
+//! - stepping: in, over and out of procedures, continuing, and pausing a running
+//!   program;
+//! - the call stack, each frame with its file and line, imported files included;
+//! - the variables of every scope a frame can see, with lists, dictionaries and
+//!   objects expandable, and the value of a variable named in the debug console.
+//!
+//! The program runs on the interpreter as `fpli run` would run it, with the
+//! adapter as its [`Observer`]: before every statement the interpreter hands it
+//! the run, and stopping is nothing more than not handing it back until the
+//! editor says to go on. What the program prints reaches the editor as output
+//! events, since standard output carries the protocol; for the same reason INPUT
+//! reads the lines given as `stdin` in the launch request.
+
+use crate::error::Span;
+use crate::interpreter::{self, Inspected, Observed, Observer, Paused, Resume};
+use crate::json::{Json, read_message, write_message};
+use crate::lexer::Lexer;
+use crate::parser::{self, Spanned};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// The one thread a program has, as the protocol needs one to name.
+const THREAD_ID: usize = 1;
+
+/// Serve the protocol on standard input and output until the editor disconnects,
+/// and return the exit status.
+pub fn run() -> i32 {
+    let (sender, inbox) = std::sync::mpsc::channel();
+    // Read on a thread of its own, so a running program can look for a pause
+    // request between two statements without waiting for one to arrive.
+    std::thread::spawn(move || {
+        let mut input = std::io::stdin().lock();
+        loop {
+            match read_message(&mut input) {
+                Ok(Some(body)) => {
+                    if sender.send(body).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(message) => {
+                    eprintln!("Error: {}", message);
+                    break;
+                }
+            }
+        }
+    });
+    serve(inbox, std::io::stdout())
+}
+
+/// Serve the protocol: message bodies arrive on `inbox`, which closing ends the
+/// session, and replies and events are written to `output`.
+///
+/// The status is 0 however the session ends. Whether the program it debugged
+/// failed is for the `exited` event to say, not the adapter's own status.
+pub fn serve(inbox: Receiver<String>, output: impl Write + 'static) -> i32 {
+    let adapter = Rc::new(RefCell::new(Adapter::new(inbox, Box::new(output))));
+    let launch = loop {
+        // Bound first: the adapter must not stay borrowed through the match.
+        let next = adapter.borrow_mut().wait(Phase::Idle);
+        match next {
+            Next::Start => match adapter.borrow_mut().launch.take() {
+                Some(launch) => break launch,
+                None => continue,
+            },
+            Next::Disconnect => return 0,
+            Next::Wait | Next::Resume => {}
+        }
+    };
+    let observed = interpreter::run_observed(
+        &launch.ast,
+        &launch.source,
+        &launch.args,
+        Some(launch.program),
+        Rc::clone(&adapter) as Rc<RefCell<dyn Observer>>,
+    );
+    let mut adapter = adapter.borrow_mut();
+    if adapter.disconnected {
+        return 0;
+    }
+    adapter.finish(observed, &launch.source);
+    while !matches!(adapter.wait(Phase::Idle), Next::Disconnect) {}
+    0
+}
+
+/// A program, parsed and ready to run once the editor has set its breakpoints.
+struct Launch {
+    program: PathBuf,
+    source: String,
+    ast: Spanned,
+    args: Vec<String>,
+}
+
+/// What the program is doing when a request arrives, which decides what the
+/// request can be answered with.
+enum Phase<'p, 'a> {
+    /// Not started, or already over.
+    Idle,
+    /// Between two statements, on the way to the next stop.
+    Running,
+    Paused(&'p Paused<'a>),
+}
+
+/// What handling a message leads to.
+enum Next {
+    /// Nothing: wait for the next message.
+    Wait,
+    /// The launched program can start.
+    Start,
+    /// Let the paused program go on.
+    Resume,
+    /// The editor has disconnected, or gone away.
+    Disconnect,
+}
+
+/// The statement the program last stopped on.
+struct Stop {
+    file: Option<PathBuf>,
+    span: Span,
+    line: usize,
+    depth: usize,
+}
+
+struct Adapter {
+    inbox: Receiver<String>,
+    output: Box<dyn Write>,
+    /// The `seq` of the last message sent.
+    seq: usize,
+    launch: Option<Launch>,
+    /// Whether the editor has finished configuring, which it says after setting
+    /// its breakpoints; the program starts once it has and it is launched.
+    configured: bool,
+    /// Lines with a breakpoint, by file.
+    breakpoints: HashMap<PathBuf, Vec<usize>>,
+    /// Where to stop next, besides at a breakpoint.
+    resume: Resume,
+    /// What a stop that `resume` makes is reported to the editor as.
+    reason: &'static str,
+    stopped: Option<Stop>,
+    /// Requests that came in while the program was running but need it paused,
+    /// and any that came after them. An editor asks for the stack just after it
+    /// is told of a stop, and a request can overtake that news; they are answered
+    /// at the next stop, or once the program is over.
+    deferred: VecDeque<String>,
+    /// Lines left for INPUT to read.
+    stdin: VecDeque<String>,
+    /// The variables behind each `variablesReference`, which is an index into
+    /// this plus one. Good only until the program moves, when it is emptied.
+    handles: Vec<Vec<(String, Inspected)>>,
+    disconnected: bool,
+}
+
+impl Adapter {
+    fn new(inbox: Receiver<String>, output: Box<dyn Write>) -> Adapter {
+        Adapter {
+            inbox,
+            output,
+            seq: 0,
+            launch: None,
+            configured: false,
+            breakpoints: HashMap::new(),
+            resume: Resume::Run,
+            reason: "step",
+            stopped: None,
+            deferred: VecDeque::new(),
+            stdin: VecDeque::new(),
+            handles: Vec::new(),
+            disconnected: false,
+        }
+    }
+
+    fn send(&mut self, mut message: Vec<(&str, Json)>) {
+        self.seq += 1;
+        message.insert(0, ("seq", self.seq.into()));
+        // A failed write means the editor has gone, which the inbox closing will
+        // say soon enough.
+        let _ = write_message(&mut self.output, &Json::object(message));
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(vec![
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ]);
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) {
+        let request_seq = request.get("seq").cloned().unwrap_or(Json::Null);
+        let command = request.get("command").cloned().unwrap_or(Json::Null);
+        let mut message = vec![
+            ("type", "response".into()),
+            ("request_seq", request_seq),
+            ("success", result.is_ok().into()),
+            ("command", command),
+        ];
+        match result {
+            Ok(body) => message.push(("body", body)),
+            Err(error) => message.push(("message", error.into())),
+        }
+        self.send(message);
+    }
+
+    fn print(&mut self, category: &str, text: &str) {
+        if !text.is_empty() {
+            self.event(
+                "output",
+                Json::object([("category", category.into()), ("output", text.into())]),
+            );
+        }
+    }
+
+    /// Handle the next message: the first deferred request if there is one,
+    /// otherwise the next to arrive, waiting for it.
+    fn wait(&mut self, phase: Phase) -> Next {
+        let body = self.deferred.pop_front();
+        match body.or_else(|| self.inbox.recv().ok()) {
+            Some(body) => self.handle(&body, phase),
+            None => Next::Disconnect,
+        }
+    }
+
+    fn handle(&mut self, body: &str, phase: Phase) -> Next {
+        let request = match Json::parse(body) {
+            Ok(request) => request,
+            Err(message) => {
+                self.print("stderr", &format!("Error: {}\n", message));
+                return Next::Wait;
+            }
+        };
+        if request.get("type").and_then(Json::as_str) != Some("request") {
+            return Next::Wait;
+        }
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let arguments = request.get("arguments").unwrap_or(&Json::Null);
+        let needs_pause = matches!(
+            command,
+            "stackTrace"
+                | "scopes"
+                | "variables"
+                | "evaluate"
+                | "continue"
+                | "next"
+                | "stepIn"
+                | "stepOut"
+        );
+        let paused = match phase {
+            Phase::Paused(paused) => Some(paused),
+            Phase::Running if needs_pause => {
+                self.deferred.push_back(body.to_string());
+                return Next::Wait;
+            }
+            _ if needs_pause => {
+                self.respond(&request, Err("The program is not paused".to_string()));
+                return Next::Wait;
+            }
+            _ => None,
+        };
+        let mut next = Next::Wait;
+        let result = match (command, paused) {
+            ("initialize", _) => Ok(capabilities()),
+            ("launch", _) => self.launch(arguments).map(|()| {
+                if self.configured {
+                    next = Next::Start;
+                }
+                Json::Null
+            }),
+            ("setBreakpoints", _) => Ok(self.set_breakpoints(arguments)),
+            ("setExceptionBreakpoints", _) => Ok(Json::object::<&str>([])),
+            ("configurationDone", _) => {
+                self.configured = true;
+                if self.launch.is_some() {
+                    next = Next::Start;
+                }
+                Ok(Json::Null)
+            }
+            ("threads", _) => Ok(Json::object([(
+                "threads",
+                vec![Json::object([
+                    ("id", THREAD_ID.into()),
+                    ("name", "main".into()),
+                ])]
+                .into(),
+            )])),
+            ("pause", _) => {
+                if matches!(phase, Phase::Running) {
+                    self.stop_next("pause");
+                }
+                Ok(Json::Null)
+            }
+            ("disconnect" | "terminate", _) => {
+                next = Next::Disconnect;
+                Ok(Json::Null)
+            }
+            ("stackTrace", Some(paused)) => Ok(stack_trace(paused)),
+            ("scopes", Some(paused)) => Ok(self.scopes(paused, arguments)),
+            ("variables", Some(_)) => self.variables(arguments),
+            ("evaluate", Some(paused)) => self.evaluate(paused, arguments),
+            ("continue" | "next" | "stepIn" | "stepOut", Some(paused)) => {
+                self.resume = match command {
+                    "continue" => Resume::Run,
+                    "next" => Resume::Over(paused.depth()),
+                    "stepIn" => Resume::Step,
+                    _ => Resume::Out(paused.depth()),
+                };
+                self.reason = "step";
+                next = Next::Resume;
+                Ok(if command == "continue" {
+                    Json::object([("allThreadsContinued", true.into())])
+                } else {
+                    Json::Null
+                })
+            }
+            _ => Err(format!("Unsupported request '{}'", command)),
+        };
+        self.respond(&request, result);
+        if command == "initialize" {
+            self.event("initialized", Json::Null);
+        }
+        next
+    }
+
+    /// Stop at the very next statement, reporting it as stopped for `reason`.
+    fn stop_next(&mut self, reason: &'static str) {
+        self.resume = Resume::Step;
+        self.reason = reason;
+    }
+
+    /// Read and parse the program to debug. Refusing a program that does not
+    /// parse here shows the editor the syntax error as the launch failing.
+    fn launch(&mut self, arguments: &Json) -> Result<(), String> {
+        let program = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("The launch request has no 'program' to run")?;
+        let source = std::fs::read_to_string(program)
+            .map_err(|e| format!("Error reading file {}: {}", program, e))?;
+        let tokens = Lexer::new(&source).tokenize();
+        let ast =
+            parser::parse_with_source(tokens, &source, false).map_err(|e| e.format(&source))?;
+        let strings = |key: &str| -> Vec<String> {
+            arguments
+                .get(key)
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|arg| arg.as_str().map(str::to_string))
+                .collect()
+        };
+        if arguments.get("stopOnEntry") == Some(&Json::Bool(true)) {
+            self.stop_next("entry");
+        }
+        self.stdin = match arguments.get("stdin") {
+            Some(Json::String(text)) => text.lines().map(str::to_string).collect(),
+            _ => strings("stdin").into(),
+        };
+        self.launch = Some(Launch {
+            program: PathBuf::from(program),
+            source,
+            ast,
+            args: strings("args"),
+        });
+        Ok(())
+    }
+
+    /// Replace the breakpoints of one file. Every line is accepted as asked for:
+    /// one with no statement on it is simply never reached.
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let lines: Vec<usize> = match arguments.get("breakpoints").and_then(Json::as_array) {
+            Some(breakpoints) => breakpoints
+                .iter()
+                .filter_map(|b| b.get("line").and_then(Json::as_usize))
+                .collect(),
+            None => arguments
+                .get("lines")
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Json::as_usize)
+                .collect(),
+        };
+        let verified = lines
+            .iter()
+            .map(|&line| Json::object([("verified", true.into()), ("line", line.into())]))
+            .collect::<Vec<_>>();
+        if let Some(path) = arguments.at(&["source", "path"]).and_then(Json::as_str) {
+            let file = canonical(Path::new(path));
+            if lines.is_empty() {
+                self.breakpoints.remove(&file);
+            } else {
+                self.breakpoints.insert(file, lines);
+            }
+        }
+        Json::object([("breakpoints", verified.into())])
+    }
+
+    fn scopes(&mut self, paused: &Paused, arguments: &Json) -> Json {
+        let frame = arguments
+            .get("frameId")
+            .and_then(Json::as_usize)
+            .unwrap_or(0);
+        let scopes: Vec<Json> = paused
+            .scopes(frame)
+            .into_iter()
+            .map(|scope| {
+                self.handles.push(scope.variables);
+                Json::object([
+                    ("name", scope.name.into()),
+                    ("variablesReference", self.handles.len().into()),
+                    ("expensive", false.into()),
+                ])
+            })
+            .collect();
+        Json::object([("scopes", scopes.into())])
+    }
+
+    fn variables(&mut self, arguments: &Json) -> Result<Json, String> {
+        let reference = arguments
+            .get("variablesReference")
+            .and_then(Json::as_usize)
+            .unwrap_or(0);
+        let variables = reference
+            .checked_sub(1)
+            .and_then(|i| self.handles.get(i))
+            .cloned()
+            .ok_or("Those variables are no longer available")?;
+        let variables: Vec<Json> = variables
+            .into_iter()
+            .map(|(name, value)| {
+                let mut variable = self.describe(&value);
+                if let Json::Object(entries) = &mut variable {
+                    entries.insert(0, ("name".to_string(), name.into()));
+                }
+                variable
+            })
+            .collect();
+        Ok(Json::object([("variables", variables.into())]))
+    }
+
+    /// Look up the variable an expression names. Only a name: the debug console
+    /// and hovering in the editor both ask this way, and evaluating arbitrary
+    /// source could change the program being looked at.
+    fn evaluate(&mut self, paused: &Paused, arguments: &Json) -> Result<Json, String> {
+        let expression = arguments
+            .get("expression")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .trim();
+        let frame = arguments
+            .get("frameId")
+            .and_then(Json::as_usize)
+            .unwrap_or(0);
+        let value = paused
+            .lookup(frame, expression)
+            .ok_or_else(|| format!("Undefined variable: {}", expression))?;
+        let mut described = self.describe(&value);
+        if let Json::Object(entries) = &mut described {
+            entries[0].0 = "result".to_string();
+        }
+        Ok(described)
+    }
+
+    /// `value`, its type, and a reference to what it holds if it holds anything.
+    fn describe(&mut self, value: &Inspected) -> Json {
+        let children = value.children();
+        let reference = if children.is_empty() {
+            0
+        } else {
+            self.handles.push(children);
+            self.handles.len()
+        };
+        Json::object([
+            ("value", value.render().into()),
+            ("type", value.type_name().into()),
+            ("variablesReference", reference.into()),
+        ])
+    }
+
+    /// Why to stop before the statement `paused` is on, or `None` to run it.
+    fn stop_reason(&self, paused: &Paused) -> Option<&'static str> {
+        let file = paused.file();
+        let line = || paused.line();
+        // A statement inside the one last stopped on, and on the same line --
+        // the body of `IF (x) { DISPLAY(x) }` -- is part of that stop.
+        if let Some(stop) = &self.stopped {
+            let span = paused.span();
+            if stop.depth == paused.depth()
+                && stop.file.as_deref() == file
+                && span != stop.span
+                && stop.span.start <= span.start
+                && span.end <= stop.span.end
+                && stop.line == line()
+            {
+                return None;
+            }
+        }
+        let stepped = paused.stops_for(self.resume).then_some(self.reason);
+        stepped.or_else(|| {
+            file.and_then(|file| self.breakpoints.get(file))
+                .is_some_and(|lines| lines.contains(&line()))
+                .then_some("breakpoint")
+        })
+    }
+
+    /// Report how the run ended.
+    fn finish(&mut self, observed: Observed, source: &str) {
+        self.print("stdout", &observed.output);
+        let status = match observed.status {
+            Ok(status) => status,
+            Err(error) => {
+                self.print("stderr", &format!("{}\n", error.format(source)));
+                1
+            }
+        };
+        self.event(
+            "exited",
+            Json::object([("exitCode", i64::from(status).into())]),
+        );
+        self.event("terminated", Json::Null);
+    }
+}
+
+impl Observer for Adapter {
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+        self.print("stdout", &paused.take_output());
+        loop {
+            match self.inbox.try_recv() {
+                // Requests are answered in the order they came, so once one is
+                // waiting for a stop, everything after it waits too.
+                Ok(body) if !self.deferred.is_empty() => self.deferred.push_back(body),
+                Ok(body) => {
+                    if let Next::Disconnect = self.handle(&body, Phase::Running) {
+                        self.disconnected = true;
+                        return Some(0);
+                    }
+                }
+                // With the input closed there is nothing more to hear until the
+                // next stop, where waiting for a request finds the editor gone. A
+                // session piped in whole, last request and all, still gets the
+                // run it asked for.
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            }
+        }
+        let reason = self.stop_reason(paused)?;
+        self.resume = Resume::Run;
+        self.stopped = Some(Stop {
+            file: paused.file().map(Path::to_path_buf),
+            span: paused.span(),
+            line: paused.line(),
+            depth: paused.depth(),
+        });
+        self.event(
+            "stopped",
+            Json::object([
+                ("reason", reason.into()),
+                ("threadId", THREAD_ID.into()),
+                ("allThreadsStopped", true.into()),
+            ]),
+        );
+        loop {
+            match self.wait(Phase::Paused(paused)) {
+                Next::Resume => {
+                    self.handles.clear();
+                    return None;
+                }
+                Next::Disconnect => {
+                    self.disconnected = true;
+                    return Some(0);
+                }
+                Next::Wait | Next::Start => {}
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Option<Result<String, String>> {
+        Some(self.stdin.pop_front().ok_or_else(|| {
+            "INPUT has nothing to read: give the program's input as 'stdin' in the launch configuration"
+                .to_string()
+        }))
+    }
+
+    fn breakpoint(&mut self) {
+        self.stop_next("breakpoint");
+    }
+}
+
+/// What the adapter can do, as the `initialize` response tells the editor.
+fn capabilities() -> Json {
+    Json::object([
+        ("supportsConfigurationDoneRequest", true.into()),
+        ("supportsEvaluateForHovers", true.into()),
+        ("supportsTerminateRequest", true.into()),
+    ])
+}
+
+fn stack_trace(paused: &Paused) -> Json {
+    let frames: Vec<Json> = paused
+        .frames()
+        .into_iter()
+        .enumerate()
+        .map(|(id, frame)| {
+            let mut entries = vec![
+                ("id", id.into()),
+                ("name", frame.name.into()),
+                ("line", frame.line.into()),
+                ("column", frame.column.into()),
+            ];
+            if let Some(file) = frame.file {
+                let name = file
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                entries.push((
+                    "source",
+                    Json::object([
+                        ("name", name.into()),
+                        ("path", file.display().to_string().into()),
+                    ]),
+                ));
+            }
+            Json::object(entries)
+        })
+        .collect();
+    let total = frames.len();
+    Json::object([
+        ("stackFrames", frames.into()),
+        ("totalFrames", total.into()),
+    ])
+}
+
+/// `path` as the interpreter names the file it runs: resolved, so that a
+/// breakpoint set through a symlink or a relative path still matches.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,219 @@
+//! The console debugger: what a program stops in at a `BREAKPOINT()`, or from
+//! its first statement under `fpli run --step`.
+//!
+//! It is the same [`Observer`] hook `fpli dap` drives a program through, with a
+//! terminal at the other end instead of an editor. At a stop it shows the line
+//! about to run and reads commands until told to go on:
+//!
+//! ```text
+//! Stopped at main.psl:3, in double
+//!    3 |     result <- n * 2
+//! (fpli) p n + 1
+//! 6
+//! (fpli) c
+//! ```
+//!
+//! Everything it shows goes to standard error, so standard output still holds
+//! only what the program printed. Commands come from standard input, one line at
+//! a time, the same stream INPUT reads: the program and the debugger take turns
+//! with it, each reading only when it is its turn.
+
+use crate::error::resolve_span;
+use crate::interpreter::{Observer, Paused, Resume};
+use std::io::{self, Write};
+
+const HELP: &str = "Commands:
+  c, continue      Run until the next BREAKPOINT()
+  s, step          Run one statement, stepping into a procedure it calls
+  n, next          Run to the next statement here, stepping over calls
+  o, out           Run until the current procedure returns
+  p, print EXPR    Show the value of an expression, evaluated here
+  v, vars          Show the variables of every scope in reach
+  w, where         Show the call stack
+  q, quit          End the program
+  h, help          Show this list
+An empty line repeats the last command.";
+
+/// Reads one line of commands into the buffer, returning how many bytes it read:
+/// 0 at the end of the input.
+type ReadLine = Box<dyn FnMut(&mut String) -> io::Result<usize>>;
+
+pub struct Console {
+    read_line: ReadLine,
+    /// Where the program's own output goes, when the run captures it rather
+    /// than printing it itself.
+    program: Box<dyn Write>,
+    output: Box<dyn Write>,
+    /// Where to stop next, besides at a BREAKPOINT().
+    resume: Resume,
+    /// The last command, which an empty line repeats.
+    last: String,
+    /// Set once the commands have run out: with nobody left to answer, the
+    /// program runs on to its end.
+    detached: bool,
+}
+
+impl Console {
+    /// A debugger on standard input and standard error that stops at the first
+    /// statement it sees.
+    pub fn new() -> Console {
+        // A line at a time, through the process-wide handle, and never holding
+        // its lock: INPUT reads the same stream between two stops.
+        Console::with_io(
+            Box::new(|line| io::stdin().read_line(line)),
+            Box::new(io::stdout()),
+            Box::new(io::stderr()),
+        )
+    }
+
+    /// A debugger reading commands with `read_line`, passing on what a capturing
+    /// run printed to `program`, and showing what it has to show on `output`.
+    pub fn with_io(
+        read_line: ReadLine,
+        program: Box<dyn Write>,
+        output: Box<dyn Write>,
+    ) -> Console {
+        Console {
+            read_line,
+            program,
+            output,
+            resume: Resume::Step,
+            last: String::new(),
+            detached: false,
+        }
+    }
+
+    fn pass_on_output(&mut self, paused: &Paused) {
+        let printed = paused.take_output();
+        let _ = self.program.write_all(printed.as_bytes());
+        let _ = self.program.flush();
+    }
+
+    /// Show the statement about to run, the way an error shows where it happened.
+    fn show_location(&mut self, paused: &Paused) {
+        let (line, _, text) = resolve_span(paused.source(), &paused.span());
+        let file = paused.file().and_then(|file| file.file_name()).map_or_else(
+            || "generated source".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let name = paused
+            .frames()
+            .first()
+            .map(|frame| frame.name.clone())
+            .unwrap_or_default();
+        let _ = writeln!(self.output, "Stopped at {}:{}, in {}", file, line, name);
+        let _ = writeln!(self.output, "{:>4} | {}", line, text);
+    }
+
+    /// Carry out one command. `Some` lets the program go on, ending it with the
+    /// status inside if there is one.
+    fn command(&mut self, paused: &Paused, line: &str) -> Option<Option<i32>> {
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match command {
+            "c" | "continue" => self.resume = Resume::Run,
+            "s" | "step" => self.resume = Resume::Step,
+            "n" | "next" => self.resume = Resume::Over(paused.depth()),
+            "o" | "out" => self.resume = Resume::Out(paused.depth()),
+            "q" | "quit" => return Some(Some(1)),
+            "p" | "print" if rest.is_empty() => {
+                let _ = writeln!(self.output, "Usage: print EXPRESSION");
+                return None;
+            }
+            "p" | "print" => {
+                let shown = match paused.evaluate(0, rest) {
+                    Ok(value) => value.render(),
+                    Err(message) => format!("Error: {}", message),
+                };
+                // Anything the expression printed comes before its value.
+                self.pass_on_output(paused);
+                let _ = writeln!(self.output, "{}", shown);
+                return None;
+            }
+            "v" | "vars" => {
+                for scope in paused.scopes(0) {
+                    let _ = writeln!(self.output, "{}:", scope.name);
+                    for (name, value) in scope.variables {
+                        let _ = writeln!(self.output, "  {} = {}", name, value.render());
+                    }
+                }
+                return None;
+            }
+            "w" | "where" => {
+                for (i, frame) in paused.frames().iter().enumerate() {
+                    let file = frame
+                        .file
+                        .as_ref()
+                        .and_then(|file| file.file_name())
+                        .map_or_else(
+                            || "generated source".to_string(),
+                            |name| name.to_string_lossy().into_owned(),
+                        );
+                    let _ = writeln!(
+                        self.output,
+                        "  #{} {} at {}:{}",
+                        i, frame.name, file, frame.line
+                    );
+                }
+                return None;
+            }
+            "h" | "help" => {
+                let _ = writeln!(self.output, "{}", HELP);
+                return None;
+            }
+            _ => {
+                let _ = writeln!(
+                    self.output,
+                    "Unknown command '{}'. Type 'help' for the list.",
+                    command
+                );
+                return None;
+            }
+        }
+        Some(None)
+    }
+}
+
+impl Default for Console {
+    fn default() -> Console {
+        Console::new()
+    }
+}
+
+impl Observer for Console {
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+        if self.detached || !paused.stops_for(self.resume) {
+            return None;
+        }
+        // What the program printed so far goes out first, so the terminal shows
+        // it above the stop.
+        self.pass_on_output(paused);
+        self.show_location(paused);
+        loop {
+            let _ = write!(self.output, "(fpli) ");
+            let _ = self.output.flush();
+            let mut line = String::new();
+            if !matches!((self.read_line)(&mut line), Ok(n) if n > 0) {
+                let _ = writeln!(self.output);
+                self.detached = true;
+                return None;
+            }
+            let mut line = line.trim().to_string();
+            if line.is_empty() {
+                line = self.last.clone();
+            } else {
+                self.last = line.clone();
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(status) = self.command(paused, &line) {
+                return status;
+            }
+        }
+    }
+
+    fn breakpoint(&mut self) {
+        self.resume = Resume::Step;
+    }
+}
@@ -43,6 +43,42 @@ pub struct PSLError {
     /// The file `source` came from, named in the rendered output so the reader knows
     /// which file to open.
     pub origin: Option<String>,
+    /// Whether the program failed to parse or failed while running, as a CATCH
+    /// handler reads it from `err["kind"]`.
+    pub kind: ErrorKind,
+}
+
+/// What sort of failure a [`PSLError`] is.
+///
+/// A closed set on purpose, and deliberately small: it sits in every error the
+/// interpreter unwinds, and the evaluator's stack frames are sized by it. A THROW
+/// names its own kinds, and those travel with the thrown value instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ErrorKind {
+    /// Code that did not parse: a bad program, or a bad string given to EVAL or
+    /// EXECUTE.
+    Syntax,
+    /// An error the interpreter raised while running the program.
+    #[default]
+    Runtime,
+    /// A failed ASSERT, ASSERTEQUAL or ASSERTRAISES. Kept apart from `Runtime` so
+    /// that `fpli test` can tell a test that checked something and found it wrong
+    /// from one that broke before it got that far.
+    Assertion,
+    /// A run stopped for going over one of its limits: too many statements, too
+    /// long, too much memory, too deep. Never caught by TRY; see `limits`.
+    Limit,
+}
+
+impl ErrorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::Assertion => "assertion",
+            ErrorKind::Limit => "limit",
+        }
+    }
 }
 
 pub fn resolve_span(source: &str, span: &Span) -> (usize, usize, String) {
@@ -80,6 +116,7 @@ impl PSLError {
             stack_trace: Vec::new(),
             source: None,
             origin: None,
+            kind: ErrorKind::Runtime,
         }
     }
 
@@ -90,6 +127,15 @@ impl PSLError {
             stack_trace: Vec::new(),
             source: None,
             origin: None,
+            kind: ErrorKind::Runtime,
+        }
+    }
+
+    /// An error in the program text itself, raised by the parser.
+    pub fn syntax(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            kind: ErrorKind::Syntax,
+            ..Self::with_span(message, span)
         }
     }
 
@@ -0,0 +1,650 @@
+//! `fpli fmt`: rewrite a program in one canonical layout.
+//!
+//! The layout is the one the language guide is written in: four-space indents,
+//! every `{` and `}` on a line of its own, `ELSE`, `CATCH` and `FINALLY` on the
+//! line after the `}` before them, one space around operators and after commas,
+//! and no parentheses the grammar does not need -- `RETURN (x)` becomes
+//! `RETURN x` and `IF (a)` becomes `IF a`.
+//!
+//! The formatter prints the parsed program, not the tokens, so every file that
+//! means the same thing comes out the same. What the AST does not hold is taken
+//! from the source: literals are copied as written, so a string keeps its escapes
+//! and a float its digits, and comments come from
+//! [`Lexer::tokenize_with_comments`]. A comment on a line of its own stays on a
+//! line of its own, before the statement it preceded; a comment after a
+//! statement stays after it. A comment somewhere no statement boundary is -- in
+//! the middle of a long list, say -- moves to just before the statement holding
+//! it. Single blank lines between statements are kept, and longer runs of them
+//! are shortened to one.
+//!
+//! Formatting is idempotent: formatting formatted output changes nothing.
+
+use crate::error::{PSLError, Span};
+use crate::lexer::{Comment, Lexer};
+use crate::parser::{self, AstNode, BinaryOperator, Parameter, Pattern, Spanned, UnaryOperator};
+
+const INDENT: &str = "    ";
+
+/// A list or dictionary literal longer than this, counting its indentation, is
+/// written one element per line.
+const MAX_WIDTH: usize = 100;
+
+/// Format a whole file. A file that does not parse is returned as its error: the
+/// formatter only rewrites programs it understands.
+pub fn format_source(source: &str) -> Result<String, PSLError> {
+    let (tokens, comments) = Lexer::new(source).tokenize_with_comments();
+    let program = parser::parse(tokens, false)?;
+    Ok(Printer::new(source, comments).program(&program))
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    /// The byte offset of every character of `source`, and of its end: spans
+    /// count characters, and slicing needs bytes.
+    offsets: Vec<usize>,
+    comments: Vec<Comment>,
+    /// Which comments have been written out, each exactly once.
+    used: Vec<bool>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, comments: Vec<Comment>) -> Self {
+        let offsets = source
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(source.len()))
+            .collect();
+        let used = vec![false; comments.len()];
+        Printer {
+            source,
+            offsets,
+            comments,
+            used,
+        }
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        let last = self.offsets.len() - 1;
+        let start = self.offsets[span.start.min(last)];
+        let end = self.offsets[span.end.min(last)];
+        &self.source[start..end.max(start)]
+    }
+
+    fn program(mut self, program: &Spanned) -> String {
+        let statements = match &program.node {
+            AstNode::Program(statements) => statements.as_slice(),
+            _ => std::slice::from_ref(program),
+        };
+        let lines = self.statements(statements, 0, usize::MAX, 0);
+        if lines.is_empty() {
+            String::new()
+        } else {
+            lines.join("\n") + "\n"
+        }
+    }
+
+    /// Take every comment not yet written that starts in `from..to`.
+    fn take_comments(&mut self, from: usize, to: usize) -> Vec<Comment> {
+        let mut taken = Vec::new();
+        for (comment, used) in self.comments.iter().zip(self.used.iter_mut()) {
+            if !*used && (from..to).contains(&comment.span.start) {
+                *used = true;
+                taken.push(comment.clone());
+            }
+        }
+        taken
+    }
+
+    /// The comment that ends the line a statement ending at `end` finishes on,
+    /// if there is one.
+    fn trailing_comment(&mut self, end: usize, limit: usize) -> Option<Comment> {
+        let index = (0..self.comments.len())
+            .find(|&i| !self.used[i] && (end..limit).contains(&self.comments[i].span.start))?;
+        let comment = &self.comments[index];
+        let between = self.text(Span::new(end, comment.span.start));
+        if comment.block || between.contains('\n') {
+            return None;
+        }
+        self.used[index] = true;
+        Some(comment.clone())
+    }
+
+    /// Where the last token of `span` ends. A statement that looks past its end
+    /// for an ELSE or a CATCH has a span running over the line breaks, and the
+    /// comments between them, that it skipped on the way.
+    fn last_token_end(&self, span: Span) -> usize {
+        let mut end = span.end;
+        loop {
+            let text = self.text(Span::new(span.start, end));
+            end = span.start + text.trim_end().chars().count();
+            match self
+                .comments
+                .iter()
+                .find(|c| c.span.end == end && c.span.start >= span.start)
+            {
+                Some(comment) => end = comment.span.start,
+                None => return end,
+            }
+        }
+    }
+
+    /// Whether the source has a blank line between `from` and `to`.
+    fn blank_between(&self, from: usize, to: usize) -> bool {
+        from < to && self.text(Span::new(from, to)).matches('\n').count() >= 2
+    }
+
+    /// The lines of a run of statements at `depth`, with the comments that fall
+    /// in `from..to` placed among them.
+    fn statements(
+        &mut self,
+        statements: &[Spanned],
+        from: usize,
+        to: usize,
+        depth: usize,
+    ) -> Vec<String> {
+        let indent = INDENT.repeat(depth);
+        let mut lines = Lines::default();
+        for statement in statements {
+            if matches!(&statement.node, AstNode::Block(b) if b.is_empty())
+                || matches!(statement.node, AstNode::Comment)
+            {
+                continue;
+            }
+            for comment in self.take_comments(from, statement.span.start) {
+                let text = format!("{indent}{}", comment.text);
+                lines.push(self, comment.span.start, comment.span.end, text);
+            }
+            let mut text = format!("{indent}{}", self.statement(statement, depth));
+            let mut end = self.last_token_end(statement.span);
+            // Comments inside the statement that no block of it took go first.
+            for comment in self.take_comments(statement.span.start, end) {
+                let start = statement.span.start;
+                lines.push(self, start, start, format!("{indent}{}", comment.text));
+            }
+            if let Some(comment) = self.trailing_comment(end, to) {
+                text.push_str("  ");
+                text.push_str(&comment.text);
+                end = comment.span.end;
+            }
+            lines.push(self, statement.span.start, end, text);
+        }
+        for comment in self.take_comments(from, to) {
+            let text = format!("{indent}{}", comment.text);
+            lines.push(self, comment.span.start, comment.span.end, text);
+        }
+        lines.lines
+    }
+
+    /// A `{ ... }` body, starting with the line break before its `{`.
+    fn block(&mut self, block: &Spanned, depth: usize) -> String {
+        let indent = INDENT.repeat(depth);
+        let lines = match &block.node {
+            AstNode::Block(statements) => {
+                self.statements(statements, block.span.start, block.span.end, depth + 1)
+            }
+            _ => vec![format!(
+                "{}{}",
+                INDENT.repeat(depth + 1),
+                self.statement(block, depth + 1)
+            )],
+        };
+        if lines.is_empty() {
+            format!("\n{indent}{{\n{indent}}}")
+        } else {
+            format!("\n{indent}{{\n{}\n{indent}}}", lines.join("\n"))
+        }
+    }
+
+    fn statement(&mut self, statement: &Spanned, depth: usize) -> String {
+        match &statement.node {
+            // A statement that starts with a name is an assignment or a call, so an
+            // operation in statement position only parses inside parentheses.
+            AstNode::BinaryOp(..) => format!("({})", self.expr(statement, depth)),
+            _ => self.expr(statement, depth),
+        }
+    }
+
+    /// What follows IF, REPEAT UNTIL, REPEAT WHILE or RETURN. Those read a leading
+    /// `(` as the start of parentheses around all of it, so a value that only
+    /// starts with a parenthesised part is wrapped whole.
+    fn condition(&mut self, condition: &Spanned, depth: usize) -> String {
+        let text = self.expr(condition, depth);
+        if text.starts_with('(') {
+            format!("({text})")
+        } else {
+            text
+        }
+    }
+
+    fn expr(&mut self, node: &Spanned, depth: usize) -> String {
+        let indent = INDENT.repeat(depth);
+        match &node.node {
+            AstNode::Integer(n) => n.to_string(),
+            AstNode::Float(_) => self.text(node.span).split_whitespace().collect(),
+            AstNode::String(_) | AstNode::RawString(_) | AstNode::FormattedString(..) => {
+                self.text(node.span).to_string()
+            }
+            AstNode::Boolean(true) => "TRUE".to_string(),
+            AstNode::Boolean(false) => "FALSE".to_string(),
+            AstNode::Null => "NULL".to_string(),
+            AstNode::NaN => "NAN".to_string(),
+            AstNode::Identifier(name) => name.clone(),
+            AstNode::List(items) => {
+                let items: Vec<String> = items.iter().map(|i| self.expr(i, depth + 1)).collect();
+                sequence("[", "]", items, depth)
+            }
+            AstNode::Dictionary(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        let key = self.expr(k, depth + 1);
+                        format!("{key}: {}", self.expr(v, depth + 1))
+                    })
+                    .collect();
+                sequence("{", "}", entries, depth)
+            }
+
+            AstNode::Assignment(target, value) => {
+                let target = match &target.node {
+                    AstNode::List(names) if names.len() > 1 => {
+                        let names: Vec<String> =
+                            names.iter().map(|n| self.expr(n, depth)).collect();
+                        names.join(", ")
+                    }
+                    _ => self.expr(target, depth),
+                };
+                format!("{target} <- {}", self.expr(value, depth))
+            }
+            AstNode::ListAccess(base, index) => {
+                let base = self.postfix_base(base, depth);
+                format!("{base}[{}]", self.expr(index, depth))
+            }
+            AstNode::ListAssignment(base, index, value) => {
+                let base = self.postfix_base(base, depth);
+                let index = self.expr(index, depth);
+                format!("{base}[{index}] <- {}", self.expr(value, depth))
+            }
+            AstNode::FieldAccess(object, field) => {
+                format!("{}.{field}", self.dotted_base(object, depth))
+            }
+            AstNode::FieldAssignment(object, field, value) => {
+                let object = self.dotted_base(object, depth);
+                format!("{object}.{field} <- {}", self.expr(value, depth))
+            }
+            AstNode::MethodCall(object, method, args) => {
+                let object = self.dotted_base(object, depth);
+                format!("{object}.{method}({})", self.args(args, depth))
+            }
+            AstNode::AnonymousProcedure(params, body) => {
+                let params = self.params(params, depth);
+                format!("PROCEDURE ({params}){}", self.block(body, depth))
+            }
+            AstNode::ValueCall(callee, args) => {
+                // Through a bare name this would read back as a call by name.
+                let callee = match &callee.node {
+                    AstNode::Identifier(name) => format!("({name})"),
+                    _ => self.postfix_base(callee, depth),
+                };
+                format!("{callee}({})", self.args(args, depth))
+            }
+            AstNode::ProcedureCall(name, args) => format!("{name}({})", self.args(args, depth)),
+
+            AstNode::BinaryOp(left, op, right) => {
+                let precedence = binary_precedence(op);
+                let left = self.operand(left, precedence, depth);
+                let right = self.operand(right, precedence + 1, depth);
+                format!("{left} {} {right}", binary_symbol(op))
+            }
+            AstNode::UnaryOp(op, operand) => {
+                let operand = self.operand(operand, UNARY, depth);
+                match op {
+                    UnaryOperator::Not => format!("NOT {operand}"),
+                    UnaryOperator::Neg => format!("-{operand}"),
+                }
+            }
+
+            AstNode::If(condition, then_branch, else_branch) => {
+                let condition = self.condition(condition, depth);
+                let mut text = format!("IF {condition}{}", self.block(then_branch, depth));
+                if let Some(else_branch) = else_branch {
+                    // `ELSE IF` is an IF in the ELSE position, with no block of its own.
+                    let rest = match else_branch.node {
+                        AstNode::If(..) => format!(" {}", self.expr(else_branch, depth)),
+                        _ => self.block(else_branch, depth),
+                    };
+                    text.push_str(&format!("\n{indent}ELSE{rest}"));
+                }
+                text
+            }
+            AstNode::RepeatTimes(count, body) => {
+                let count = self.expr(count, depth);
+                format!("REPEAT {count} TIMES{}", self.block(body, depth))
+            }
+            AstNode::RepeatUntil(body, condition) => {
+                let condition = self.condition(condition, depth);
+                format!("REPEAT UNTIL {condition}{}", self.block(body, depth))
+            }
+            AstNode::RepeatWhile(condition, body) => {
+                let condition = self.condition(condition, depth);
+                format!("REPEAT WHILE {condition}{}", self.block(body, depth))
+            }
+            AstNode::Break => "BREAK".to_string(),
+            AstNode::Continue => "CONTINUE".to_string(),
+            AstNode::ForEach(name, second, list, body) => {
+                let names = match second {
+                    Some(second) => format!("{name}, {second}"),
+                    None => name.clone(),
+                };
+                let list = self.expr(list, depth);
+                format!("FOR EACH {names} IN {list}{}", self.block(body, depth))
+            }
+            AstNode::ForRange {
+                var,
+                from,
+                to,
+                step,
+                body,
+            } => {
+                let mut header = format!(
+                    "FOR {var} FROM {} TO {}",
+                    self.expr(from, depth),
+                    self.expr(to, depth)
+                );
+                if let Some(step) = step {
+                    header.push_str(&format!(" STEP {}", self.expr(step, depth)));
+                }
+                format!("{header}{}", self.block(body, depth))
+            }
+            AstNode::Match(subject, cases, default) => {
+                let inner = INDENT.repeat(depth + 1);
+                let mut text = format!("MATCH {}\n{indent}{{", self.expr(subject, depth));
+                // Comments between the arms stay between the arms.
+                let mut after = subject.span.end;
+                for case in cases {
+                    for comment in self.take_comments(after, case.body.span.start) {
+                        text.push_str(&format!("\n{inner}{}", comment.text));
+                    }
+                    text.push_str(&format!("\n{inner}CASE {}", self.pattern(&case.pattern)));
+                    if let Some(guard) = &case.guard {
+                        text.push_str(&format!(" IF {}", self.expr(guard, depth + 1)));
+                    }
+                    text.push_str(&self.block(&case.body, depth + 1));
+                    after = case.body.span.end;
+                }
+                if let Some(default) = default {
+                    for comment in self.take_comments(after, default.span.start) {
+                        text.push_str(&format!("\n{inner}{}", comment.text));
+                    }
+                    text.push_str(&format!("\n{inner}ELSE{}", self.block(default, depth + 1)));
+                }
+                text.push_str(&format!("\n{indent}}}"));
+                text
+            }
+            AstNode::TryCatch {
+                try_block,
+                error_var,
+                catch_block,
+                finally_block,
+            } => {
+                let mut text = format!("TRY{}", self.block(try_block, depth));
+                if let Some(catch_block) = catch_block {
+                    let var = error_var
+                        .as_ref()
+                        .map(|var| format!(" ({var})"))
+                        .unwrap_or_default();
+                    let body = self.block(catch_block, depth);
+                    text.push_str(&format!("\n{indent}CATCH{var}{body}"));
+                }
+                if let Some(finally_block) = finally_block {
+                    let body = self.block(finally_block, depth);
+                    text.push_str(&format!("\n{indent}FINALLY{body}"));
+                }
+                text
+            }
+            AstNode::Throw(value) => format!("THROW {}", self.expr(value, depth)),
+
+            AstNode::ProcedureDecl(name, params, body) => {
+                let params = self.params(params, depth);
+                format!("PROCEDURE {name}({params}){}", self.block(body, depth))
+            }
+            AstNode::ClassDecl(name, body) => format!("CLASS {name}{}", self.block(body, depth)),
+            AstNode::Return(value) => match &value.node {
+                AstNode::Block(statements) if statements.is_empty() => "RETURN".to_string(),
+                // RETURN reads a leading `(` as parentheses around the whole value.
+                _ => format!("RETURN {}", self.condition(value, depth)),
+            },
+
+            AstNode::Display(None) => "DISPLAY()".to_string(),
+            AstNode::Display(Some(value)) => format!("DISPLAY({})", self.expr(value, depth)),
+            AstNode::DisplayInline(value) => {
+                format!("DISPLAYINLINE({})", self.expr(value, depth))
+            }
+            AstNode::Input(None) => "INPUT()".to_string(),
+            AstNode::Input(Some(prompt)) => format!("INPUT({})", self.expr(prompt, depth)),
+            AstNode::Random(a, b) => self.builtin("RANDOM", &[a, b], depth),
+            AstNode::Insert(a, b, c) => self.builtin("INSERT", &[a, b, c], depth),
+            AstNode::Append(a, b) => self.builtin("APPEND", &[a, b], depth),
+            AstNode::Remove(a, b) => self.builtin("REMOVE", &[a, b], depth),
+            AstNode::Length(a) => self.builtin("LENGTH", &[a], depth),
+            AstNode::Substring(a, b, c) => self.builtin("SUBSTRING", &[a, b, c], depth),
+            AstNode::Concat(a, b) => self.builtin("CONCAT", &[a, b], depth),
+            AstNode::ToString(a) => self.builtin("TOSTRING", &[a], depth),
+            AstNode::ToNum(a) => self.builtin("TONUM", &[a], depth),
+            AstNode::Sort(a) => self.builtin("SORT", &[a], depth),
+            AstNode::Eval(a) => self.builtin("EVAL", &[a], depth),
+
+            AstNode::Block(statements) | AstNode::Program(statements) => {
+                let lines = self.statements(statements, node.span.start, node.span.end, depth);
+                lines.join("\n").trim_start().to_string()
+            }
+            AstNode::Comment => String::new(),
+            AstNode::Import(path, alias) => {
+                let path = path.replace('\\', "\\\\").replace('"', "\\\"");
+                match alias {
+                    Some(alias) => format!("IMPORT \"{path}\" AS {alias}"),
+                    None => format!("IMPORT \"{path}\""),
+                }
+            }
+            AstNode::Export(names) => format!("EXPORT {}", names.join(", ")),
+            AstNode::Private(declaration) => {
+                format!("PRIVATE {}", self.statement(declaration, depth))
+            }
+        }
+    }
+
+    /// `node` as an operand needing at least `precedence`, in parentheses if it
+    /// binds more loosely.
+    fn operand(&mut self, node: &Spanned, precedence: u8, depth: usize) -> String {
+        let text = self.expr(node, depth);
+        if node_precedence(node) < precedence {
+            format!("({text})")
+        } else {
+            text
+        }
+    }
+
+    /// What `[index]` or `(args)` follows.
+    fn postfix_base(&mut self, node: &Spanned, depth: usize) -> String {
+        self.operand(node, POSTFIX, depth)
+    }
+
+    /// What `.name` follows. A number needs parentheses too: `1.x` would be read
+    /// as the start of a float.
+    fn dotted_base(&mut self, node: &Spanned, depth: usize) -> String {
+        match node.node {
+            AstNode::Integer(_) | AstNode::Float(_) => format!("({})", self.expr(node, depth)),
+            _ => self.postfix_base(node, depth),
+        }
+    }
+
+    fn args(&mut self, args: &[Spanned], depth: usize) -> String {
+        let args: Vec<String> = args.iter().map(|a| self.expr(a, depth)).collect();
+        args.join(", ")
+    }
+
+    fn builtin(&mut self, name: &str, args: &[&Spanned], depth: usize) -> String {
+        let args: Vec<String> = args.iter().map(|a| self.expr(a, depth)).collect();
+        format!("{name}({})", args.join(", "))
+    }
+
+    fn params(&mut self, params: &[Parameter], depth: usize) -> String {
+        let params: Vec<String> = params
+            .iter()
+            .map(|param| match (&param.default, param.variadic) {
+                (_, true) => format!("{}...", param.name),
+                (Some(default), false) => {
+                    format!("{} <- {}", param.name, self.expr(default, depth))
+                }
+                (None, false) => param.name.clone(),
+            })
+            .collect();
+        params.join(", ")
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Binding(name) => name.clone(),
+            Pattern::Literal(literal) => self.expr(literal, 0),
+            Pattern::Range(low, high) => format!("{} TO {}", self.expr(low, 0), self.expr(high, 0)),
+            Pattern::List(elements, rest) => {
+                let mut parts: Vec<String> = elements.iter().map(|p| self.pattern(p)).collect();
+                if let Some(rest) = rest {
+                    parts.push(format!("{rest}..."));
+                }
+                format!("[{}]", parts.join(", "))
+            }
+            Pattern::Dictionary(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", self.expr(key, 0), self.pattern(value)))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+/// Lines being written, and where in the source the last of them ended.
+#[derive(Default)]
+struct Lines {
+    lines: Vec<String>,
+    previous: Option<usize>,
+}
+
+impl Lines {
+    /// Add what ran from `start` to `end` in the source, after a blank line if
+    /// the source had one there.
+    fn push(&mut self, printer: &Printer, start: usize, end: usize, text: String) {
+        if self
+            .previous
+            .is_some_and(|previous| printer.blank_between(previous, start))
+        {
+            self.lines.push(String::new());
+        }
+        self.lines.push(text);
+        self.previous = Some(end);
+    }
+}
+
+/// A list or dictionary literal: on one line when it fits, otherwise one element
+/// per line. `items` were printed one level deeper than `depth`, as the second
+/// layout needs.
+fn sequence(open: &str, close: &str, items: Vec<String>, depth: usize) -> String {
+    let flat = items.join(", ");
+    let fits = INDENT.len() * depth + flat.len() + open.len() + close.len() <= MAX_WIDTH;
+    if items.is_empty() || (fits && !flat.contains('\n')) {
+        return format!("{open}{flat}{close}");
+    }
+    let inner = INDENT.repeat(depth + 1);
+    let items: Vec<String> = items.iter().map(|item| format!("{inner}{item}")).collect();
+    format!(
+        "{open}\n{}\n{}{close}",
+        items.join(",\n"),
+        INDENT.repeat(depth)
+    )
+}
+
+/// Precedence of a prefix operator's operand: tighter than every binary operator.
+const UNARY: u8 = 7;
+/// Precedence of anything that needs no parentheses to be indexed or called.
+const POSTFIX: u8 = 8;
+
+fn binary_precedence(op: &BinaryOperator) -> u8 {
+    match op {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::Eq | BinaryOperator::NotEq => 3,
+        BinaryOperator::Gt | BinaryOperator::Lt | BinaryOperator::GtEq | BinaryOperator::LtEq => 4,
+        BinaryOperator::Add | BinaryOperator::Sub => 5,
+        BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Mod => 6,
+    }
+}
+
+fn binary_symbol(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        BinaryOperator::Mul => "*",
+        BinaryOperator::Div => "/",
+        BinaryOperator::Mod => "MOD",
+        BinaryOperator::Eq => "=",
+        BinaryOperator::NotEq => "NOT=",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::GtEq => ">=",
+        BinaryOperator::LtEq => "<=",
+        BinaryOperator::And => "AND",
+        BinaryOperator::Or => "OR",
+    }
+}
+
+fn node_precedence(node: &Spanned) -> u8 {
+    match &node.node {
+        AstNode::BinaryOp(_, op, _) => binary_precedence(op),
+        AstNode::UnaryOp(..) => UNARY,
+        _ => POSTFIX,
+    }
+}
+
+/// Format the files named on the command line, in place or, with `check`, only
+/// report the ones that are not formatted. Returns the exit status: 1 when a file
+/// does not parse or, with `check`, would change.
+pub fn run(paths: &[String], check: bool) -> i32 {
+    let mut status = 0;
+    for path in paths {
+        if !path.ends_with(".psl") {
+            eprintln!("Error: Input file must have .psl extension, got: {}", path);
+            status = 1;
+            continue;
+        }
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Error: Could not read {}: {}", path, e);
+                status = 1;
+                continue;
+            }
+        };
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("Error: {}: {}", path, error.format(&source));
+                status = 1;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", path);
+            status = 1;
+        } else if let Err(e) = std::fs::write(path, formatted) {
+            eprintln!("Error: Could not write {}: {}", path, e);
+            status = 1;
+        } else {
+            println!("Formatted {}", path);
+        }
+    }
+    status
+}
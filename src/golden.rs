@@ -0,0 +1,562 @@
+//! `fpli test --golden`: run programs and compare what they print with a
+//! recorded expectation.
+//!
+//! A case is a program and the output it should produce, laid out the way the
+//! crate's own `tests/programs/` is:
+//!
+//! ```text
+//! cases/sorting.psl          the program
+//! cases/sorting.expected     exactly what it should print
+//! cases/sorting.stdin        optional: fed to it as standard input
+//! cases/sorting.args         optional: its arguments, separated by whitespace
+//! cases/sorting.exit         optional: the exit status it should end with
+//! ```
+//!
+//! A program made of several files is a directory instead, holding `main.psl`,
+//! `expected`, and optionally `stdin`, `args` and `exit`, beside whatever the
+//! program imports or reads. The `# ARGS:`, `# STDIN:`, `# EXIT:` and `# STDERR:`
+//! header comments the crate's fixtures use are honoured too, so a directory of
+//! those runs unchanged; a sidecar file wins over the comment it duplicates.
+//!
+//! Every case runs as a child `fpli run`, in a temporary directory holding a copy
+//! of it, so a program that writes files leaves nothing behind in the case
+//! directory and one case cannot see another's leftovers. With `--bless` a case
+//! that does not match has its `.expected` and `.exit` rewritten from what it did.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// One program to run, and what it should do.
+#[derive(Debug, PartialEq)]
+pub struct Case {
+    /// The name the report uses: the program's path relative to the directory
+    /// searched, without `.psl`.
+    pub name: String,
+    /// The file or directory to copy to run it.
+    pub root: PathBuf,
+    /// The entry file, relative to the copy.
+    pub entry: String,
+    /// Where its expected output is, and where `--bless` writes it.
+    pub expected_path: PathBuf,
+    /// Where its expected status is, and where `--bless` writes it.
+    pub exit_path: PathBuf,
+    pub expected: String,
+    pub args: Vec<String>,
+    pub stdin: Option<String>,
+    pub exit: i32,
+    /// Text standard error has to contain, from a `# STDERR:` comment.
+    pub stderr_contains: Option<String>,
+}
+
+/// What running a case did.
+pub struct Ran {
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Every case under `dir`, in name order.
+pub fn discover(dir: &Path) -> Result<Vec<Case>, String> {
+    let mut cases = Vec::new();
+    collect(dir, dir, &mut cases)?;
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+fn collect(base: &Path, dir: &Path, cases: &mut Vec<Case>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Could not read directory {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Could not read directory {}: {}", dir.display(), e))?
+            .path();
+        let name = relative_name(base, &path);
+        if path.is_dir() {
+            if path.join("main.psl").is_file() {
+                cases.push(load(name, path.clone(), "main.psl", |file| {
+                    path.join(file)
+                })?);
+            } else if !file_name(&path).starts_with('.') {
+                collect(base, &path, cases)?;
+            }
+        } else if path.extension().is_some_and(|e| e == "psl")
+            && path.with_extension("expected").is_file()
+        {
+            // A .psl without a .expected is a library the cases import, not a case.
+            let entry = file_name(&path);
+            cases.push(load(
+                name.trim_end_matches(".psl").to_string(),
+                path.clone(),
+                &entry,
+                |extension| path.with_extension(extension),
+            )?);
+        }
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn relative_name(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Read a case. `sidecar` names its companion files: `sorting.stdin` beside a
+/// single file, `stdin` inside a directory.
+fn load(
+    name: String,
+    root: PathBuf,
+    entry: &str,
+    sidecar: impl Fn(&str) -> PathBuf,
+) -> Result<Case, String> {
+    let program = if root.is_dir() {
+        root.join(entry)
+    } else {
+        root.clone()
+    };
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))
+    };
+    let optional = |path: PathBuf| path.is_file().then(|| read(&path)).transpose();
+
+    let source = read(&program)?;
+    let mut case = Case {
+        name,
+        entry: entry.to_string(),
+        expected_path: sidecar("expected"),
+        exit_path: sidecar("exit"),
+        expected: read(&sidecar("expected"))?,
+        args: Vec::new(),
+        stdin: None,
+        exit: 0,
+        stderr_contains: None,
+        root,
+    };
+    apply_directives(&source, &mut case)?;
+    if let Some(args) = optional(sidecar("args"))? {
+        case.args = args.split_whitespace().map(str::to_string).collect();
+    }
+    if let Some(stdin) = optional(sidecar("stdin"))? {
+        case.stdin = Some(stdin);
+    }
+    if let Some(exit) = optional(sidecar("exit"))? {
+        case.exit = parse_status(&exit, &case.exit_path)?;
+    }
+    Ok(case)
+}
+
+/// Take the `#` header comments at the top of a program as settings for its case.
+fn apply_directives(source: &str, case: &mut Case) -> Result<(), String> {
+    let mut stdin_lines: Vec<&str> = Vec::new();
+    for line in source.lines() {
+        let trimmed = line.trim();
+        let Some(body) = trimmed.strip_prefix('#') else {
+            // A header block: the first line of code ends it, so a `#` comment
+            // further down is never read as a setting.
+            if trimmed.is_empty() {
+                continue;
+            }
+            break;
+        };
+        let body = body.trim();
+        if let Some(rest) = body.strip_prefix("ARGS:") {
+            case.args = rest.split_whitespace().map(str::to_string).collect();
+        } else if let Some(rest) = body.strip_prefix("STDIN:") {
+            stdin_lines.push(rest.strip_prefix(' ').unwrap_or(rest));
+        } else if let Some(rest) = body.strip_prefix("EXIT:") {
+            case.exit = parse_status(rest, &case.root)?;
+        } else if let Some(rest) = body.strip_prefix("STDERR:") {
+            case.stderr_contains = Some(rest.trim().to_string());
+        }
+    }
+    if !stdin_lines.is_empty() {
+        case.stdin = Some(format!("{}\n", stdin_lines.join("\n")));
+    }
+    Ok(())
+}
+
+fn parse_status(text: &str, from: &Path) -> Result<i32, String> {
+    text.trim().parse().map_err(|_| {
+        format!(
+            "{}: the exit status must be a number, got {:?}",
+            from.display(),
+            text.trim()
+        )
+    })
+}
+
+/// Run `case` with the `fpli` at `fpli`, in a fresh copy of it.
+pub fn run_case(case: &Case, fpli: &Path) -> Result<Ran, String> {
+    let scratch = Scratch::new()?;
+    if case.root.is_dir() {
+        copy_tree(&case.root, scratch.path())?;
+    } else {
+        std::fs::copy(&case.root, scratch.path().join(&case.entry))
+            .map_err(|e| format!("Could not copy {}: {}", case.root.display(), e))?;
+    }
+
+    let mut child = Command::new(fpli)
+        .current_dir(scratch.path())
+        .arg("run")
+        .arg(&case.entry)
+        .args(&case.args)
+        // Always a pipe, so a program that reads INPUT gets the case's input or a
+        // clean end of input, never the terminal running the tests.
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not start {}: {}", fpli.display(), e))?;
+    // Fed from another thread: a program that prints more than a pipe holds
+    // before reading its input would otherwise never get to read it.
+    let mut pipe = child.stdin.take();
+    let stdin = case.stdin.clone().unwrap_or_default();
+    let feeder = std::thread::spawn(move || {
+        if let Some(pipe) = pipe.as_mut() {
+            let _ = pipe.write_all(stdin.as_bytes());
+        }
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Could not run {}: {}", case.name, e))?;
+    let _ = feeder.join();
+    Ok(Ran {
+        status: output.status.code(),
+        stdout: normalise(&output.stdout),
+        stderr: normalise(&output.stderr),
+    })
+}
+
+/// Text as compared: CRLF line ends, which a `.expected` checked out on Windows
+/// may have, read as plain ones.
+fn normalise(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).replace("\r\n", "\n")
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<(), String> {
+    let entries = std::fs::read_dir(from)
+        .map_err(|e| format!("Could not read directory {}: {}", from.display(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Could not read directory {}: {}", from.display(), e))?
+            .path();
+        let target = to.join(file_name(&path));
+        if path.is_dir() {
+            std::fs::create_dir_all(&target)
+                .map_err(|e| format!("Could not create {}: {}", target.display(), e))?;
+            copy_tree(&path, &target)?;
+        } else {
+            std::fs::copy(&path, &target)
+                .map_err(|e| format!("Could not copy {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// A temporary directory that is removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new() -> Result<Self, String> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("fpli-golden-{}-{}", std::process::id(), n));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+        Ok(Scratch(dir))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// How the status and the output of `case` differ from what it should do, one
+/// entry per difference, with a diff of the output when that differs. These are
+/// what `--bless` can record.
+pub fn mismatches(case: &Case, ran: &Ran) -> Vec<String> {
+    let mut found = Vec::new();
+    if ran.status != Some(case.exit) {
+        let status = match ran.status {
+            Some(status) => status.to_string(),
+            None => "none (killed by a signal)".to_string(),
+        };
+        found.push(format!("exit status {}, expected {}", status, case.exit));
+    }
+    let expected = case.expected.replace("\r\n", "\n");
+    // Trailing blank lines are no difference: an editor adds one to a
+    // `.expected` file as readily as a program leaves one off.
+    if ran.stdout.trim_end() != expected.trim_end() {
+        found.push(format!(
+            "output differs\n{}",
+            unified_diff(expected.trim_end(), ran.stdout.trim_end())
+        ));
+    }
+    found
+}
+
+/// What is wrong with the standard error of `case`, which a `# STDERR:` comment
+/// checks and `--bless` leaves alone.
+pub fn stderr_mismatch(case: &Case, ran: &Ran) -> Option<String> {
+    let needle = case.stderr_contains.as_ref()?;
+    (!ran.stderr.contains(needle)).then(|| {
+        format!(
+            "standard error does not contain {:?}; it was:\n{}",
+            needle,
+            ran.stderr.trim_end()
+        )
+    })
+}
+
+/// Lines of context kept around each change in a diff.
+const CONTEXT: usize = 3;
+
+/// The changes from `expected` to `actual` as a unified diff: `-` lines are
+/// expected and missing, `+` lines printed and not expected.
+pub fn unified_diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let Some(edits) = line_edits(&old, &new) else {
+        return first_difference(&old, &new);
+    };
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    let mut i = 0;
+    while i < edits.len() {
+        if edits[i].0 == ' ' {
+            i += 1;
+            continue;
+        }
+        // A hunk: this change, every change within twice the context of the one
+        // before it, and the context around them.
+        let start = i.saturating_sub(CONTEXT);
+        let mut end = i;
+        let mut quiet = 0;
+        while end < edits.len() && quiet <= 2 * CONTEXT {
+            quiet = if edits[end].0 == ' ' { quiet + 1 } else { 0 };
+            end += 1;
+        }
+        let end = end - quiet.saturating_sub(CONTEXT);
+        let hunk = &edits[start..end];
+        let (old_start, new_start) = (edits[start].1, edits[start].2);
+        let old_len = hunk.iter().filter(|e| e.0 != '+').count();
+        let new_len = hunk.iter().filter(|e| e.0 != '-').count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        ));
+        for (kind, old_at, new_at) in hunk {
+            let line = if *kind == '+' {
+                new[*new_at]
+            } else {
+                old[*old_at]
+            };
+            out.push(*kind);
+            out.push_str(line);
+            out.push('\n');
+        }
+        i = end;
+    }
+    out.trim_end().to_string()
+}
+
+/// A hunk header's `start,length`, counted from 1. An empty range is given as
+/// the line before it, as `diff -u` does.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// The most cells the table [`line_edits`] fills may have: 16 MB of it. Output
+/// that differs over more lines than this allows is most likely runaway, and
+/// gets only its first difference shown.
+const MAX_DIFF_CELLS: usize = 4 << 20;
+
+/// The shortest edit from `old` to `new`, as (`' '`, `'-'` or `'+'`, index in
+/// `old`, index in `new`) for every line, by longest common subsequence.
+///
+/// The lines the two share at the start and at the end are matched as they are,
+/// so the table only covers the part in between. `None` when even that part is
+/// too big for it.
+fn line_edits(old: &[&str], new: &[&str]) -> Option<Vec<(char, usize, usize)>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    if (old_mid.len() + 1).saturating_mul(new_mid.len() + 1) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    // common[i][j]: the longest common subsequence of old_mid[i..] and new_mid[j..].
+    let mut common = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            common[i][j] = if old_mid[i] == new_mid[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut edits: Vec<(char, usize, usize)> = (0..prefix).map(|k| (' ', k, k)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            edits.push((' ', prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < old_mid.len() && (j == new_mid.len() || common[i + 1][j] >= common[i][j + 1])
+        {
+            edits.push(('-', prefix + i, prefix + j));
+            i += 1;
+        } else {
+            edits.push(('+', prefix + i, prefix + j));
+            j += 1;
+        }
+    }
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    edits.extend((0..suffix).map(|k| (' ', old_end + k, new_end + k)));
+    Some(edits)
+}
+
+/// In place of a diff too big to work out, where `old` and `new` first part.
+fn first_difference(old: &[&str], new: &[&str]) -> String {
+    let at = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let mut out = format!(
+        "--- expected\n+++ actual\nToo many lines differ to diff ({} expected, {} printed); the first difference is at line {}:\n",
+        old.len(),
+        new.len(),
+        at + 1
+    );
+    if let Some(line) = old.get(at) {
+        out.push_str(&format!("-{}\n", line));
+    }
+    if let Some(line) = new.get(at) {
+        out.push_str(&format!("+{}\n", line));
+    }
+    out.trim_end().to_string()
+}
+
+/// Record what `case` did as what it should do.
+pub fn bless(case: &Case, ran: &Ran) -> Result<(), String> {
+    let write = |path: &Path, text: &str| {
+        std::fs::write(path, text).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    };
+    write(&case.expected_path, &ran.stdout)?;
+    // Only when it changed, so a case that always exited 0 gets no `.exit` file. A
+    // `.exit` file outranks an `# EXIT:` comment, so writing one always takes.
+    match ran.status {
+        Some(status) if status != case.exit => write(&case.exit_path, &format!("{}\n", status)),
+        _ => Ok(()),
+    }
+}
+
+/// Run every case under `dir`, print a report, and return the exit status: 0
+/// when every case matched (or, with `bless`, has been recorded), 1 otherwise.
+pub fn run(dir: &str, bless_mismatches: bool) -> i32 {
+    let fpli = match std::env::current_exe() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Error: Could not find the fpli executable: {}", e);
+            return 1;
+        }
+    };
+    let cases = match discover(Path::new(dir)) {
+        Ok(cases) if cases.is_empty() => {
+            eprintln!(
+                "Error: No cases found in {}. A case is a .psl file with a .expected file beside it",
+                dir
+            );
+            return 1;
+        }
+        Ok(cases) => cases,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            return 1;
+        }
+    };
+
+    let (mut passed, mut failed, mut blessed) = (0, 0, 0);
+    let mut failures: Vec<(String, String)> = Vec::new();
+    for case in &cases {
+        let ran = match run_case(case, &fpli) {
+            Ok(ran) => ran,
+            Err(message) => {
+                println!("golden {} ... ERROR", case.name);
+                failed += 1;
+                failures.push((case.name.clone(), message));
+                continue;
+            }
+        };
+        let mut problems = mismatches(case, &ran);
+        let rewritten = bless_mismatches && !problems.is_empty();
+        if rewritten {
+            if let Err(message) = bless(case, &ran) {
+                println!("golden {} ... ERROR", case.name);
+                failed += 1;
+                failures.push((case.name.clone(), message));
+                continue;
+            }
+            blessed += 1;
+            problems.clear();
+        }
+        problems.extend(stderr_mismatch(case, &ran));
+        if !problems.is_empty() {
+            println!("golden {} ... FAILED", case.name);
+            failed += 1;
+            failures.push((case.name.clone(), problems.join("\n")));
+        } else if rewritten {
+            println!("golden {} ... blessed", case.name);
+        } else {
+            println!("golden {} ... ok", case.name);
+            passed += 1;
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, report) in &failures {
+            println!("\n---- {} ----\n{}", name, report);
+        }
+    }
+    let verdict = if failed == 0 { "ok" } else { "FAILED" };
+    if bless_mismatches {
+        println!(
+            "\ntest result: {}. {} passed; {} blessed; {} failed",
+            verdict, passed, blessed, failed
+        );
+    } else {
+        println!(
+            "\ntest result: {}. {} passed; {} failed",
+            verdict, passed, failed
+        );
+    }
+    i32::from(failed > 0)
+}
@@ -0,0 +1,156 @@
+//! The values a Rust program embedding PseudoLang passes to an
+//! [`Interpreter`](crate::interpreter::Interpreter) and gets back from it.
+//!
+//! [`Value`] mirrors the interpreter's own values but owns what it holds: a list
+//! or dictionary is copied out of the program, and copied back in when handed
+//! over, which is what assigning one in PseudoLang does anyway. Objects,
+//! procedures and modules are different. An object is the same object wherever
+//! it is held, and a procedure keeps the scope it closed over, so none of them
+//! can be rebuilt from parts on the Rust side. They come back as
+//! [`Value::Other`], which can be looked at and handed back to the program as it
+//! is -- an object passed to a procedure the program then changes is changed for
+//! the program too.
+
+use crate::interpreter::Inspected;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+/// A PseudoLang value, as a Rust program holds it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Integer(BigInt),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    List(Vec<Value>),
+    /// A dictionary's entries in insertion order. A key can only be a string, an
+    /// integer or a boolean; handing the program any other is an error.
+    Dictionary(Vec<(Value, Value)>),
+    Null,
+    NaN,
+    /// What a statement with no value -- an assignment, say -- evaluates to.
+    Unit,
+    /// An object, a procedure or a module, shared with the program.
+    Other(Inspected),
+}
+
+// The accessors are for Rust hosts, through the library; the fpli binary only
+// builds values and hands them over.
+#[allow(dead_code)]
+impl Value {
+    /// The integer, if this is one that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => n.to_i64(),
+            _ => None,
+        }
+    }
+
+    /// The number, integer or float. A huge integer comes back as the nearest
+    /// float, as it would in arithmetic with one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => n.to_f64(),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The value a dictionary holds under the string `key`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dictionary(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Value::Integer(BigInt::from(n))
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Integer(BigInt::from(n))
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Value::Integer(BigInt::from(n))
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        Value::Integer(n)
+    }
+}
+
+/// A float that is not a number becomes PseudoLang's NAN.
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        if f.is_nan() {
+            Value::NaN
+        } else {
+            Value::Float(f)
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// `None` becomes NULL.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
@@ -1,10 +1,15 @@
-use crate::error::{PSLError, Span, StackFrame};
-use crate::parser::{AstNode, BinaryOperator, Spanned, UnaryOperator};
+use crate::error::{ErrorKind, PSLError, Span, StackFrame, resolve_span};
+use crate::host;
+use crate::limits::Limits;
+use crate::parser::{
+    AstNode, BinaryOperator, MatchCase, Parameter, Pattern, Spanned, UnaryOperator,
+};
+use crate::sandbox::{Need, Permissions};
 use crate::system;
 use num_bigint::BigInt;
 use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
 use rand::RngExt;
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, OnceCell, RefCell};
 use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
@@ -13,6 +18,7 @@ use std::rc::Rc;
 use std::thread;
 #[cfg(any(not(target_arch = "wasm32"), feature = "wasi"))]
 use std::time::Duration;
+use std::time::Instant;
 
 #[derive(Debug, Clone)]
 enum Value {
@@ -24,6 +30,13 @@ enum Value {
     /// Insertion-ordered dictionary. Overwriting an existing key keeps its
     /// original position; a new key is appended. See [`Dict`].
     Dictionary(Dict),
+    /// An instance of a CLASS. Shared, not copied: see [`Instance`].
+    Object(Object),
+    /// A procedure used as a value: a declared one named without its call
+    /// parentheses, or an anonymous `PROCEDURE (x) { ... }`.
+    Procedure(ProcedureValue),
+    /// A file imported with `IMPORT ... AS name`, reached through `name.member`.
+    Module(Rc<Module>),
     Unit,
     Null,
     NaN,
@@ -223,6 +236,242 @@ impl Dict {
     }
 }
 
+/// A declared CLASS: its name and the methods written in its body.
+///
+/// The methods live in a table of their own rather than in the scope's procedure
+/// table, so they are only reachable through an instance: two classes may each
+/// declare `area`, and neither is callable as a bare `area()`.
+struct Class {
+    name: String,
+    methods: HashMap<String, Procedure>,
+    /// The file the CLASS was written in, as for a procedure.
+    declared_in: Option<Rc<PathBuf>>,
+}
+
+/// The state of one object: the class it was made from and its fields, in the
+/// order they were first assigned.
+///
+/// Objects are the one value with *reference* semantics. Lists and dictionaries
+/// are copied on assignment, but an object exists to be passed to a procedure
+/// and changed there -- `account.deposit(10)` has to change `account` -- so
+/// `q <- p` makes `q` a second name for the same object, as in Python and Java.
+/// Equality is identity for the same reason.
+struct Instance {
+    class: Rc<Class>,
+    fields: Dict,
+}
+
+type Object = Rc<RefCell<Instance>>;
+
+/// A procedure held in a variable, a list or a field.
+///
+/// `scope` is what makes an anonymous procedure a closure: the scope it was
+/// written in, kept alive for as long as the value is. The body runs in a child
+/// of that scope, so it sees the variables around its definition -- live, not
+/// copied, as in Python -- rather than the variables of whoever ends up calling
+/// it. A declared procedure taken as a value has no scope of its own and keeps
+/// the dynamic scoping every named call has.
+///
+/// A closure stored in a variable of the very scope it captured forms a
+/// reference cycle, which `Rc` never frees. That is bounded by the number of
+/// closures a program creates, and a run is short-lived.
+#[derive(Clone)]
+struct ProcedureValue {
+    /// The declared name, or `None` for an anonymous procedure. Used for the
+    /// stack frame and for printing.
+    name: Option<Rc<str>>,
+    procedure: Procedure,
+    scope: Option<Rc<RefCell<Environment>>>,
+}
+
+impl ProcedureValue {
+    fn frame_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+}
+
+/// The environment is not printable, and the body would be noise: show what
+/// `value_to_string` shows.
+impl std::fmt::Debug for ProcedureValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<procedure {}>", name),
+            None => write!(f, "<procedure>"),
+        }
+    }
+}
+
+/// A file imported with `IMPORT "file" AS name`.
+///
+/// Its top level runs in a scope of its own, `env`, instead of the importing
+/// program's root, so two libraries that both declare `parse` can be used side by
+/// side as `a.parse` and `b.parse`. A procedure declared in the file keeps running
+/// in that scope wherever it is called from -- see [`invoke_procedure`] -- so it
+/// reaches its own helpers and the file's top-level variables, and never the
+/// caller's.
+///
+/// Imported once per run whatever the number of IMPORTs naming it, so every
+/// `name` bound to it is the same module, with the same state. A module that
+/// imports a module that imports it back makes a reference cycle, which, as with
+/// closures, lasts until the run ends.
+struct Module {
+    /// The `Rc` every procedure declared in the file carries as its file, which
+    /// is how a call finds its way back to `env`. An identity, not just a path:
+    /// the same file imported without AS declares into the importer's scope and
+    /// must keep the dynamic scoping every other procedure has -- unless it has
+    /// private names, when even a plain IMPORT runs it as a module.
+    file: Rc<PathBuf>,
+    env: Rc<RefCell<Environment>>,
+    /// Files the module's own top level imported without AS. Kept per module
+    /// because they were declared into `env`, so the importing program importing
+    /// the same file still has to run it into its own scope.
+    loaded: RefCell<Vec<PathBuf>>,
+    /// Which of its names the file lets other files use.
+    visibility: Visibility,
+}
+
+/// The variables every module scope starts with, copied from the program's.
+/// Never handed back to an importer: they are the importer's to begin with.
+const MODULE_BUILTINS: [&str; 3] = ["ARGS", "ARGCOUNT", "POSITIONALS"];
+
+/// Which top-level names of a file other files may use, from its EXPORT and
+/// PRIVATE declarations.
+///
+/// Read from the parsed file before it runs rather than collected as it runs, so
+/// it cannot depend on which branches the top level took, and so IMPORT knows
+/// before running a file whether it must run it in a module scope of its own: a
+/// file with private names does, because its public procedures still have to
+/// reach them after the import, from wherever they are called.
+#[derive(Default)]
+struct Visibility {
+    /// Every name listed by an EXPORT, with where it was listed. `None` when the
+    /// file has no EXPORT, which leaves public every name not marked PRIVATE.
+    exported: Option<HashMap<String, Span>>,
+    private: Vec<String>,
+}
+
+impl Visibility {
+    fn of(program: &Spanned, env: &Rc<RefCell<Environment>>) -> Result<Visibility, Interruption> {
+        let statements: &[Spanned] = match &program.node {
+            AstNode::Program(statements) => statements,
+            _ => std::slice::from_ref(program),
+        };
+        let mut visibility = Visibility::default();
+        for statement in statements {
+            match &statement.node {
+                AstNode::Export(names) => {
+                    let exported = visibility.exported.get_or_insert_with(HashMap::new);
+                    for name in names {
+                        exported.entry(name.clone()).or_insert(statement.span);
+                    }
+                }
+                AstNode::Private(declaration) => {
+                    let name = match &declaration.node {
+                        AstNode::ProcedureDecl(name, ..) | AstNode::ClassDecl(name, _) => name,
+                        AstNode::Assignment(target, _) => match &target.node {
+                            AstNode::Identifier(name) => name,
+                            _ => continue,
+                        },
+                        _ => continue,
+                    };
+                    visibility.private.push(name.clone());
+                }
+                _ => {}
+            }
+        }
+        if let Some(exported) = &visibility.exported {
+            // In source order, so the conflict reported is the same every run.
+            let mut conflicts: Vec<(&String, &Span)> = exported
+                .iter()
+                .filter(|(name, _)| visibility.private.contains(name))
+                .collect();
+            conflicts.sort_by_key(|(name, span)| (span.start, *name));
+            if let Some((name, span)) = conflicts.first() {
+                return Err(runtime_err(
+                    format!("'{}' is both EXPORTed and PRIVATE", name),
+                    **span,
+                    env,
+                ));
+            }
+        }
+        Ok(visibility)
+    }
+
+    /// Whether the file says anything about visibility at all. A file that does
+    /// not is imported the way files always have been.
+    fn is_declared(&self) -> bool {
+        self.exported.is_some() || !self.private.is_empty()
+    }
+
+    fn is_public(&self, name: &str) -> bool {
+        match &self.exported {
+            Some(exported) => exported.contains_key(name),
+            None => !self.private.iter().any(|private| private == name),
+        }
+    }
+
+    /// Refuse an EXPORT of a name the file never declared, once its top level has
+    /// run in `scope`: almost always a typo, which would otherwise only surface as
+    /// a "not found" in whichever program imported it.
+    fn check_exported(
+        &self,
+        scope: &Rc<RefCell<Environment>>,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<(), Interruption> {
+        let Some(exported) = &self.exported else {
+            return Ok(());
+        };
+        let mut missing: Vec<(&String, &Span)> = {
+            let scope = scope.borrow();
+            exported
+                .iter()
+                .filter(|(name, _)| {
+                    !scope.variables.contains_key(name.as_str())
+                        && scope.get_procedure(name).is_none()
+                        && scope.get_class(name).is_none()
+                })
+                .collect()
+        };
+        missing.sort_by_key(|(name, span)| (span.start, *name));
+        match missing.first() {
+            Some((name, span)) => Err(runtime_err(
+                format!(
+                    "EXPORT names '{}', but the file declares no variable, procedure or CLASS of that name",
+                    name
+                ),
+                **span,
+                env,
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Module {
+    /// The name the module is printed with: its file name.
+    fn display_name(&self) -> String {
+        self.file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.file.display().to_string())
+    }
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<module {}>", self.display_name())
+    }
+}
+
+/// Printed as the class name alone. The derived form would print every field,
+/// and an object that refers to itself -- a node whose `parent` field leads back
+/// to it -- would never finish printing.
+impl std::fmt::Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{} object>", self.class.name)
+    }
+}
+
 /// Number of characters in `s`.
 ///
 /// Every string position in PseudoLang is a character position, never a byte
@@ -298,6 +547,16 @@ enum ContainerKind {
 enum Interruption {
     Return(Value),
     Error(PSLError),
+    /// An error raised by THROW.
+    ///
+    /// Kept apart from `Error` because the value is the point of a THROW: the
+    /// handler gets it back as `err["value"]`, whatever it was. Everything that
+    /// is not a TRY treats the two alike, and an uncaught one ends the run with
+    /// its `PSLError`, exactly like an error the interpreter raised. Boxed so the
+    /// variant costs no more than a pointer: every `EvalResult` is as large as
+    /// the largest `Interruption`, and the evaluator's deep recursion pays for
+    /// each byte in every frame.
+    Throw(Box<Thrown>),
     /// EXIT: stop the program with this status.
     ///
     /// Unwinds rather than calling `process::exit` at the point of the call, so the
@@ -306,11 +565,40 @@ enum Interruption {
     /// gets the output back and keeps its process. Distinct from `Error`, so TRY does
     /// not catch it.
     Exit(i32),
+    /// A limit of the run's was exceeded: see [`Limits`]. Neither TRY nor
+    /// ASSERTRAISES catches it and FINALLY does not run, so nothing the program
+    /// does can keep it going; otherwise it ends the run like an uncaught error.
+    /// Boxed, like `Throw`: a second unboxed `PSLError` made every `EvalResult`
+    /// eight bytes larger.
+    Limit(Box<PSLError>),
+    /// BREAK: leave the innermost loop.
+    ///
+    /// Consumed by [`evaluate_loop_body`] and never seen above the loop that
+    /// caught it. The parser only accepts BREAK inside a loop body of the same
+    /// procedure, so it cannot reach a procedure boundary or the top level.
+    Break,
+    /// CONTINUE: end this iteration of the innermost loop. Same containment as
+    /// `Break`.
+    Continue,
+}
+
+/// What a THROW raised: the value itself, the kind it named, and the error that
+/// reports it if nothing catches it.
+struct Thrown {
+    value: Value,
+    kind: String,
+    error: PSLError,
 }
 
 type EvalResult = Result<Value, Interruption>;
 
 fn runtime_err(msg: impl Into<String>, span: Span, env: &Rc<RefCell<Environment>>) -> Interruption {
+    Interruption::Error(raised_error(msg, span, env))
+}
+
+/// The `PSLError` behind [`runtime_err`], for callers that adjust it before it
+/// unwinds.
+fn raised_error(msg: impl Into<String>, span: Span, env: &Rc<RefCell<Environment>>) -> PSLError {
     // Spans are per-file, so an error raised while an imported file is executing has
     // to carry that file's text: resolved against the entry script the offsets land on
     // unrelated lines. `invoke_procedure` enters a procedure's declaring file, so this
@@ -319,13 +607,14 @@ fn runtime_err(msg: impl Into<String>, span: Span, env: &Rc<RefCell<Environment>
         Some((source, name)) => (Some(source), Some(name)),
         None => (None, None),
     };
-    Interruption::Error(PSLError {
+    PSLError {
         message: msg.into(),
         span: Some(span),
         stack_trace: env.borrow().get_call_stack(),
         source,
         origin,
-    })
+        kind: ErrorKind::Runtime,
+    }
 }
 
 /// Rank of a value's kind for [`sort_cmp`]. Numbers sort before strings, then
@@ -337,9 +626,12 @@ fn sort_rank(value: &Value) -> u8 {
         Value::Boolean(_) => 2,
         Value::List(_) => 3,
         Value::Dictionary(_) => 4,
-        Value::Null => 5,
-        Value::NaN => 6,
-        Value::Unit => 7,
+        Value::Object(_) => 5,
+        Value::Procedure(_) => 6,
+        Value::Module(_) => 7,
+        Value::Null => 8,
+        Value::NaN => 9,
+        Value::Unit => 10,
     }
 }
 
@@ -368,18 +660,292 @@ fn sort_cmp(a: &Value, b: &Value) -> std::cmp::Ordering {
     }
 }
 
-const MAX_STACK_DEPTH: usize = 1000;
+/// The error that ends a run for exceeding one of its [`Limits`].
+fn limit_exceeded(
+    msg: impl Into<String>,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+) -> Interruption {
+    let mut error = raised_error(msg, span, env);
+    error.kind = ErrorKind::Limit;
+    Interruption::Limit(Box::new(error))
+}
+
+/// What a run has used of its [`Limits`] so far. Shared by every scope, like the
+/// call stack.
+struct Budget {
+    limits: Limits,
+    /// Statements started.
+    steps: Cell<u64>,
+    /// When the run has to be over, if it has a time limit. Only worked out when
+    /// it does: `Instant::now` panics on the WASM playground, which has no clock.
+    deadline: Cell<Option<Instant>>,
+    /// Statements and loop turns so far, the clock the memory cap goes by.
+    ticks: Cell<u64>,
+    /// The tick at which the program's values are next measured.
+    next_measure: Cell<u64>,
+    /// The scope every procedure call under way was made from, when memory is
+    /// capped. A closure or a procedure of a module runs in a scope whose parents
+    /// are not its caller's, so the caller's variables are only found through
+    /// here.
+    scopes: RefCell<Vec<Rc<RefCell<Environment>>>>,
+}
+
+impl Budget {
+    fn new(limits: Limits) -> Self {
+        let budget = Budget {
+            limits,
+            steps: Cell::new(0),
+            deadline: Cell::new(None),
+            ticks: Cell::new(0),
+            next_measure: Cell::new(0),
+            scopes: RefCell::new(Vec::new()),
+        };
+        budget.restart();
+        budget
+    }
+
+    /// Give the run the whole of its limits again, from now: what an
+    /// [`Interpreter`] does each time its host calls into it.
+    fn restart(&self) {
+        self.steps.set(0);
+        self.ticks.set(0);
+        self.next_measure.set(0);
+        // A timeout too long to add to the clock is as good as none.
+        self.deadline.set(
+            self.limits
+                .timeout
+                .and_then(|timeout| Instant::now().checked_add(timeout)),
+        );
+    }
+
+    /// The budget of the run `env` belongs to, if anything has to be looked at
+    /// before each of its statements.
+    fn metering(env: &Rc<RefCell<Environment>>) -> Option<Rc<Budget>> {
+        let scope = env.borrow();
+        scope
+            .budget
+            .limits
+            .metered()
+            .then(|| Rc::clone(&scope.budget))
+    }
+
+    /// Count `statement`, about to run, and stop the run if that is one too many
+    /// or if time or memory has run out. Counts what `--count-ops` counts.
+    fn statement(
+        &self,
+        statement: &Spanned,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<(), Interruption> {
+        if does_nothing(statement) {
+            return Ok(());
+        }
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if let Some(most) = self.limits.steps
+            && steps > most
+        {
+            return Err(limit_exceeded(
+                format!(
+                    "Step limit exceeded: the program ran {} statements (--max-steps)",
+                    most
+                ),
+                statement.span,
+                env,
+            ));
+        }
+        self.tick(statement.span, env)
+    }
+
+    /// Stop the run if its time is up or its values have outgrown the memory cap.
+    fn tick(&self, span: Span, env: &Rc<RefCell<Environment>>) -> Result<(), Interruption> {
+        if let Some(deadline) = self.deadline.get()
+            && Instant::now() >= deadline
+        {
+            return Err(self.out_of_time(span, env));
+        }
+        if let Some(cap) = self.limits.memory {
+            let tick = self.ticks.get() + 1;
+            self.ticks.set(tick);
+            if tick >= self.next_measure.get() {
+                let mut measure = Measure::default();
+                measure.scope(env);
+                for scope in self.scopes.borrow().iter() {
+                    measure.scope(scope);
+                }
+                if measure.bytes > cap {
+                    return Err(limit_exceeded(
+                        format!(
+                            "Memory limit exceeded: the program's values take about {} bytes, more than {} (--max-memory)",
+                            measure.bytes, cap
+                        ),
+                        span,
+                        env,
+                    ));
+                }
+                // Measuring walks every value, so a program holding many is
+                // measured less often, keeping the walks to about an eighth of
+                // its work.
+                self.next_measure.set(tick + (measure.values / 8).max(1));
+            }
+        }
+        Ok(())
+    }
+
+    fn out_of_time(&self, span: Span, env: &Rc<RefCell<Environment>>) -> Interruption {
+        let seconds = self.limits.timeout.unwrap_or_default().as_secs_f64();
+        limit_exceeded(
+            format!(
+                "Time limit exceeded: the program ran for more than {} seconds (--timeout)",
+                seconds
+            ),
+            span,
+            env,
+        )
+    }
+
+    /// How long a SLEEP of `duration` may sleep without outlasting the time
+    /// limit, and whether that cuts it short.
+    #[cfg(any(not(target_arch = "wasm32"), feature = "wasi"))]
+    fn sleepable(&self, duration: Duration) -> (Duration, bool) {
+        match self.deadline.get() {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                (duration.min(left), duration > left)
+            }
+            None => (duration, false),
+        }
+    }
+}
+
+/// The turns of one run of a loop, counted against the loop limit. Each turn
+/// also gives the time and memory limits a look, as a loop whose body runs no
+/// statement would otherwise never meet them.
+struct Turns {
+    /// The run's budget, when there is any limit to look at.
+    budget: Option<Rc<Budget>>,
+    taken: u64,
+    /// The loop's, for the error that stops it.
+    span: Span,
+}
+
+impl Turns {
+    fn new(span: Span, env: &Rc<RefCell<Environment>>) -> Self {
+        let scope = env.borrow();
+        let limits = &scope.budget.limits;
+        let budget = (limits.loop_iterations.is_some() || limits.metered())
+            .then(|| Rc::clone(&scope.budget));
+        Turns {
+            budget,
+            taken: 0,
+            span,
+        }
+    }
+
+    /// Count a turn about to start.
+    fn next(&mut self, env: &Rc<RefCell<Environment>>) -> Result<(), Interruption> {
+        let span = self.span;
+        let Some(budget) = &self.budget else {
+            return Ok(());
+        };
+        self.taken += 1;
+        if let Some(most) = budget.limits.loop_iterations
+            && self.taken > most
+        {
+            return Err(limit_exceeded(
+                format!(
+                    "Maximum loop iterations exceeded (limit: {}, --max-loop-iterations)",
+                    most
+                ),
+                span,
+                env,
+            ));
+        }
+        budget.tick(span, env)
+    }
+}
 
-/// How deeply EVAL and EXECUTE may nest inside one another.
+/// An estimate of how much memory a program's values take, for `--max-memory`.
 ///
-/// Much smaller than [`MAX_STACK_DEPTH`]. One level of nested source evaluation
-/// holds a lexer, a token vector, a parser and an AST live across the recursive
-/// call: about 35 KiB of real stack per level in a debug build, against about
-/// 4 KiB for a procedure frame. At 1000 levels the process dies of a genuine
-/// stack overflow before the counter trips, which `code <- "EVAL(code)"` did.
-/// 32 keeps the worst case near a megabyte and still allows any real nesting.
-const MAX_META_DEPTH: usize = 32;
-const MAX_LOOP_ITERATIONS: usize = 1_000_000;
+/// Each value costs the slot it sits in, plus what it owns: a string its text,
+/// a list its items, a dictionary its entries, an object its fields, a closure
+/// the scope it captured. A dictionary, object or scope is shared, not copied,
+/// so it is counted the first time it is met and not again.
+#[derive(Default)]
+struct Measure {
+    bytes: u64,
+    values: u64,
+    seen: std::collections::HashSet<*const ()>,
+}
+
+impl Measure {
+    /// Whether the shared thing at `pointer` is met for the first time.
+    fn first<T>(&mut self, pointer: *const T) -> bool {
+        self.seen.insert(pointer.cast())
+    }
+
+    /// The variables of `env` and of every scope around it.
+    fn scope(&mut self, env: &Rc<RefCell<Environment>>) {
+        let mut next = Some(Rc::clone(env));
+        while let Some(env) = next {
+            if !self.first(Rc::as_ptr(&env)) {
+                return;
+            }
+            // A scope being changed right now is skipped: this is an estimate.
+            let Ok(scope) = env.try_borrow() else {
+                return;
+            };
+            for (name, value) in &scope.variables {
+                self.bytes += name.len() as u64;
+                self.value(value);
+            }
+            next = scope.parent.clone();
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        self.values += 1;
+        self.bytes += std::mem::size_of::<Value>() as u64;
+        match value {
+            Value::Integer(n) => self.bytes += n.bits() / 8,
+            Value::String(s) => self.bytes += s.len() as u64,
+            Value::List(items) => {
+                for item in items {
+                    self.value(item);
+                }
+            }
+            Value::Dictionary(dict) => self.dict(dict),
+            Value::Object(object) => {
+                if self.first(Rc::as_ptr(object))
+                    && let Ok(instance) = object.try_borrow()
+                {
+                    self.dict(&instance.fields);
+                }
+            }
+            Value::Procedure(procedure) => {
+                if let Some(scope) = &procedure.scope {
+                    self.scope(scope);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dict(&mut self, dict: &Dict) {
+        if !self.first(Rc::as_ptr(&dict.inner)) {
+            return;
+        }
+        for (key, value) in &dict.inner.entries {
+            self.bytes += std::mem::size_of::<DictKey>() as u64;
+            match key {
+                DictKey::String(s) => self.bytes += s.len() as u64,
+                DictKey::Integer(n) => self.bytes += n.bits() / 8,
+                DictKey::Boolean(_) => {}
+            }
+            self.value(value);
+        }
+    }
+}
 
 /// Size of the userspace buffer used when streaming straight to stdout.
 const STREAM_BUF_BYTES: usize = 64 * 1024;
@@ -573,7 +1139,7 @@ impl OutputSink {
 /// to find a data file beside itself. `None` for a program with no location
 /// (EVAL, the library API, the browser playground). One shared `Rc<PathBuf>` per
 /// file costs a refcount bump per call instead of a path copy.
-type Procedure = Rc<(Vec<String>, Spanned, Option<Rc<PathBuf>>)>;
+type Procedure = Rc<(Vec<Parameter>, Spanned, Option<Rc<PathBuf>>)>;
 
 /// Name -> procedure map.
 ///
@@ -583,10 +1149,15 @@ type Procedure = Rc<(Vec<String>, Spanned, Option<Rc<PathBuf>>)>;
 /// also holds forks it instead of writing through.
 type ProcedureTable = Rc<HashMap<String, Procedure>>;
 
+/// Name -> class map, snapshotted and copied-on-write exactly like
+/// [`ProcedureTable`] so a CLASS declared inside a procedure stays private to it.
+type ClassTable = Rc<HashMap<String, Rc<Class>>>;
+
 #[derive(Clone)]
 struct Environment {
     variables: HashMap<String, Value>,
     procedures: ProcedureTable,
+    classes: ClassTable,
     /// Shared by every scope in the run. Child scopes used to own a private
     /// `String` that was concatenated into the parent's on every procedure
     /// return and every caught error; sharing one sink removes those copies
@@ -602,10 +1173,22 @@ struct Environment {
     /// the call stack, because IMPORT can appear at any depth.
     modules: Rc<RefCell<ModuleState>>,
     /// How many EVAL/EXECUTE evaluations are currently nested, guarded by
-    /// [`MAX_META_DEPTH`]. Counted separately from the call stack because a
+    /// [`Limits::meta_depth`]. Counted separately from the call stack because a
     /// level of nested source costs an order of magnitude more real stack than a
     /// procedure frame does.
     meta_depth: Rc<Cell<usize>>,
+    /// The [`Observer`] following the run, if any: empty but under `fpli dap`, a
+    /// `fpli run --step`, or once a BREAKPOINT has been reached. Shared by every
+    /// scope, like the call stack, so a statement anywhere reaches it without a
+    /// walk, and so a BREAKPOINT deep in a call can attach one for the whole run.
+    watch: Rc<OnceCell<Rc<Watch>>>,
+    /// What the run may reach outside the program: everything, unless it was
+    /// started in a sandbox. Set once on the root scope, before anything runs,
+    /// and shared from there.
+    permissions: Rc<Permissions>,
+    /// What the run may take, and what it has taken so far. Set on the root
+    /// scope before anything runs, like `permissions`.
+    budget: Rc<Budget>,
 }
 
 /// Bookkeeping for a program spread across several `.psl` files.
@@ -615,6 +1198,10 @@ struct ModuleState {
     /// `None` for [`run_with_source`], EVAL-only use and the WASM playground,
     /// where there is no file on disk to be relative to.
     entry: Option<Rc<PathBuf>>,
+    /// Text of the entry script. `sources` holds imported files only, and a CATCH
+    /// handler needs this to turn the span of an error raised in the entry script
+    /// itself into a line and column.
+    entry_source: Option<Rc<str>>,
     /// The files whose code is executing, innermost last. The top of this stack
     /// is what a relative IMPORT resolves against and what SCRIPTPATH reports, so
     /// a library can reach its own neighbours without caring where the
@@ -641,7 +1228,13 @@ struct ModuleState {
     /// Recorded before the body runs, which makes IMPORT idempotent and stops a
     /// cycle from recursing: an import leading back to a file is skipped. Same
     /// semantics as Python -- a cycle is allowed, and each body runs once.
+    ///
+    /// Only imports into the entry program's scope: a module imported with AS
+    /// keeps its own list in [`Module::loaded`].
     loaded: Vec<PathBuf>,
+    /// Files imported with AS, in import order. Recorded before the body runs,
+    /// for the same reasons as `loaded`.
+    namespaces: Vec<Rc<Module>>,
 }
 
 impl ModuleState {
@@ -674,6 +1267,53 @@ impl ModuleState {
             None => false,
         }
     }
+
+    /// The module a procedure declared in `file` belongs to, if that file was
+    /// imported with AS. By identity: see [`Module::file`].
+    fn namespace_declaring(&self, file: &Rc<PathBuf>) -> Option<Rc<Module>> {
+        self.namespaces
+            .iter()
+            .find(|module| Rc::ptr_eq(&module.file, file))
+            .cloned()
+    }
+
+    /// The module already running `path`, if any.
+    fn namespace_of(&self, path: &Path) -> Option<Rc<Module>> {
+        self.namespaces
+            .iter()
+            .find(|module| module.file.as_path() == path)
+            .cloned()
+    }
+
+    /// Whether a plain IMPORT of `path` into `root` has already run.
+    fn is_loaded(&self, root: &Rc<RefCell<Environment>>, path: &Path) -> bool {
+        match self.namespace_at(root) {
+            Some(module) => module.loaded.borrow().iter().any(|p| p == path),
+            None => self.loaded.iter().any(|p| p == path),
+        }
+    }
+
+    fn set_loaded(&mut self, root: &Rc<RefCell<Environment>>, path: &Path, loaded: bool) {
+        let update = |list: &mut Vec<PathBuf>| {
+            if loaded {
+                list.push(path.to_path_buf());
+            } else {
+                list.retain(|p| p != path);
+            }
+        };
+        match self.namespace_at(root) {
+            Some(module) => update(&mut module.loaded.borrow_mut()),
+            None => update(&mut self.loaded),
+        }
+    }
+
+    /// The module whose top-level scope `root` is.
+    fn namespace_at(&self, root: &Rc<RefCell<Environment>>) -> Option<Rc<Module>> {
+        self.namespaces
+            .iter()
+            .find(|module| Rc::ptr_eq(&module.env, root))
+            .cloned()
+    }
 }
 
 impl Environment {
@@ -681,37 +1321,86 @@ impl Environment {
         Environment {
             variables: HashMap::new(),           // skipcq: RS-W1079
             procedures: Rc::new(HashMap::new()), // skipcq: RS-W1079
+            classes: Rc::new(HashMap::new()),    // skipcq: RS-W1079
             output: Rc::new(RefCell::new(OutputSink::new(mode, debug))), // skipcq: RS-W1079
             parent: None,
             call_stack: Rc::new(RefCell::new(Vec::new())), // skipcq: RS-W1079
             parsed_flags: Rc::new(HashMap::new()),         // skipcq: RS-W1079
             modules: Rc::new(RefCell::new(ModuleState::default())), // skipcq: RS-W1079
             meta_depth: Rc::new(Cell::new(0)),             // skipcq: RS-W1079
+            watch: Rc::new(OnceCell::new()),               // skipcq: RS-W1079
+            permissions: Rc::new(Permissions::default()),  // skipcq: RS-W1079
+            budget: Rc::new(Budget::new(Limits::default())), // skipcq: RS-W1079
         }
     }
 
     fn new_with_parent(parent: Rc<RefCell<Environment>>) -> Self {
-        let (procedures, output, call_stack, parsed_flags, modules, meta_depth) = {
+        let (
+            procedures,
+            classes,
+            output,
+            call_stack,
+            parsed_flags,
+            modules,
+            meta_depth,
+            watch,
+            permissions,
+            budget,
+        ) = {
             let p = parent.borrow();
             (
                 Rc::clone(&p.procedures),
+                Rc::clone(&p.classes),
                 Rc::clone(&p.output),
                 Rc::clone(&p.call_stack),
                 Rc::clone(&p.parsed_flags),
                 Rc::clone(&p.modules),
                 Rc::clone(&p.meta_depth),
+                Rc::clone(&p.watch),
+                Rc::clone(&p.permissions),
+                Rc::clone(&p.budget),
             )
         };
         Environment {
             variables: HashMap::new(), // skipcq: RS-W1079
             procedures,
+            classes,
             output,
             parent: Some(Rc::clone(&parent)),
             call_stack,
             parsed_flags,
             modules,
             meta_depth,
+            watch,
+            permissions,
+            budget,
+        }
+    }
+
+    /// The top-level scope of a module imported with AS: a root of its own, sharing
+    /// everything run-wide with `root`, the entry program's. The built-in
+    /// variables come along, so a library reads ARGS the way the program does.
+    fn new_module(root: &Environment) -> Self {
+        let mut env = Environment {
+            variables: HashMap::new(),           // skipcq: RS-W1079
+            procedures: Rc::new(HashMap::new()), // skipcq: RS-W1079
+            classes: Rc::new(HashMap::new()),    // skipcq: RS-W1079
+            output: Rc::clone(&root.output),
+            parent: None,
+            call_stack: Rc::clone(&root.call_stack),
+            parsed_flags: Rc::clone(&root.parsed_flags),
+            modules: Rc::clone(&root.modules),
+            meta_depth: Rc::clone(&root.meta_depth),
+            watch: Rc::clone(&root.watch),
+            permissions: Rc::clone(&root.permissions),
+            budget: Rc::clone(&root.budget),
+        };
+        for name in MODULE_BUILTINS {
+            if let Some(value) = root.variables.get(name) {
+                env.variables.insert(name.to_string(), value.clone());
+            }
         }
+        env
     }
 
     /// The run-wide output sink. Cheap to reach: every scope holds the same
@@ -722,27 +1411,31 @@ impl Environment {
     }
 
     fn get(&self, name: &str) -> Option<Value> {
-        if let Some(value) = self.variables.get(name) {
-            return Some(value.clone());
-        }
-        if let Some(ref parent) = self.parent {
-            return parent.borrow().get(name);
-        }
-        None
+        self.with_var(name, Value::clone)
     }
 
     /// Look `name` up along the scope chain and hand the binding to `f` without
     /// copying it, so reading one element out of a container does not clone the
     /// container. `None` means the name is unbound.
     ///
-    /// `f` runs while every scope from here to the owning one is immutably
-    /// borrowed, so it must not evaluate anything that could mutate a scope.
+    /// The chain is walked in a loop rather than by recursion: scoping is
+    /// dynamic, so a call chain as deep as the recursion limit is a scope chain
+    /// that deep too, and a name found only at the top must not cost a native
+    /// frame per scope.
+    ///
+    /// `f` runs while the owning scope is immutably borrowed, so it must not
+    /// evaluate anything that could mutate a scope.
     fn with_var<R>(&self, name: &str, f: impl FnOnce(&Value) -> R) -> Option<R> {
         if let Some(value) = self.variables.get(name) {
             return Some(f(value));
         }
-        if let Some(ref parent) = self.parent {
-            return parent.borrow().with_var(name, f);
+        let mut next = self.parent.clone();
+        while let Some(scope) = next {
+            let scope = scope.borrow();
+            if let Some(value) = scope.variables.get(name) {
+                return Some(f(value));
+            }
+            next = scope.parent.clone();
         }
         None
     }
@@ -856,6 +1549,32 @@ impl Environment {
         }
     }
 
+    fn declare_class(&mut self, class: Class) {
+        Rc::make_mut(&mut self.classes).insert(class.name.clone(), Rc::new(class));
+    }
+
+    fn get_class(&self, name: &str) -> Option<Rc<Class>> {
+        self.classes.get(name).cloned()
+    }
+
+    fn class_table(&self) -> ClassTable {
+        Rc::clone(&self.classes)
+    }
+
+    /// [`Environment::merge_procedures`] for classes, with the same rule that a
+    /// scope's own declaration wins.
+    fn merge_classes(&mut self, other: &ClassTable) {
+        if other.keys().all(|name| self.classes.contains_key(name)) {
+            return;
+        }
+        let table = Rc::make_mut(&mut self.classes);
+        for (name, class) in other.iter() {
+            table
+                .entry(name.clone())
+                .or_insert_with(|| Rc::clone(class));
+        }
+    }
+
     fn push_frame(&self, frame: StackFrame) {
         self.call_stack.borrow_mut().push(frame);
     }
@@ -922,10 +1641,42 @@ fn init_env_with_args(env: &Rc<RefCell<Environment>>, args: &[String]) {
     env_mut.parsed_flags = Rc::new(flags);
 }
 
+/// How a run of a whole program is set up, beyond its source and where its
+/// output goes. [`RunOptions::default`] is a run with no arguments, no file
+/// behind it, nothing following it, every permission and the default limits.
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    /// The program's command-line arguments, as ARGS and the flag built-ins see
+    /// them.
+    pub args: Vec<String>,
+    /// The file the source came from. It is what IMPORT resolves relative paths
+    /// against and what SCRIPTPATH and ISMAIN report; `None` means the program
+    /// has no location, and those report nothing rather than guessing.
+    pub script_path: Option<PathBuf>,
+    /// Follows the run from its first statement: the console debugger for
+    /// `fpli run --step`, the tracer for `--trace`. Without one, the console
+    /// debugger is only attached if the program reaches BREAKPOINT().
+    pub observer: Option<Rc<RefCell<dyn Observer>>>,
+    /// What the program may reach outside itself: everything, or what the
+    /// sandbox flags of `fpli run` grant.
+    pub permissions: Permissions,
+    /// How much the program may take before it is stopped: see [`Limits`].
+    pub limits: Limits,
+}
+
 /// Capturing run: the whole output is accumulated and returned. Used by the
 /// test suite, the library API and WASM.
 pub fn run_with_source(ast: Spanned, source: &str, args: &[String]) -> Result<String, PSLError> {
-    run_with_mode(ast, source, args, OutputMode::Capture, false, None)
+    run_with_mode(
+        ast,
+        source,
+        OutputMode::Capture,
+        false,
+        RunOptions {
+            args: args.to_vec(),
+            ..RunOptions::default()
+        },
+    )
 }
 
 /// Capturing run of a program that came from a file.
@@ -945,10 +1696,13 @@ pub fn run_with_source_at(
     run_with_mode(
         ast,
         source,
-        args,
         OutputMode::Capture,
         false,
-        script_path.map(Path::to_path_buf),
+        RunOptions {
+            args: args.to_vec(),
+            script_path: script_path.map(Path::to_path_buf),
+            ..RunOptions::default()
+        },
     )
 }
 
@@ -960,35 +1714,37 @@ pub fn run_with_source_at(
 /// terminal ahead of the caller's stderr report.
 ///
 /// `debug` here only selects the sink's flush policy; the evaluator's own trace
-/// flag stays `false`, exactly as it always has been.
+/// flag stays `false`, exactly as it always has been. Everything else about the
+/// run is in `options`.
 pub fn run_with_mode(
     ast: Spanned,
-    _source: &str,
-    args: &[String],
+    source: &str,
     mode: OutputMode,
     debug: bool,
-    script_path: Option<PathBuf>,
+    options: RunOptions,
 ) -> Result<String, PSLError> {
-    let env = Rc::new(RefCell::new(Environment::new(mode, debug)));
-    init_env_with_args(&env, args);
-    if let Some(path) = script_path {
-        // Fully resolved from the start, for two reasons. The program may CHDIR,
-        // and an entry recorded as a relative path would then no longer point at
-        // the same file. And it has to be comparable with the canonicalised paths
-        // IMPORT produces, or a library that imports the entry script would not be
-        // recognised as importing something already running -- on macOS, for
-        // instance, `/tmp/x.psl` and its real path `/private/tmp/x.psl` are the
-        // same file spelled two ways.
-        let spelled = path.to_string_lossy().into_owned();
-        let resolved = system::realpath(&spelled)
-            .or_else(|_| system::abspath(&spelled))
-            .map(PathBuf::from)
-            .unwrap_or(path);
-        env.borrow().modules.borrow_mut().entry = Some(Rc::new(resolved));
+    let RunOptions {
+        args,
+        script_path,
+        observer,
+        permissions,
+        limits,
+    } = options;
+    let env = entry_env(source, &args, mode, debug, script_path, permissions, limits);
+    if let Some(observer) = observer {
+        let _ = env.borrow().watch.set(Rc::new(Watch::new(observer)));
     }
     let result = evaluate_node(&ast, Rc::clone(&env), false);
+    let watch = env.borrow().watch.get().cloned();
     let sink = Rc::clone(env.borrow().sink());
     let output = sink.borrow_mut().finish();
+    if let Some(watch) = watch {
+        // The same cycle [`run_observed`] breaks.
+        watch.reached.borrow_mut().clear();
+        // After the program's output is flushed, so that what the observer has
+        // to say at the end comes after it.
+        watch.ended();
+    }
     // A failed write to stdout (a full disk, a closed descriptor) must not be
     // swallowed: the program would otherwise exit 0 having silently lost
     // output. A real error beats a plausible-looking empty result.
@@ -999,10 +1755,17 @@ pub fn run_with_mode(
             stack_trace: Vec::new(),
             source: None,
             origin: None,
+            kind: ErrorKind::Runtime,
         });
     }
     match result {
-        Ok(_) | Err(Interruption::Return(_)) => Ok(output),
+        // The parser refuses BREAK and CONTINUE outside a loop, so neither gets
+        // here; if one did, ending the run like a top-level RETURN is the only
+        // reading that fits.
+        Ok(_)
+        | Err(Interruption::Return(_))
+        | Err(Interruption::Break)
+        | Err(Interruption::Continue) => Ok(output),
         Err(Interruption::Exit(code)) => {
             // Streaming to a terminal means this is the CLI, and EXIT there has to
             // set the process's status. Everything printed is already flushed above.
@@ -1015,946 +1778,3241 @@ pub fn run_with_mode(
             Ok(output)
         }
         Err(Interruption::Error(e)) => Err(e),
+        Err(Interruption::Limit(e)) => Err(*e),
+        Err(Interruption::Throw(thrown)) => Err(thrown.error),
     }
 }
 
-fn evaluate_node(node: &Spanned, env: Rc<RefCell<Environment>>, debug: bool) -> EvalResult {
-    #[cfg(not(target_arch = "wasm32"))]
-    return stacker::maybe_grow(64 * 1024, 2 * 1024 * 1024, || {
-        evaluate_node_impl(node, env, debug)
-    });
-
-    #[cfg(target_arch = "wasm32")]
-    evaluate_node_impl(node, env, debug)
+/// The root scope of a run of the program `source`, read from `script_path`,
+/// allowed what `permissions` grant and as much as `limits` allow.
+fn entry_env(
+    source: &str,
+    args: &[String],
+    mode: OutputMode,
+    debug: bool,
+    script_path: Option<PathBuf>,
+    permissions: Permissions,
+    limits: Limits,
+) -> Rc<RefCell<Environment>> {
+    let env = Rc::new(RefCell::new(Environment::new(mode, debug)));
+    env.borrow_mut().permissions = Rc::new(permissions);
+    env.borrow_mut().budget = Rc::new(Budget::new(limits));
+    init_env_with_args(&env, args);
+    env.borrow().modules.borrow_mut().entry_source = Some(Rc::from(source));
+    if let Some(path) = script_path {
+        // Fully resolved from the start, for two reasons. The program may CHDIR,
+        // and an entry recorded as a relative path would then no longer point at
+        // the same file. And it has to be comparable with the canonicalised paths
+        // IMPORT produces, or a library that imports the entry script would not be
+        // recognised as importing something already running -- on macOS, for
+        // instance, `/tmp/x.psl` and its real path `/private/tmp/x.psl` are the
+        // same file spelled two ways.
+        let spelled = path.to_string_lossy().into_owned();
+        let resolved = system::realpath(&spelled)
+            .or_else(|_| system::abspath(&spelled))
+            .map(PathBuf::from)
+            .unwrap_or(path);
+        env.borrow().modules.borrow_mut().entry = Some(Rc::new(resolved));
+    }
+    env
 }
 
-/// Evaluate a node in *statement position*, where its value is discarded.
+/// How one test procedure went.
+pub struct TestOutcome {
+    /// Everything the test printed, the program's top level included.
+    pub output: String,
+    /// What ended the test early: a failed assertion or any other error.
+    pub error: Option<PSLError>,
+}
+
+/// Run the program in `source` in a fresh environment, then call its procedure
+/// `test` with no arguments: what `fpli test` does once for every test.
 ///
-/// Behaviour is identical to [`evaluate_node`] in every observable way; the
-/// only difference is that an assignment is free to append into the string it
-/// already holds instead of building a new one, which it cannot do when the
-/// assignment's value is still needed. Contexts that DO observe the value --
-/// the last statement of a block (a procedure without RETURN yields it) and the
-/// last iteration of FOR EACH -- still go through [`evaluate_node`].
-fn evaluate_for_effect(
-    node: &Spanned,
+/// Each test gets an environment of its own, so nothing one test sets or
+/// changes is seen by the next, whatever order they run in. Output is captured
+/// rather than printed, to be shown only for a test that fails. An EXIT with
+/// status 0 ends a test as passing; any other status fails it.
+///
+/// `observer`, if there is one, follows the test from the file's first
+/// statement: the coverage of `fpli test --coverage`. It is not told the run
+/// has ended, as the same observer may follow every test there is to run.
+///
+/// `permissions` and `limits` are what the test may reach outside the program
+/// and how much it may take, as for [`run_with_mode`]. Each test has the whole
+/// of `limits` to itself.
+#[allow(clippy::too_many_arguments)]
+pub fn run_test(
+    ast: &Spanned,
+    source: &str,
+    script_path: &Path,
+    test: &str,
+    span: Span,
+    observer: Option<Rc<RefCell<dyn Observer>>>,
+    permissions: &Permissions,
+    limits: &Limits,
+) -> TestOutcome {
+    let env = entry_env(
+        source,
+        &[],
+        OutputMode::Capture,
+        false,
+        Some(script_path.to_path_buf()),
+        permissions.clone(),
+        limits.clone(),
+    );
+    let watch = observer.map(|observer| Rc::new(Watch::new(observer)));
+    if let Some(watch) = &watch {
+        let _ = env.borrow().watch.set(Rc::clone(watch));
+    }
+    let result = evaluate_node(ast, Rc::clone(&env), false)
+        .and_then(|_| invoke_named(test, Vec::new(), &env, span, false));
+    if let Some(watch) = watch {
+        // The same cycle [`run_observed`] breaks.
+        watch.reached.borrow_mut().clear();
+    }
+    let output = env.borrow().sink().borrow_mut().finish();
+    let error = match result {
+        Ok(_)
+        | Err(Interruption::Return(_))
+        | Err(Interruption::Break)
+        | Err(Interruption::Continue)
+        | Err(Interruption::Exit(0)) => None,
+        Err(Interruption::Exit(code)) => Some(PSLError::with_span(
+            format!("The test ended the program with EXIT({})", code),
+            span,
+        )),
+        Err(Interruption::Error(e)) => Some(e),
+        Err(Interruption::Limit(e)) => Some(*e),
+        Err(Interruption::Throw(thrown)) => Some(thrown.error),
+    };
+    TestOutcome { output, error }
+}
+
+/// Something that follows a run statement by statement, and can hold it still in
+/// between: how `fpli dap` and the console debugger debug a program, and how
+/// `fpli run --trace` follows one.
+///
+/// The interpreter calls [`Observer::before_statement`] before every statement
+/// it runs, in every block, at every depth, in imported files and EVALed source
+/// alike. The run does not move until the call returns, so an observer that
+/// wants the program paused simply does not return yet: it can look at the
+/// stack and the variables through the [`Paused`] it was handed, for as long as
+/// it likes.
+///
+/// An observer that also wants to see values change -- every assignment, and
+/// every procedure's arguments and result -- says so with
+/// [`Observer::follows_values`]. Handing those over costs a copy of each value,
+/// so the others are spared it.
+pub trait Observer {
+    /// Called before each statement, with the run paused on it. Returning
+    /// `Some(status)` ends the run there, as `EXIT(status)` would.
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32>;
+
+    /// The line the next INPUT reads, or `None` to read standard input as usual.
+    /// An observer that owns standard input -- a debug adapter, whose protocol
+    /// arrives on it -- supplies the lines itself, or a message for an error
+    /// that the INPUT then raises.
+    fn read_line(&mut self) -> Option<Result<String, String>> {
+        None
+    }
+
+    /// Called when the program reaches BREAKPOINT(), asking to be stopped at the
+    /// statement after it.
+    fn breakpoint(&mut self) {}
+
+    /// Whether to call [`Observer::assigned`], [`Observer::entered`] and
+    /// [`Observer::returned`]. Asked once, when the observer is attached.
+    fn follows_values(&self) -> bool {
+        false
+    }
+
+    /// Called when an assignment or a loop binds `name` to `value`, at call
+    /// depth `depth`.
+    fn assigned(&mut self, _depth: usize, _name: &str, _value: &Inspected) {}
+
+    /// Called when a procedure starts, with each parameter bound to its argument.
+    /// `depth` is the procedure's own: one more than its caller's.
+    fn entered(&mut self, _depth: usize, _name: &str, _arguments: &[(String, Inspected)]) {}
+
+    /// Called when a procedure finishes with `value`, at its own depth. A
+    /// procedure ended by an error does not return.
+    fn returned(&mut self, _depth: usize, _name: &str, _value: &Inspected) {}
+
+    /// Whether to call [`Observer::called`] and [`Observer::left`]: every call,
+    /// with nothing copied. Asked once, when the observer is attached.
+    fn follows_calls(&self) -> bool {
+        false
+    }
+
+    /// Called when the procedure `name`, declared in `file`, is called: before
+    /// its arguments are bound, so that working out a default is part of it.
+    fn called(&mut self, _name: &str, _file: Option<&Path>) {}
+
+    /// Called when the procedure last called is over, however it ended.
+    fn left(&mut self) {}
+
+    /// Called once the run is over, however it ended, and before EXIT ends the
+    /// process.
+    fn ended(&mut self) {}
+}
+
+/// An [`Observer`] attached to a run, and what the run has reached so far.
+struct Watch {
+    observer: Rc<RefCell<dyn Observer>>,
+    /// What [`Observer::follows_values`] said.
+    values: bool,
+    /// What [`Observer::follows_calls`] said.
+    calls: bool,
+    /// The statement last reached at each depth of the call stack, the top level
+    /// first. Everything below the innermost is a statement whose call is still
+    /// in progress, which is what gives every frame of a paused run a line to
+    /// show and a scope to look into.
+    reached: RefCell<Vec<Reached>>,
+    /// Where each line of a file starts, by the file's text: worked out once per
+    /// file rather than by counting from the top on every statement.
+    lines: RefCell<Vec<(Rc<str>, Vec<usize>)>>,
+}
+
+#[derive(Clone)]
+struct Reached {
     env: Rc<RefCell<Environment>>,
-    debug: bool,
-) -> Result<(), Interruption> {
-    match &node.node {
-        AstNode::Assignment(target, value) => {
-            if let Some(result) = try_self_append(target, value, &env, debug) {
-                return result;
+    span: Span,
+    /// The file the statement is in: `None` in source EVAL or EXECUTE generated,
+    /// and in a program that did not come from a file.
+    file: Option<Rc<PathBuf>>,
+    /// The text `span` indexes.
+    source: Rc<str>,
+}
+
+impl Watch {
+    fn new(observer: Rc<RefCell<dyn Observer>>) -> Watch {
+        let values = observer.borrow().follows_values();
+        let calls = observer.borrow().follows_calls();
+        Watch {
+            observer,
+            values,
+            calls,
+            reached: RefCell::default(),
+            lines: RefCell::default(),
+        }
+    }
+
+    /// Record that `statement` is about to run in `env`, and hand the run to the
+    /// observer until it lets go.
+    ///
+    /// The observer is busy while the run is paused, and anything that runs then
+    /// -- an expression it evaluates, and whatever that calls -- runs unobserved,
+    /// here and in [`Watch::read_line`] and [`Watch::breakpoint`] alike.
+    fn before(
+        &self,
+        statement: &Spanned,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<(), Interruption> {
+        let Ok(mut observer) = self.observer.try_borrow_mut() else {
+            return Ok(());
+        };
+        // Nothing can be learned by stopping on a statement that does nothing.
+        if does_nothing(statement) {
+            return Ok(());
+        }
+        let (depth, reached) = {
+            let scope = env.borrow();
+            let modules = scope.modules.borrow();
+            // Which text the span indexes, by the same rule errors are rendered by.
+            let (file, source) = match modules.generated.last() {
+                Some((source, _)) => (None, Rc::clone(source)),
+                None => (
+                    modules.current_file(),
+                    modules
+                        .current_source()
+                        .map(|(source, _)| source)
+                        .or_else(|| modules.entry_source.clone())
+                        .unwrap_or_else(|| Rc::from("")),
+                ),
+            };
+            let depth = scope.call_stack.borrow().len();
+            let reached = Reached {
+                env: Rc::clone(env),
+                span: statement.span,
+                file,
+                source,
+            };
+            (depth, reached)
+        };
+        {
+            let mut trail = self.reached.borrow_mut();
+            trail.truncate(depth);
+            // A depth with no statement of its own -- a procedure called from a
+            // default parameter value -- borrows this one's place.
+            while trail.len() < depth {
+                trail.push(reached.clone());
             }
-            evaluate_node(node, env, debug).map(|_| ())
+            trail.push(reached);
+        }
+        let paused = Paused {
+            watch: self,
+            trail: self.reached.borrow(),
+        };
+        match observer.before_statement(&paused) {
+            Some(status) => Err(Interruption::Exit(status)),
+            None => Ok(()),
         }
+    }
 
-        // The only node kinds that recurse back into this function, and so the
-        // only ones needing the stack-headroom check `evaluate_node` makes.
-        // Every other arm ends in a call to `evaluate_node`, which makes it.
-        AstNode::Program(_) | AstNode::Block(_) | AstNode::If(_, _, _) => {
-            evaluate_nested_for_effect(node, env, debug)
+    fn read_line(&self) -> Option<Result<String, String>> {
+        self.observer.try_borrow_mut().ok()?.read_line()
+    }
+
+    fn breakpoint(&self) {
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            observer.breakpoint();
         }
+    }
 
-        _ => evaluate_node(node, env, debug).map(|_| ()),
+    /// The watch on the run `env` belongs to, if its observer follows values.
+    fn following(env: &Rc<RefCell<Environment>>) -> Option<Rc<Watch>> {
+        env.borrow()
+            .watch
+            .get()
+            .filter(|watch| watch.values)
+            .cloned()
     }
-}
 
-fn evaluate_nested_for_effect(
-    node: &Spanned,
-    env: Rc<RefCell<Environment>>,
-    debug: bool,
-) -> Result<(), Interruption> {
-    #[cfg(not(target_arch = "wasm32"))]
-    return stacker::maybe_grow(64 * 1024, 2 * 1024 * 1024, || {
-        evaluate_nested_for_effect_impl(node, env, debug)
-    });
+    /// The watch on the run `env` belongs to, if its observer follows calls,
+    /// with or without their values.
+    fn following_calls(env: &Rc<RefCell<Environment>>) -> Option<Rc<Watch>> {
+        env.borrow()
+            .watch
+            .get()
+            .filter(|watch| watch.calls || watch.values)
+            .cloned()
+    }
 
-    #[cfg(target_arch = "wasm32")]
-    evaluate_nested_for_effect_impl(node, env, debug)
-}
+    fn assigned(&self, name: &str, value: &Value, env: &Rc<RefCell<Environment>>) {
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            let depth = env.borrow().stack_depth();
+            observer.assigned(depth, name, &Inspected(value.clone()));
+        }
+    }
 
-fn evaluate_nested_for_effect_impl(
-    node: &Spanned,
-    env: Rc<RefCell<Environment>>,
-    debug: bool,
-) -> Result<(), Interruption> {
-    match &node.node {
-        // Every statement of a discarded block is itself discarded.
-        AstNode::Program(statements) | AstNode::Block(statements) => {
-            for stmt in statements {
-                evaluate_for_effect(stmt, Rc::clone(&env), debug)?;
-            }
-            Ok(())
+    fn called(&self, name: &str, file: Option<&Path>) {
+        if !self.calls {
+            return;
         }
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            observer.called(name, file);
+        }
+    }
 
-        // A discarded IF discards whichever branch it takes. Mirrors the
-        // `AstNode::If` arm of `evaluate_node_impl`, error and all.
-        AstNode::If(condition, then_branch, else_branch) => {
-            let cond_val = evaluate_node(condition, Rc::clone(&env), debug)?;
-            match cond_val {
-                Value::Boolean(true) => evaluate_for_effect(then_branch, env, debug),
-                Value::Boolean(false) => match else_branch {
-                    Some(else_branch) => evaluate_for_effect(else_branch, env, debug),
-                    None => Ok(()),
-                },
-                _ => Err(runtime_err("Condition must be a boolean", node.span, &env)),
-            }
+    fn left(&self) {
+        if !self.calls {
+            return;
+        }
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            observer.left();
         }
+    }
 
-        _ => evaluate_node(node, env, debug).map(|_| ()),
+    fn ended(&self) {
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            observer.ended();
+        }
     }
-}
 
-/// Run one loop-body iteration, in statement position unless the loop hands its
-/// body's value back (FOR EACH evaluates to the value of its LAST iteration).
-fn evaluate_loop_body(
-    body: &Spanned,
-    env: Rc<RefCell<Environment>>,
-    debug: bool,
-    is_last: bool,
-) -> EvalResult {
-    if is_last {
-        evaluate_node(body, env, debug)
-    } else {
-        evaluate_for_effect(body, env, debug).map(|()| Value::Unit)
+    /// `scope` is the procedure's own, its parameters already bound.
+    fn entered(&self, name: &str, params: &[Parameter], scope: &Rc<RefCell<Environment>>) {
+        if !self.values {
+            return;
+        }
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            let scope = scope.borrow();
+            let arguments: Vec<(String, Inspected)> = params
+                .iter()
+                .map(|param| {
+                    let value = scope.get(&param.name).unwrap_or(Value::Unit);
+                    (param.name.clone(), Inspected(value))
+                })
+                .collect();
+            observer.entered(scope.stack_depth(), name, &arguments);
+        }
     }
-}
 
-/// The source expression of a self-append assignment: `x <- x + <expr>` or
-/// `x <- CONCAT(x, <expr>)`, both written with `name` as the left operand.
-fn self_append_source<'a>(name: &str, value: &'a Spanned) -> Option<&'a Spanned> {
-    let (left, right) = match &value.node {
-        AstNode::BinaryOp(left, BinaryOperator::Add, right) => (left, right),
-        AstNode::Concat(left, right) => (left, right),
-        _ => return None,
-    };
-    match &left.node {
-        AstNode::Identifier(left_name) if left_name == name => Some(right),
-        _ => None,
+    fn returned(&self, name: &str, value: &Value, env: &Rc<RefCell<Environment>>) {
+        if !self.values {
+            return;
+        }
+        if let Ok(mut observer) = self.observer.try_borrow_mut() {
+            let depth = env.borrow().stack_depth();
+            observer.returned(depth, name, &Inspected(value.clone()));
+        }
+    }
+
+    /// The 1-based line and column of `reached`.
+    fn position(&self, reached: &Reached) -> (usize, usize) {
+        let starts_of = |source: &str| -> Vec<usize> {
+            std::iter::once(0)
+                .chain(
+                    source
+                        .chars()
+                        .enumerate()
+                        .filter(|(_, c)| *c == '\n')
+                        .map(|(i, _)| i + 1),
+                )
+                .collect()
+        };
+        let locate = |starts: &[usize]| {
+            let line = starts.partition_point(|&start| start <= reached.span.start);
+            (line, reached.span.start - starts[line - 1] + 1)
+        };
+        // Generated source is new text every time EVAL runs, and short: counting
+        // it afresh beats keeping every string a loop ever EVALed.
+        if reached.file.is_none() && !self.is_entry(reached) {
+            return locate(&starts_of(&reached.source));
+        }
+        let mut lines = self.lines.borrow_mut();
+        let index = match lines
+            .iter()
+            .position(|(source, _)| Rc::ptr_eq(source, &reached.source))
+        {
+            Some(index) => index,
+            None => {
+                lines.push((Rc::clone(&reached.source), starts_of(&reached.source)));
+                lines.len() - 1
+            }
+        };
+        locate(&lines[index].1)
+    }
+
+    /// Whether `reached` is in the entry program's own text.
+    fn is_entry(&self, reached: &Reached) -> bool {
+        reached
+            .env
+            .borrow()
+            .modules
+            .borrow()
+            .entry_source
+            .as_ref()
+            .is_some_and(|entry| Rc::ptr_eq(entry, &reached.source))
     }
 }
 
-/// Fast path for the string-building idiom every student writes:
-///
-/// ```text
-/// s <- ""
-/// REPEAT n TIMES { s <- s + "x" }
-/// ```
-///
-/// Evaluated literally that is O(n^2): each iteration clones `s` out of the
-/// environment, allocates a fresh `String` for the concatenation, and clones it
-/// again on the way back in. Appending into the `String` that is already there
-/// makes the loop O(n) amortised and drops the allocation per iteration.
+/// A run held still before a statement, as an [`Observer`] sees it.
 ///
-/// Returns `None` when the shape does not apply, leaving the caller to take the
-/// ordinary assignment path. `Some` means the assignment is finished (or has
-/// failed); the right-hand side has been evaluated exactly once either way.
-fn try_self_append(
-    target: &Spanned,
-    value: &Spanned,
-    env: &Rc<RefCell<Environment>>,
-    debug: bool,
-) -> Option<Result<(), Interruption>> {
-    if debug {
-        // Keep the `Assigning ...` trace exactly as the ordinary path prints it.
-        return None;
+/// Frames are numbered from the innermost, 0 being the one the statement about
+/// to run is in, as a debugger lists them.
+pub struct Paused<'a> {
+    watch: &'a Watch,
+    trail: std::cell::Ref<'a, Vec<Reached>>,
+}
+
+/// Where a debugger stepping through a run should stop next, besides at a
+/// breakpoint. Shared by `fpli dap` and the console debugger, which ask
+/// [`Paused::stops_for`] at every statement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resume {
+    /// Only at a breakpoint.
+    Run,
+    /// At the very next statement.
+    Step,
+    /// At the next statement no deeper in the call stack than this.
+    Over(usize),
+    /// At the next statement shallower in the call stack than this.
+    Out(usize),
+}
+
+/// One frame of a paused run's call stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The procedure running in it, or `<main>` for the program's top level.
+    pub name: String,
+    /// The file it is running in, or `None` for source with no file.
+    pub file: Option<PathBuf>,
+    /// Where it is, 1-based: the statement about to run in the innermost frame,
+    /// and the statement making the call in progress in every other.
+    pub line: usize,
+    pub column: usize,
+}
+
+/// One scope of a frame: its variables, sorted by name.
+pub struct Scope {
+    /// `Locals` for the frame's own scope, `Globals` for the top level of the file
+    /// it is running in, and `Enclosing` for any scope in between.
+    pub name: &'static str,
+    pub variables: Vec<(String, Inspected)>,
+}
+
+/// A value a run holds, for an observer to show or for a Rust program
+/// embedding the interpreter to hand back to it. Looking at it changes nothing
+/// in the program.
+#[derive(Clone)]
+pub struct Inspected(Value);
+
+/// Shown as a debugger would show it.
+impl std::fmt::Debug for Inspected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render())
     }
-    let AstNode::Identifier(name) = &target.node else {
-        return None;
-    };
-    let source = self_append_source(name, value)?;
+}
 
-    // One lookup does double duty, so a non-string target pays nothing for
-    // passing through here. A string is appended to in place below; anything
-    // else (`i <- i + 1`, a list, ...) comes back as the copy the ordinary path
-    // would have made when it evaluated the left operand, and is combined with
-    // the right-hand side further down instead. An unbound name is left to the
-    // ordinary path, which owns that error.
-    let bound = {
-        let scope = env.borrow();
-        scope.with_var(name, |current| match current {
-            Value::String(_) => None,
-            other => Some(other.clone()),
-        })
-    };
-    let not_a_string = bound?;
+/// Equal as `=` finds them: an object only to itself.
+impl PartialEq for Inspected {
+    fn eq(&self, other: &Self) -> bool {
+        values_equal(&self.0, &other.0)
+    }
+}
 
-    // Evaluated BEFORE the mutable borrow below, which is what makes
-    // `s <- s + s`, `s <- s + f(s)` and friends safe: the right-hand side may
-    // read the target or call into a procedure, and taking the borrow first
-    // would panic the RefCell.
-    let appended = match evaluate_node(source, Rc::clone(env), debug) {
-        Ok(val) => val,
-        Err(err) => return Some(Err(err)),
-    };
+impl Paused<'_> {
+    fn innermost(&self) -> &Reached {
+        self.trail
+            .last()
+            .expect("a paused run is always on some statement")
+    }
 
-    // Past this point the right-hand side has already run, so there is no
-    // falling back to the ordinary path: that would run its side effects twice.
-    if let Some(left) = not_a_string {
-        return Some(store_combined(name, left, appended, value, env));
+    /// How many calls deep the statement is: 0 at the top level.
+    pub fn depth(&self) -> usize {
+        self.trail.len() - 1
     }
-    let Value::String(text) = appended else {
-        // `s <- s + 1` and the like: the ordinary path's type error, from the
-        // same span, with the right-hand side still evaluated only once.
-        return Some(combine_with_current(name, appended, value, env));
-    };
-    let stored = env
-        .borrow_mut()
-        .with_var_mut(name, |current| match current {
-            Value::String(s) => {
-                s.push_str(&text);
-                Ok(())
-            }
-            _ => Err(()),
+
+    /// Whether a debugger stepping as `resume` stops at this statement.
+    pub fn stops_for(&self, resume: Resume) -> bool {
+        match resume {
+            Resume::Run => false,
+            Resume::Step => true,
+            Resume::Over(depth) => self.depth() <= depth,
+            Resume::Out(depth) => self.depth() < depth,
+        }
+    }
+
+    /// The span of the statement about to run, in the text of its file.
+    pub fn span(&self) -> Span {
+        self.innermost().span
+    }
+
+    /// The file the statement is in, or `None` for source with no file.
+    pub fn file(&self) -> Option<&Path> {
+        self.innermost().file.as_deref().map(PathBuf::as_path)
+    }
+
+    /// The 1-based line the statement starts on.
+    pub fn line(&self) -> usize {
+        self.watch.position(self.innermost()).0
+    }
+
+    /// The text of the file the statement is in, which [`Paused::span`] indexes.
+    pub fn source(&self) -> &str {
+        &self.innermost().source
+    }
+
+    /// The call stack, innermost frame first. Names come from the interpreter's
+    /// own call stack, the one an error's stack trace is made from.
+    pub fn frames(&self) -> Vec<Frame> {
+        let call_stack = Rc::clone(&self.innermost().env.borrow().call_stack);
+        let call_stack = call_stack.borrow();
+        self.trail
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(depth, reached)| {
+                let (line, column) = self.watch.position(reached);
+                let name = match depth.checked_sub(1) {
+                    None => "<main>".to_string(),
+                    Some(index) => call_stack
+                        .get(index)
+                        .map_or_else(String::new, |frame| frame.name.clone()),
+                };
+                Frame {
+                    name,
+                    file: reached.file.as_deref().cloned(),
+                    line,
+                    column,
+                }
+            })
+            .collect()
+    }
+
+    fn reached(&self, frame: usize) -> Option<&Reached> {
+        self.trail
+            .len()
+            .checked_sub(frame + 1)
+            .map(|i| &self.trail[i])
+    }
+
+    /// The scopes `frame` can see, its own first and its file's top level last.
+    /// Empty for a frame that does not exist.
+    pub fn scopes(&self, frame: usize) -> Vec<Scope> {
+        let mut scopes = Vec::new();
+        let mut scope = self.reached(frame).map(|reached| Rc::clone(&reached.env));
+        while let Some(env) = scope {
+            let env = env.borrow();
+            let name = if env.parent.is_none() {
+                "Globals"
+            } else if scopes.is_empty() {
+                "Locals"
+            } else {
+                "Enclosing"
+            };
+            let mut variables: Vec<(String, Inspected)> = env
+                .variables
+                .iter()
+                .map(|(name, value)| (name.clone(), Inspected(value.clone())))
+                .collect();
+            variables.sort_by(|a, b| a.0.cmp(&b.0));
+            scopes.push(Scope { name, variables });
+            scope = env.parent.clone();
+        }
+        scopes
+    }
+
+    /// The variable `name` as `frame` sees it.
+    pub fn lookup(&self, frame: usize, name: &str) -> Option<Inspected> {
+        self.reached(frame)
+            .and_then(|reached| reached.env.borrow().get(name))
+            .map(Inspected)
+    }
+
+    /// Evaluate `expression` in the scope of `frame`, as EVAL would there. It
+    /// runs for real: a procedure it calls can print, and change what it can
+    /// reach. No statement it runs is a place to stop.
+    pub fn evaluate(&self, frame: usize, expression: &str) -> Result<Inspected, String> {
+        let reached = self.reached(frame).ok_or("There is no such frame")?;
+        let env = Rc::clone(&reached.env);
+        let result = with_meta_frame("EVAL", expression, &env, reached.span, || {
+            let tokens = crate::lexer::Lexer::new(expression).tokenize();
+            let ast = crate::parser::Parser::new(tokens)
+                .parse_expression(false)
+                .map_err(Interruption::Error)?;
+            evaluate_node(&ast, Rc::clone(&env), false)
         });
-    if matches!(stored, Some(Ok(()))) {
-        return Some(Ok(()));
+        match result {
+            Ok(value) | Err(Interruption::Return(value)) => Ok(Inspected(value)),
+            Err(Interruption::Error(e)) => Err(e.message),
+            Err(Interruption::Limit(e)) => Err(e.message),
+            Err(Interruption::Throw(thrown)) => Err(thrown.error.message),
+            Err(Interruption::Exit(code)) => Err(format!(
+                "The expression called EXIT({}), which does not end a paused program",
+                code
+            )),
+            Err(Interruption::Break | Interruption::Continue) => {
+                Err("BREAK and CONTINUE only work inside a loop".to_string())
+            }
+        }
+    }
+
+    /// Whatever the program has printed since this was last called, which the
+    /// observer has to pass on when the run captures its output. A run printing
+    /// to standard output has it flushed instead, so that it is on screen before
+    /// anything the observer shows there.
+    pub fn take_output(&self) -> String {
+        self.innermost().env.borrow().sink().borrow_mut().finish()
+    }
+
+    /// Push what the program has printed so far out to standard output, when
+    /// the run prints there, so that what the observer writes to standard error
+    /// next comes after it even when both go to one file. A capturing run keeps
+    /// its text, for the caller to collect at the end.
+    pub fn flush_output(&self) {
+        self.innermost().env.borrow().sink().borrow_mut().flush();
     }
-    // Only reachable if evaluating the right-hand side rebound the target.
-    Some(combine_with_current(name, Value::String(text), value, env))
 }
 
-/// Combine the target's own value with an already-evaluated right-hand side and
-/// store the result, exactly as the ordinary assignment path would have.
-fn store_combined(
-    name: &str,
-    left: Value,
-    rhs: Value,
-    value: &Spanned,
-    env: &Rc<RefCell<Environment>>,
-) -> Result<(), Interruption> {
-    let combined = match &value.node {
-        AstNode::Concat(_, _) => match (&left, &rhs) {
-            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
-            _ => {
-                return Err(runtime_err(
-                    "CONCAT requires string arguments",
-                    value.span,
-                    env,
-                ));
+impl Inspected {
+    /// The value as a debugger shows it: as DISPLAY prints it, but for a string,
+    /// which is quoted so that `"1"` and `1` can be told apart.
+    pub fn render(&self) -> String {
+        assertion_value(&self.0)
+    }
+
+    /// What TYPE would say of it.
+    pub fn type_name(&self) -> &'static str {
+        type_name(&self.0)
+    }
+
+    /// What it holds, for a debugger to expand: a list's items by 1-based index, a
+    /// dictionary's entries by key, an object's fields and a module's names.
+    /// Empty for anything else.
+    pub fn children(&self) -> Vec<(String, Inspected)> {
+        let entries = |dict: &Dict| -> Vec<(String, Inspected)> {
+            dict.iter()
+                .map(|(key, value)| {
+                    (
+                        assertion_value(&key_to_value(key)),
+                        Inspected(value.clone()),
+                    )
+                })
+                .collect()
+        };
+        match &self.0 {
+            Value::List(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (format!("[{}]", i + 1), Inspected(item.clone())))
+                .collect(),
+            Value::Dictionary(dict) => entries(dict),
+            Value::Object(object) => entries(&object.borrow().fields),
+            Value::Module(module) => {
+                let env = module.env.borrow();
+                let mut names: Vec<(String, Inspected)> = env
+                    .variables
+                    .iter()
+                    .filter(|(name, _)| !MODULE_BUILTINS.contains(&name.as_str()))
+                    .map(|(name, value)| (name.clone(), Inspected(value.clone())))
+                    .collect();
+                names.sort_by(|a, b| a.0.cmp(&b.0));
+                names
             }
-        },
-        _ => evaluate_binary_op(&left, &BinaryOperator::Add, &rhs)
-            .map_err(|msg| runtime_err(msg, value.span, env))?,
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Attach the console debugger to the run `env` belongs to, stopping at the next
+/// statement.
+fn attach_console(env: &Rc<RefCell<Environment>>) {
+    let console: Rc<RefCell<dyn Observer>> = Rc::new(RefCell::new(crate::debugger::Console::new()));
+    let _ = env.borrow().watch.set(Rc::new(Watch::new(console)));
+}
+
+/// What a run under an [`Observer`] came to.
+pub struct Observed {
+    /// What the program printed that the observer has not already taken.
+    pub output: String,
+    /// The exit status -- 0, or what EXIT or the observer ended the run with --
+    /// or the error that ended it.
+    pub status: Result<i32, PSLError>,
+}
+
+/// Run a program from a file under `observer`, capturing its output.
+pub fn run_observed(
+    ast: &Spanned,
+    source: &str,
+    args: &[String],
+    script_path: Option<PathBuf>,
+    observer: Rc<RefCell<dyn Observer>>,
+) -> Observed {
+    let env = entry_env(
+        source,
+        args,
+        OutputMode::Capture,
+        false,
+        script_path,
+        Permissions::default(),
+        Limits::default(),
+    );
+    let watch = Rc::new(Watch::new(observer));
+    let _ = env.borrow().watch.set(Rc::clone(&watch));
+    let result = evaluate_node(ast, Rc::clone(&env), false);
+    // Every scope holds the watch and the trail holds scopes: a cycle, broken
+    // here so the run's values are dropped when it is over.
+    watch.reached.borrow_mut().clear();
+    let output = env.borrow().sink().borrow_mut().finish();
+    watch.ended();
+    let status = match result {
+        Ok(_)
+        | Err(Interruption::Return(_))
+        | Err(Interruption::Break)
+        | Err(Interruption::Continue) => Ok(0),
+        Err(Interruption::Exit(code)) => Ok(code),
+        Err(Interruption::Error(e)) => Err(e),
+        Err(Interruption::Limit(e)) => Err(*e),
+        Err(Interruption::Throw(thrown)) => Err(thrown.error),
     };
-    env.borrow_mut().set(name.to_string(), combined);
-    Ok(())
+    Observed { output, status }
 }
 
-/// [`store_combined`] for the paths that have not already copied the target out
-/// of the environment.
-fn combine_with_current(
-    name: &str,
-    rhs: Value,
-    value: &Spanned,
-    env: &Rc<RefCell<Environment>>,
-) -> Result<(), Interruption> {
-    let current = env.borrow().get(name);
-    match current {
-        Some(left) => store_combined(name, left, rhs, value, env),
-        None => Err(runtime_err(
-            format!("Undefined variable: {}", name),
-            value.span,
+/// A program typed in one piece at a time, every piece running in the same root
+/// scope: what `fpli repl` drives.
+///
+/// Every input is appended to one session-long source and its spans are shifted
+/// to point into it. A procedure declared several inputs ago keeps spans into the
+/// text it was declared in, so an error raised inside it much later still shows
+/// the right line, which formatting against the latest input alone could not do.
+/// Lines are therefore numbered across the whole session.
+pub struct Session {
+    env: Rc<RefCell<Environment>>,
+    source: String,
+}
+
+/// What one input to a [`Session`] did.
+#[derive(Debug, PartialEq)]
+pub enum SessionOutcome {
+    /// Ran to the end. Holds the value of a trailing expression, rendered as
+    /// DISPLAY renders it, when the input ended in one that had a value.
+    Done(Option<String>),
+    /// The input ran EXIT with this status.
+    Exit(i32),
+}
+
+impl Session {
+    /// A session whose ARGS are `args`, printing straight to stdout.
+    pub fn new(args: &[String]) -> Self {
+        let env = Rc::new(RefCell::new(Environment::new(OutputMode::Stdout, false)));
+        init_env_with_args(&env, args);
+        Session {
             env,
-        )),
+            source: String::new(),
+        }
+    }
+
+    /// Run one input.
+    ///
+    /// An error comes back formatted for display. Whatever the input did before
+    /// failing stays done, exactly as in a program, and the session carries on
+    /// from there.
+    pub fn run(&mut self, input: &str) -> Result<SessionOutcome, String> {
+        let mut ast = parse_continuing(input, self.source.len()).map_err(|e| e.format(input))?;
+        continue_source(&mut self.source, input, &self.env);
+        let shows_value =
+            strip_end_of_input(&mut ast).is_some_and(|last| is_value_statement(&last.node));
+        let result = evaluate_node(&ast, Rc::clone(&self.env), false);
+        let sink = Rc::clone(self.env.borrow().sink());
+        sink.borrow_mut().flush();
+        if let Some(err) = sink.borrow_mut().take_write_error() {
+            return Err(format!("Failed writing program output: {}", err));
+        }
+        match result {
+            Ok(value) => Ok(SessionOutcome::Done(
+                (shows_value && !matches!(value, Value::Unit)).then(|| value_to_string(&value)),
+            )),
+            // A top-level RETURN ends the input, as it would end a program.
+            Err(Interruption::Return(_))
+            | Err(Interruption::Break)
+            | Err(Interruption::Continue) => Ok(SessionOutcome::Done(None)),
+            Err(Interruption::Exit(code)) => Ok(SessionOutcome::Exit(code)),
+            Err(Interruption::Error(e)) => Err(e.format(&self.source)),
+            Err(Interruption::Limit(e)) => Err(e.format(&self.source)),
+            Err(Interruption::Throw(thrown)) => Err(thrown.error.format(&self.source)),
+        }
+    }
+
+    /// Every variable defined so far, sorted, as VARIABLES() reports them.
+    pub fn variable_names(&self) -> Vec<String> {
+        self.env.borrow().visible_variable_names()
+    }
+
+    /// Every procedure declared so far, sorted, as PROCEDURES() reports them.
+    pub fn procedure_names(&self) -> Vec<String> {
+        self.env.borrow().procedure_names()
     }
 }
 
-// skipcq: RS-R1000
-fn evaluate_node_impl(node: &Spanned, env: Rc<RefCell<Environment>>, debug: bool) -> EvalResult {
-    let Spanned {
-        node: ref ast_node,
-        span,
-    } = *node;
+/// Parse `input`, the next piece of a program whose earlier pieces take up the
+/// first `offset` bytes of its source, with its spans shifted past them. What is
+/// not a program may still be an expression on its own, and is parsed as a
+/// program of just that.
+///
+/// Nothing of a rejected input runs, so it never joins the source and its error
+/// is shown against the input alone. A span the parser made up for the end of
+/// input was never shifted.
+fn parse_continuing(input: &str, offset: usize) -> Result<Spanned, PSLError> {
+    let tokens: Vec<_> = crate::lexer::Lexer::new(input)
+        .tokenize()
+        .into_iter()
+        .map(|(token, span)| (token, Span::new(span.start + offset, span.end + offset)))
+        .collect();
+    let parsed = crate::parser::parse(tokens.clone(), false).or_else(|program_error| {
+        crate::parser::parse_single_expression(tokens, false)
+            .map(|expression| {
+                let span = expression.span;
+                Spanned::new(AstNode::Program(vec![expression]), span)
+            })
+            .map_err(|_| program_error)
+    });
+    parsed.map_err(|mut error| {
+        if let Some(span) = error.span.as_mut()
+            && span.start >= offset
+        {
+            *span = Span::new(span.start - offset, span.end - offset);
+        }
+        error
+    })
+}
 
-    if debug {
-        println!("Evaluating node: {:?}", ast_node);
+/// Add `input`, which has parsed, to the `source` of the program running in
+/// `env`, so that errors raised in it resolve against the whole.
+fn continue_source(source: &mut String, input: &str, env: &Rc<RefCell<Environment>>) {
+    source.push_str(input);
+    if !source.ends_with('\n') {
+        source.push('\n');
     }
+    env.borrow().modules.borrow_mut().entry_source = Some(Rc::from(source.as_str()));
+}
 
-    match ast_node {
-        AstNode::Program(statements) | AstNode::Block(statements) => {
-            // Only the last statement's value survives, so every earlier one
-            // runs in statement position (see `evaluate_for_effect`).
-            let Some((last, leading)) = statements.split_last() else {
-                return Ok(Value::Unit);
-            };
-            for stmt in leading {
-                evaluate_for_effect(stmt, Rc::clone(&env), debug)?;
-            }
-            evaluate_node(last, Rc::clone(&env), debug)
+/// The last statement of a parsed input. The parser ends a program with an
+/// empty block standing for the end of input, which is dropped first: it must
+/// not hide the value of the statement before.
+fn strip_end_of_input(ast: &mut Spanned) -> Option<&Spanned> {
+    let AstNode::Program(statements) = &mut ast.node else {
+        return None;
+    };
+    while statements
+        .last()
+        .is_some_and(|last| matches!(&last.node, AstNode::Block(b) if b.is_empty()))
+    {
+        statements.pop();
+    }
+    statements.last()
+}
+
+/// PseudoLang embedded in a Rust program: one program, kept across any number
+/// of calls into it.
+///
+/// Each [`Interpreter::run`] or [`Interpreter::eval`] parses only the source it
+/// is given and runs it in the same top-level scope as everything before, so
+/// rules loaded once can be called from Rust with [`Interpreter::call`] as often
+/// as needed without being read again. The pieces are joined into one source,
+/// as a REPL session's inputs are, so an error raised in a procedure loaded many
+/// calls ago still points at the line it is on. Errors come back as they are,
+/// not formatted: `error.format(interpreter.source())` shows one the way
+/// `fpli run` would.
+///
+/// Output is captured, never printed. `run` returns what its source printed;
+/// what `eval` and `call` print waits for [`Interpreter::take_output`].
+///
+/// An EXIT ends the call that ran it, not the interpreter: with status 0 the
+/// call succeeds, and with any other it fails with an error giving the status.
+/// Every call has the whole of the interpreter's [`Limits`] to itself.
+///
+/// Not `Send`: the program's values are reference counted, so an interpreter
+/// stays on the thread that made it.
+pub struct Interpreter {
+    env: Rc<RefCell<Environment>>,
+    source: String,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+// Reached through the library API and the test suite, not by the fpli binary.
+#[allow(dead_code)]
+impl Interpreter {
+    /// An interpreter allowed everything, as `fpli run` is by default.
+    pub fn new() -> Self {
+        Interpreter::restricted(Permissions::default(), Limits::default())
+    }
+
+    /// An interpreter allowed only what `permissions` grant and as much as
+    /// `limits` allow: the embedding's `--deny-all` and `--max-steps`.
+    pub fn restricted(permissions: Permissions, limits: Limits) -> Self {
+        Interpreter {
+            env: entry_env(
+                "",
+                &[],
+                OutputMode::Capture,
+                false,
+                None,
+                permissions,
+                limits,
+            ),
+            source: String::new(),
         }
+    }
 
-        AstNode::Integer(n) => Ok(Value::Integer(n.clone())),
-        AstNode::Float(f) => Ok(Value::Float(*f)),
-        AstNode::String(s) => Ok(Value::String(s.clone())),
-        AstNode::Boolean(b) => Ok(Value::Boolean(*b)),
-        AstNode::Null => Ok(Value::Null),
-        AstNode::NaN => Ok(Value::NaN),
-        AstNode::RawString(s) => Ok(Value::String(s.clone())),
+    /// Run `source`, returning what it printed.
+    ///
+    /// Whatever it did before an error stays done, exactly as in a program, and
+    /// what it printed until then is left for [`Interpreter::take_output`].
+    pub fn run(&mut self, source: &str) -> Result<String, PSLError> {
+        self.eval(source)?;
+        Ok(self.take_output())
+    }
+
+    /// Run `source` and return the value of its last statement, when that is an
+    /// expression: `eval("total * 2")`. Anything else -- an assignment, a
+    /// DISPLAY, a loop -- has no value and gives [`host::Value::Unit`].
+    pub fn eval(&mut self, source: &str) -> Result<host::Value, PSLError> {
+        let mut ast = parse_continuing(source, self.source.len()).map_err(|mut error| {
+            error.source = Some(Rc::from(source));
+            error
+        })?;
+        continue_source(&mut self.source, source, &self.env);
+        let has_value =
+            strip_end_of_input(&mut ast).is_some_and(|last| is_value_statement(&last.node));
+        self.env.borrow().budget.restart();
+        let value = self.settle(evaluate_node(&ast, Rc::clone(&self.env), false))?;
+        Ok(if has_value { value } else { host::Value::Unit })
+    }
 
-        AstNode::List(elements) => {
-            let mut values = Vec::new();
-            for elem in elements {
-                values.push(evaluate_node(elem, Rc::clone(&env), debug)?);
+    /// Call the procedure `name` with `args` and return what it returned.
+    ///
+    /// `name` is looked up as a call in the program would look it up: a
+    /// variable holding a procedure first, then a declared procedure. The call is added
+    /// to the source as a line of its own, `name(args)`, which is where an error
+    /// in it points and where its stack trace starts.
+    pub fn call(&mut self, name: &str, args: &[host::Value]) -> Result<host::Value, PSLError> {
+        let args = args
+            .iter()
+            .cloned()
+            .map(from_host)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(PSLError::new)?;
+        let shown: Vec<String> = args.iter().map(assertion_value).collect();
+        let line = format!("{}({})", name, shown.join(", "));
+        let span = Span::new(self.source.len(), self.source.len() + line.len());
+        continue_source(&mut self.source, &line, &self.env);
+        self.env.borrow().budget.restart();
+        self.settle(invoke_named(name, args, &self.env, span, false))
+    }
+
+    /// The value of the top-level variable `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<host::Value> {
+        self.env.borrow().get(name).as_ref().map(to_host)
+    }
+
+    /// Set the top-level variable `name` to `value`, as `name <- value` would.
+    pub fn set(&mut self, name: &str, value: impl Into<host::Value>) -> Result<(), PSLError> {
+        if !is_assignable_name(name) {
+            return Err(PSLError::new(format!(
+                "'{}' is not a usable variable name: it must start with a letter and contain only letters, digits and underscores",
+                name
+            )));
+        }
+        let value = from_host(value.into()).map_err(PSLError::new)?;
+        self.env.borrow_mut().set(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Whatever has been printed since this was last called, or since `run`
+    /// last returned it.
+    pub fn take_output(&mut self) -> String {
+        self.env.borrow().sink().borrow_mut().finish()
+    }
+
+    /// Everything run so far, joined: the text the spans of a runtime error
+    /// index into. A syntax error's span is into the source that failed to
+    /// parse, which the error carries itself.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The outcome of one call into the program.
+    fn settle(&self, result: EvalResult) -> Result<host::Value, PSLError> {
+        match result {
+            Ok(value) | Err(Interruption::Return(value)) => Ok(to_host(&value)),
+            Err(Interruption::Break) | Err(Interruption::Continue) | Err(Interruption::Exit(0)) => {
+                Ok(host::Value::Unit)
             }
-            Ok(Value::List(values))
+            Err(Interruption::Exit(status)) => Err(PSLError::new(format!(
+                "The program ended with EXIT({})",
+                status
+            ))),
+            Err(Interruption::Error(e)) => Err(e),
+            Err(Interruption::Limit(e)) => Err(*e),
+            Err(Interruption::Throw(thrown)) => Err(thrown.error),
         }
+    }
+}
 
-        AstNode::Dictionary(pairs) => {
-            let mut entries = Dict::default();
-            for (key_expr, value_expr) in pairs {
-                let key_val = evaluate_node(key_expr, Rc::clone(&env), debug)?;
-                let key = value_to_key(&key_val).map_err(|msg| runtime_err(msg, span, &env))?;
-                let value = evaluate_node(value_expr, Rc::clone(&env), debug)?;
-                entries.insert(key, value);
+/// `value` as a Rust program holds it.
+fn to_host(value: &Value) -> host::Value {
+    match value {
+        Value::Integer(n) => host::Value::Integer(n.clone()),
+        Value::Float(f) => host::Value::Float(*f),
+        Value::String(s) => host::Value::String(s.clone()),
+        Value::Boolean(b) => host::Value::Boolean(*b),
+        Value::List(items) => host::Value::List(items.iter().map(to_host).collect()),
+        Value::Dictionary(dict) => host::Value::Dictionary(
+            dict.iter()
+                .map(|(key, value)| (to_host(&key_to_value(key)), to_host(value)))
+                .collect(),
+        ),
+        Value::Null => host::Value::Null,
+        Value::NaN => host::Value::NaN,
+        Value::Unit => host::Value::Unit,
+        Value::Object(_) | Value::Procedure(_) | Value::Module(_) => {
+            host::Value::Other(Inspected(value.clone()))
+        }
+    }
+}
+
+/// A value from a Rust program, as the program holds it, or why it cannot be
+/// one.
+fn from_host(value: host::Value) -> Result<Value, String> {
+    Ok(match value {
+        host::Value::Integer(n) => Value::Integer(n),
+        host::Value::Float(f) if f.is_nan() => Value::NaN,
+        host::Value::Float(f) => Value::Float(f),
+        host::Value::String(s) => Value::String(s),
+        host::Value::Boolean(b) => Value::Boolean(b),
+        host::Value::List(items) => {
+            Value::List(items.into_iter().map(from_host).collect::<Result<_, _>>()?)
+        }
+        host::Value::Dictionary(entries) => {
+            let mut dict = Dict::default();
+            for (key, value) in entries {
+                dict.insert(value_to_key(&from_host(key)?)?, from_host(value)?);
             }
-            Ok(Value::Dictionary(entries))
+            Value::Dictionary(dict)
         }
+        host::Value::Null => Value::Null,
+        host::Value::NaN => Value::NaN,
+        host::Value::Unit => Value::Unit,
+        host::Value::Other(inspected) => inspected.0,
+    })
+}
 
-        AstNode::Identifier(name) => match env.borrow().get(name) {
-            Some(val) => Ok(val),
-            None => Err(runtime_err(undefined_variable_message(name), span, &env)),
-        },
+/// Whether a statement is an expression whose value is worth echoing when it
+/// ends a REPL input. Declarations, assignments and the output statements have
+/// either no value or one the user has just been shown.
+fn is_value_statement(node: &AstNode) -> bool {
+    !matches!(
+        node,
+        AstNode::Assignment(..)
+            | AstNode::ListAssignment(..)
+            | AstNode::FieldAssignment(..)
+            | AstNode::ProcedureDecl(..)
+            | AstNode::ClassDecl(..)
+            | AstNode::Display(_)
+            | AstNode::DisplayInline(_)
+            | AstNode::Append(..)
+            | AstNode::Insert(..)
+            | AstNode::Remove(..)
+            | AstNode::If(..)
+            | AstNode::RepeatTimes(..)
+            | AstNode::RepeatUntil(..)
+            | AstNode::RepeatWhile(..)
+            | AstNode::ForEach(..)
+            | AstNode::ForRange { .. }
+            | AstNode::Match(..)
+            | AstNode::TryCatch { .. }
+            | AstNode::Throw(_)
+            | AstNode::Return(_)
+            | AstNode::Break
+            | AstNode::Continue
+            | AstNode::Import(..)
+            | AstNode::Export(_)
+            | AstNode::Private(_)
+            | AstNode::Comment
+            | AstNode::Block(_)
+            | AstNode::Program(_)
+    )
+}
+
+fn evaluate_node(node: &Spanned, env: Rc<RefCell<Environment>>, debug: bool) -> EvalResult {
+    #[cfg(not(target_arch = "wasm32"))]
+    return stacker::maybe_grow(64 * 1024, 2 * 1024 * 1024, || {
+        evaluate_node_impl(node, env, debug)
+    });
+
+    #[cfg(target_arch = "wasm32")]
+    evaluate_node_impl(node, env, debug)
+}
 
+/// Evaluate a node in *statement position*, where its value is discarded.
+///
+/// Behaviour is identical to [`evaluate_node`] in every observable way; the
+/// only difference is that an assignment is free to append into the string it
+/// already holds instead of building a new one, which it cannot do when the
+/// assignment's value is still needed. Contexts that DO observe the value --
+/// the last statement of a block (a procedure without RETURN yields it) and the
+/// last iteration of FOR EACH -- still go through [`evaluate_node`].
+fn evaluate_for_effect(
+    node: &Spanned,
+    env: Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<(), Interruption> {
+    match &node.node {
         AstNode::Assignment(target, value) => {
-            let val = evaluate_node(value, Rc::clone(&env), debug)?;
-            if let AstNode::Identifier(name) = &target.node {
-                if debug {
-                    println!("Assigning {} = {:?}", name, val);
-                }
-                if matches!(&value.node, AstNode::FormattedString(_, _)) {
-                    // Capture-only: assigning an f-string has never printed to
-                    // the CLI's stdout, it only ever showed up in the captured
-                    // output.
-                    let output = value_to_string(&val);
-                    env.borrow().sink().borrow_mut().record_line(&output);
-                }
-                env.borrow_mut().set(name.clone(), val.clone());
-                Ok(val)
-            } else {
-                Err(runtime_err("Invalid assignment target", span, &env))
+            if let Some(result) = try_self_append(target, value, &env, debug) {
+                return result;
             }
+            evaluate_node(node, env, debug).map(|_| ())
         }
 
-        AstNode::BinaryOp(left_expr, op, right_expr) => match op {
-            // Both operands are type-checked, not just the right one: a
-            // non-boolean left operand is an error even though the operator
-            // short-circuits.
-            BinaryOperator::And => {
-                let left_val = evaluate_node(left_expr, Rc::clone(&env), debug)?;
-                let Value::Boolean(left_bool) = left_val else {
-                    return Err(runtime_err(
-                        "Left operand of AND must be boolean",
-                        span,
-                        &env,
-                    ));
-                };
-                if !left_bool {
-                    Ok(Value::Boolean(false))
-                } else {
-                    let right_val = evaluate_node(right_expr, Rc::clone(&env), debug)?;
-                    if let Value::Boolean(right_bool) = right_val {
-                        Ok(Value::Boolean(right_bool))
-                    } else {
-                        Err(runtime_err(
-                            "Right operand of AND must be boolean",
-                            span,
-                            &env,
-                        ))
-                    }
-                }
-            }
-            BinaryOperator::Or => {
-                let left_val = evaluate_node(left_expr, Rc::clone(&env), debug)?;
-                let Value::Boolean(left_bool) = left_val else {
-                    return Err(runtime_err(
-                        "Left operand of OR must be boolean",
-                        span,
-                        &env,
-                    ));
-                };
-                if left_bool {
-                    Ok(Value::Boolean(true))
-                } else {
-                    let right_val = evaluate_node(right_expr, Rc::clone(&env), debug)?;
-                    if let Value::Boolean(right_bool) = right_val {
-                        Ok(Value::Boolean(right_bool))
-                    } else {
-                        Err(runtime_err(
-                            "Right operand of OR must be boolean",
-                            span,
-                            &env,
-                        ))
-                    }
-                }
-            }
-            _ => {
-                let left_val = evaluate_node(left_expr, Rc::clone(&env), debug)?;
-                let right_val = evaluate_node(right_expr, Rc::clone(&env), debug)?;
-                evaluate_binary_op(&left_val, op, &right_val)
-                    .map_err(|msg| runtime_err(msg, span, &env))
-            }
-        },
+        // The only node kinds that recurse back into this function, and so the
+        // only ones needing the stack-headroom check `evaluate_node` makes.
+        // Every other arm ends in a call to `evaluate_node`, which makes it.
+        AstNode::Program(_) | AstNode::Block(_) | AstNode::If(_, _, _) => {
+            evaluate_nested_for_effect(node, env, debug)
+        }
 
-        AstNode::UnaryOp(op, expr) => {
-            let val = evaluate_node(expr, Rc::clone(&env), debug)?;
-            evaluate_unary_op(op, &val).map_err(|msg| runtime_err(msg, span, &env))
+        _ => evaluate_node(node, env, debug).map(|_| ()),
+    }
+}
+
+/// What comes before each statement of a block. The run's `budget` counts it,
+/// when the run has limits to look at. And every statement is a place a
+/// debugger can stop: see [`Observer`]. The `watch` is looked for again until
+/// there is one, as BREAKPOINT() can attach the console debugger part way
+/// through a block.
+fn before_statement(
+    statement: &Spanned,
+    env: &Rc<RefCell<Environment>>,
+    budget: Option<&Budget>,
+    watch: &mut Option<Rc<Watch>>,
+) -> Result<(), Interruption> {
+    if let Some(budget) = budget {
+        budget.statement(statement, env)?;
+    }
+    if watch.is_none() {
+        *watch = env.borrow().watch.get().cloned();
+    }
+    match watch {
+        Some(watch) => watch.before(statement, env),
+        None => Ok(()),
+    }
+}
+
+/// Whether `statement` does nothing when run: a comment, or the empty block the
+/// parser ends every program with. No debugger stops on one and no budget
+/// counts it.
+fn does_nothing(statement: &Spanned) -> bool {
+    matches!(&statement.node, AstNode::Comment)
+        || matches!(&statement.node, AstNode::Block(b) if b.is_empty())
+}
+
+fn evaluate_nested_for_effect(
+    node: &Spanned,
+    env: Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<(), Interruption> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return stacker::maybe_grow(64 * 1024, 2 * 1024 * 1024, || {
+        evaluate_nested_for_effect_impl(node, env, debug)
+    });
+
+    #[cfg(target_arch = "wasm32")]
+    evaluate_nested_for_effect_impl(node, env, debug)
+}
+
+fn evaluate_nested_for_effect_impl(
+    node: &Spanned,
+    env: Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<(), Interruption> {
+    match &node.node {
+        // Every statement of a discarded block is itself discarded.
+        AstNode::Program(statements) | AstNode::Block(statements) => {
+            let budget = Budget::metering(&env);
+            let mut watch = None;
+            for stmt in statements {
+                before_statement(stmt, &env, budget.as_deref(), &mut watch)?;
+                evaluate_for_effect(stmt, Rc::clone(&env), debug)?;
+            }
+            Ok(())
         }
 
+        // A discarded IF discards whichever branch it takes. Mirrors the
+        // `AstNode::If` arm of `evaluate_node_impl`, error and all.
         AstNode::If(condition, then_branch, else_branch) => {
             let cond_val = evaluate_node(condition, Rc::clone(&env), debug)?;
             match cond_val {
-                Value::Boolean(true) => evaluate_node(then_branch, Rc::clone(&env), debug),
+                Value::Boolean(true) => evaluate_for_effect(then_branch, env, debug),
                 Value::Boolean(false) => match else_branch {
-                    Some(else_branch) => evaluate_node(else_branch, Rc::clone(&env), debug),
-                    None => Ok(Value::Unit),
+                    Some(else_branch) => evaluate_for_effect(else_branch, env, debug),
+                    None => Ok(()),
                 },
-                _ => Err(runtime_err("Condition must be a boolean", span, &env)),
-            }
-        }
-
-        AstNode::RepeatTimes(count, body) => {
-            let count_val = evaluate_node(count, Rc::clone(&env), debug)?;
-            if let Value::Integer(n) = count_val {
-                let iterations = n
-                    .to_i64()
-                    .ok_or_else(|| runtime_err("REPEAT count too large", span, &env))?;
-                for _ in 0..iterations {
-                    evaluate_for_effect(body, Rc::clone(&env), debug)?;
-                }
-                Ok(Value::Unit)
-            } else {
-                Err(runtime_err("REPEAT count must be an integer", span, &env))
-            }
-        }
-
-        AstNode::Display(expr) => match expr {
-            Some(expr) => {
-                let result = evaluate_node(expr, Rc::clone(&env), debug)?;
-                let output = value_to_string(&result);
-                env.borrow().sink().borrow_mut().write_line(&output);
-                Ok(result)
-            }
-            None => {
-                env.borrow().sink().borrow_mut().write_line("");
-                Ok(Value::Unit)
+                _ => Err(runtime_err("Condition must be a boolean", node.span, &env)),
             }
-        },
-
-        AstNode::DisplayInline(expr) => {
-            let value = evaluate_node(expr, Rc::clone(&env), debug)?;
-            let output = value_to_string(&value);
-            env.borrow().sink().borrow_mut().write_str(&output);
-            Ok(Value::Unit)
         }
 
-        AstNode::Input(prompt) => {
-            #[cfg(any(not(target_arch = "wasm32"), feature = "wasi"))]
-            {
-                let mut input_str = String::default();
-
-                if let Some(prompt_expr) = prompt {
-                    let prompt_val = evaluate_node(prompt_expr, Rc::clone(&env), debug)?;
-                    let prompt_str = value_to_string(&prompt_val);
-                    // Through the sink, so a buffered stream is drained (and
-                    // the prompt is on screen) before we block on stdin.
-                    env.borrow().sink().borrow_mut().write_prompt(&prompt_str);
-                } else {
-                    // No prompt of our own, but the program may have just
-                    // DISPLAYINLINEd one. Drain before blocking either way.
-                    env.borrow().sink().borrow_mut().flush();
-                }
-
-                io::stdin()
-                    .read_line(&mut input_str)
-                    .map_err(|e| runtime_err(e.to_string(), span, &env))?;
-                let input = input_str.trim().to_string();
-
-                if prompt.is_none() {
-                    // Capture-only echo, as before: the terminal has already
-                    // echoed what the user typed.
-                    env.borrow().sink().borrow_mut().record_line(&input);
-                }
-
-                Ok(Value::String(input))
-            }
+        _ => evaluate_node(node, env, debug).map(|_| ()),
+    }
+}
 
-            #[cfg(all(target_arch = "wasm32", not(feature = "wasi")))]
-            {
-                let prompt_text = if let Some(prompt_expr) = prompt {
-                    let prompt_val = evaluate_node(prompt_expr, Rc::clone(&env), debug)?;
-                    value_to_string(&prompt_val)
-                } else {
-                    "Input:".to_string()
-                };
+/// What a loop does after one iteration of its body.
+enum LoopFlow {
+    /// Carry on, with the value the iteration produced.
+    Next(Value),
+    /// A BREAK ran: stop looping.
+    Break,
+}
 
-                let input = crate::interpreter::prompt(&prompt_text);
+/// Run one loop-body iteration, in statement position unless the loop hands its
+/// body's value back (FOR EACH evaluates to the value of its LAST iteration).
+///
+/// Every loop runs its body through here, which makes this the one place BREAK
+/// and CONTINUE are caught: at the innermost loop, since an inner loop's own
+/// call to this function stops them first. It is also where each iteration is
+/// counted in `turns`, out of the loop arms of [`evaluate_node_impl`], whose
+/// frame every nested expression pays for.
+fn evaluate_loop_body(
+    body: &Spanned,
+    env: Rc<RefCell<Environment>>,
+    debug: bool,
+    is_last: bool,
+    turns: &mut Turns,
+) -> Result<LoopFlow, Interruption> {
+    turns.next(&env)?;
+    let result = if is_last {
+        evaluate_node(body, env, debug)
+    } else {
+        evaluate_for_effect(body, env, debug).map(|()| Value::Unit)
+    };
+    match result {
+        Ok(value) => Ok(LoopFlow::Next(value)),
+        Err(Interruption::Continue) => Ok(LoopFlow::Next(Value::Unit)),
+        Err(Interruption::Break) => Ok(LoopFlow::Break),
+        Err(other) => Err(other),
+    }
+}
 
-                if prompt.is_none() {
-                    env.borrow().sink().borrow_mut().record_line(&input);
-                }
+/// The values a `FOR i FROM a TO b STEP s` loop counts through, produced one at a
+/// time.
+///
+/// `FOR EACH i IN RANGE(1, n)` builds all `n` values before the first iteration;
+/// this holds one. Integer bounds and step count exactly in `BigInt`, so the loop
+/// is not limited to `i64`. Any float among them makes the whole count a float
+/// one, where each value is computed as `from + k * step` rather than by repeated
+/// addition, so rounding error does not build up over a long loop.
+enum Counter {
+    Integer {
+        next: BigInt,
+        to: BigInt,
+        step: BigInt,
+    },
+    Float {
+        from: f64,
+        to: f64,
+        step: f64,
+        index: u64,
+    },
+}
 
-                Ok(Value::String(input))
+impl Counter {
+    fn new(from: Value, to: Value, step: Value) -> Result<Counter, String> {
+        let as_float = |value: &Value, what: &str| match value {
+            Value::Integer(n) => Ok(bigint_to_f64(n)),
+            Value::Float(f) => Ok(*f),
+            other => Err(format!(
+                "FOR {} must be a number, found {}",
+                what,
+                type_name(other)
+            )),
+        };
+        if let (Value::Integer(from), Value::Integer(to), Value::Integer(step)) =
+            (&from, &to, &step)
+        {
+            if step.is_zero() {
+                return Err("FOR STEP must not be zero".to_string());
             }
+            return Ok(Counter::Integer {
+                next: from.clone(),
+                to: to.clone(),
+                step: step.clone(),
+            });
         }
-
-        AstNode::ProcedureDecl(name, params, body) => {
-            let declared_in = env.borrow().modules.borrow().current_file();
-            env.borrow_mut().declare_procedure(
-                name.clone(),
-                Rc::new((params.clone(), (**body).clone(), declared_in)),
-            );
-            Ok(Value::Unit)
+        let step = as_float(&step, "STEP")?;
+        // A NaN step would never move, exactly like a zero one.
+        if step == 0.0 || step.is_nan() {
+            return Err("FOR STEP must not be zero".to_string());
         }
+        Ok(Counter::Float {
+            from: as_float(&from, "FROM value")?,
+            to: as_float(&to, "TO value")?,
+            step,
+            index: 0,
+        })
+    }
 
-        AstNode::ProcedureCall(name, args) => {
-            if let Some(result) = eval_builtin(name, args, &env, span, debug) {
-                return result;
+    /// The next value and whether it is the last one, or `None` once the count
+    /// has passed its bound. Both bounds are inclusive; a negative step counts
+    /// down.
+    fn next(&mut self) -> Option<(Value, bool)> {
+        match self {
+            Counter::Integer { next, to, step } => {
+                let ascending = step.is_positive();
+                let within = |value: &BigInt| if ascending { value <= to } else { value >= to };
+                if !within(next) {
+                    return None;
+                }
+                let following = &*next + &*step;
+                let is_last = !within(&following);
+                let current = std::mem::replace(next, following);
+                Some((Value::Integer(current), is_last))
             }
-            // Arguments are evaluated in the caller's scope, before the callee's
-            // scope exists, which is what `invoke_procedure` then binds them in.
-            let mut arg_values = Vec::with_capacity(args.len());
-            for arg in args {
-                arg_values.push(evaluate_node(arg, Rc::clone(&env), debug)?);
+            Counter::Float {
+                from,
+                to,
+                step,
+                index,
+            } => {
+                let at = |k: u64| *from + *step * k as f64;
+                let within = |value: f64| {
+                    if *step > 0.0 {
+                        value <= *to
+                    } else {
+                        value >= *to
+                    }
+                };
+                let current = at(*index);
+                if !within(current) {
+                    return None;
+                }
+                *index += 1;
+                Some((Value::Float(current), !within(at(*index))))
             }
-            invoke_procedure(name, arg_values, &env, span, debug)
         }
+    }
+}
 
-        AstNode::ListAccess(list, index) => {
-            if let Some(result) = eval_indexed_read_in_place(list, index, &env, span, debug) {
-                return result;
-            }
-            let current_value = evaluate_node(list, Rc::clone(&env), debug)?;
-            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
-            index_value(&current_value, &index_val, span, &env)
-        }
+/// The source expression of a self-append assignment: `x <- x + <expr>` or
+/// `x <- CONCAT(x, <expr>)`, both written with `name` as the left operand.
+fn self_append_source<'a>(name: &str, value: &'a Spanned) -> Option<&'a Spanned> {
+    let (left, right) = match &value.node {
+        AstNode::BinaryOp(left, BinaryOperator::Add, right) => (left, right),
+        AstNode::Concat(left, right) => (left, right),
+        _ => return None,
+    };
+    match &left.node {
+        AstNode::Identifier(left_name) if left_name == name => Some(right),
+        _ => None,
+    }
+}
 
-        AstNode::ListAssignment(list, index, value) => {
-            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
-            let new_val = evaluate_node(value, Rc::clone(&env), debug)?;
-            let ret = new_val.clone();
-            assign_indexed(list, index_val, new_val, &env, span, debug)?;
-            Ok(ret)
-        }
+/// Fast path for the string-building idiom every student writes:
+///
+/// ```text
+/// s <- ""
+/// REPEAT n TIMES { s <- s + "x" }
+/// ```
+///
+/// Evaluated literally that is O(n^2): each iteration clones `s` out of the
+/// environment, allocates a fresh `String` for the concatenation, and clones it
+/// again on the way back in. Appending into the `String` that is already there
+/// makes the loop O(n) amortised and drops the allocation per iteration.
+///
+/// Returns `None` when the shape does not apply, leaving the caller to take the
+/// ordinary assignment path. `Some` means the assignment is finished (or has
+/// failed); the right-hand side has been evaluated exactly once either way.
+fn try_self_append(
+    target: &Spanned,
+    value: &Spanned,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Option<Result<(), Interruption>> {
+    if debug {
+        // Keep the `Assigning ...` trace exactly as the ordinary path prints it.
+        return None;
+    }
+    if Watch::following(env).is_some() {
+        // Likewise for an observer that is told of every assignment.
+        return None;
+    }
+    let AstNode::Identifier(name) = &target.node else {
+        return None;
+    };
+    let source = self_append_source(name, value)?;
 
-        AstNode::Substring(string, start, end) => {
-            let str_val = evaluate_node(string, Rc::clone(&env), debug)?;
-            let start_val = evaluate_node(start, Rc::clone(&env), debug)?;
-            let end_val = evaluate_node(end, Rc::clone(&env), debug)?;
+    // One lookup does double duty, so a non-string target pays nothing for
+    // passing through here. A string is appended to in place below; anything
+    // else (`i <- i + 1`, a list, ...) comes back as the copy the ordinary path
+    // would have made when it evaluated the left operand, and is combined with
+    // the right-hand side further down instead. An unbound name is left to the
+    // ordinary path, which owns that error.
+    let bound = {
+        let scope = env.borrow();
+        scope.with_var(name, |current| match current {
+            Value::String(_) => None,
+            other => Some(other.clone()),
+        })
+    };
+    let not_a_string = bound?;
 
-            if let (Value::String(s), Value::Integer(start), Value::Integer(end)) =
-                (str_val, start_val, end_val)
-            {
-                let start_idx = &start - BigInt::one();
-                let end_idx = &end - BigInt::one();
-                match (start_idx.to_usize(), end_idx.to_usize()) {
-                    (Some(si), Some(ei)) if !start_idx.is_negative() => char_range(&s, si, ei)
-                        .map(|slice| Value::String(slice.to_string()))
-                        .ok_or_else(|| runtime_err("Invalid substring indices", span, &env)),
-                    _ => Err(runtime_err("Invalid substring indices", span, &env)),
-                }
-            } else {
-                Err(runtime_err("Invalid substring arguments", span, &env))
+    // Evaluated BEFORE the mutable borrow below, which is what makes
+    // `s <- s + s`, `s <- s + f(s)` and friends safe: the right-hand side may
+    // read the target or call into a procedure, and taking the borrow first
+    // would panic the RefCell.
+    let appended = match evaluate_node(source, Rc::clone(env), debug) {
+        Ok(val) => val,
+        Err(err) => return Some(Err(err)),
+    };
+
+    // Past this point the right-hand side has already run, so there is no
+    // falling back to the ordinary path: that would run its side effects twice.
+    if let Some(left) = not_a_string {
+        return Some(store_combined(name, left, appended, value, env));
+    }
+    let Value::String(text) = appended else {
+        // `s <- s + 1` and the like: the ordinary path's type error, from the
+        // same span, with the right-hand side still evaluated only once.
+        return Some(combine_with_current(name, appended, value, env));
+    };
+    let stored = env
+        .borrow_mut()
+        .with_var_mut(name, |current| match current {
+            Value::String(s) => {
+                s.push_str(&text);
+                Ok(())
             }
-        }
+            _ => Err(()),
+        });
+    if matches!(stored, Some(Ok(()))) {
+        return Some(Ok(()));
+    }
+    // Only reachable if evaluating the right-hand side rebound the target.
+    Some(combine_with_current(name, Value::String(text), value, env))
+}
 
-        AstNode::Concat(str1, str2) => {
-            let s1 = evaluate_node(str1, Rc::clone(&env), debug)?;
-            let s2 = evaluate_node(str2, Rc::clone(&env), debug)?;
-            if let (Value::String(s1), Value::String(s2)) = (s1, s2) {
-                Ok(Value::String(format!("{}{}", s1, s2)))
-            } else {
-                Err(runtime_err("CONCAT requires string arguments", span, &env))
+/// Combine the target's own value with an already-evaluated right-hand side and
+/// store the result, exactly as the ordinary assignment path would have.
+fn store_combined(
+    name: &str,
+    left: Value,
+    rhs: Value,
+    value: &Spanned,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
+    let combined = match &value.node {
+        AstNode::Concat(_, _) => match (&left, &rhs) {
+            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+            _ => {
+                return Err(runtime_err(
+                    "CONCAT requires string arguments",
+                    value.span,
+                    env,
+                ));
             }
-        }
+        },
+        _ => evaluate_binary_op(&left, &BinaryOperator::Add, &rhs)
+            .map_err(|msg| runtime_err(msg, value.span, env))?,
+    };
+    env.borrow_mut().set(name.to_string(), combined);
+    Ok(())
+}
 
-        AstNode::ToString(expr) => {
-            let val = evaluate_node(expr, Rc::clone(&env), debug)?;
-            Ok(Value::String(value_to_string(&val)))
-        }
+/// [`store_combined`] for the paths that have not already copied the target out
+/// of the environment.
+fn combine_with_current(
+    name: &str,
+    rhs: Value,
+    value: &Spanned,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
+    let current = env.borrow().get(name);
+    match current {
+        Some(left) => store_combined(name, left, rhs, value, env),
+        None => Err(runtime_err(
+            format!("Undefined variable: {}", name),
+            value.span,
+            env,
+        )),
+    }
+}
 
-        AstNode::ToNum(expr) => {
-            let val = evaluate_node(expr, Rc::clone(&env), debug)?;
-            if let Value::String(s) = val {
-                if let Ok(n) = s.parse::<BigInt>() {
-                    Ok(Value::Integer(n))
-                } else if let Ok(f) = s.parse::<f64>() {
-                    Ok(Value::Float(f))
-                } else {
-                    Err(runtime_err("Cannot convert string to number", span, &env))
-                }
-            } else {
-                Err(runtime_err("TONUM requires string argument", span, &env))
+// skipcq: RS-R1000
+fn evaluate_node_impl(node: &Spanned, env: Rc<RefCell<Environment>>, debug: bool) -> EvalResult {
+    let Spanned {
+        node: ref ast_node,
+        span,
+    } = *node;
+
+    if debug {
+        println!("Evaluating node: {:?}", ast_node);
+    }
+
+    match ast_node {
+        AstNode::Program(statements) | AstNode::Block(statements) => {
+            // Only the last statement's value survives, so every earlier one
+            // runs in statement position (see `evaluate_for_effect`).
+            let Some((last, leading)) = statements.split_last() else {
+                return Ok(Value::Unit);
+            };
+            let budget = Budget::metering(&env);
+            let mut watch = None;
+            for stmt in leading {
+                before_statement(stmt, &env, budget.as_deref(), &mut watch)?;
+                evaluate_for_effect(stmt, Rc::clone(&env), debug)?;
             }
+            before_statement(last, &env, budget.as_deref(), &mut watch)?;
+            evaluate_node(last, Rc::clone(&env), debug)
         }
 
-        AstNode::RepeatUntil(body, condition) => {
-            let mut iterations = 0;
+        AstNode::Integer(n) => Ok(Value::Integer(n.clone())),
+        AstNode::Float(f) => Ok(Value::Float(*f)),
+        AstNode::String(s) => Ok(Value::String(s.clone())),
+        AstNode::Boolean(b) => Ok(Value::Boolean(*b)),
+        AstNode::Null => Ok(Value::Null),
+        AstNode::NaN => Ok(Value::NaN),
+        AstNode::RawString(s) => Ok(Value::String(s.clone())),
 
-            loop {
-                iterations += 1;
-                if iterations > MAX_LOOP_ITERATIONS {
-                    return Err(runtime_err("Maximum loop iterations exceeded", span, &env));
-                }
+        AstNode::List(elements) => {
+            let mut values = Vec::new();
+            for elem in elements {
+                values.push(evaluate_node(elem, Rc::clone(&env), debug)?);
+            }
+            Ok(Value::List(values))
+        }
 
-                evaluate_for_effect(body, Rc::clone(&env), debug)?;
+        AstNode::Dictionary(pairs) => {
+            let mut entries = Dict::default();
+            for (key_expr, value_expr) in pairs {
+                let key_val = evaluate_node(key_expr, Rc::clone(&env), debug)?;
+                let key = value_to_key(&key_val).map_err(|msg| runtime_err(msg, span, &env))?;
+                let value = evaluate_node(value_expr, Rc::clone(&env), debug)?;
+                entries.insert(key, value);
+            }
+            Ok(Value::Dictionary(entries))
+        }
 
-                let cond_val = evaluate_node(condition, Rc::clone(&env), debug)?;
-                match cond_val {
-                    Value::Boolean(true) => break,
-                    Value::Boolean(false) => continue,
-                    _ => {
-                        return Err(runtime_err(
-                            "REPEAT UNTIL condition must evaluate to boolean",
-                            span,
-                            &env,
-                        ));
-                    }
-                }
+        AstNode::Identifier(name) => {
+            if let Some(val) = env.borrow().get(name) {
+                return Ok(val);
+            }
+            // A declared procedure named without parentheses is the procedure
+            // itself, so `handlers <- [onOpen, onClose]` needs no wrapper. Checked
+            // only after the variables, so no existing program changes meaning.
+            let procedure = env.borrow().get_procedure(name);
+            match procedure {
+                Some(procedure) => Ok(Value::Procedure(ProcedureValue {
+                    name: Some(Rc::from(name.as_str())),
+                    procedure,
+                    scope: None,
+                })),
+                None => Err(runtime_err(undefined_variable_message(name), span, &env)),
             }
-            Ok(Value::Unit)
         }
 
-        AstNode::ForEach(var_name, list, body) => {
-            let list_val = evaluate_node(list, Rc::clone(&env), debug)?;
-            match list_val {
-                Value::List(elements) => {
-                    let mut result = Value::Unit;
-                    let count = elements.len();
-                    for (i, element) in elements.into_iter().enumerate() {
-                        env.borrow_mut().set(var_name.clone(), element);
-                        result = evaluate_loop_body(body, Rc::clone(&env), debug, i + 1 == count)?;
-                    }
-                    Ok(result)
+        AstNode::Assignment(target, value) => {
+            let val = evaluate_node(value, Rc::clone(&env), debug)?;
+            if let AstNode::Identifier(name) = &target.node {
+                if debug {
+                    println!("Assigning {} = {:?}", name, val);
                 }
-                Value::String(s) => {
-                    let mut result = Value::Unit;
-                    let count = s.chars().count();
-                    for (i, c) in s.chars().enumerate() {
-                        env.borrow_mut()
-                            .set(var_name.clone(), Value::String(c.to_string()));
-                        result = evaluate_loop_body(body, Rc::clone(&env), debug, i + 1 == count)?;
-                    }
-                    Ok(result)
+                if matches!(&value.node, AstNode::FormattedString(_, _)) {
+                    // Capture-only: assigning an f-string has never printed to
+                    // the CLI's stdout, it only ever showed up in the captured
+                    // output.
+                    let output = value_to_string(&val);
+                    env.borrow().sink().borrow_mut().record_line(&output);
                 }
-                Value::Dictionary(entries) => {
-                    let mut result = Value::Unit;
-                    let count = entries.len();
-                    for (i, (key, _)) in entries.iter().enumerate() {
-                        env.borrow_mut().set(var_name.clone(), key_to_value(key));
-                        result = evaluate_loop_body(body, Rc::clone(&env), debug, i + 1 == count)?;
-                    }
-                    Ok(result)
+                env.borrow_mut().set(name.clone(), val.clone());
+                if let Some(watch) = Watch::following(&env) {
+                    watch.assigned(name, &val, &env);
                 }
-                _ => Err(runtime_err(
-                    "FOR EACH requires list, string, or dictionary",
-                    span,
-                    &env,
-                )),
+                Ok(val)
+            } else if let AstNode::List(_) = &target.node {
+                unpack_assignment(target, &val, &env)?;
+                Ok(val)
+            } else {
+                Err(runtime_err("Invalid assignment target", span, &env))
             }
         }
 
-        AstNode::FormattedString(s, expressions) => {
-            let mut result = String::with_capacity(s.len());
-            let mut placeholders = s.split("{}");
-            let mut expr_iter = expressions.iter();
-
-            if let Some(first_part) = placeholders.next() {
-                result.push_str(first_part);
-            }
-
-            for part in placeholders {
-                if let Some(expr) = expr_iter.next() {
-                    let value = evaluate_node(expr, Rc::clone(&env), debug)?;
-                    result.push_str(&value_to_string(&value));
+        AstNode::BinaryOp(left_expr, op, right_expr) => match op {
+            // Both operands are type-checked, not just the right one: a
+            // non-boolean left operand is an error even though the operator
+            // short-circuits.
+            BinaryOperator::And => {
+                let left_val = evaluate_node(left_expr, Rc::clone(&env), debug)?;
+                let Value::Boolean(left_bool) = left_val else {
+                    return Err(runtime_err(
+                        "Left operand of AND must be boolean",
+                        span,
+                        &env,
+                    ));
+                };
+                if !left_bool {
+                    Ok(Value::Boolean(false))
+                } else {
+                    let right_val = evaluate_node(right_expr, Rc::clone(&env), debug)?;
+                    if let Value::Boolean(right_bool) = right_val {
+                        Ok(Value::Boolean(right_bool))
+                    } else {
+                        Err(runtime_err(
+                            "Right operand of AND must be boolean",
+                            span,
+                            &env,
+                        ))
+                    }
                 }
-                result.push_str(part);
             }
-
-            Ok(Value::String(result))
-        }
-
-        AstNode::Length(list) => with_value(list, &env, debug, container_len)?
-            .map(|len| Value::Integer(BigInt::from(len)))
-            .ok_or_else(|| {
-                runtime_err(
-                    "LENGTH requires a list, string, or dictionary argument",
-                    span,
-                    &env,
-                )
-            }),
-
-        AstNode::Insert(list, index, value) => {
-            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
-            let insert_val = evaluate_node(value, Rc::clone(&env), debug)?;
-
-            if let AstNode::Identifier(name) = &list.node {
-                if !env
-                    .borrow()
-                    .with_var(name, |value| matches!(value, Value::List(_)))
-                    .unwrap_or(false)
-                {
+            BinaryOperator::Or => {
+                let left_val = evaluate_node(left_expr, Rc::clone(&env), debug)?;
+                let Value::Boolean(left_bool) = left_val else {
                     return Err(runtime_err(
-                        format!("Variable {} is not a list", name),
+                        "Left operand of OR must be boolean",
                         span,
                         &env,
                     ));
-                }
-
-                if let Value::Integer(i) = index_val {
-                    let idx = &i - BigInt::one();
-                    let outcome = env.borrow_mut().with_var_mut(name, |value| {
-                        let Value::List(elements) = value else {
-                            return Err(());
-                        };
-                        match idx.to_usize() {
-                            Some(uidx) if !idx.is_negative() && uidx <= elements.len() => {
-                                elements.insert(uidx, insert_val.clone());
-                                Ok(())
-                            }
-                            _ => Err(()),
-                        }
-                    });
-                    match outcome {
-                        Some(Ok(())) => Ok(insert_val),
-                        _ => Err(runtime_err("List index out of bounds", span, &env)),
-                    }
+                };
+                if left_bool {
+                    Ok(Value::Boolean(true))
                 } else {
-                    Err(runtime_err("Invalid list index", span, &env))
+                    let right_val = evaluate_node(right_expr, Rc::clone(&env), debug)?;
+                    if let Value::Boolean(right_bool) = right_val {
+                        Ok(Value::Boolean(right_bool))
+                    } else {
+                        Err(runtime_err(
+                            "Right operand of OR must be boolean",
+                            span,
+                            &env,
+                        ))
+                    }
                 }
-            } else {
-                Err(runtime_err("INSERT requires a list variable", span, &env))
             }
+            _ => {
+                let left_val = evaluate_node(left_expr, Rc::clone(&env), debug)?;
+                let right_val = evaluate_node(right_expr, Rc::clone(&env), debug)?;
+                evaluate_binary_op(&left_val, op, &right_val)
+                    .map_err(|msg| runtime_err(msg, span, &env))
+            }
+        },
+
+        AstNode::UnaryOp(op, expr) => {
+            let val = evaluate_node(expr, Rc::clone(&env), debug)?;
+            evaluate_unary_op(op, &val).map_err(|msg| runtime_err(msg, span, &env))
         }
 
-        AstNode::Append(list, value) => {
-            let append_val = evaluate_node(value, Rc::clone(&env), debug)?;
+        AstNode::If(condition, then_branch, else_branch) => {
+            let cond_val = evaluate_node(condition, Rc::clone(&env), debug)?;
+            match cond_val {
+                Value::Boolean(true) => evaluate_node(then_branch, Rc::clone(&env), debug),
+                Value::Boolean(false) => match else_branch {
+                    Some(else_branch) => evaluate_node(else_branch, Rc::clone(&env), debug),
+                    None => Ok(Value::Unit),
+                },
+                _ => Err(runtime_err("Condition must be a boolean", span, &env)),
+            }
+        }
 
-            if let AstNode::Identifier(name) = &list.node {
-                let outcome = env.borrow_mut().with_var_mut(name, |value| {
-                    let Value::List(elements) = value else {
-                        return Err(());
-                    };
-                    elements.push(append_val.clone());
-                    Ok(())
-                });
-                match outcome {
-                    Some(Ok(())) => Ok(append_val),
-                    _ => Err(runtime_err(
-                        format!("Variable {} is not a list", name),
-                        span,
-                        &env,
-                    )),
+        AstNode::RepeatTimes(count, body) => {
+            let count_val = evaluate_node(count, Rc::clone(&env), debug)?;
+            if let Value::Integer(n) = count_val {
+                let iterations = n
+                    .to_i64()
+                    .ok_or_else(|| runtime_err("REPEAT count too large", span, &env))?;
+                let mut turns = Turns::new(span, &env);
+                for _ in 0..iterations {
+                    if let LoopFlow::Break =
+                        evaluate_loop_body(body, Rc::clone(&env), debug, false, &mut turns)?
+                    {
+                        break;
+                    }
                 }
+                Ok(Value::Unit)
             } else {
-                Err(runtime_err("APPEND requires a list variable", span, &env))
+                Err(runtime_err("REPEAT count must be an integer", span, &env))
             }
         }
 
-        AstNode::Remove(list, index) => {
-            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
+        AstNode::Display(expr) => match expr {
+            Some(expr) => {
+                let result = evaluate_node(expr, Rc::clone(&env), debug)?;
+                let output = value_to_string(&result);
+                env.borrow().sink().borrow_mut().write_line(&output);
+                Ok(result)
+            }
+            None => {
+                env.borrow().sink().borrow_mut().write_line("");
+                Ok(Value::Unit)
+            }
+        },
 
-            if let AstNode::Identifier(name) = &list.node {
-                let kind = env.borrow().with_var(name, |value| match value {
-                    Value::Dictionary(_) => ContainerKind::Dictionary,
-                    Value::List(_) => ContainerKind::List,
-                    _ => ContainerKind::Other,
-                });
-                match kind {
-                    Some(ContainerKind::Dictionary) => {
-                        return dict_remove_entry(name, &index_val, &env, span);
+        AstNode::DisplayInline(expr) => {
+            let value = evaluate_node(expr, Rc::clone(&env), debug)?;
+            let output = value_to_string(&value);
+            env.borrow().sink().borrow_mut().write_str(&output);
+            Ok(Value::Unit)
+        }
+
+        AstNode::Input(prompt) => {
+            #[cfg(any(not(target_arch = "wasm32"), feature = "wasi"))]
+            {
+                let mut input_str = String::default();
+                let prompt_str = match prompt {
+                    Some(prompt_expr) => Some(value_to_string(&evaluate_node(
+                        prompt_expr,
+                        Rc::clone(&env),
+                        debug,
+                    )?)),
+                    None => None,
+                };
+
+                let watch = env.borrow().watch.get().cloned();
+                let supplied = watch.and_then(|watch| watch.read_line());
+                if let Some(line) = supplied {
+                    // A debug adapter's standard input carries its protocol, so the
+                    // line comes from the observer, and the prompt goes out with the
+                    // rest of the program's output instead of to the terminal.
+                    let sink = Rc::clone(env.borrow().sink());
+                    if let Some(prompt_str) = &prompt_str {
+                        sink.borrow_mut().write_str(prompt_str);
                     }
-                    Some(ContainerKind::List) => {}
-                    _ => {
-                        return Err(runtime_err(
-                            format!("Variable {} is not a list", name),
-                            span,
-                            &env,
-                        ));
+                    input_str = line.map_err(|message| runtime_err(message, span, &env))?;
+                    if prompt.is_some() {
+                        sink.borrow_mut().record_line(input_str.trim());
+                    }
+                } else {
+                    if let Some(prompt_str) = &prompt_str {
+                        // Through the sink, so a buffered stream is drained (and
+                        // the prompt is on screen) before we block on stdin.
+                        env.borrow().sink().borrow_mut().write_prompt(prompt_str);
+                    } else {
+                        // No prompt of our own, but the program may have just
+                        // DISPLAYINLINEd one. Drain before blocking either way.
+                        env.borrow().sink().borrow_mut().flush();
                     }
+
+                    io::stdin()
+                        .read_line(&mut input_str)
+                        .map_err(|e| runtime_err(e.to_string(), span, &env))?;
                 }
+                let input = input_str.trim().to_string();
 
-                if let Value::Integer(i) = index_val {
-                    let idx = &i - BigInt::one();
-                    let outcome = env.borrow_mut().with_var_mut(name, |value| {
-                        let Value::List(elements) = value else {
-                            return Err(());
-                        };
-                        match idx.to_usize() {
-                            Some(uidx) if !idx.is_negative() && uidx < elements.len() => {
-                                Ok(elements.remove(uidx))
-                            }
-                            _ => Err(()),
-                        }
-                    });
-                    match outcome {
-                        Some(Ok(removed_value)) => Ok(removed_value),
-                        _ => Err(runtime_err("List index out of bounds", span, &env)),
-                    }
+                if prompt.is_none() {
+                    // Capture-only echo, as before: the terminal has already
+                    // echoed what the user typed.
+                    env.borrow().sink().borrow_mut().record_line(&input);
+                }
+
+                Ok(Value::String(input))
+            }
+
+            #[cfg(all(target_arch = "wasm32", not(feature = "wasi")))]
+            {
+                let prompt_text = if let Some(prompt_expr) = prompt {
+                    let prompt_val = evaluate_node(prompt_expr, Rc::clone(&env), debug)?;
+                    value_to_string(&prompt_val)
                 } else {
-                    Err(runtime_err("REMOVE requires an integer index", span, &env))
+                    "Input:".to_string()
+                };
+
+                let input = crate::interpreter::prompt(&prompt_text);
+
+                if prompt.is_none() {
+                    env.borrow().sink().borrow_mut().record_line(&input);
                 }
-            } else {
-                Err(runtime_err("REMOVE requires a list variable", span, &env))
+
+                Ok(Value::String(input))
             }
         }
 
-        AstNode::Random(min, max) => {
-            let min_val = evaluate_node(min, Rc::clone(&env), debug)?;
-            let max_val = evaluate_node(max, Rc::clone(&env), debug)?;
+        AstNode::ProcedureDecl(name, params, body) => {
+            let declared_in = env.borrow().modules.borrow().current_file();
+            env.borrow_mut().declare_procedure(
+                name.clone(),
+                Rc::new((params.clone(), (**body).clone(), declared_in)),
+            );
+            Ok(Value::Unit)
+        }
+
+        AstNode::ProcedureCall(name, args) => {
+            if let Some(result) = eval_builtin(name, args, &env, span, debug) {
+                return result;
+            }
+            // Arguments are evaluated in the caller's scope, before the callee's
+            // scope exists, which is what `invoke_procedure` then binds them in.
+            let mut arg_values = Vec::with_capacity(args.len());
+            for arg in args {
+                arg_values.push(evaluate_node(arg, Rc::clone(&env), debug)?);
+            }
+            let class = env.borrow().get_class(name);
+            match class {
+                Some(class) => instantiate(&class, arg_values, &env, span, debug),
+                None => invoke_named(name, arg_values, &env, span, debug),
+            }
+        }
+
+        AstNode::AnonymousProcedure(params, body) => {
+            let declared_in = env.borrow().modules.borrow().current_file();
+            Ok(Value::Procedure(ProcedureValue {
+                name: None,
+                procedure: Rc::new((params.clone(), (**body).clone(), declared_in)),
+                scope: Some(Rc::clone(&env)),
+            }))
+        }
+
+        // The object and call arms live in their own functions. This one is
+        // re-entered on every level of recursion, and in a debug build its frame
+        // is the sum of every arm's locals: inlining these pushed a deep CALL
+        // recursion past `evaluate_node`'s stack red zone.
+        AstNode::ValueCall(callee, args) => eval_value_call(callee, args, &env, span, debug),
+        AstNode::FieldAccess(object, field) => eval_field_access(object, field, &env, span, debug),
+        AstNode::FieldAssignment(object, field, value) => {
+            eval_field_assignment(object, field, value, &env, span, debug)
+        }
+        AstNode::MethodCall(object, method, args) => {
+            eval_method_call(object, method, args, &env, span, debug)
+        }
+
+        AstNode::ListAccess(list, index) => {
+            if let Some(result) = eval_indexed_read_in_place(list, index, &env, span, debug) {
+                return result;
+            }
+            let current_value = evaluate_node(list, Rc::clone(&env), debug)?;
+            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
+            index_value(&current_value, &index_val, span, &env)
+        }
+
+        AstNode::ListAssignment(list, index, value) => {
+            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
+            let new_val = evaluate_node(value, Rc::clone(&env), debug)?;
+            let ret = new_val.clone();
+            assign_indexed(list, index_val, new_val, &env, span, debug)?;
+            Ok(ret)
+        }
+
+        AstNode::Substring(string, start, end) => {
+            let str_val = evaluate_node(string, Rc::clone(&env), debug)?;
+            let start_val = evaluate_node(start, Rc::clone(&env), debug)?;
+            let end_val = evaluate_node(end, Rc::clone(&env), debug)?;
+
+            if let (Value::String(s), Value::Integer(start), Value::Integer(end)) =
+                (str_val, start_val, end_val)
+            {
+                let start_idx = &start - BigInt::one();
+                let end_idx = &end - BigInt::one();
+                match (start_idx.to_usize(), end_idx.to_usize()) {
+                    (Some(si), Some(ei)) if !start_idx.is_negative() => char_range(&s, si, ei)
+                        .map(|slice| Value::String(slice.to_string()))
+                        .ok_or_else(|| runtime_err("Invalid substring indices", span, &env)),
+                    _ => Err(runtime_err("Invalid substring indices", span, &env)),
+                }
+            } else {
+                Err(runtime_err("Invalid substring arguments", span, &env))
+            }
+        }
+
+        AstNode::Concat(str1, str2) => {
+            let s1 = evaluate_node(str1, Rc::clone(&env), debug)?;
+            let s2 = evaluate_node(str2, Rc::clone(&env), debug)?;
+            if let (Value::String(s1), Value::String(s2)) = (s1, s2) {
+                Ok(Value::String(format!("{}{}", s1, s2)))
+            } else {
+                Err(runtime_err("CONCAT requires string arguments", span, &env))
+            }
+        }
+
+        AstNode::ToString(expr) => {
+            let val = evaluate_node(expr, Rc::clone(&env), debug)?;
+            Ok(Value::String(value_to_string(&val)))
+        }
+
+        AstNode::ToNum(expr) => {
+            let val = evaluate_node(expr, Rc::clone(&env), debug)?;
+            if let Value::String(s) = val {
+                if let Ok(n) = s.parse::<BigInt>() {
+                    Ok(Value::Integer(n))
+                } else if let Ok(f) = s.parse::<f64>() {
+                    Ok(Value::Float(f))
+                } else {
+                    Err(runtime_err("Cannot convert string to number", span, &env))
+                }
+            } else {
+                Err(runtime_err("TONUM requires string argument", span, &env))
+            }
+        }
+
+        AstNode::RepeatUntil(body, condition) => {
+            let mut turns = Turns::new(span, &env);
+
+            loop {
+                // CONTINUE still tests the condition, as it does in a C do-while:
+                // skipping the test could turn a terminating loop into an endless one.
+                if let LoopFlow::Break =
+                    evaluate_loop_body(body, Rc::clone(&env), debug, false, &mut turns)?
+                {
+                    break;
+                }
+
+                let cond_val = evaluate_node(condition, Rc::clone(&env), debug)?;
+                match cond_val {
+                    Value::Boolean(true) => break,
+                    Value::Boolean(false) => continue,
+                    _ => {
+                        return Err(runtime_err(
+                            "REPEAT UNTIL condition must evaluate to boolean",
+                            span,
+                            &env,
+                        ));
+                    }
+                }
+            }
+            Ok(Value::Unit)
+        }
+
+        AstNode::RepeatWhile(condition, body) => {
+            let mut turns = Turns::new(span, &env);
+
+            loop {
+                match evaluate_node(condition, Rc::clone(&env), debug)? {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => break,
+                    _ => {
+                        return Err(runtime_err(
+                            "REPEAT WHILE condition must evaluate to boolean",
+                            span,
+                            &env,
+                        ));
+                    }
+                }
+
+                if let LoopFlow::Break =
+                    evaluate_loop_body(body, Rc::clone(&env), debug, false, &mut turns)?
+                {
+                    break;
+                }
+            }
+            Ok(Value::Unit)
+        }
+
+        AstNode::ForRange {
+            var,
+            from,
+            to,
+            step,
+            body,
+        } => {
+            let from = evaluate_node(from, Rc::clone(&env), debug)?;
+            let to = evaluate_node(to, Rc::clone(&env), debug)?;
+            let step = match step {
+                Some(step) => evaluate_node(step, Rc::clone(&env), debug)?,
+                None => Value::Integer(BigInt::one()),
+            };
+            let mut counter =
+                Counter::new(from, to, step).map_err(|msg| runtime_err(msg, span, &env))?;
+            let mut result = Value::Unit;
+            let watch = Watch::following(&env);
+            let mut turns = Turns::new(span, &env);
+            while let Some((value, is_last)) = counter.next() {
+                if let Some(watch) = &watch {
+                    watch.assigned(var, &value, &env);
+                }
+                env.borrow_mut().set(var.clone(), value);
+                match evaluate_loop_body(body, Rc::clone(&env), debug, is_last, &mut turns)? {
+                    LoopFlow::Next(value) => result = value,
+                    LoopFlow::Break => break,
+                }
+            }
+            Ok(result)
+        }
+
+        AstNode::ForEach(var_name, second, list, body) => {
+            eval_for_each(var_name, second.as_deref(), list, body, span, &env, debug)
+        }
+
+        AstNode::FormattedString(s, expressions) => {
+            let mut result = String::with_capacity(s.len());
+            let mut placeholders = s.split("{}");
+            let mut expr_iter = expressions.iter();
+
+            if let Some(first_part) = placeholders.next() {
+                result.push_str(first_part);
+            }
+
+            for part in placeholders {
+                if let Some(expr) = expr_iter.next() {
+                    let value = evaluate_node(expr, Rc::clone(&env), debug)?;
+                    result.push_str(&value_to_string(&value));
+                }
+                result.push_str(part);
+            }
+
+            Ok(Value::String(result))
+        }
+
+        AstNode::Length(list) => with_value(list, &env, debug, container_len)?
+            .map(|len| Value::Integer(BigInt::from(len)))
+            .ok_or_else(|| {
+                runtime_err(
+                    "LENGTH requires a list, string, or dictionary argument",
+                    span,
+                    &env,
+                )
+            }),
+
+        AstNode::Insert(list, index, value) => {
+            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
+            let insert_val = evaluate_node(value, Rc::clone(&env), debug)?;
+
+            if let AstNode::Identifier(name) = &list.node {
+                if !env
+                    .borrow()
+                    .with_var(name, |value| matches!(value, Value::List(_)))
+                    .unwrap_or(false)
+                {
+                    return Err(runtime_err(
+                        format!("Variable {} is not a list", name),
+                        span,
+                        &env,
+                    ));
+                }
+
+                if let Value::Integer(i) = index_val {
+                    let idx = &i - BigInt::one();
+                    let outcome = env.borrow_mut().with_var_mut(name, |value| {
+                        let Value::List(elements) = value else {
+                            return Err(());
+                        };
+                        match idx.to_usize() {
+                            Some(uidx) if !idx.is_negative() && uidx <= elements.len() => {
+                                elements.insert(uidx, insert_val.clone());
+                                Ok(())
+                            }
+                            _ => Err(()),
+                        }
+                    });
+                    match outcome {
+                        Some(Ok(())) => Ok(insert_val),
+                        _ => Err(runtime_err("List index out of bounds", span, &env)),
+                    }
+                } else {
+                    Err(runtime_err("Invalid list index", span, &env))
+                }
+            } else if let AstNode::FieldAccess(object, field) = &list.node {
+                with_field_mut(object, field, &env, span, debug, |value| {
+                    let Value::List(elements) = value else {
+                        return Err(format!("Field {} is not a list", field));
+                    };
+                    let Value::Integer(i) = &index_val else {
+                        return Err("Invalid list index".to_string());
+                    };
+                    let idx = i - BigInt::one();
+                    match idx.to_usize() {
+                        Some(uidx) if !idx.is_negative() && uidx <= elements.len() => {
+                            elements.insert(uidx, insert_val.clone());
+                            Ok(())
+                        }
+                        _ => Err("List index out of bounds".to_string()),
+                    }
+                })?;
+                Ok(insert_val)
+            } else {
+                Err(runtime_err("INSERT requires a list variable", span, &env))
+            }
+        }
+
+        AstNode::Append(list, value) => {
+            let append_val = evaluate_node(value, Rc::clone(&env), debug)?;
+
+            if let AstNode::Identifier(name) = &list.node {
+                let outcome = env.borrow_mut().with_var_mut(name, |value| {
+                    let Value::List(elements) = value else {
+                        return Err(());
+                    };
+                    elements.push(append_val.clone());
+                    Ok(())
+                });
+                match outcome {
+                    Some(Ok(())) => Ok(append_val),
+                    _ => Err(runtime_err(
+                        format!("Variable {} is not a list", name),
+                        span,
+                        &env,
+                    )),
+                }
+            } else if let AstNode::FieldAccess(object, field) = &list.node {
+                with_field_mut(object, field, &env, span, debug, |value| {
+                    let Value::List(elements) = value else {
+                        return Err(format!("Field {} is not a list", field));
+                    };
+                    elements.push(append_val.clone());
+                    Ok(())
+                })?;
+                Ok(append_val)
+            } else {
+                Err(runtime_err("APPEND requires a list variable", span, &env))
+            }
+        }
+
+        AstNode::Remove(list, index) => {
+            let index_val = evaluate_node(index, Rc::clone(&env), debug)?;
+
+            if let AstNode::Identifier(name) = &list.node {
+                let kind = env.borrow().with_var(name, |value| match value {
+                    Value::Dictionary(_) => ContainerKind::Dictionary,
+                    Value::List(_) => ContainerKind::List,
+                    _ => ContainerKind::Other,
+                });
+                match kind {
+                    Some(ContainerKind::Dictionary) => {
+                        return dict_remove_entry(name, &index_val, &env, span);
+                    }
+                    Some(ContainerKind::List) => {}
+                    _ => {
+                        return Err(runtime_err(
+                            format!("Variable {} is not a list", name),
+                            span,
+                            &env,
+                        ));
+                    }
+                }
+
+                if let Value::Integer(i) = index_val {
+                    let idx = &i - BigInt::one();
+                    let outcome = env.borrow_mut().with_var_mut(name, |value| {
+                        let Value::List(elements) = value else {
+                            return Err(());
+                        };
+                        match idx.to_usize() {
+                            Some(uidx) if !idx.is_negative() && uidx < elements.len() => {
+                                Ok(elements.remove(uidx))
+                            }
+                            _ => Err(()),
+                        }
+                    });
+                    match outcome {
+                        Some(Ok(removed_value)) => Ok(removed_value),
+                        _ => Err(runtime_err("List index out of bounds", span, &env)),
+                    }
+                } else {
+                    Err(runtime_err("REMOVE requires an integer index", span, &env))
+                }
+            } else if let AstNode::FieldAccess(object, field) = &list.node {
+                with_field_mut(object, field, &env, span, debug, |value| match value {
+                    Value::Dictionary(entries) => {
+                        let key = value_to_key(&index_val)?;
+                        entries
+                            .remove(&key)
+                            .ok_or_else(|| format!("Key not found: {}", key_to_string(&key)))
+                    }
+                    Value::List(elements) => {
+                        let Value::Integer(i) = &index_val else {
+                            return Err("REMOVE requires an integer index".to_string());
+                        };
+                        let idx = i - BigInt::one();
+                        match idx.to_usize() {
+                            Some(uidx) if !idx.is_negative() && uidx < elements.len() => {
+                                Ok(elements.remove(uidx))
+                            }
+                            _ => Err("List index out of bounds".to_string()),
+                        }
+                    }
+                    _ => Err(format!("Field {} is not a list", field)),
+                })
+            } else {
+                Err(runtime_err("REMOVE requires a list variable", span, &env))
+            }
+        }
+
+        AstNode::Random(min, max) => {
+            let min_val = evaluate_node(min, Rc::clone(&env), debug)?;
+            let max_val = evaluate_node(max, Rc::clone(&env), debug)?;
+
+            match (min_val, max_val) {
+                (Value::Integer(min_int), Value::Integer(max_int)) => {
+                    if min_int > max_int {
+                        return Err(runtime_err(
+                            "Min value must be less than or equal to max value",
+                            span,
+                            &env,
+                        ));
+                    }
+                    let min_i64 = min_int
+                        .to_i64()
+                        .ok_or_else(|| runtime_err("RANDOM bounds too large", span, &env))?;
+                    let max_i64 = max_int
+                        .to_i64()
+                        .ok_or_else(|| runtime_err("RANDOM bounds too large", span, &env))?;
+                    let mut rng = rand::rng();
+                    Ok(Value::Integer(BigInt::from(
+                        rng.random_range(min_i64..=max_i64),
+                    )))
+                }
+                _ => Err(runtime_err("RANDOM requires integer arguments", span, &env)),
+            }
+        }
+
+        AstNode::ClassDecl(name, body) => eval_class_decl(name, body, &env),
+
+        AstNode::Import(path, None) => eval_import(path, &env, span, debug),
+        AstNode::Import(path, Some(alias)) => eval_import_as(path, alias, &env, span, debug),
+        // Visibility is read from the file's text before it runs; see `Visibility`.
+        AstNode::Export(_) => Ok(Value::Unit),
+        AstNode::Private(declaration) => evaluate_node(declaration, env, debug),
+
+        AstNode::Return(expr) => {
+            let value = evaluate_node(expr, Rc::clone(&env), debug)?;
+            Err(Interruption::Return(value))
+        }
+
+        AstNode::Sort(list_expr) => {
+            let list_val = evaluate_node(list_expr, Rc::clone(&env), debug)?;
+            if let Value::List(mut elements) = list_val {
+                elements.sort_by(sort_cmp);
+                Ok(Value::List(elements))
+            } else {
+                Err(runtime_err(
+                    "SORT requires a list as an argument",
+                    span,
+                    &env,
+                ))
+            }
+        }
+
+        AstNode::TryCatch {
+            try_block,
+            error_var,
+            catch_block,
+            finally_block,
+        } => eval_try(
+            try_block,
+            error_var.as_deref(),
+            catch_block.as_deref(),
+            finally_block.as_deref(),
+            &env,
+            debug,
+        ),
+
+        AstNode::Throw(expr) => eval_throw(expr, span, &env, debug),
+
+        AstNode::Match(subject, cases, default) => {
+            eval_match(subject, cases, default.as_deref(), &env, debug)
+        }
+
+        AstNode::Eval(expr) => {
+            let expr_val = evaluate_node(expr, Rc::clone(&env), debug)?;
+            if let Value::String(s) = expr_val {
+                // Frame-guarded: `code <- "EVAL(code)"` would otherwise recurse
+                // through the real stack without ever touching the recursion limit.
+                with_meta_frame("EVAL", &s, &env, span, || {
+                    let mut lexer = crate::lexer::Lexer::new(&s);
+                    let tokens = lexer.tokenize();
+                    let mut parser = crate::parser::Parser::new(tokens);
+                    let ast = parser.parse_expression(debug).map_err(|mut e| {
+                        e.span = Some(span);
+                        Interruption::Error(e)
+                    })?;
+
+                    evaluate_node(&ast, Rc::clone(&env), debug)
+                })
+            } else {
+                Err(runtime_err("EVAL requires a string argument", span, &env))
+            }
+        }
+
+        AstNode::Break => Err(Interruption::Break),
+        AstNode::Continue => Err(Interruption::Continue),
+
+        AstNode::Comment => Ok(Value::Unit),
+    }
+}
+
+/// TRY, its CATCH and its FINALLY.
+///
+/// FINALLY runs however the TRY and CATCH ended -- normally, or by RETURN,
+/// error, EXIT, BREAK or CONTINUE -- and the outcome then carries on as if the
+/// FINALLY were not there. The one exception is a FINALLY that is itself
+/// interrupted: its own RETURN, error or EXIT replaces the outcome it
+/// interrupted, because that is the last thing the program asked for.
+fn eval_try(
+    try_block: &Spanned,
+    error_var: Option<&str>,
+    catch_block: Option<&Spanned>,
+    finally_block: Option<&Spanned>,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> EvalResult {
+    let outcome = match (evaluate_node(try_block, Rc::clone(env), debug), catch_block) {
+        // EXIT is not an error, so CATCH must let it through. Nor are RETURN,
+        // BREAK and CONTINUE: a TRY inside a loop leaves the loop with them.
+        (Err(Interruption::Error(error)), Some(catch_block)) => {
+            let kind = error.kind.name().to_string();
+            eval_catch(error, kind, None, error_var, catch_block, env, debug)
+        }
+        (Err(Interruption::Throw(thrown)), Some(catch_block)) => {
+            let Thrown { value, kind, error } = *thrown;
+            eval_catch(error, kind, Some(value), error_var, catch_block, env, debug)
+        }
+        (outcome, _) => outcome,
+    };
+    // A run over its limits is over: FINALLY would give it more to do.
+    if let Some(finally_block) = finally_block
+        && !matches!(outcome, Err(Interruption::Limit(_)))
+    {
+        evaluate_node(finally_block, Rc::clone(env), debug)?;
+    }
+    outcome
+}
+
+fn eval_catch(
+    error: PSLError,
+    kind: String,
+    thrown: Option<Value>,
+    error_var: Option<&str>,
+    catch_block: &Spanned,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> EvalResult {
+    // The catch block runs in the current scope, like `IF`, `FOR EACH`,
+    // `REPEAT` and the TRY block. A child scope threw the assignments
+    // away, and
+    //
+    //     TRY { config <- READFILE(p) } CATCH (e) { config <- "" }
+    //     DISPLAY(config)
+    //
+    // failed with "Undefined variable: config". Only the error
+    // variable is scoped to the block, and whatever it shadowed in
+    // this scope is put back afterwards.
+    let shadowed = error_var.map(|name| (name.to_string(), env.borrow().local(name)));
+    if let Some(var_name) = error_var {
+        let caught = caught_error(error, kind, thrown, env);
+        env.borrow_mut().set(var_name.to_string(), caught);
+    }
+    let result = evaluate_node(catch_block, Rc::clone(env), debug);
+    // Restored on the error path too, so a CATCH that itself fails, or
+    // that RETURNs, does not leave the error variable behind.
+    if let Some((name, previous)) = shadowed {
+        match previous {
+            Some(value) => env.borrow_mut().set(name, value),
+            None => env.borrow_mut().remove_local(&name),
+        }
+    }
+    result
+}
+
+/// What a CATCH variable holds: everything the `PSLError` knows, as a
+/// dictionary a program can read.
+///
+/// `line` and `column` are where the error was raised, `file` the script it was
+/// raised in, and `stack` the procedure calls it was raised under, innermost
+/// last, each a dictionary of `name` and the `line` it was called from. Any of
+/// them is NULL when there is nothing to report -- code run from a string has no
+/// file. `value` is what THROW threw, and NULL for an error the interpreter
+/// raised.
+fn caught_error(
+    error: PSLError,
+    kind: String,
+    thrown: Option<Value>,
+    env: &Rc<RefCell<Environment>>,
+) -> Value {
+    let (entry_source, entry_file) = {
+        let env = env.borrow();
+        let modules = env.modules.borrow();
+        (
+            modules.entry_source.clone(),
+            modules
+                .entry
+                .as_ref()
+                .map(|path| path.display().to_string()),
+        )
+    };
+    let source = error.source.or(entry_source);
+    let line_of = |span: &Span| source.as_deref().map(|text| resolve_span(text, span));
+    let (line, column) = match error.span.as_ref().and_then(line_of) {
+        Some((line, column, _)) => (
+            Value::Integer(BigInt::from(line)),
+            Value::Integer(BigInt::from(column)),
+        ),
+        None => (Value::Null, Value::Null),
+    };
+    let stack = error
+        .stack_trace
+        .iter()
+        .map(|frame| {
+            let line = match line_of(&frame.span) {
+                Some((line, _, _)) => Value::Integer(BigInt::from(line)),
+                None => Value::Null,
+            };
+            dict_of(vec![
+                ("name", Value::String(frame.name.clone())),
+                ("line", line),
+            ])
+        })
+        .collect();
+    dict_of(vec![
+        ("message", Value::String(error.message)),
+        ("kind", Value::String(kind)),
+        ("line", line),
+        ("column", column),
+        ("file", optional_string(error.origin.or(entry_file))),
+        ("stack", Value::List(stack)),
+        ("value", thrown.unwrap_or(Value::Null)),
+    ])
+}
+
+/// THROW: raise any value as an error.
+///
+/// A dictionary or object with a string `message` or `kind` supplies those, so
+/// `THROW {"kind": "ValueError", "message": "negative"}` reads naturally in the
+/// handler, and throwing a caught error on keeps its message and kind. Any
+/// other value becomes the message as DISPLAY would print it, of kind "thrown".
+fn eval_throw(
+    expr: &Spanned,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> EvalResult {
+    let value = evaluate_node(expr, Rc::clone(env), debug)?;
+    let message = error_member(&value, "message").unwrap_or_else(|| value_to_string(&value));
+    let kind = error_member(&value, "kind").unwrap_or_else(|| "thrown".to_string());
+    let error = raised_error(message, span, env);
+    Err(Interruption::Throw(Box::new(Thrown { value, kind, error })))
+}
+
+/// A string `member` of a thrown dictionary or object.
+fn error_member(value: &Value, member: &str) -> Option<String> {
+    let key = DictKey::String(member.to_string());
+    let found = match value {
+        Value::Dictionary(dict) => dict.get(&key).cloned(),
+        Value::Object(object) => object.borrow().fields.get(&key).cloned(),
+        _ => None,
+    };
+    match found {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+/// MATCH: run the body of the first CASE whose pattern fits and whose guard
+/// holds, or else the ELSE body, or nothing.
+///
+/// The names a pattern binds go into the current scope, like any other
+/// assignment in a block, so they are still there after the MATCH. A CASE that
+/// does not run leaves no trace: its names are only bound once the whole
+/// pattern has matched, and put back if the guard then fails.
+fn eval_match(
+    subject: &Spanned,
+    cases: &[MatchCase],
+    default: Option<&Spanned>,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> EvalResult {
+    let value = evaluate_node(subject, Rc::clone(env), debug)?;
+    for case in cases {
+        let mut bindings = Vec::new();
+        if !match_pattern(&case.pattern, &value, &mut bindings, env, debug)? {
+            continue;
+        }
+        let shadowed: Vec<(String, Option<Value>)> = bindings
+            .iter()
+            .map(|(name, _)| (name.clone(), env.borrow().local(name)))
+            .collect();
+        for (name, bound) in bindings {
+            env.borrow_mut().set(name, bound);
+        }
+        if let Some(guard) = &case.guard {
+            match evaluate_node(guard, Rc::clone(env), debug)? {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => {
+                    // In reverse, so a name bound twice ends up as it started.
+                    for (name, previous) in shadowed.into_iter().rev() {
+                        match previous {
+                            Some(value) => env.borrow_mut().set(name, value),
+                            None => env.borrow_mut().remove_local(&name),
+                        }
+                    }
+                    continue;
+                }
+                other => {
+                    return Err(runtime_err(
+                        format!(
+                            "A CASE guard must be a boolean, found {}",
+                            type_name(&other)
+                        ),
+                        guard.span,
+                        env,
+                    ));
+                }
+            }
+        }
+        return evaluate_node(&case.body, Rc::clone(env), debug);
+    }
+    match default {
+        Some(body) => evaluate_node(body, Rc::clone(env), debug),
+        None => Ok(Value::Unit),
+    }
+}
+
+/// Whether `value` fits `pattern`, collecting the names it binds.
+///
+/// Literals compare with [`values_equal`], the same deep equality `=` uses, so
+/// `CASE [1, 2]` and `CASE 1` match an equal list and `1.0` exactly when `=`
+/// would say so. A range only ever matches what it can be compared with: a
+/// string subject does not fit `CASE 1 TO 5`, rather than being an error.
+fn match_pattern(
+    pattern: &Pattern,
+    value: &Value,
+    bindings: &mut Vec<(String, Value)>,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<bool, Interruption> {
+    match pattern {
+        Pattern::Binding(name) => {
+            bindings.push((name.clone(), value.clone()));
+            Ok(true)
+        }
+        Pattern::Literal(literal) => {
+            let literal = evaluate_node(literal, Rc::clone(env), debug)?;
+            Ok(values_equal(value, &literal))
+        }
+        Pattern::Range(low, high) => {
+            let low = evaluate_node(low, Rc::clone(env), debug)?;
+            let high = evaluate_node(high, Rc::clone(env), debug)?;
+            let within = |bound: &Value, op: BinaryOperator| {
+                matches!(
+                    evaluate_binary_op(value, &op, bound),
+                    Ok(Value::Boolean(true))
+                )
+            };
+            Ok(within(&low, BinaryOperator::GtEq) && within(&high, BinaryOperator::LtEq))
+        }
+        Pattern::List(elements, rest) => {
+            let Value::List(items) = value else {
+                return Ok(false);
+            };
+            let fits = match rest {
+                Some(_) => items.len() >= elements.len(),
+                None => items.len() == elements.len(),
+            };
+            if !fits {
+                return Ok(false);
+            }
+            for (element, item) in elements.iter().zip(items.iter()) {
+                if !match_pattern(element, item, bindings, env, debug)? {
+                    return Ok(false);
+                }
+            }
+            if let Some(rest) = rest {
+                bindings.push((rest.clone(), Value::List(items[elements.len()..].to_vec())));
+            }
+            Ok(true)
+        }
+        Pattern::Dictionary(entries) => {
+            let Value::Dictionary(dict) = value else {
+                return Ok(false);
+            };
+            for (key_node, entry) in entries {
+                let key = evaluate_node(key_node, Rc::clone(env), debug)?;
+                let key = value_to_key(&key).map_err(|e| runtime_err(e, key_node.span, env))?;
+                match dict.get(&key) {
+                    Some(found) => {
+                        if !match_pattern(entry, found, bindings, env, debug)? {
+                            return Ok(false);
+                        }
+                    }
+                    None => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+fn evaluate_arguments(
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<Vec<Value>, Interruption> {
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        values.push(evaluate_node(arg, Rc::clone(env), debug)?);
+    }
+    Ok(values)
+}
+
+fn eval_value_call(
+    callee: &Spanned,
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let callee = evaluate_node(callee, Rc::clone(env), debug)?;
+    let Value::Procedure(callee) = callee else {
+        return Err(runtime_err(
+            format!(
+                "Cannot call a value of type {}: only procedures can be called",
+                type_name(&callee)
+            ),
+            span,
+            env,
+        ));
+    };
+    let arg_values = evaluate_arguments(args, env, debug)?;
+    invoke_value(&callee, arg_values, env, span, debug)
+}
+
+fn eval_field_access(
+    object: &Spanned,
+    field: &str,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let object = evaluate_node(object, Rc::clone(env), debug)?;
+    if let Value::Module(module) = &object {
+        return module_member(module, field, env, span);
+    }
+    let object = expect_object(object, "read field", field, span, env)?;
+    let instance = object.borrow();
+    instance
+        .fields
+        .get(&DictKey::String(field.to_string()))
+        .cloned()
+        .ok_or_else(|| {
+            runtime_err(
+                format!("{} has no field '{}'", instance.class.name, field),
+                span,
+                env,
+            )
+        })
+}
+
+/// `obj.field <- value`. Assigning a field that does not exist yet creates it,
+/// which is how a constructor gives an object its fields.
+fn eval_field_assignment(
+    object: &Spanned,
+    field: &str,
+    value: &Spanned,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let object = evaluate_node(object, Rc::clone(env), debug)?;
+    let object = expect_object(object, "assign field", field, span, env)?;
+    let new_val = evaluate_node(value, Rc::clone(env), debug)?;
+    object
+        .borrow_mut()
+        .fields
+        .insert(DictKey::String(field.to_string()), new_val.clone());
+    Ok(new_val)
+}
+
+fn eval_method_call(
+    object: &Spanned,
+    method: &str,
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let object = evaluate_node(object, Rc::clone(env), debug)?;
+    if let Value::Module(module) = &object {
+        return call_module_member(module, method, args, env, span, debug);
+    }
+    let object = expect_object(object, "call method", method, span, env)?;
+    let class = Rc::clone(&object.borrow().class);
+    if let Some(procedure) = class.methods.get(method) {
+        let arg_values = evaluate_arguments(args, env, debug)?;
+        let frame = format!("{}.{}", class.name, method);
+        return invoke_procedure(
+            &frame,
+            procedure,
+            CallScope::Method(object),
+            arg_values,
+            env,
+            span,
+            debug,
+        );
+    }
+    // Without a method of that name, a field holding a procedure is called
+    // instead -- `button.onClick()` -- as a plain call, with no `self`.
+    let field = match object
+        .borrow()
+        .fields
+        .get(&DictKey::String(method.to_string()))
+    {
+        Some(Value::Procedure(callee)) => callee.clone(),
+        _ => {
+            return Err(runtime_err(
+                format!("{} has no method '{}'", class.name, method),
+                span,
+                env,
+            ));
+        }
+    };
+    let arg_values = evaluate_arguments(args, env, debug)?;
+    invoke_value(&field, arg_values, env, span, debug)
+}
+
+/// `module.name`: a top-level variable of the module, or one of its procedures
+/// as a value.
+fn module_member(
+    module: &Rc<Module>,
+    name: &str,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+) -> EvalResult {
+    check_public(module, name, env, span)?;
+    let scope = module.env.borrow();
+    if let Some(value) = scope.variables.get(name) {
+        return Ok(value.clone());
+    }
+    if let Some(procedure) = scope.get_procedure(name) {
+        return Ok(Value::Procedure(ProcedureValue {
+            name: Some(Rc::from(name)),
+            procedure,
+            scope: None,
+        }));
+    }
+    Err(runtime_err(
+        format!(
+            "Module {} has no variable or procedure '{}'",
+            module.display_name(),
+            name
+        ),
+        span,
+        env,
+    ))
+}
+
+/// Refuse `module.name` for a name the module's file keeps to itself. A name it
+/// does not declare at all is left to the caller's "has no" error.
+fn check_public(
+    module: &Module,
+    name: &str,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+) -> Result<(), Interruption> {
+    let declared = {
+        let scope = module.env.borrow();
+        scope.variables.contains_key(name)
+            || scope.get_procedure(name).is_some()
+            || scope.get_class(name).is_some()
+    };
+    if !declared || module.visibility.is_public(name) {
+        return Ok(());
+    }
+    Err(runtime_err(
+        format!("'{}' is private to {}", name, module.display_name()),
+        span,
+        env,
+    ))
+}
+
+/// `module.name(args)`: one of the module's procedures, a CLASS it declares, or a
+/// top-level variable of it holding a procedure.
+fn call_module_member(
+    module: &Rc<Module>,
+    name: &str,
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    check_public(module, name, env, span)?;
+    let (procedure, class) = {
+        let scope = module.env.borrow();
+        (scope.get_procedure(name), scope.get_class(name))
+    };
+    let arg_values = evaluate_arguments(args, env, debug)?;
+    if let Some(procedure) = procedure {
+        let frame = format!("{}.{}", module.display_name(), name);
+        return invoke_procedure(
+            &frame,
+            &procedure,
+            CallScope::Caller,
+            arg_values,
+            env,
+            span,
+            debug,
+        );
+    }
+    if let Some(class) = class {
+        return instantiate(&class, arg_values, env, span, debug);
+    }
+    match module_member(module, name, env, span)? {
+        Value::Procedure(callee) => invoke_value(&callee, arg_values, env, span, debug),
+        other => Err(runtime_err(
+            format!(
+                "Cannot call '{}': it holds a value of type {}, not a procedure",
+                name,
+                type_name(&other)
+            ),
+            span,
+            env,
+        )),
+    }
+}
+
+/// `FOR EACH item IN x`, or with a second name `FOR EACH key, value IN` a
+/// dictionary and `FOR EACH index, item IN` a list or string, the index counting
+/// from 1 like every other index.
+fn eval_for_each(
+    var_name: &str,
+    second: Option<&str>,
+    list: &Spanned,
+    body: &Spanned,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> EvalResult {
+    let list_val = evaluate_node(list, Rc::clone(env), debug)?;
+    // With one name it gets the item; with two, the first gets the key or index.
+    let watch = Watch::following(env);
+    let bind = |key: Value, item: Value| {
+        if let Some(watch) = &watch {
+            if let Some(second) = second {
+                watch.assigned(var_name, &key, env);
+                watch.assigned(second, &item, env);
+            } else {
+                watch.assigned(var_name, &item, env);
+            }
+        }
+        let mut scope = env.borrow_mut();
+        match second {
+            Some(second) => {
+                scope.set(var_name.to_string(), key);
+                scope.set(second.to_string(), item);
+            }
+            None => scope.set(var_name.to_string(), item),
+        }
+    };
+    let mut result = Value::Unit;
+    let mut turns = Turns::new(span, env);
+    match list_val {
+        Value::List(elements) => {
+            let count = elements.len();
+            for (i, element) in elements.into_iter().enumerate() {
+                bind(Value::Integer(BigInt::from(i + 1)), element);
+                match evaluate_loop_body(body, Rc::clone(env), debug, i + 1 == count, &mut turns)? {
+                    LoopFlow::Next(value) => result = value,
+                    LoopFlow::Break => break,
+                }
+            }
+        }
+        Value::String(s) => {
+            let count = s.chars().count();
+            for (i, c) in s.chars().enumerate() {
+                bind(
+                    Value::Integer(BigInt::from(i + 1)),
+                    Value::String(c.to_string()),
+                );
+                match evaluate_loop_body(body, Rc::clone(env), debug, i + 1 == count, &mut turns)? {
+                    LoopFlow::Next(value) => result = value,
+                    LoopFlow::Break => break,
+                }
+            }
+        }
+        Value::Dictionary(entries) => {
+            let count = entries.len();
+            for (i, (key, value)) in entries.iter().enumerate() {
+                // Alone, a dictionary's items are its keys; the value is only
+                // copied out when there is a name for it.
+                match second {
+                    Some(_) => bind(key_to_value(key), value.clone()),
+                    None => bind(Value::Unit, key_to_value(key)),
+                }
+                match evaluate_loop_body(body, Rc::clone(env), debug, i + 1 == count, &mut turns)? {
+                    LoopFlow::Next(value) => result = value,
+                    LoopFlow::Break => break,
+                }
+            }
+        }
+        _ => {
+            return Err(runtime_err(
+                "FOR EACH requires list, string, or dictionary",
+                span,
+                env,
+            ));
+        }
+    }
+    Ok(result)
+}
+
+/// `a, b <- value` and `[x, [y, z]] <- value`. The whole shape is checked before
+/// anything is bound, so an assignment that does not fit leaves every name as it
+/// was.
+fn unpack_assignment(
+    target: &Spanned,
+    value: &Value,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
+    let mut bindings = Vec::new();
+    collect_unpacked(target, value, &mut bindings, env)?;
+    if let Some(watch) = Watch::following(env) {
+        for (name, value) in &bindings {
+            watch.assigned(name, value, env);
+        }
+    }
+    let mut scope = env.borrow_mut();
+    for (name, value) in bindings {
+        scope.set(name.to_string(), value);
+    }
+    Ok(())
+}
+
+fn collect_unpacked<'a>(
+    target: &'a Spanned,
+    value: &Value,
+    bindings: &mut Vec<(&'a str, Value)>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
+    let targets = match &target.node {
+        AstNode::Identifier(name) => {
+            bindings.push((name, value.clone()));
+            return Ok(());
+        }
+        AstNode::List(targets) => targets,
+        // The parser admits nothing else into an unpacking target.
+        _ => return Err(runtime_err("Invalid assignment target", target.span, env)),
+    };
+    let names = if targets.len() == 1 { "name" } else { "names" };
+    let Value::List(items) = value else {
+        return Err(runtime_err(
+            format!(
+                "Cannot unpack a value of type {} into {} {}: only a list can be unpacked",
+                type_name(value),
+                targets.len(),
+                names
+            ),
+            target.span,
+            env,
+        ));
+    };
+    if items.len() != targets.len() {
+        return Err(runtime_err(
+            format!(
+                "Cannot unpack a list of {} {} into {} {}",
+                items.len(),
+                if items.len() == 1 {
+                    "element"
+                } else {
+                    "elements"
+                },
+                targets.len(),
+                names
+            ),
+            target.span,
+            env,
+        ));
+    }
+    for (target, item) in targets.iter().zip(items) {
+        collect_unpacked(target, item, bindings, env)?;
+    }
+    Ok(())
+}
 
-            match (min_val, max_val) {
-                (Value::Integer(min_int), Value::Integer(max_int)) => {
-                    if min_int > max_int {
-                        return Err(runtime_err(
-                            "Min value must be less than or equal to max value",
-                            span,
-                            &env,
-                        ));
-                    }
-                    let min_i64 = min_int
-                        .to_i64()
-                        .ok_or_else(|| runtime_err("RANDOM bounds too large", span, &env))?;
-                    let max_i64 = max_int
-                        .to_i64()
-                        .ok_or_else(|| runtime_err("RANDOM bounds too large", span, &env))?;
-                    let mut rng = rand::rng();
-                    Ok(Value::Integer(BigInt::from(
-                        rng.random_range(min_i64..=max_i64),
-                    )))
-                }
-                _ => Err(runtime_err("RANDOM requires integer arguments", span, &env)),
+/// Declare a CLASS in the current scope.
+///
+/// The parser accepts any block as a class body; only the methods mean anything
+/// in it. A field initialiser written at class level would have no instance to
+/// land on, so it is refused rather than run once and lost. Fields are made by
+/// assigning to them, normally in the constructor.
+fn eval_class_decl(name: &str, body: &Spanned, env: &Rc<RefCell<Environment>>) -> EvalResult {
+    let statements: &[Spanned] = match &body.node {
+        AstNode::Block(statements) => statements,
+        _ => std::slice::from_ref(body),
+    };
+    let declared_in = env.borrow().modules.borrow().current_file();
+    let mut methods = HashMap::new();
+    for statement in statements {
+        match &statement.node {
+            AstNode::ProcedureDecl(method, params, method_body) => {
+                methods.insert(
+                    method.clone(),
+                    Rc::new((params.clone(), (**method_body).clone(), declared_in.clone())),
+                );
+            }
+            AstNode::Comment => {}
+            _ => {
+                return Err(runtime_err(
+                    format!(
+                        "Only PROCEDURE declarations may appear in the body of CLASS {}",
+                        name
+                    ),
+                    statement.span,
+                    env,
+                ));
             }
         }
+    }
+    env.borrow_mut().declare_class(Class {
+        name: name.to_string(),
+        methods,
+        declared_in,
+    });
+    Ok(Value::Unit)
+}
 
-        AstNode::ClassDecl(name, _body) => Err(runtime_err(
+/// Call a procedure by name with arguments that have already been evaluated.
+///
+/// Shared by `AstNode::ProcedureCall` and CALL so that dispatching by name at
+/// runtime gets identical semantics. A variable in scope holding a procedure
+/// value is tried first, the same order plain identifier lookup uses, so in
+/// `PROCEDURE apply(f, x) { RETURN f(x) }` the parameter `f` is called even when
+/// a procedure `f` is also declared. Only then is a declared procedure found;
+/// a variable holding anything else does not hide one.
+fn invoke_named(
+    name: &str,
+    args: Vec<Value>,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let callee = env.borrow().with_var(name, |value| match value {
+        Value::Procedure(callee) => Ok(callee.clone()),
+        other => Err(type_name(other)),
+    });
+    if let Some(Ok(callee)) = callee {
+        return invoke_value(&callee, args, env, span, debug);
+    }
+    let procedure = env.borrow().get_procedure(name);
+    if let Some(procedure) = procedure {
+        return invoke_procedure(name, &procedure, CallScope::Caller, args, env, span, debug);
+    }
+    match callee {
+        Some(Err(kind)) => Err(runtime_err(
             format!(
-                "CLASS '{}' is not yet implemented. Class declarations are parsed but instantiation, method dispatch, and field access are not supported.",
-                name
+                "Cannot call '{}': it holds a value of type {}, not a procedure",
+                name, kind
             ),
             span,
-            &env,
+            env,
+        )),
+        _ => Err(runtime_err(
+            format!("Procedure '{}' not found", name),
+            span,
+            env,
         )),
+    }
+}
 
-        AstNode::Import(path) => eval_import(path, &env, span, debug),
+/// Call a procedure value: in the scope it closed over when it has one, in the
+/// caller's otherwise.
+fn invoke_value(
+    callee: &ProcedureValue,
+    args: Vec<Value>,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let scope = match &callee.scope {
+        Some(scope) => CallScope::Closure(Rc::clone(scope)),
+        None => CallScope::Caller,
+    };
+    invoke_procedure(
+        callee.frame_name(),
+        &callee.procedure,
+        scope,
+        args,
+        env,
+        span,
+        debug,
+    )
+}
 
-        AstNode::Return(expr) => {
-            let value = evaluate_node(expr, Rc::clone(&env), debug)?;
-            Err(Interruption::Return(value))
-        }
+/// What the scope a procedure body runs in is a child of.
+enum CallScope {
+    /// The caller's scope: the dynamic scoping every named procedure has.
+    Caller,
+    /// The caller's scope, with `self` bound to the object a method was called
+    /// on. Bound before the parameters, so a parameter named `self` wins.
+    Method(Object),
+    /// The scope an anonymous procedure was written in.
+    Closure(Rc<RefCell<Environment>>),
+}
 
-        AstNode::Sort(list_expr) => {
-            let list_val = evaluate_node(list_expr, Rc::clone(&env), debug)?;
-            if let Value::List(mut elements) = list_val {
-                elements.sort_by(sort_cmp);
-                Ok(Value::List(elements))
-            } else {
-                Err(runtime_err(
-                    "SORT requires a list as an argument",
-                    span,
-                    &env,
-                ))
-            }
+/// Make a new object of `class`, running its constructor -- the method named
+/// after the class -- when it has one.
+///
+/// A class without a constructor can still be instantiated with `Point()`, and
+/// its fields assigned afterwards; passing arguments to it is an error, since
+/// there is nothing to receive them.
+fn instantiate(
+    class: &Rc<Class>,
+    args: Vec<Value>,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let object = Rc::new(RefCell::new(Instance {
+        class: Rc::clone(class),
+        fields: Dict::default(),
+    }));
+    match class.methods.get(&class.name) {
+        Some(constructor) => {
+            let frame = format!("{}.{}", class.name, class.name);
+            // Whatever the constructor RETURNs is discarded: the call's value is
+            // the object, as it is in every language with this convention.
+            invoke_procedure(
+                &frame,
+                constructor,
+                CallScope::Method(Rc::clone(&object)),
+                args,
+                env,
+                span,
+                debug,
+            )?;
         }
-
-        AstNode::TryCatch {
-            try_block,
-            error_var,
-            catch_block,
-        } => match evaluate_node(try_block, Rc::clone(&env), debug) {
-            Ok(result) => Ok(result),
-            Err(Interruption::Return(val)) => Err(Interruption::Return(val)),
-            // EXIT is not an error, so CATCH must let it through.
-            Err(Interruption::Exit(code)) => Err(Interruption::Exit(code)),
-            Err(Interruption::Error(error)) => {
-                // The catch block runs in the current scope, like `IF`, `FOR EACH`,
-                // `REPEAT` and the TRY block. A child scope threw the assignments
-                // away, and
-                //
-                //     TRY { config <- READFILE(p) } CATCH (e) { config <- "" }
-                //     DISPLAY(config)
-                //
-                // failed with "Undefined variable: config". Only the error
-                // variable is scoped to the block, and whatever it shadowed in
-                // this scope is put back afterwards.
-                let shadowed = error_var
-                    .as_ref()
-                    .map(|name| (name.clone(), env.borrow().local(name)));
-                if let Some(var_name) = error_var {
-                    env.borrow_mut()
-                        .set(var_name.clone(), Value::String(error.message));
-                }
-                let result = evaluate_node(catch_block, Rc::clone(&env), debug);
-                // Restored on the error path too, so a CATCH that itself fails, or
-                // that RETURNs, does not leave the error variable behind.
-                if let Some((name, previous)) = shadowed {
-                    match previous {
-                        Some(value) => env.borrow_mut().set(name, value),
-                        None => env.borrow_mut().remove_local(&name),
-                    }
-                }
-                result
-            }
-        },
-
-        AstNode::Eval(expr) => {
-            let expr_val = evaluate_node(expr, Rc::clone(&env), debug)?;
-            if let Value::String(s) = expr_val {
-                // Frame-guarded: `code <- "EVAL(code)"` would otherwise recurse
-                // through the real stack without ever touching MAX_STACK_DEPTH.
-                with_meta_frame("EVAL", &s, &env, span, || {
-                    let mut lexer = crate::lexer::Lexer::new(&s);
-                    let tokens = lexer.tokenize();
-                    let mut parser = crate::parser::Parser::new(tokens);
-                    let ast = parser.parse_expression(debug).map_err(|mut e| {
-                        e.span = Some(span);
-                        Interruption::Error(e)
-                    })?;
-
-                    evaluate_node(&ast, Rc::clone(&env), debug)
-                })
-            } else {
-                Err(runtime_err("EVAL requires a string argument", span, &env))
-            }
+        None if !args.is_empty() => {
+            return Err(runtime_err(
+                format!(
+                    "CLASS {} has no constructor, so it takes no arguments (got {})",
+                    class.name,
+                    args.len()
+                ),
+                span,
+                env,
+            ));
         }
+        None => {}
+    }
+    Ok(Value::Object(object))
+}
 
-        AstNode::Comment => Ok(Value::Unit),
+/// The object a field or method operation was applied to, or an error naming
+/// what it was applied to instead.
+fn expect_object(
+    value: Value,
+    operation: &str,
+    member: &str,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Object, Interruption> {
+    match value {
+        Value::Object(object) => Ok(object),
+        other => Err(runtime_err(
+            format!(
+                "Cannot {} '{}': a value of type {} is not an object",
+                operation,
+                member,
+                type_name(&other)
+            ),
+            span,
+            env,
+        )),
     }
 }
 
-/// Call a user-declared procedure with arguments that have already been
-/// evaluated.
+/// Run a procedure body: the one path every call takes, whether it came from a
+/// plain call, CALL, a method, a constructor or a procedure value -- so all of
+/// them share the recursion guard, the stack frame for error traces, and the
+/// treatment of a `RETURN` as the call's value.
 ///
-/// Split out of `AstNode::ProcedureCall` so that CALL can dispatch by name at
-/// runtime and get identical semantics -- the same recursion guard, the same
-/// stack frame for error traces, and the same treatment of a `RETURN` as the
-/// call's value.
+/// `frame` is the name the stack trace shows: `Point.move` for a method,
+/// `<anonymous>` for an anonymous procedure.
 fn invoke_procedure(
-    name: &str,
+    frame: &str,
+    procedure: &Procedure,
+    scope: CallScope,
     args: Vec<Value>,
     env: &Rc<RefCell<Environment>>,
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    if env.borrow().stack_depth() >= MAX_STACK_DEPTH {
-        return Err(runtime_err(
-            "Stack overflow: maximum recursion depth exceeded",
+    let most = env.borrow().budget.limits.stack_depth;
+    if env.borrow().stack_depth() >= most {
+        return Err(limit_exceeded(
+            format!(
+                "Stack overflow: maximum recursion depth exceeded (limit: {}, --max-depth)",
+                most
+            ),
             span,
             env,
         ));
     }
-    let procedure = env
-        .borrow()
-        .get_procedure(name)
-        .ok_or_else(|| runtime_err(format!("Procedure '{}' not found", name), span, env))?;
-    let local_env = Rc::new(RefCell::new(Environment::new_with_parent(Rc::clone(env))));
     let (params, body, declared_in) = (&procedure.0, &procedure.1, &procedure.2);
-    // `zip` stops at the shorter side, so a call with too few arguments leaves
-    // the remaining parameters unbound and extra arguments are ignored -- the
-    // behaviour this interpreter has always had.
-    for (param, arg) in params.iter().zip(args) {
-        local_env.borrow_mut().set(param.clone(), arg);
+    check_arity(frame, params, args.len(), env, span)?;
+    let modules = Rc::clone(&env.borrow().modules);
+    let parent = match &scope {
+        CallScope::Closure(captured) => Rc::clone(captured),
+        // A procedure declared in a module imported with AS runs in that module,
+        // whoever calls it: `strings.parse(x)` has to reach the helpers and the
+        // top-level variables of strings.psl, not those of the caller. Only looked
+        // for once a module exists, so a program without one pays nothing.
+        CallScope::Caller | CallScope::Method(_) => {
+            let home = match declared_in {
+                Some(file) if !modules.borrow().namespaces.is_empty() => {
+                    modules.borrow().namespace_declaring(file)
+                }
+                _ => None,
+            };
+            match home {
+                Some(module) => Rc::clone(&module.env),
+                None => Rc::clone(env),
+            }
+        }
+    };
+    let local_env = Rc::new(RefCell::new(Environment::new_with_parent(parent)));
+    if let CallScope::Method(object) = scope {
+        local_env
+            .borrow_mut()
+            .set("self".to_string(), Value::Object(object));
     }
 
     // Enter the procedure's own file for the duration of the call, so SCRIPTPATH
@@ -1962,7 +5020,6 @@ fn invoke_procedure(
     // rather than where it was called from. Skipped -- to a single pointer
     // comparison -- when the procedure lives in the file already executing, which
     // is every call in a one-file program.
-    let modules = Rc::clone(&env.borrow().modules);
     let entered_file = match declared_in {
         Some(file) => {
             let already_here = modules.borrow().is_current(file);
@@ -1977,10 +5034,44 @@ fn invoke_procedure(
     };
 
     env.borrow().push_frame(StackFrame {
-        name: name.to_string(),
+        name: frame.to_string(),
         span,
     });
-    let body_result = evaluate_node(body, Rc::clone(&local_env), debug);
+    // The caller's variables, for the memory cap to find: see [`Budget::scopes`].
+    let measured = env.borrow().budget.limits.memory.is_some();
+    if measured {
+        env.borrow().budget.scopes.borrow_mut().push(Rc::clone(env));
+    }
+    // Bound inside the frame and the procedure's file: a default is code written
+    // in the declaration, and an error in one belongs there.
+    let watch = Watch::following_calls(env);
+    if let Some(watch) = &watch {
+        watch.called(frame, declared_in.as_deref().map(PathBuf::as_path));
+    }
+    let body_result = bind_arguments(params, args, &local_env, debug).and_then(|()| {
+        if let Some(watch) = &watch {
+            watch.entered(frame, params, &local_env);
+        }
+        evaluate_node(body, Rc::clone(&local_env), debug)
+    });
+    if let (Some(watch), Ok(value) | Err(Interruption::Return(value))) =
+        (watch.as_ref().filter(|watch| watch.values), &body_result)
+    {
+        // A constructor -- the method named after its class -- gives the object
+        // it built, whatever its body ends with: see [`instantiate`].
+        let constructed = frame
+            .split_once('.')
+            .is_some_and(|(class, method)| class == method)
+            .then(|| local_env.borrow().get("self"))
+            .flatten();
+        watch.returned(frame, constructed.as_ref().unwrap_or(value), &local_env);
+    }
+    if let Some(watch) = &watch {
+        watch.left();
+    }
+    if measured {
+        env.borrow().budget.scopes.borrow_mut().pop();
+    }
     env.borrow().pop_frame();
     if entered_file {
         modules.borrow_mut().stack.pop();
@@ -1993,7 +5084,105 @@ fn invoke_procedure(
     }
 }
 
-/// Find the file an IMPORT names.
+/// Refuse a call with more arguments than the procedure has parameters, or fewer
+/// than it has parameters without a default.
+///
+/// Calls used to zip the two lists, so a missing argument left its parameter
+/// unbound -- surfacing, if at all, as an "undefined variable" deep inside the
+/// body -- and an extra one vanished without a trace.
+fn check_arity(
+    frame: &str,
+    params: &[Parameter],
+    given: usize,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+) -> Result<(), Interruption> {
+    match expected_arguments(params, given) {
+        None => Ok(()),
+        Some(expected) => Err(runtime_err(
+            format!(
+                "Procedure '{}' expects {}, but was called with {}",
+                frame, expected, given
+            ),
+            span,
+            env,
+        )),
+    }
+}
+
+/// How many arguments a procedure taking `params` expects -- "2 arguments", "1 to
+/// 3 arguments", "at least 1 argument" -- when `given` is not among them, or
+/// `None` when it is. Shared with `fpli lint`, which finds the same mistake
+/// without running the call.
+pub fn expected_arguments(params: &[Parameter], given: usize) -> Option<String> {
+    let required = params
+        .iter()
+        .filter(|p| p.default.is_none() && !p.variadic)
+        .count();
+    let variadic = params.last().is_some_and(|p| p.variadic);
+    let most = params.len() - usize::from(variadic);
+    if given >= required && (variadic || given <= most) {
+        return None;
+    }
+    let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+    Some(if variadic {
+        format!("at least {} {}", required, plural(required))
+    } else if required == most {
+        format!("{} {}", most, plural(most))
+    } else {
+        format!("{} to {} arguments", required, most)
+    })
+}
+
+/// Bind a call's arguments to its parameters, which [`check_arity`] has already
+/// matched them against: in order, then defaults for the parameters left over,
+/// then the rest into the variadic parameter as a list.
+fn bind_arguments(
+    params: &[Parameter],
+    args: Vec<Value>,
+    local_env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<(), Interruption> {
+    let mut args = args.into_iter();
+    for param in params {
+        let value = if param.variadic {
+            Value::List(args.by_ref().collect())
+        } else {
+            match (args.next(), &param.default) {
+                (Some(arg), _) => arg,
+                // Evaluated in the callee's own scope, so a default can use the
+                // parameters before it: `PROCEDURE pad(s, width <- LENGTH(s))`.
+                (None, Some(default)) => evaluate_node(default, Rc::clone(local_env), debug)?,
+                (None, None) => {
+                    unreachable!("check_arity admits no call that leaves a required parameter out")
+                }
+            }
+        };
+        local_env.borrow_mut().set(param.name.clone(), value);
+    }
+    Ok(())
+}
+
+/// Find the file an IMPORT names, for the file being run.
+fn resolve_import(
+    path: &str,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+) -> Result<PathBuf, Interruption> {
+    let importer = env.borrow().modules.borrow().current_file();
+    let resolved = find_import(path, importer.as_deref().map(PathBuf::as_path))
+        .map_err(|message| runtime_err(message, span, env))?;
+    // Checked where the file was found, which a relative IMPORT does not spell.
+    fs_guard(
+        "IMPORT",
+        Need::Read(resolved.display().to_string()),
+        span,
+        env,
+    )?;
+    Ok(resolved)
+}
+
+/// Find the file an IMPORT in `importer` names.
 ///
 /// A relative path is resolved against the directory of the file doing the
 /// importing first, and only then against the process's working directory. That
@@ -2004,19 +5193,15 @@ fn invoke_procedure(
 /// `IMPORT "strings.psl"` mean the same file.
 ///
 /// The returned path is canonicalised so that `lib.psl`, `./lib.psl` and an
-/// absolute spelling of the same file are recognised as one module.
-fn resolve_import(
-    path: &str,
-    env: &Rc<RefCell<Environment>>,
-    span: Span,
-) -> Result<PathBuf, Interruption> {
+/// absolute spelling of the same file are recognised as one module. `fpli check`
+/// follows IMPORTs through here too, so it finds exactly the files a run would.
+pub fn find_import(path: &str, importer: Option<&Path>) -> Result<PathBuf, String> {
     let requested = Path::new(path);
     // Directories to try the name against, in order. An absolute path has none: it
     // already names where to look.
     let mut bases: Vec<PathBuf> = Vec::new();
     if !requested.is_absolute() {
-        let importer = env.borrow().modules.borrow().current_file();
-        if let Some(dir) = importer.as_deref().and_then(|p| p.parent()) {
+        if let Some(dir) = importer.and_then(|p| p.parent()) {
             bases.push(dir.to_path_buf());
         }
         bases.push(PathBuf::from("."));
@@ -2042,25 +5227,17 @@ fn resolve_import(
                 return std::fs::canonicalize(&candidate)
                     .map(|resolved| PathBuf::from(system::strip_unc(&resolved)))
                     .map_err(|e| {
-                        runtime_err(
-                            format!("Could not resolve import '{}': {}", candidate.display(), e),
-                            span,
-                            env,
-                        )
+                        format!("Could not resolve import '{}': {}", candidate.display(), e)
                     });
             }
             tried.push(candidate.display().to_string());
         }
     }
 
-    Err(runtime_err(
-        format!(
-            "Could not find imported file '{}'. Tried: {}",
-            path,
-            tried.join(", ")
-        ),
-        span,
-        env,
+    Err(format!(
+        "Could not find imported file '{}'. Tried: {}",
+        path,
+        tried.join(", ")
     ))
 }
 
@@ -2073,6 +5250,15 @@ fn resolve_import(
 fn eval_import(path: &str, env: &Rc<RefCell<Environment>>, span: Span, debug: bool) -> EvalResult {
     let resolved = resolve_import(path, env, span)?;
 
+    // The imported file's declarations go into the *root* scope, not the scope the
+    // IMPORT was written in. A flat namespace is what the language documents, and an
+    // IMPORT inside a procedure body or a CATCH block would otherwise declare
+    // everything into that block's scope and lose it all on the way out -- while
+    // still marking the file loaded, so a later top-level IMPORT of the same file
+    // became a silent no-op and its names were unreachable for the rest of the run.
+    // Inside a module imported with AS, the root is that module's own scope.
+    let root = root_env(env);
+
     // Recorded before the body runs, so a cycle finds the file already loaded
     // and stops instead of recursing.
     {
@@ -2083,78 +5269,81 @@ fn eval_import(path: &str, env: &Rc<RefCell<Environment>>, span: Span, debug: bo
         // must not restart it. Without this, importing the entry script re-ran its
         // whole top level -- including any ISMAIN block -- half-way through the
         // first run, before the declarations that block depends on existed.
-        if modules.loaded.contains(&resolved) || modules.entry.as_deref() == Some(&resolved) {
+        if modules.is_loaded(&root, &resolved) || modules.entry.as_deref() == Some(&resolved) {
             return Ok(Value::Unit);
         }
-        modules.loaded.push(resolved.clone());
+        modules.set_loaded(&root, &resolved, true);
         modules.stack.push(Rc::new(resolved.clone()));
     }
 
-    // The imported file's declarations go into the *root* scope, not the scope the
-    // IMPORT was written in. A flat namespace is what the language documents, and an
-    // IMPORT inside a procedure body or a CATCH block would otherwise declare
-    // everything into that block's scope and lose it all on the way out -- while
-    // still marking the file loaded, so a later top-level IMPORT of the same file
-    // became a silent no-op and its names were unreachable for the rest of the run.
-    let root = root_env(env);
-
-    let result = (|| -> EvalResult {
-        let content = std::fs::read_to_string(&resolved).map_err(|e| {
-            runtime_err(
-                format!("Failed to read imported file {}: {}", resolved.display(), e),
-                span,
-                env,
-            )
-        })?;
-
-        let mut lexer = crate::lexer::Lexer::new(&content);
-        let tokens = lexer.tokenize();
-        let imported_ast = crate::parser::parse(tokens, debug).map_err(|e| {
-            runtime_err(
-                format!(
-                    "Failed to parse imported file {}: {}",
-                    resolved.display(),
-                    e.format(&content)
-                ),
-                span,
-                env,
-            )
-        })?;
-
-        env.borrow()
-            .modules
-            .borrow_mut()
-            .sources
-            .insert(resolved.clone(), Rc::from(content.as_str()));
-        let outcome = evaluate_node(&imported_ast, Rc::clone(&root), debug);
+    let procedures_before = root.borrow().procedure_table();
+    let classes_before = root.borrow().class_table();
+    // A clash is only a message until the file is off the module stack: built any
+    // earlier, the error would carry the imported file's text with the span of the
+    // IMPORT statement, which is in the importing file.
+    let mut clash: Option<String> = None;
+    let existing = env.borrow().modules.borrow().namespace_of(&resolved);
+    let imported = match existing {
+        // Already running as a module -- imported with AS, or a file with
+        // private names imported elsewhere: its public names are all there is to
+        // add.
+        Some(module) => {
+            declare_public_names(&module, &root);
+            Ok(Value::Unit)
+        }
+        None => parse_imported_file(&resolved, env, span, debug).and_then(|ast| {
+            let visibility = Visibility::of(&ast, env)?;
+            // Checked before any of the file runs, so a refused IMPORT has printed
+            // nothing and assigned nothing.
+            clash = clash_message(
+                path,
+                top_level_collisions(&ast, &visibility, &root, &resolved),
+            );
+            if clash.is_some() {
+                return Ok(Value::Unit);
+            }
+            if !visibility.is_declared() {
+                return run_imported_file(&ast, &root, debug);
+            }
+            // Its private names must stay reachable from its own procedures, so
+            // the file runs as a module and only the public names come across.
+            let module = run_module(&resolved, &ast, visibility, env, debug)?;
+            declare_public_names(&module, &root);
+            Ok(Value::Unit)
+        }),
+    };
+    let result = imported.inspect(|_| {
+        // What the file's top level could not show in advance -- a declaration
+        // inside an IF, say -- is caught once it has run, and undone.
+        if clash.is_none() {
+            clash = clash_message(
+                path,
+                check_import_collisions(&procedures_before, &classes_before, &root),
+            );
+        }
+        if clash.is_some() {
+            return;
+        }
         // Procedure tables are snapshotted into each scope when it is created, so a
         // scope that already existed when the import ran would not see the names the
         // import just declared -- which is what an IMPORT inside a procedure body
-        // hits. Merge the root's table down the chain to the importing scope.
+        // hits. Merge the root's tables down the chain to the importing scope.
         let table = root.borrow().procedure_table();
+        let classes = root.borrow().class_table();
         let mut scope = Rc::clone(env);
         loop {
             if Rc::ptr_eq(&scope, &root) {
                 break;
             }
             scope.borrow_mut().merge_procedures(&table);
+            scope.borrow_mut().merge_classes(&classes);
             let parent = scope.borrow().parent.clone();
             match parent {
                 Some(parent) => scope = parent,
                 None => break,
             }
         }
-        match outcome {
-            // A top-level RETURN ends the imported file, nothing more. Letting it
-            // through made it unwind the *importing* program: everything after the
-            // IMPORT was skipped and the run ended silently with status 0.
-            Ok(_) | Err(Interruption::Return(_)) => Ok(Value::Unit),
-            // The error already carries the file and source it came from, so it is
-            // passed through untouched. Re-wrapping it here nested the diagnostic once
-            // per level of import.
-            Err(other) => Err(other),
-        }
-    })();
+    });
 
     let modules = Rc::clone(&env.borrow().modules);
     {
@@ -2165,11 +5354,273 @@ fn eval_import(path: &str, env: &Rc<RefCell<Environment>>, span: Span, debug: bo
         // A file whose body failed is *not* loaded. Leaving it recorded made the
         // failure permanent: a retry after fixing the cause silently did nothing,
         // and MODULES() listed a module that never finished.
-        if result.is_err() {
-            modules.loaded.retain(|loaded| loaded != &resolved);
+        if result.is_err() || clash.is_some() {
+            modules.set_loaded(&root, &resolved, false);
         }
     }
-    result
+    match clash {
+        Some(message) => Err(runtime_err(message, span, env)),
+        None => result,
+    }
+}
+
+/// Which file a declaration came from, as a clash report names it.
+fn declared_by(file: &Option<Rc<PathBuf>>) -> String {
+    match file {
+        Some(file) => file.display().to_string(),
+        None => "the program".to_string(),
+    }
+}
+
+/// The procedures and classes a file declares at its top level, and would make
+/// public, that another file has already declared into `root`.
+fn top_level_collisions(
+    ast: &Spanned,
+    visibility: &Visibility,
+    root: &Rc<RefCell<Environment>>,
+    resolved: &Path,
+) -> Vec<(String, String)> {
+    let statements: &[Spanned] = match &ast.node {
+        AstNode::Program(statements) => statements,
+        _ => std::slice::from_ref(ast),
+    };
+    let procedures = root.borrow().procedure_table();
+    let classes = root.borrow().class_table();
+    let elsewhere =
+        |file: &Option<Rc<PathBuf>>| file.as_deref().map(PathBuf::as_path) != Some(resolved);
+    let mut collisions = Vec::new();
+    for statement in statements {
+        match &statement.node {
+            AstNode::ProcedureDecl(name, ..) if visibility.is_public(name) => {
+                if let Some(before) = procedures.get(name)
+                    && elsewhere(&before.2)
+                {
+                    collisions.push((format!("procedure '{}'", name), declared_by(&before.2)));
+                }
+            }
+            AstNode::ClassDecl(name, _) if visibility.is_public(name) => {
+                if let Some(before) = classes.get(name)
+                    && elsewhere(&before.declared_in)
+                {
+                    collisions.push((format!("CLASS {}", name), declared_by(&before.declared_in)));
+                }
+            }
+            _ => {}
+        }
+    }
+    collisions
+}
+
+/// Undo a plain IMPORT that redeclared a procedure or class another file had
+/// already declared, putting the earlier declaration back, and return what it
+/// redeclared.
+///
+/// Without this the file imported last silently won, and every caller of the
+/// first library's `parse` started running the second one's. A file may still
+/// redeclare its own names, which is what importing it again after a failure
+/// does.
+fn check_import_collisions(
+    procedures_before: &ProcedureTable,
+    classes_before: &ClassTable,
+    root: &Rc<RefCell<Environment>>,
+) -> Vec<(String, String)> {
+    let mut collisions = Vec::new();
+    let procedures = root.borrow().procedure_table();
+    for (name, procedure) in procedures.iter() {
+        if let Some(before) = procedures_before.get(name)
+            && !Rc::ptr_eq(before, procedure)
+            && before.2 != procedure.2
+        {
+            collisions.push((format!("procedure '{}'", name), declared_by(&before.2)));
+            root.borrow_mut()
+                .declare_procedure(name.clone(), Rc::clone(before));
+        }
+    }
+    let classes = root.borrow().class_table();
+    for (name, class) in classes.iter() {
+        if let Some(before) = classes_before.get(name)
+            && !Rc::ptr_eq(before, class)
+            && before.declared_in != class.declared_in
+        {
+            collisions.push((format!("CLASS {}", name), declared_by(&before.declared_in)));
+            Rc::make_mut(&mut root.borrow_mut().classes).insert(name.clone(), Rc::clone(before));
+        }
+    }
+    collisions
+}
+
+/// The error a refused IMPORT of `path` reports, if it clashed at all.
+fn clash_message(path: &str, mut collisions: Vec<(String, String)>) -> Option<String> {
+    if collisions.is_empty() {
+        return None;
+    }
+    // Sorted, so the report is the same every run despite the hash maps.
+    collisions.sort();
+    let listed: Vec<String> = collisions
+        .iter()
+        .map(|(what, file)| format!("{} is already declared by {}", what, file))
+        .collect();
+    Some(format!(
+        "IMPORT \"{}\" clashes with names already in use: {}. Import one of the files with AS to keep both",
+        path,
+        listed.join("; ")
+    ))
+}
+
+/// `IMPORT "file" AS name`: run the file in a module scope of its own, once per
+/// run, and bind `name` to the module in the importing scope.
+fn eval_import_as(
+    path: &str,
+    alias: &str,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    let resolved = resolve_import(path, env, span)?;
+    let modules = Rc::clone(&env.borrow().modules);
+    if modules.borrow().entry.as_deref() == Some(&resolved) {
+        return Err(runtime_err(
+            format!(
+                "Cannot IMPORT \"{}\" AS {}: it is the script being run",
+                path, alias
+            ),
+            span,
+            env,
+        ));
+    }
+    let existing = modules.borrow().namespace_of(&resolved);
+    let module = match existing {
+        Some(module) => module,
+        None => {
+            modules.borrow_mut().stack.push(Rc::new(resolved.clone()));
+            let result = parse_imported_file(&resolved, env, span, debug).and_then(|ast| {
+                let visibility = Visibility::of(&ast, env)?;
+                run_module(&resolved, &ast, visibility, env, debug)
+            });
+            modules.borrow_mut().stack.pop();
+            result?
+        }
+    };
+    env.borrow_mut()
+        .set(alias.to_string(), Value::Module(module));
+    Ok(Value::Unit)
+}
+
+/// Run a parsed file's top level in a new module scope and register the module.
+///
+/// The caller has already pushed the file onto the module stack.
+fn run_module(
+    resolved: &Path,
+    ast: &Spanned,
+    visibility: Visibility,
+    env: &Rc<RefCell<Environment>>,
+    debug: bool,
+) -> Result<Rc<Module>, Interruption> {
+    let modules = Rc::clone(&env.borrow().modules);
+    let file = modules
+        .borrow()
+        .stack
+        .last()
+        .cloned()
+        .unwrap_or_else(|| Rc::new(resolved.to_path_buf()));
+    let module_env = Environment::new_module(&root_env(env).borrow());
+    let module = Rc::new(Module {
+        file,
+        env: Rc::new(RefCell::new(module_env)),
+        loaded: RefCell::default(),
+        visibility,
+    });
+    // Registered before the body runs, so a cycle back to this file gets the
+    // module as far as it has got, as with a plain IMPORT.
+    modules.borrow_mut().namespaces.push(Rc::clone(&module));
+    let result = run_imported_file(ast, &module.env, debug)
+        .and_then(|_| module.visibility.check_exported(&module.env, env));
+    if let Err(failure) = result {
+        modules
+            .borrow_mut()
+            .namespaces
+            .retain(|m| !Rc::ptr_eq(m, &module));
+        return Err(failure);
+    }
+    Ok(module)
+}
+
+/// Declare a module's public procedures, classes and top-level variables in
+/// `root`, the scope a plain IMPORT declares into.
+///
+/// Variables are copied as they are when the import runs, as assigning them would
+/// copy them; procedures and classes are shared, and keep running in the module.
+fn declare_public_names(module: &Module, root: &Rc<RefCell<Environment>>) {
+    let scope = module.env.borrow();
+    let mut root = root.borrow_mut();
+    for (name, value) in &scope.variables {
+        if module.visibility.is_public(name) && !MODULE_BUILTINS.contains(&name.as_str()) {
+            root.variables.insert(name.clone(), value.clone());
+        }
+    }
+    for (name, procedure) in scope.procedure_table().iter() {
+        if module.visibility.is_public(name) {
+            root.declare_procedure(name.clone(), Rc::clone(procedure));
+        }
+    }
+    for (name, class) in scope.class_table().iter() {
+        if module.visibility.is_public(name) {
+            Rc::make_mut(&mut root.classes).insert(name.clone(), Rc::clone(class));
+        }
+    }
+}
+
+/// Read and parse an imported file, and record its text for error reports.
+fn parse_imported_file(
+    resolved: &Path,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> Result<Spanned, Interruption> {
+    let content = std::fs::read_to_string(resolved).map_err(|e| {
+        runtime_err(
+            format!("Failed to read imported file {}: {}", resolved.display(), e),
+            span,
+            env,
+        )
+    })?;
+
+    let mut lexer = crate::lexer::Lexer::new(&content);
+    let tokens = lexer.tokenize();
+    let imported_ast = crate::parser::parse(tokens, debug).map_err(|e| {
+        runtime_err(
+            format!(
+                "Failed to parse imported file {}: {}",
+                resolved.display(),
+                e.format(&content)
+            ),
+            span,
+            env,
+        )
+    })?;
+
+    env.borrow()
+        .modules
+        .borrow_mut()
+        .sources
+        .insert(resolved.to_path_buf(), Rc::from(content.as_str()));
+    Ok(imported_ast)
+}
+
+/// Run an imported file's top level in `scope`.
+///
+/// The caller has already pushed the file onto the module stack.
+fn run_imported_file(ast: &Spanned, scope: &Rc<RefCell<Environment>>, debug: bool) -> EvalResult {
+    match evaluate_node(ast, Rc::clone(scope), debug) {
+        // A top-level RETURN ends the imported file, nothing more. Letting it
+        // through made it unwind the *importing* program: everything after the
+        // IMPORT was skipped and the run ended silently with status 0.
+        Ok(_) | Err(Interruption::Return(_)) => Ok(Value::Unit),
+        // The error already carries the file and source it came from, so it is
+        // passed through untouched. Re-wrapping it here nested the diagnostic once
+        // per level of import.
+        Err(other) => Err(other),
+    }
 }
 
 /// The outermost scope of the run: where IMPORT puts what it declares.
@@ -2547,16 +5998,21 @@ fn undefined_variable_message(name: &str) -> String {
 /// built-in, and answering "is PROCESSES a name I know" should not enumerate every
 /// process. `test_every_builtin_is_listed_for_the_undefined_variable_hint` reads the
 /// dispatcher's own match arms and fails if this list falls behind.
-/// Every name [`eval_builtin`] dispatches, sorted for `binary_search`.
-const BUILTIN_NAMES: &[&str] = &[
+/// Every name [`eval_builtin`] dispatches, sorted for `binary_search`. Public
+/// for `fpli lsp`, which offers them as completions and documents each on hover.
+pub const BUILTIN_NAMES: &[&str] = &[
     "ABS",
     "ABSPATH",
     "ACOS",
     "APPENDFILE",
     "ARCH",
     "ASIN",
+    "ASSERT",
+    "ASSERTEQUAL",
+    "ASSERTRAISES",
     "ATAN",
     "BASENAME",
+    "BREAKPOINT",
     "CACHEDIR",
     "CALL",
     "CEIL",
@@ -2712,6 +6168,37 @@ fn set_in_path(container: &mut Value, path: &[Value], new_val: Value) -> Result<
     set_in_path(inner, rest, new_val)
 }
 
+/// Evaluate `object` and hand its field `field` to `f` for mutation in place.
+///
+/// The field-rooted counterpart of [`Environment::with_var_mut`], for the
+/// operations that change a container where it lives: indexed assignment and
+/// APPEND, INSERT and REMOVE. A missing field is an error rather than being
+/// created, since none of those operations can sensibly start from nothing.
+fn with_field_mut<T>(
+    object: &Spanned,
+    field: &str,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+    f: impl FnOnce(&mut Value) -> Result<T, String>,
+) -> Result<T, Interruption> {
+    let object = evaluate_node(object, Rc::clone(env), debug)?;
+    let object = expect_object(object, "modify field", field, span, env)?;
+    let mut instance = object.borrow_mut();
+    let class_name = instance.class.name.clone();
+    let value = instance
+        .fields
+        .get_mut(&DictKey::String(field.to_string()))
+        .ok_or_else(|| {
+            runtime_err(
+                format!("{} has no field '{}'", class_name, field),
+                span,
+                env,
+            )
+        })?;
+    f(value).map_err(|msg| runtime_err(msg, span, env))
+}
+
 /// Assign into `target[index_val]`, where `target` is either a variable or
 /// itself an indexed access. Nested paths of any depth are followed in place
 /// from the variable at the root of the path. Every index expression along the
@@ -2737,6 +6224,18 @@ fn assign_indexed(
                 path.push(evaluate_node(inner_index, Rc::clone(env), debug)?);
                 current = inner_target;
             }
+            // `self.items[i] <- v`: the container is an object's field. The object
+            // is shared, so the write lands in it directly -- there is no binding
+            // to copy down into the current scope first.
+            AstNode::FieldAccess(object, field) => {
+                path.reverse();
+                return with_field_mut(object, field, env, span, debug, |container| {
+                    if !matches!(container, Value::List(_) | Value::Dictionary(_)) {
+                        return Err(format!("Field {} is not a list or dictionary", field));
+                    }
+                    set_in_path(container, &path, new_val)
+                });
+            }
             _ => return Err(runtime_err("Invalid list assignment target", span, env)),
         }
     };
@@ -2849,6 +6348,7 @@ fn eval_builtin(
         "TIMEZONE" => Some(eval_builtin_timezone(args, env, span, debug)),
         "TIMEZONES" => Some(eval_builtin_timezones(args, env, span)),
         "MILLITIME" => Some(eval_builtin_millitime(args, env, span)),
+        "BREAKPOINT" => Some(eval_builtin_breakpoint(args, env, span)),
         "CONTAINS" => Some(eval_builtin_contains(args, env, span, debug)),
         "FIND" => Some(eval_builtin_find(args, env, span, debug)),
         "RANGE" => Some(eval_builtin_range(args, env, span, debug)),
@@ -2940,6 +6440,10 @@ fn eval_builtin(
         "PROCEDURES" => Some(eval_builtin_procedures(args, env, span)),
         "CALL" => Some(eval_builtin_call(args, env, span, debug)),
 
+        "ASSERT" => Some(eval_builtin_assert(args, env, span, debug)),
+        "ASSERTEQUAL" => Some(eval_builtin_assertequal(args, env, span, debug)),
+        "ASSERTRAISES" => Some(eval_builtin_assertraises(args, env, span, debug)),
+
         "SCRIPTPATH" => Some(eval_builtin_scriptpath(args, env, span)),
         "ISMAIN" => Some(eval_builtin_ismain(args, env, span)),
         "MODULES" => Some(eval_builtin_modules(args, env, span)),
@@ -2963,18 +6467,19 @@ fn eval_builtin_sleep(
     #[cfg(any(not(target_arch = "wasm32"), feature = "wasi"))]
     {
         let seconds = evaluate_node(&args[0], Rc::clone(env), debug)?;
-        match seconds {
-            Value::Integer(n) => {
-                let secs = n.to_u64().unwrap_or(0);
-                thread::sleep(Duration::from_secs(secs));
-                Ok(Value::Unit)
-            }
-            Value::Float(f) => {
-                thread::sleep(Duration::from_secs_f64(f));
-                Ok(Value::Unit)
-            }
-            _ => Err(runtime_err("SLEEP requires a numeric argument", span, env)),
+        let duration = match seconds {
+            Value::Integer(n) => Duration::from_secs(n.to_u64().unwrap_or(0)),
+            Value::Float(f) => Duration::from_secs_f64(f),
+            _ => return Err(runtime_err("SLEEP requires a numeric argument", span, env)),
+        };
+        // Only as long as the time limit leaves, and then the run is over.
+        let budget = Rc::clone(&env.borrow().budget);
+        let (duration, cut_short) = budget.sleepable(duration);
+        thread::sleep(duration);
+        if cut_short {
+            return Err(budget.out_of_time(span, env));
         }
+        Ok(Value::Unit)
     }
     #[cfg(all(target_arch = "wasm32", not(feature = "wasi")))]
     {
@@ -3552,6 +7057,33 @@ fn eval_builtin_timezones(
     Ok(Value::List(tzs))
 }
 
+/// Stop before the next statement. Under `fpli dap` that is a stop like any
+/// other, reported to the editor. In a program printing to the terminal with no
+/// debugger attached yet, the console debugger is attached, reading its commands
+/// from the same standard input as INPUT. A run that captures its output -- a
+/// test, the library API, the browser playground -- has nobody at a terminal to
+/// ask, so there it does nothing.
+fn eval_builtin_breakpoint(
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+) -> EvalResult {
+    if !args.is_empty() {
+        return Err(runtime_err("BREAKPOINT takes no arguments", span, env));
+    }
+    let watch = env.borrow().watch.get().cloned();
+    match watch {
+        Some(watch) => watch.breakpoint(),
+        None => {
+            let streaming = matches!(*env.borrow().sink().borrow(), OutputSink::Stream { .. });
+            if streaming {
+                attach_console(env);
+            }
+        }
+    }
+    Ok(Value::Unit)
+}
+
 fn eval_builtin_millitime(
     args: &[Spanned],
     env: &Rc<RefCell<Environment>>,
@@ -3951,16 +7483,24 @@ fn eval_builtin_removekey(
     dict_remove_entry(name, &key_val, env, span)
 }
 
-/// Reject a file builtin on targets with no filesystem to reach.
+/// Reject a file builtin on targets with no filesystem to reach, or where the
+/// run's permissions do not cover the path it `need`s.
 ///
 /// `std::fs` compiles for `wasm32-unknown-unknown` but every call there fails
 /// with an opaque "operation not supported" from the browser sandbox, so the
 /// guard reports the real reason instead. Native targets and WASI both have a
-/// real filesystem, so there it is a no-op. The error is an ordinary runtime
-/// error, so a program that wants to degrade gracefully can wrap the call in
-/// TRY/CATCH.
+/// real filesystem, so there it asks [`permit`]. Either way the error is an
+/// ordinary runtime error, so a program that wants to degrade gracefully can
+/// wrap the call in TRY/CATCH.
+///
+/// Called once the path is evaluated, and before anything is done with it.
 #[cfg(all(target_arch = "wasm32", not(feature = "wasi")))]
-fn fs_guard(name: &str, span: Span, env: &Rc<RefCell<Environment>>) -> Result<(), Interruption> {
+fn fs_guard(
+    name: &str,
+    _need: Need,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
     Err(runtime_err(
         format!(
             "{} is not supported in WebAssembly: the browser sandbox has no filesystem",
@@ -3972,8 +7512,26 @@ fn fs_guard(name: &str, span: Span, env: &Rc<RefCell<Environment>>) -> Result<()
 }
 
 #[cfg(any(not(target_arch = "wasm32"), feature = "wasi"))]
-fn fs_guard(_name: &str, _span: Span, _env: &Rc<RefCell<Environment>>) -> Result<(), Interruption> {
-    Ok(())
+fn fs_guard(
+    name: &str,
+    need: Need,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
+    permit(name, need, span, env)
+}
+
+/// Refuse what the run's [`Permissions`] do not grant: the check every builtin
+/// reaching past the program makes, through [`fs_guard`] for the filesystem
+/// and directly for processes and the environment.
+fn permit(
+    name: &str,
+    need: Need,
+    span: Span,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<(), Interruption> {
+    let checked = env.borrow().permissions.check(name, need);
+    checked.map_err(|message| runtime_err(message, span, env))
 }
 
 /// Evaluate the single argument every file builtin starts with: a path, which
@@ -4012,9 +7570,11 @@ fn fs_err(
     )
 }
 
-/// Shared prologue for the file builtins that take exactly one path argument.
+/// Shared prologue for the file builtins that take exactly one path argument,
+/// which they `need` to read or to change.
 fn eval_one_path_builtin(
     name: &str,
+    need: fn(String) -> Need,
     args: &[Spanned],
     env: &Rc<RefCell<Environment>>,
     span: Span,
@@ -4027,8 +7587,9 @@ fn eval_one_path_builtin(
             env,
         ));
     }
-    fs_guard(name, span, env)?;
-    eval_path_arg(name, args, env, span, debug)
+    let path = eval_path_arg(name, args, env, span, debug)?;
+    fs_guard(name, need(path.clone()), span, env)?;
+    Ok(path)
 }
 
 /// Shared prologue for the file builtins that take a path and the text to write.
@@ -4046,8 +7607,8 @@ fn eval_path_and_text(
             env,
         ));
     }
-    fs_guard(name, span, env)?;
     let path = eval_path_arg(name, args, env, span, debug)?;
+    fs_guard(name, Need::Write(path.clone()), span, env)?;
     match evaluate_node(&args[1], Rc::clone(env), debug)? {
         Value::String(text) => Ok((path, text)),
         _ => Err(runtime_err(
@@ -4067,7 +7628,7 @@ fn eval_builtin_readfile(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("READFILE", args, env, span, debug)?;
+    let path = eval_one_path_builtin("READFILE", Need::Read, args, env, span, debug)?;
     match std::fs::read_to_string(&path) {
         Ok(contents) => Ok(Value::String(contents)),
         Err(e) => Err(fs_err("READFILE", &path, &e, span, env)),
@@ -4080,7 +7641,7 @@ fn eval_builtin_readlines(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("READLINES", args, env, span, debug)?;
+    let path = eval_one_path_builtin("READLINES", Need::Read, args, env, span, debug)?;
     match std::fs::read_to_string(&path) {
         // `lines` strips the terminator, treats "\r\n" and "\n" alike, and does
         // not invent a trailing empty line for a file that ends in a newline --
@@ -4131,7 +7692,7 @@ fn eval_builtin_fileexists(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("FILEEXISTS", args, env, span, debug)?;
+    let path = eval_one_path_builtin("FILEEXISTS", Need::Read, args, env, span, debug)?;
     // A path that cannot be inspected (no permission on a parent directory, say)
     // is reported as absent rather than as an error: the question asked is only
     // whether this program can see something there.
@@ -4144,7 +7705,7 @@ fn eval_builtin_filesize(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("FILESIZE", args, env, span, debug)?;
+    let path = eval_one_path_builtin("FILESIZE", Need::Read, args, env, span, debug)?;
     match std::fs::metadata(&path) {
         // Bytes, not characters: LENGTH(READFILE(p)) is the character count, and
         // for non-ASCII text the two legitimately differ.
@@ -4159,7 +7720,7 @@ fn eval_builtin_deletefile(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("DELETEFILE", args, env, span, debug)?;
+    let path = eval_one_path_builtin("DELETEFILE", Need::Write, args, env, span, debug)?;
     // Files only, and said so plainly. The OS is no help here: `remove_file` on a
     // directory reports EPERM on macOS and EISDIR on Linux, neither of which tells
     // the reader that the *kind* of path was the problem.
@@ -4190,7 +7751,7 @@ fn eval_builtin_deletedir(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("DELETEDIR", args, env, span, debug)?;
+    let path = eval_one_path_builtin("DELETEDIR", Need::Write, args, env, span, debug)?;
     // Empty directories only, which is what makes this safe by construction: a
     // directory holding anything refuses to go, so a mistyped path cannot destroy
     // work.
@@ -4222,7 +7783,7 @@ fn eval_builtin_deletetree(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("DELETETREE", args, env, span, debug)?;
+    let path = eval_one_path_builtin("DELETETREE", Need::Write, args, env, span, debug)?;
     // Refuses a plain file, so a path that turned out to name something narrower
     // than expected is reported rather than quietly acted on.
     if system::is_file(&path) || system::is_symlink(&path) {
@@ -4252,7 +7813,7 @@ fn eval_builtin_filemtime(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("FILEMTIME", args, env, span, debug)?;
+    let path = eval_one_path_builtin("FILEMTIME", Need::Read, args, env, span, debug)?;
     let metadata =
         std::fs::metadata(&path).map_err(|e| fs_err("FILEMTIME", &path, &e, span, env))?;
     let modified = metadata
@@ -4273,7 +7834,7 @@ fn eval_builtin_listdir(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("LISTDIR", args, env, span, debug)?;
+    let path = eval_one_path_builtin("LISTDIR", Need::Read, args, env, span, debug)?;
     let entries = std::fs::read_dir(&path).map_err(|e| fs_err("LISTDIR", &path, &e, span, env))?;
     let mut names = Vec::new();
     for entry in entries {
@@ -4292,7 +7853,7 @@ fn eval_builtin_makedir(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("MAKEDIR", args, env, span, debug)?;
+    let path = eval_one_path_builtin("MAKEDIR", Need::Write, args, env, span, debug)?;
     // Recursive, and succeeds when the directory is already there: MAKEDIR states
     // the directory should exist rather than that it should be created now.
     match std::fs::create_dir_all(&path) {
@@ -4437,9 +7998,10 @@ fn eval_builtin_rename(
     debug: bool,
 ) -> EvalResult {
     expect_arity("RENAME", args, 2, env, span)?;
-    fs_guard("RENAME", span, env)?;
     let from = eval_string_arg("RENAME", &args[0], "source path", env, span, debug)?;
     let to = eval_string_arg("RENAME", &args[1], "destination path", env, span, debug)?;
+    fs_guard("RENAME", Need::Write(from.clone()), span, env)?;
+    fs_guard("RENAME", Need::Write(to.clone()), span, env)?;
     // Also the move operation: `fs::rename` relocates within a filesystem and
     // replaces an existing destination, which is what `os.rename` does too.
     match std::fs::rename(&from, &to) {
@@ -4459,9 +8021,10 @@ fn eval_builtin_copyfile(
     debug: bool,
 ) -> EvalResult {
     expect_arity("COPYFILE", args, 2, env, span)?;
-    fs_guard("COPYFILE", span, env)?;
     let from = eval_string_arg("COPYFILE", &args[0], "source path", env, span, debug)?;
     let to = eval_string_arg("COPYFILE", &args[1], "destination path", env, span, debug)?;
+    fs_guard("COPYFILE", Need::Read(from.clone()), span, env)?;
+    fs_guard("COPYFILE", Need::Write(to.clone()), span, env)?;
     // `fs::copy` truncates the destination before reading the source, so copying a
     // file onto itself destroyed it and still reported success.
     if system::is_same_file(&from, &to) {
@@ -4504,6 +8067,7 @@ fn eval_builtin_getenv(
         ));
     }
     let name = eval_string_arg("GETENV", &args[0], "variable name", env, span, debug)?;
+    permit("GETENV", Need::Env(name.clone()), span, env)?;
     match system::env_var(&name) {
         Some(value) => Ok(Value::String(value)),
         // With a default supplied, a missing variable is the expected case; with
@@ -4529,6 +8093,7 @@ fn eval_builtin_setenv(
     expect_arity("SETENV", args, 2, env, span)?;
     let name = eval_string_arg("SETENV", &args[0], "variable name", env, span, debug)?;
     let value = eval_string_arg("SETENV", &args[1], "value", env, span, debug)?;
+    permit("SETENV", Need::Env(name.clone()), span, env)?;
     sys_err(system::set_env_var(&name, &value), env, span)?;
     Ok(Value::Unit)
 }
@@ -4541,6 +8106,7 @@ fn eval_builtin_unsetenv(
 ) -> EvalResult {
     expect_arity("UNSETENV", args, 1, env, span)?;
     let name = eval_string_arg("UNSETENV", &args[0], "variable name", env, span, debug)?;
+    permit("UNSETENV", Need::Env(name.clone()), span, env)?;
     sys_err(system::unset_env_var(&name), env, span)?;
     Ok(Value::Unit)
 }
@@ -4551,6 +8117,7 @@ fn eval_builtin_envvars(
     span: Span,
 ) -> EvalResult {
     expect_no_args("ENVVARS", args, env, span)?;
+    permit("ENVVARS", Need::EveryEnv, span, env)?;
     let mut dict = Dict::default();
     for (name, value) in system::env_vars() {
         dict.insert(DictKey::String(name), Value::String(value));
@@ -4625,6 +8192,7 @@ fn eval_builtin_exec(
     // already displayed is pushed all the way out first -- the same reason SLEEP
     // flushes. The child's own output is captured into the returned dictionary
     // rather than written to the terminal, so the two cannot interleave.
+    permit("EXEC", Need::Exec, span, env)?;
     flush_all(env);
     let output = sys_err(system::exec(&program, &argv), env, span)?;
     Ok(command_output_value(output))
@@ -4638,6 +8206,7 @@ fn eval_builtin_shell(
 ) -> EvalResult {
     expect_arity("SHELL", args, 1, env, span)?;
     let command = eval_string_arg("SHELL", &args[0], "command line", env, span, debug)?;
+    permit("SHELL", Need::Exec, span, env)?;
     flush_all(env);
     let output = sys_err(system::shell(&command), env, span)?;
     Ok(command_output_value(output))
@@ -4651,6 +8220,7 @@ fn eval_builtin_which(
 ) -> EvalResult {
     expect_arity("WHICH", args, 1, env, span)?;
     let program = eval_string_arg("WHICH", &args[0], "program name", env, span, debug)?;
+    permit("WHICH", Need::Exec, span, env)?;
     // NULL rather than an error: "is this tool installed?" is a question, and the
     // answer "no" is not a failure.
     Ok(optional_string(system::which(&program)))
@@ -4719,6 +8289,7 @@ fn eval_builtin_processinfo(
 ) -> EvalResult {
     expect_arity("PROCESSINFO", args, 1, env, span)?;
     let pid = eval_pid_arg("PROCESSINFO", &args[0], env, span, debug)?;
+    permit("PROCESSINFO", Need::Exec, span, env)?;
     // NULL for "nothing is running under that pid", which a program checking on
     // a child it started needs to be able to ask without catching an error.
     match system::process_info(pid) {
@@ -4733,6 +8304,7 @@ fn eval_builtin_processes(
     span: Span,
 ) -> EvalResult {
     expect_no_args("PROCESSES", args, env, span)?;
+    permit("PROCESSES", Need::Exec, span, env)?;
     Ok(Value::List(
         system::processes().iter().map(process_info_value).collect(),
     ))
@@ -4746,6 +8318,7 @@ fn eval_builtin_kill(
 ) -> EvalResult {
     expect_arity("KILL", args, 1, env, span)?;
     let pid = eval_pid_arg("KILL", &args[0], env, span, debug)?;
+    permit("KILL", Need::Exec, span, env)?;
     if pid == system::current_pid() {
         return Err(runtime_err(
             "KILL refuses to terminate the interpreter itself; use EXIT instead",
@@ -4776,6 +8349,7 @@ fn eval_builtin_chdir(
 ) -> EvalResult {
     expect_arity("CHDIR", args, 1, env, span)?;
     let path = eval_string_arg("CHDIR", &args[0], "path", env, span, debug)?;
+    fs_guard("CHDIR", Need::Read(path.clone()), span, env)?;
     sys_err(system::chdir(&path), env, span)?;
     Ok(Value::Unit)
 }
@@ -4844,8 +8418,8 @@ fn eval_builtin_realpath(
     debug: bool,
 ) -> EvalResult {
     expect_arity("REALPATH", args, 1, env, span)?;
-    fs_guard("REALPATH", span, env)?;
     let path = eval_string_arg("REALPATH", &args[0], "path", env, span, debug)?;
+    fs_guard("REALPATH", Need::Read(path.clone()), span, env)?;
     Ok(Value::String(sys_err(system::realpath(&path), env, span)?))
 }
 
@@ -4855,7 +8429,7 @@ fn eval_builtin_isfile(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("ISFILE", args, env, span, debug)?;
+    let path = eval_one_path_builtin("ISFILE", Need::Read, args, env, span, debug)?;
     Ok(Value::Boolean(system::is_file(&path)))
 }
 
@@ -4865,7 +8439,7 @@ fn eval_builtin_isdir(
     span: Span,
     debug: bool,
 ) -> EvalResult {
-    let path = eval_one_path_builtin("ISDIR", args, env, span, debug)?;
+    let path = eval_one_path_builtin("ISDIR", Need::Read, args, env, span, debug)?;
     Ok(Value::Boolean(system::is_dir(&path)))
 }
 
@@ -4995,6 +8569,9 @@ fn type_name(value: &Value) -> &'static str {
         Value::Boolean(_) => "boolean",
         Value::List(_) => "list",
         Value::Dictionary(_) => "dictionary",
+        Value::Object(_) => "object",
+        Value::Procedure(_) => "procedure",
+        Value::Module(_) => "module",
         Value::Null => "null",
         Value::NaN => "nan",
         Value::Unit => "unit",
@@ -5009,7 +8586,13 @@ fn eval_builtin_typeof(
 ) -> EvalResult {
     expect_arity("TYPEOF", args, 1, env, span)?;
     let value = evaluate_node(&args[0], Rc::clone(env), debug)?;
-    Ok(Value::String(type_name(&value).to_string()))
+    // An object reports its class, which is the question a program asking for the
+    // type of an object is actually asking. Error messages keep the generic
+    // "object" from `type_name`.
+    Ok(Value::String(match &value {
+        Value::Object(object) => object.borrow().class.name.clone(),
+        other => type_name(other).to_string(),
+    }))
 }
 
 /// Run `body` as a nested evaluation of source the program produced itself.
@@ -5020,7 +8603,8 @@ fn eval_builtin_typeof(
 /// ```
 ///
 /// Nothing in that loop is a procedure call, so the recursion bypasses
-/// [`MAX_STACK_DEPTH`] and overflows the real stack. [`MAX_META_DEPTH`] bounds it.
+/// [`Limits::stack_depth`] and overflows the real stack. [`Limits::meta_depth`]
+/// bounds it.
 fn with_meta_frame(
     name: &str,
     source: &str,
@@ -5029,11 +8613,12 @@ fn with_meta_frame(
     body: impl FnOnce() -> EvalResult,
 ) -> EvalResult {
     let meta_depth = Rc::clone(&env.borrow().meta_depth);
-    if meta_depth.get() >= MAX_META_DEPTH {
-        return Err(runtime_err(
+    let most = env.borrow().budget.limits.meta_depth;
+    if meta_depth.get() >= most {
+        return Err(limit_exceeded(
             format!(
-                "Maximum {} nesting depth exceeded (limit: {}). Source evaluated by EVAL or EXECUTE may not go on evaluating itself.",
-                name, MAX_META_DEPTH
+                "Maximum {} nesting depth exceeded (limit: {}, --max-eval-depth). Source evaluated by EVAL or EXECUTE may not go on evaluating itself.",
+                name, most
             ),
             span,
             env,
@@ -5238,7 +8823,12 @@ fn eval_builtin_call(
     // Deliberately user-defined procedures only. Built-ins take unevaluated
     // arguments so that INPUT, RANDOM and the assignment-style list operations
     // can see their own syntax, and there is no need to reach them by name: a
-    // built-in's name is known when the program is written.
+    // built-in's name is known when the program is written. A class name makes
+    // an object, exactly as writing the call out would.
+    let class = env.borrow().get_class(&name);
+    if let Some(class) = class {
+        return instantiate(&class, call_args, env, span, debug);
+    }
     if env.borrow().get_procedure(&name).is_none() {
         return Err(runtime_err(
             format!(
@@ -5249,7 +8839,161 @@ fn eval_builtin_call(
             env,
         ));
     }
-    invoke_procedure(&name, call_args, env, span, debug)
+    invoke_named(&name, call_args, env, span, debug)
+}
+
+// ---------------------------------------------------------------------------
+// Assertions
+// ---------------------------------------------------------------------------
+
+/// A failed assertion: an error like any other, which TRY can catch, but of its
+/// own kind so that `fpli test` reports it as the test failing rather than as the
+/// test breaking.
+fn assertion_failed(
+    detail: String,
+    message: Option<&Spanned>,
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> Interruption {
+    let message = match message.map(|m| evaluate_node(m, Rc::clone(env), debug)) {
+        None => format!("Assertion failed: {}", detail),
+        Some(Ok(message)) => format!(
+            "Assertion failed: {}: {}",
+            value_to_string(&message),
+            detail
+        ),
+        Some(Err(interruption)) => return interruption,
+    };
+    let mut error = raised_error(message, span, env);
+    error.kind = ErrorKind::Assertion;
+    Interruption::Error(error)
+}
+
+/// A value as an assertion message shows it. Quoted when it is a string, or
+/// `expected 1, got 1` would leave the reader to guess that one was text.
+fn assertion_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => value_to_string(other),
+    }
+}
+
+fn eval_builtin_assert(
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    if args.is_empty() || args.len() > 2 {
+        return Err(runtime_err(
+            "ASSERT requires a condition, and optionally a message",
+            span,
+            env,
+        ));
+    }
+    match evaluate_node(&args[0], Rc::clone(env), debug)? {
+        Value::Boolean(true) => Ok(Value::Unit),
+        Value::Boolean(false) => Err(assertion_failed(
+            "the condition was FALSE".to_string(),
+            args.get(1),
+            env,
+            span,
+            debug,
+        )),
+        other => Err(runtime_err(
+            format!(
+                "ASSERT requires a boolean condition, got {}",
+                type_name(&other)
+            ),
+            span,
+            env,
+        )),
+    }
+}
+
+fn eval_builtin_assertequal(
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(runtime_err(
+            "ASSERTEQUAL requires the actual and the expected value, and optionally a message",
+            span,
+            env,
+        ));
+    }
+    let actual = evaluate_node(&args[0], Rc::clone(env), debug)?;
+    let expected = evaluate_node(&args[1], Rc::clone(env), debug)?;
+    if values_equal(&actual, &expected) {
+        return Ok(Value::Unit);
+    }
+    let detail = format!(
+        "expected {}, got {}",
+        assertion_value(&expected),
+        assertion_value(&actual)
+    );
+    Err(assertion_failed(detail, args.get(2), env, span, debug))
+}
+
+/// ASSERTRAISES(expression) passes when evaluating `expression` raises an error,
+/// and hands back that error as CATCH would, so the test can look at it further.
+///
+/// The argument is evaluated here rather than before the call, which is what lets
+/// the error be caught at all. An argument that evaluates to a procedure is called
+/// with no arguments, for code that takes more than one expression to go wrong.
+/// A second argument is text the error's message must contain.
+fn eval_builtin_assertraises(
+    args: &[Spanned],
+    env: &Rc<RefCell<Environment>>,
+    span: Span,
+    debug: bool,
+) -> EvalResult {
+    if args.is_empty() || args.len() > 2 {
+        return Err(runtime_err(
+            "ASSERTRAISES requires an expression, and optionally text its error must contain",
+            span,
+            env,
+        ));
+    }
+    let raised = match evaluate_node(&args[0], Rc::clone(env), debug) {
+        Ok(Value::Procedure(callee)) => invoke_value(&callee, Vec::new(), env, span, debug),
+        other => other,
+    };
+    let (error, kind, thrown) = match raised {
+        Ok(_) => {
+            return Err(assertion_failed(
+                "expected an error, but none was raised".to_string(),
+                None,
+                env,
+                span,
+                debug,
+            ));
+        }
+        Err(Interruption::Error(error)) => {
+            let kind = error.kind.name().to_string();
+            (error, kind, None)
+        }
+        Err(Interruption::Throw(thrown)) => {
+            let Thrown { value, kind, error } = *thrown;
+            (error, kind, Some(value))
+        }
+        // EXIT, and a RETURN from inside a procedure argument, are not errors.
+        Err(other) => return Err(other),
+    };
+    if let Some(wanted) = args.get(1) {
+        let wanted = eval_string_arg("ASSERTRAISES", wanted, "message", env, span, debug)?;
+        if !error.message.contains(&wanted) {
+            let detail = format!(
+                "expected an error containing {:?}, got {:?}",
+                wanted, error.message
+            );
+            return Err(assertion_failed(detail, None, env, span, debug));
+        }
+    }
+    Ok(caught_error(error, kind, thrown, env))
 }
 
 // ---------------------------------------------------------------------------
@@ -5298,15 +9042,36 @@ fn eval_builtin_modules(
     span: Span,
 ) -> EvalResult {
     expect_no_args("MODULES", args, env, span)?;
-    let loaded: Vec<Value> = env
-        .borrow()
-        .modules
-        .borrow()
-        .loaded
+    let modules = Rc::clone(&env.borrow().modules);
+    let modules = modules.borrow();
+    // Every file imported by any means, once: plain imports into the program, then
+    // each module imported with AS along with the plain imports it made itself.
+    let mut files: Vec<&Path> = Vec::new();
+    let namespaced: Vec<std::cell::Ref<Vec<PathBuf>>> = modules
+        .namespaces
         .iter()
-        .map(|p| Value::String(p.to_string_lossy().into_owned()))
+        .map(|m| m.loaded.borrow())
         .collect();
-    Ok(Value::List(loaded))
+    let paths = modules.loaded.iter().map(PathBuf::as_path).chain(
+        modules
+            .namespaces
+            .iter()
+            .zip(&namespaced)
+            .flat_map(|(module, loaded)| {
+                std::iter::once(module.file.as_path()).chain(loaded.iter().map(PathBuf::as_path))
+            }),
+    );
+    for path in paths {
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    Ok(Value::List(
+        files
+            .iter()
+            .map(|p| Value::String(p.to_string_lossy().into_owned()))
+            .collect(),
+    ))
 }
 
 fn eval_single_num_fn(
@@ -5517,6 +9282,26 @@ fn evaluate_binary_op(left: &Value, op: &BinaryOperator, right: &Value) -> Resul
             Ok(Value::Dictionary(result))
         }
 
+        // Object identity
+        (Value::Object(a), BinaryOperator::Eq, Value::Object(b)) => {
+            Ok(Value::Boolean(Rc::ptr_eq(a, b)))
+        }
+        (Value::Object(a), BinaryOperator::NotEq, Value::Object(b)) => {
+            Ok(Value::Boolean(!Rc::ptr_eq(a, b)))
+        }
+        (Value::Procedure(_), BinaryOperator::Eq, Value::Procedure(_)) => {
+            Ok(Value::Boolean(values_equal(left, right)))
+        }
+        (Value::Procedure(_), BinaryOperator::NotEq, Value::Procedure(_)) => {
+            Ok(Value::Boolean(!values_equal(left, right)))
+        }
+        (Value::Module(_), BinaryOperator::Eq, Value::Module(_)) => {
+            Ok(Value::Boolean(values_equal(left, right)))
+        }
+        (Value::Module(_), BinaryOperator::NotEq, Value::Module(_)) => {
+            Ok(Value::Boolean(!values_equal(left, right)))
+        }
+
         _ => Err(format!(
             "Invalid operation: {:?} {:?} {:?}",
             left, op, right
@@ -5543,6 +9328,12 @@ fn values_equal(left: &Value, right: &Value) -> bool {
                 && a.iter()
                     .all(|(key, value)| b.get(key).is_some_and(|other| values_equal(value, other)))
         }
+        // Identity, not structure: two objects with equal fields are still two
+        // objects, and comparing structurally would never finish on a cycle.
+        (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(a, b),
+        // The same declaration, or the same evaluation of an anonymous one.
+        (Value::Procedure(a), Value::Procedure(b)) => Rc::ptr_eq(&a.procedure, &b.procedure),
+        (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
         (Value::Null, Value::Null) | (Value::Unit, Value::Unit) => true,
         _ => false,
     }
@@ -5558,22 +9349,52 @@ fn evaluate_unary_op(op: &UnaryOperator, val: &Value) -> Result<Value, String> {
 }
 
 fn value_to_string(value: &Value) -> String {
+    render_value(value, &mut Vec::new())
+}
+
+/// [`value_to_string`], carrying the objects currently being printed.
+///
+/// Objects are shared, so one can contain itself -- `node.parent.child` leading
+/// back to `node` -- and printing it naively would recurse forever. An object
+/// met again inside its own rendering prints as `Name{...}`, the way Python
+/// prints a list that contains itself.
+fn render_value(value: &Value, open: &mut Vec<*const RefCell<Instance>>) -> String {
     match value {
         Value::Integer(n) => n.to_string(),
         Value::Float(f) => f.to_string(),
         Value::String(s) => s.clone(),
         Value::Boolean(b) => b.to_string(),
         Value::List(elements) => {
-            let elements_str: Vec<String> = elements.iter().map(value_to_string).collect();
+            let elements_str: Vec<String> =
+                elements.iter().map(|e| render_value(e, open)).collect();
             format!("[{}]", elements_str.join(", "))
         }
         Value::Dictionary(entries) => {
             let entries_str: Vec<String> = entries
                 .iter()
-                .map(|(k, v)| format!("{}: {}", key_to_string(k), value_to_string(v)))
+                .map(|(k, v)| format!("{}: {}", key_to_string(k), render_value(v, open)))
                 .collect();
             format!("{{{}}}", entries_str.join(", "))
         }
+        Value::Object(object) => {
+            let instance = object.borrow();
+            if open.contains(&Rc::as_ptr(object)) {
+                return format!("{}{{...}}", instance.class.name);
+            }
+            open.push(Rc::as_ptr(object));
+            let fields_str: Vec<String> = instance
+                .fields
+                .iter()
+                .map(|(k, v)| format!("{}: {}", key_to_string(k), render_value(v, open)))
+                .collect();
+            open.pop();
+            format!("{}{{{}}}", instance.class.name, fields_str.join(", "))
+        }
+        Value::Procedure(callee) => match &callee.name {
+            Some(name) => format!("<procedure {}>", name),
+            None => "<procedure>".to_string(),
+        },
+        Value::Module(module) => format!("{:?}", module),
         Value::Unit => "".to_string(),
         Value::Null => "NULL".to_string(),
         Value::NaN => "NAN".to_string(),
@@ -30,7 +30,10 @@ pub enum Token {
     Else,
     Repeat,
     Until,
+    While,
     Times,
+    Break,
+    Continue,
 
     ListInsert,
     ListAppend,
@@ -57,6 +60,7 @@ pub enum Token {
     CloseBracket,
     Comma,
     Colon,
+    Dot,
     Newline,
     OpenBrace,
     CloseBrace,
@@ -67,24 +71,54 @@ pub enum Token {
     For,
     Each,
     In,
+    From,
+    To,
+    Step,
     Substring,
     Concat,
     Import,
+    As,
+    Export,
+    Private,
 
     Random,
     Sort,
+    Match,
+    Case,
     Try,
     Catch,
+    Finally,
+    Throw,
 
     Null,
     NaN,
     Eval,
 }
 
+/// A comment as written, which the token stream leaves out.
+///
+/// The parser never sees comments, but a formatter that dropped them would be
+/// useless, so [`Lexer::tokenize_with_comments`] hands them back beside the
+/// tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The comment's full text, marker included (`COMMENT note`, `// note`,
+    /// `# note`, or a whole `COMMENTBLOCK ... COMMENTBLOCK`), without the line
+    /// break that ends it.
+    pub text: String,
+    pub span: Span,
+    /// Whether this is a COMMENTBLOCK, which may span lines and so can never
+    /// share a line with code after it.
+    pub block: bool,
+}
+
 pub struct Lexer<'a> {
     chars: std::iter::Peekable<std::str::Chars<'a>>,
     input: &'a str,
     pos: usize,
+    /// `Some` only for [`Lexer::tokenize_with_comments`]: running a program has
+    /// no use for its comments.
+    comments: Option<Vec<Comment>>,
 }
 
 impl<'a> Lexer<'a> {
@@ -93,6 +127,27 @@ impl<'a> Lexer<'a> {
             chars: input.chars().peekable(),
             input,
             pos: 0,
+            comments: None,
+        }
+    }
+
+    /// Tokenize, and keep the comments the tokens leave out, in source order.
+    pub fn tokenize_with_comments(mut self) -> (Vec<(Token, Span)>, Vec<Comment>) {
+        self.comments = Some(Vec::new());
+        let tokens = self.tokenize();
+        (tokens, self.comments.unwrap_or_default())
+    }
+
+    /// Record a comment that runs from `start` to the current position.
+    fn keep_comment(&mut self, text: String, start: usize, block: bool) {
+        if let Some(comments) = &mut self.comments {
+            let text = text.trim_end().to_string();
+            let end = start + text.chars().count();
+            comments.push(Comment {
+                text,
+                span: Span::new(start, end),
+                block,
+            });
         }
     }
 
@@ -103,33 +158,41 @@ impl<'a> Lexer<'a> {
                 Token::Comment => {
                     // Same reason as `skip_line_comment`: the newline the comment ends
                     // on separates statements and has to survive.
+                    let mut text = String::from("COMMENT");
                     let mut ended_at_newline = false;
                     for c in self.chars.by_ref() {
-                        self.pos += 1;
                         if c == '\n' {
                             ended_at_newline = true;
                             break;
                         }
+                        self.pos += 1;
+                        text.push(c);
                     }
+                    self.keep_comment(text, span.start, false);
                     if ended_at_newline {
+                        self.pos += 1;
                         tokens.push((Token::Newline, Span::new(self.pos - 1, self.pos)));
                     }
                     continue;
                 }
                 Token::CommentBlock => {
+                    let mut text = String::from("COMMENTBLOCK");
                     let mut found_end = false;
-                    while self.chars.next().is_some() {
+                    while let Some(c) = self.chars.next() {
                         self.pos += 1;
+                        text.push(c);
 
                         if self.input[self.pos..].starts_with("COMMENTBLOCK") {
                             for _ in 0.."COMMENTBLOCK".len() {
                                 self.chars.next();
                                 self.pos += 1;
                             }
+                            text.push_str("COMMENTBLOCK");
                             found_end = true;
                             break;
                         }
                     }
+                    self.keep_comment(text, span.start, true);
                     if !found_end {
                         return tokens;
                     }
@@ -148,12 +211,22 @@ impl<'a> Lexer<'a> {
     /// `[` was read as indexing the call. Returns `None` only at end of input, where
     /// there is no newline to report.
     fn skip_line_comment(&mut self, token_start: usize) -> Option<(Token, Span)> {
-        for c in self.chars.by_ref() {
-            self.pos += 1;
+        // The marker, `//` or `#`, has already been consumed.
+        let mut text = String::from(if self.pos - token_start == 2 {
+            "//"
+        } else {
+            "#"
+        });
+        while let Some(c) = self.chars.next() {
             if c == '\n' {
+                self.keep_comment(text, token_start, false);
+                self.pos += 1;
                 return Some((Token::Newline, Span::new(token_start, self.pos)));
             }
+            self.pos += 1;
+            text.push(c);
         }
+        self.keep_comment(text, token_start, false);
         None
     }
 
@@ -212,6 +285,9 @@ impl<'a> Lexer<'a> {
             ']' => Some((Token::CloseBracket, Span::new(token_start, self.pos))),
             ',' => Some((Token::Comma, Span::new(token_start, self.pos))),
             ':' => Some((Token::Colon, Span::new(token_start, self.pos))),
+            // A digit followed by `.` never reaches here: the number arm below
+            // consumes the point as part of a float, so `1.5` stays one token.
+            '.' => Some((Token::Dot, Span::new(token_start, self.pos))),
 
             'r' if self.chars.peek() == Some(&'"') => {
                 self.chars.next();
@@ -466,6 +542,12 @@ impl<'a> Lexer<'a> {
                     "IMPORT" => Some((Token::Import, Span::new(token_start, self.pos))),
                     "UNTIL" => Some((Token::Until, Span::new(token_start, self.pos))),
                     "TIMES" => Some((Token::Times, Span::new(token_start, self.pos))),
+                    "WHILE" => Some((Token::While, Span::new(token_start, self.pos))),
+                    "FROM" => Some((Token::From, Span::new(token_start, self.pos))),
+                    "TO" => Some((Token::To, Span::new(token_start, self.pos))),
+                    "STEP" => Some((Token::Step, Span::new(token_start, self.pos))),
+                    "BREAK" => Some((Token::Break, Span::new(token_start, self.pos))),
+                    "CONTINUE" => Some((Token::Continue, Span::new(token_start, self.pos))),
                     "NOT=" => Some((Token::NotEqual, Span::new(token_start, self.pos))),
                     "INSERT" => Some((Token::ListInsert, Span::new(token_start, self.pos))),
                     "APPEND" => Some((Token::ListAppend, Span::new(token_start, self.pos))),
@@ -473,8 +555,15 @@ impl<'a> Lexer<'a> {
                     "LENGTH" => Some((Token::ListLength, Span::new(token_start, self.pos))),
                     "RANDOM" => Some((Token::Random, Span::new(token_start, self.pos))),
                     "SORT" => Some((Token::Sort, Span::new(token_start, self.pos))),
+                    "MATCH" => Some((Token::Match, Span::new(token_start, self.pos))),
+                    "CASE" => Some((Token::Case, Span::new(token_start, self.pos))),
+                    "AS" => Some((Token::As, Span::new(token_start, self.pos))),
+                    "EXPORT" => Some((Token::Export, Span::new(token_start, self.pos))),
+                    "PRIVATE" => Some((Token::Private, Span::new(token_start, self.pos))),
                     "TRY" => Some((Token::Try, Span::new(token_start, self.pos))),
                     "CATCH" => Some((Token::Catch, Span::new(token_start, self.pos))),
+                    "FINALLY" => Some((Token::Finally, Span::new(token_start, self.pos))),
+                    "THROW" => Some((Token::Throw, Span::new(token_start, self.pos))),
                     "EVAL" => Some((Token::Eval, Span::new(token_start, self.pos))),
                     _ => Some((
                         Token::Identifier(identifier),
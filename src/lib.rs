@@ -1,10 +1,29 @@
+pub mod check;
 pub mod core;
+pub mod coverage;
+pub mod dap;
+pub mod debugger;
 pub mod error;
+pub mod format;
+pub mod golden;
+pub mod host;
 pub mod interpreter;
+pub mod json;
 pub mod lexer;
+pub mod limits;
+pub mod lint;
+pub mod lsp;
 pub mod parser;
+pub mod profile;
+pub mod sandbox;
 pub mod system;
+pub mod testing;
 #[cfg(test)]
 mod tests;
+pub mod trace;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
+
+pub use error::PSLError;
+pub use host::Value;
+pub use interpreter::Interpreter;
@@ -0,0 +1,117 @@
+//! `fpli run --max-steps`, `--timeout` and the other limits: how much a run may
+//! take before it is stopped.
+//!
+//! A program that exceeds a limit is terminated, not handed an error: TRY does
+//! not catch it and FINALLY does not run, so a program cannot go on running by
+//! catching the thing meant to stop it. The run ends with an error naming the
+//! limit, whose kind is `limit`.
+//!
+//! The statement budget counts the statements run, the same ones `--count-ops`
+//! counts. The time limit is wall-clock time, looked at before every statement
+//! and every turn of a loop, so a single builtin that takes long -- a SORT of a
+//! huge list -- is only stopped once it returns. SLEEP does not outlast it.
+//!
+//! The memory cap is approximate. What it measures is the values the program's
+//! variables hold, in every scope from the running one out to the top level:
+//! each string's text, each list's items, each dictionary's entries and each
+//! object's fields, a shared dictionary or object counted once. That is
+//! measured again every so often as the program runs -- more seldom as there is
+//! more to measure, so the measuring stays a small part of the run -- and a
+//! program can therefore go somewhat past the cap before it is stopped.
+//!
+//! Recursion depth and EVAL nesting are limited by default, as they guard the
+//! interpreter's own stack; the rest are off unless asked for.
+
+use std::time::Duration;
+
+/// How much a run may take. [`Limits::default`] is what a run gets without
+/// asking for anything.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// Statements the whole run may start.
+    pub steps: Option<u64>,
+    /// How long the run may take, from its first statement.
+    pub timeout: Option<Duration>,
+    /// About how many bytes the program's values may take.
+    pub memory: Option<u64>,
+    /// How deeply procedure calls may nest.
+    pub stack_depth: usize,
+    /// How deeply EVAL and EXECUTE may nest inside one another. Each level costs
+    /// far more of the interpreter's own stack than a procedure call: see
+    /// [`Limits::default`].
+    pub meta_depth: usize,
+    /// How many times any one loop may go round, each time it runs.
+    pub loop_iterations: Option<u64>,
+}
+
+impl Default for Limits {
+    /// Recursion capped at 1000 calls, EVAL and EXECUTE at 32 levels, and
+    /// nothing else.
+    ///
+    /// One level of nested source evaluation holds a lexer, a token vector, a
+    /// parser and an AST live across the recursive call: about 35 KiB of real
+    /// stack per level in a debug build, against about 4 KiB for a procedure
+    /// frame. At 1000 levels the process died of a genuine stack overflow before
+    /// the counter tripped, which `code <- "EVAL(code)"` did. 32 keeps the worst
+    /// case near a megabyte and still allows any real nesting.
+    fn default() -> Self {
+        Limits {
+            steps: None,
+            timeout: None,
+            memory: None,
+            stack_depth: 1000,
+            meta_depth: 32,
+            loop_iterations: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Whether anything must be looked at before each statement.
+    pub(crate) fn metered(&self) -> bool {
+        self.steps.is_some() || self.timeout.is_some() || self.memory.is_some()
+    }
+}
+
+/// A size as `--max-memory` takes it: a number of bytes, or of kilobytes,
+/// megabytes or gigabytes with a `K`, `M` or `G` after it. Binary units, as
+/// memory is counted: `64M` is 64 MiB.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let trimmed = text.trim();
+    let (digits, shift) = match trimmed.char_indices().last() {
+        Some((at, unit)) if unit.is_ascii_alphabetic() => {
+            let shift = match unit.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                _ => {
+                    return Err(format!(
+                        "'{}' is not a size: use a number of bytes, or K, M or G",
+                        text
+                    ));
+                }
+            };
+            (&trimmed[..at], shift)
+        }
+        _ => (trimmed, 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| {
+            format!(
+                "'{}' is not a size: use a number of bytes, or K, M or G",
+                text
+            )
+        })
+}
+
+/// A time as `--timeout` takes it: seconds, which may have a fraction.
+pub fn parse_seconds(text: &str) -> Result<Duration, String> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("'{}' is not a number of seconds", text))
+}
@@ -0,0 +1,732 @@
+//! `fpli lint`: warnings about code that parses but is probably wrong, found
+//! without running anything.
+//!
+//! Each rule has a code, shown after its message:
+//!
+//! | Code                     | What it finds                                          |
+//! |--------------------------|--------------------------------------------------------|
+//! | `shadowed-builtin`       | A PROCEDURE with a built-in's name, which no call reaches |
+//! | `read-before-assignment` | A variable read before anything has assigned it        |
+//! | `unused-assignment`      | A variable assigned but never read                     |
+//! | `unreachable-code`       | Statements after a RETURN or EXIT, which never run     |
+//! | `argument-count`         | A call with more or fewer arguments than the procedure takes |
+//! | `shadowed-parameter`     | An assignment to a name that is a parameter            |
+//!
+//! A warning is rendered like an error, file, line and caret included. A comment
+//! `lint: allow(code, ...)` silences those codes on its own line, or, written
+//! on a line of its own, on the line after it.
+//!
+//! The rules err towards saying nothing. A procedure can read its callers'
+//! variables, so a procedure's read only counts as being before any assignment
+//! when nothing anywhere else in the file assigns the name. A file that reaches
+//! variables by name -- through EVAL, EXECUTE, GETVAR, SETVAR, UNSETVAR,
+//! ISDEFINED or VARIABLES -- gets neither of the rules about variables, and
+//! neither does one with an IMPORT that cannot be found, as it could declare
+//! anything. What the imports that can be found declare is read from them.
+
+use crate::check::plural;
+use crate::error::{PSLError, Span, resolve_span};
+use crate::interpreter::{BUILTIN_NAMES, expected_arguments, find_import};
+use crate::lexer::Lexer;
+use crate::parser::{self, AstNode, Parameter, Pattern, Spanned};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The variables every run starts with.
+const PREDEFINED: &[&str] = &["ARGS", "ARGCOUNT", "POSITIONALS"];
+
+/// The built-ins that read or write variables by name.
+const BY_NAME: &[&str] = &[
+    "EXECUTE",
+    "GETVAR",
+    "SETVAR",
+    "UNSETVAR",
+    "ISDEFINED",
+    "VARIABLES",
+];
+
+/// One warning: the rule's code, and where and what, ready to render the way an
+/// error is.
+pub struct Lint {
+    pub code: &'static str,
+    pub error: PSLError,
+}
+
+impl Lint {
+    /// The warning as `fpli lint` shows it: like an error, with the rule's code
+    /// after the message.
+    pub fn format(&self) -> String {
+        PSLError {
+            message: format!("{} [{}]", self.error.message, self.code),
+            ..self.error.clone()
+        }
+        .format("")
+    }
+}
+
+/// A PROCEDURE body, or the file's top level, as the rules see it.
+struct Scope {
+    /// Numbered in the order the walk enters them, the same on both passes.
+    id: usize,
+    top: bool,
+    params: HashSet<String>,
+    /// The parameters of the procedures this one is written inside.
+    enclosing: HashSet<String>,
+    /// Names assigned so far, in the order the file is written.
+    bound: HashSet<String>,
+}
+
+struct Linter<'a> {
+    path: Option<&'a Path>,
+    /// `false` while gathering what the whole file declares and reads, `true`
+    /// while walking it again to check it against that.
+    checking: bool,
+
+    /// The parameters of every procedure a call by name can reach: declared
+    /// here, imported, and the constructors of classes.
+    signatures: HashMap<String, Vec<Vec<Parameter>>>,
+    /// Every procedure and class declared here.
+    declared: HashSet<String>,
+    /// The scopes that assign each name.
+    binders: HashMap<String, HashSet<usize>>,
+    /// Every name read, or called, anywhere.
+    reads: HashSet<String>,
+    /// The names plain IMPORTs bring in.
+    imported: HashSet<String>,
+    imports_seen: HashSet<PathBuf>,
+    exported: HashSet<String>,
+    /// Whether variables may come and go in ways only a run can tell.
+    untraceable: bool,
+
+    scopes: Vec<Scope>,
+    next_id: usize,
+    /// Each rule reports a name once per scope.
+    reported: HashSet<(&'static str, usize, String)>,
+    /// The f-string being walked, if any: what is inside one has spans into the
+    /// string rather than the file, so a warning there points at the string.
+    in_string: Option<Span>,
+    found: Vec<(&'static str, String, Span)>,
+}
+
+/// Lint one file's text. `path` is where IMPORTs are found from, and what the
+/// warnings are said to be in. A file that does not parse gets its syntax
+/// errors instead.
+pub fn lint_source(source: &str, path: Option<&Path>) -> Result<Vec<Lint>, Vec<PSLError>> {
+    let (tokens, comments) = Lexer::new(source).tokenize_with_comments();
+    let parsed = parser::parse_all(tokens, false);
+    let shared: Rc<str> = Rc::from(source);
+    let origin = path.map(|path| path.display().to_string());
+    let place = |mut error: PSLError| {
+        error.source = Some(Rc::clone(&shared));
+        error.origin = origin.clone();
+        error
+    };
+    if !parsed.errors.is_empty() {
+        return Err(parsed.errors.into_iter().map(place).collect());
+    }
+
+    let mut linter = Linter {
+        path,
+        checking: false,
+        signatures: HashMap::new(),
+        declared: HashSet::new(),
+        binders: HashMap::new(),
+        reads: HashSet::new(),
+        imported: HashSet::new(),
+        imports_seen: HashSet::new(),
+        exported: HashSet::new(),
+        untraceable: false,
+        scopes: Vec::new(),
+        next_id: 0,
+        reported: HashSet::new(),
+        in_string: None,
+        found: Vec::new(),
+    };
+    linter.file(&parsed.program);
+    linter.checking = true;
+    linter.file(&parsed.program);
+
+    // Which codes each line allows.
+    let mut allowed: HashMap<usize, Vec<String>> = HashMap::new();
+    for comment in &comments {
+        let Some((_, rest)) = comment.text.split_once("lint: allow(") else {
+            continue;
+        };
+        let codes = rest.split(')').next().unwrap_or("");
+        let (line, col, text) = resolve_span(source, &comment.span);
+        let alone = text.chars().take(col - 1).all(char::is_whitespace);
+        let line = if alone {
+            let end = Span::new(comment.span.end, comment.span.end);
+            resolve_span(source, &end).0 + 1
+        } else {
+            line
+        };
+        allowed
+            .entry(line)
+            .or_default()
+            .extend(codes.split(',').map(|code| code.trim().to_string()));
+    }
+
+    let mut found = linter.found;
+    found.sort_by_key(|(_, _, span)| span.start);
+    Ok(found
+        .into_iter()
+        .filter(|(code, _, span)| {
+            let line = resolve_span(source, span).0;
+            !allowed
+                .get(&line)
+                .is_some_and(|codes| codes.iter().any(|allowed| allowed == code))
+        })
+        .map(|(code, message, span)| Lint {
+            code,
+            error: place(PSLError::with_span(message, span)),
+        })
+        .collect())
+}
+
+impl Linter<'_> {
+    fn file(&mut self, program: &Spanned) {
+        self.next_id = 0;
+        self.scopes.push(Scope {
+            id: 0,
+            top: true,
+            params: HashSet::new(),
+            enclosing: HashSet::new(),
+            bound: HashSet::new(),
+        });
+        self.walk(program);
+        self.scopes.pop();
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes
+            .last_mut()
+            .expect("the walk is always in some scope")
+    }
+
+    fn report(&mut self, code: &'static str, name: &str, message: String, span: Span) {
+        let id = self.scope().id;
+        if self.reported.insert((code, id, name.to_string())) {
+            self.warn(code, message, span);
+        }
+    }
+
+    fn warn(&mut self, code: &'static str, message: String, span: Span) {
+        self.found
+            .push((code, message, self.in_string.unwrap_or(span)));
+    }
+
+    fn walk(&mut self, node: &Spanned) {
+        match &node.node {
+            AstNode::Program(items) | AstNode::Block(items) => self.statements(items),
+            AstNode::Identifier(name) => self.read(name, node.span),
+            AstNode::Assignment(target, value) => {
+                // The value is worked out before the name is assigned.
+                self.walk(value);
+                self.assign(target);
+            }
+            AstNode::ProcedureCall(name, args) => {
+                self.call(name, args.len(), node.span);
+                for arg in args {
+                    self.walk(arg);
+                }
+            }
+            AstNode::ProcedureDecl(name, params, body) => {
+                self.declare(name, params, node.span);
+                self.body(params, None, body);
+            }
+            AstNode::AnonymousProcedure(params, body) => self.body(params, None, body),
+            AstNode::ClassDecl(name, body) => {
+                if !self.checking {
+                    self.declared.insert(name.clone());
+                }
+                let AstNode::Block(members) = &body.node else {
+                    return self.walk(body);
+                };
+                for member in members {
+                    match &member.node {
+                        AstNode::ProcedureDecl(method, params, method_body) => {
+                            if method == name && !self.checking {
+                                self.signatures
+                                    .entry(name.clone())
+                                    .or_default()
+                                    .push(params.clone());
+                            }
+                            self.body(params, Some("self"), method_body);
+                        }
+                        _ => self.walk(member),
+                    }
+                }
+            }
+            AstNode::ForEach(first, second, iterable, body) => {
+                self.walk(iterable);
+                self.bind(first);
+                if let Some(second) = second {
+                    self.bind(second);
+                }
+                self.walk(body);
+            }
+            AstNode::ForRange {
+                var,
+                from,
+                to,
+                step,
+                body,
+            } => {
+                self.walk(from);
+                self.walk(to);
+                if let Some(step) = step {
+                    self.walk(step);
+                }
+                self.bind(var);
+                self.walk(body);
+            }
+            AstNode::Match(subject, cases, default) => {
+                self.walk(subject);
+                for case in cases {
+                    let mut names = Vec::new();
+                    pattern_names(&case.pattern, &mut names);
+                    for name in names {
+                        self.bind(name);
+                    }
+                    if let Some(guard) = &case.guard {
+                        self.walk(guard);
+                    }
+                    self.walk(&case.body);
+                }
+                if let Some(default) = default {
+                    self.walk(default);
+                }
+            }
+            AstNode::TryCatch {
+                try_block,
+                error_var,
+                catch_block,
+                finally_block,
+            } => {
+                self.walk(try_block);
+                if let Some(error_var) = error_var {
+                    self.bind(error_var);
+                }
+                if let Some(catch_block) = catch_block {
+                    self.walk(catch_block);
+                }
+                if let Some(finally_block) = finally_block {
+                    self.walk(finally_block);
+                }
+            }
+            AstNode::Import(path, alias) => match alias {
+                Some(alias) => self.bind(alias),
+                None if !self.checking => self.import(path, self.path.map(Path::to_path_buf)),
+                None => {}
+            },
+            AstNode::Export(names) => {
+                if !self.checking {
+                    self.exported.extend(names.iter().cloned());
+                }
+            }
+            AstNode::FormattedString(_, parts) => {
+                // Only the outermost string's span is in the file.
+                let outer = self.in_string;
+                self.in_string = outer.or(Some(node.span));
+                for part in parts {
+                    self.walk(part);
+                }
+                self.in_string = outer;
+            }
+            AstNode::Eval(code) => {
+                self.untraceable = true;
+                self.walk(code);
+            }
+            other => {
+                for child in other.children() {
+                    self.walk(child);
+                }
+            }
+        }
+    }
+
+    /// A block's statements, each after the one before.
+    fn statements(&mut self, items: &[Spanned]) {
+        let mut ended = None;
+        let mut reported = false;
+        for item in items {
+            let runs = match &item.node {
+                AstNode::Comment => false,
+                AstNode::Block(inner) => !inner.is_empty(),
+                _ => true,
+            };
+            if !runs {
+                continue;
+            }
+            // Reported once: everything after it is just as unreachable. It is
+            // all still walked, for the other rules.
+            if let Some(what) = ended
+                && self.checking
+                && !reported
+            {
+                self.warn(
+                    "unreachable-code",
+                    format!("This is never run: the {} before it always leaves", what),
+                    item.span,
+                );
+                reported = true;
+            }
+            self.walk(item);
+            if ended.is_none() {
+                ended = leaves(item);
+            }
+        }
+    }
+
+    /// A procedure's parameters and body, with `implicit` bound as well: `self`
+    /// in a method.
+    fn body(&mut self, params: &[Parameter], implicit: Option<&str>, body: &Spanned) {
+        self.next_id += 1;
+        let id = self.next_id;
+        let outer = self.scope();
+        let enclosing = if outer.top {
+            HashSet::new()
+        } else {
+            outer.params.union(&outer.enclosing).cloned().collect()
+        };
+        let mut names: HashSet<String> = params.iter().map(|p| p.name.clone()).collect();
+        names.extend(implicit.map(str::to_string));
+        if !self.checking {
+            for name in &names {
+                self.binders.entry(name.clone()).or_default().insert(id);
+            }
+        }
+        self.scopes.push(Scope {
+            id,
+            top: false,
+            params: names,
+            enclosing,
+            bound: HashSet::new(),
+        });
+        for param in params {
+            if let Some(default) = &param.default {
+                self.walk(default);
+            }
+        }
+        self.walk(body);
+        self.scopes.pop();
+    }
+
+    /// A name given a value in the current scope, other than by assignment.
+    fn bind(&mut self, name: &str) {
+        let scope = self.scope();
+        scope.bound.insert(name.to_string());
+        let id = scope.id;
+        if !self.checking {
+            self.binders.entry(name.to_string()).or_default().insert(id);
+        }
+    }
+
+    fn assign(&mut self, target: &Spanned) {
+        match &target.node {
+            AstNode::Identifier(name) => {
+                if self.checking {
+                    self.check_assignment(name, target.span);
+                }
+                self.bind(name);
+            }
+            // `[a, b] <- pair`
+            AstNode::List(targets) => {
+                for target in targets {
+                    self.assign(target);
+                }
+            }
+            _ => self.walk(target),
+        }
+    }
+
+    fn check_assignment(&mut self, name: &str, span: Span) {
+        let scope = self.scope();
+        if scope.params.contains(name) {
+            self.report(
+                "shadowed-parameter",
+                name,
+                format!(
+                    "'{}' is a parameter: assigning to it loses the argument it was given",
+                    name
+                ),
+                span,
+            );
+        } else if scope.enclosing.contains(name) {
+            self.report(
+                "shadowed-parameter",
+                name,
+                format!(
+                    "'{}' is a parameter of the enclosing procedure: assigning to it here makes a new variable that hides it",
+                    name
+                ),
+                span,
+            );
+        }
+        if !self.untraceable && !self.reads.contains(name) && !self.exported.contains(name) {
+            self.report(
+                "unused-assignment",
+                name,
+                format!("'{}' is assigned but never read", name),
+                span,
+            );
+        }
+    }
+
+    fn read(&mut self, name: &str, span: Span) {
+        if !self.checking {
+            self.reads.insert(name.to_string());
+            return;
+        }
+        if self.untraceable
+            || PREDEFINED.contains(&name)
+            || self.declared.contains(name)
+            || self.imported.contains(name)
+        {
+            return;
+        }
+        let scope = self.scope();
+        if scope.bound.contains(name) || scope.params.contains(name) {
+            return;
+        }
+        // A procedure runs when it is called, by then perhaps from inside a
+        // scope that has assigned the name: only the top level runs in the order
+        // it is written.
+        let id = scope.id;
+        if !scope.top
+            && self
+                .binders
+                .get(name)
+                .is_some_and(|scopes| scopes.iter().any(|&other| other != id))
+        {
+            return;
+        }
+        self.report(
+            "read-before-assignment",
+            name,
+            format!("'{}' is read before any assignment to it", name),
+            span,
+        );
+    }
+
+    fn call(&mut self, name: &str, given: usize, span: Span) {
+        if !self.checking {
+            // A call by name can be a call of a variable holding a procedure.
+            self.reads.insert(name.to_string());
+            if BY_NAME.contains(&name) {
+                self.untraceable = true;
+            }
+            return;
+        }
+        // Built-ins are called before any procedure of the same name.
+        if BUILTIN_NAMES.contains(&name) {
+            return;
+        }
+        let Some(signatures) = self.signatures.get(name) else {
+            return;
+        };
+        // Declared more than once, differently: which one a call reaches depends
+        // on which declaration ran last.
+        let [params] = signatures.as_slice() else {
+            return;
+        };
+        if let Some(expected) = expected_arguments(params, given) {
+            let message = format!(
+                "Procedure '{}' expects {}, but is called with {}",
+                name, expected, given
+            );
+            self.warn("argument-count", message, span);
+        }
+    }
+
+    fn declare(&mut self, name: &str, params: &[Parameter], span: Span) {
+        if !self.checking {
+            self.declared.insert(name.to_string());
+            let signatures = self.signatures.entry(name.to_string()).or_default();
+            if !signatures.iter().any(|known| same_arity(known, params)) {
+                signatures.push(params.to_vec());
+            }
+            return;
+        }
+        if BUILTIN_NAMES.contains(&name) {
+            self.warn(
+                "shadowed-builtin",
+                format!(
+                    "'{}' is a built-in, so a call to {}() never reaches this PROCEDURE",
+                    name, name
+                ),
+                span,
+            );
+        }
+    }
+
+    /// Learn what a plain IMPORT of `path` brings in: the top-level names of the
+    /// file, and of the files it imports in turn.
+    fn import(&mut self, path: &str, importer: Option<PathBuf>) {
+        let Ok(found) = find_import(path, importer.as_deref()) else {
+            self.untraceable = true;
+            return;
+        };
+        if !self.imports_seen.insert(found.clone()) {
+            return;
+        }
+        let Ok(source) = std::fs::read_to_string(&found) else {
+            self.untraceable = true;
+            return;
+        };
+        let parsed = parser::parse_all(Lexer::new(&source).tokenize(), false);
+        let AstNode::Program(statements) = &parsed.program.node else {
+            return;
+        };
+        for statement in statements {
+            let statement = match &statement.node {
+                AstNode::Private(inner) => inner,
+                _ => statement,
+            };
+            match &statement.node {
+                AstNode::ProcedureDecl(name, params, _) => {
+                    self.imported.insert(name.clone());
+                    let signatures = self.signatures.entry(name.clone()).or_default();
+                    if !signatures.iter().any(|known| same_arity(known, params)) {
+                        signatures.push(params.clone());
+                    }
+                }
+                AstNode::ClassDecl(name, _) => {
+                    self.imported.insert(name.clone());
+                }
+                AstNode::Assignment(target, _) => {
+                    let mut names = Vec::new();
+                    target_names(target, &mut names);
+                    self.imported.extend(names.into_iter().map(str::to_string));
+                }
+                AstNode::ForEach(first, second, _, _) => {
+                    self.imported.insert(first.clone());
+                    self.imported.extend(second.clone());
+                }
+                AstNode::ForRange { var, .. } => {
+                    self.imported.insert(var.clone());
+                }
+                AstNode::Import(inner, None) => self.import(inner, Some(found.clone())),
+                AstNode::Import(_, Some(alias)) => {
+                    self.imported.insert(alias.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether two parameter lists take the same numbers of arguments.
+fn same_arity(a: &[Parameter], b: &[Parameter]) -> bool {
+    let shape = |params: &[Parameter]| {
+        (
+            params
+                .iter()
+                .filter(|p| p.default.is_none() && !p.variadic)
+                .count(),
+            params.len(),
+            params.last().is_some_and(|p| p.variadic),
+        )
+    };
+    shape(a) == shape(b)
+}
+
+/// What a statement always leaves its block by, if it does: "RETURN" or "EXIT".
+fn leaves(statement: &Spanned) -> Option<&'static str> {
+    match &statement.node {
+        AstNode::Return(_) => Some("RETURN"),
+        AstNode::ProcedureCall(name, _) if name == "EXIT" => Some("EXIT"),
+        AstNode::Block(items) => items.iter().find_map(leaves),
+        AstNode::If(_, then_branch, Some(else_branch)) => {
+            match (leaves(then_branch), leaves(else_branch)) {
+                (Some(a), Some(b)) if a == b => Some(a),
+                (Some(_), Some(_)) => Some("RETURN or EXIT"),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The names an assignment's target assigns.
+fn target_names<'a>(target: &'a Spanned, names: &mut Vec<&'a str>) {
+    match &target.node {
+        AstNode::Identifier(name) => names.push(name),
+        AstNode::List(targets) => {
+            for target in targets {
+                target_names(target, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The names a MATCH pattern assigns.
+fn pattern_names<'a>(pattern: &'a Pattern, names: &mut Vec<&'a str>) {
+    match pattern {
+        Pattern::Binding(name) => names.push(name),
+        Pattern::List(items, rest) => {
+            for item in items {
+                pattern_names(item, names);
+            }
+            names.extend(rest.as_deref());
+        }
+        Pattern::Dictionary(entries) => {
+            for (_, value) in entries {
+                pattern_names(value, names);
+            }
+        }
+        Pattern::Literal(_) | Pattern::Range(..) => {}
+    }
+}
+
+/// Lint the files named on the command line, print what was found, and return
+/// the exit status: 0 when there was nothing to warn about, 1 otherwise.
+pub fn run(paths: &[String]) -> i32 {
+    if let Some(bad) = paths.iter().find(|path| !path.ends_with(".psl")) {
+        eprintln!("Error: Input file must have .psl extension, got: {}", bad);
+        return 1;
+    }
+    let mut warnings = 0;
+    let mut errors = 0;
+    for path in paths {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Error: Could not read {}: {}", path, e);
+                errors += 1;
+                continue;
+            }
+        };
+        match lint_source(&source, Some(Path::new(path))) {
+            Ok(lints) => {
+                for lint in &lints {
+                    eprintln!("Warning: {}", lint.format());
+                }
+                warnings += lints.len();
+            }
+            Err(found) => {
+                for error in &found {
+                    eprintln!("Error: {}", error.format(""));
+                }
+                errors += found.len();
+            }
+        }
+    }
+    let files = plural(paths.len(), "file");
+    if warnings == 0 && errors == 0 {
+        println!("Linted {}: no warnings", files);
+        return 0;
+    }
+    let mut found = Vec::new();
+    if warnings > 0 {
+        found.push(plural(warnings, "warning"));
+    }
+    if errors > 0 {
+        found.push(plural(errors, "error"));
+    }
+    eprintln!("Linted {}: found {}", files, found.join(" and "));
+    1
+}
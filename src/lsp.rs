@@ -0,0 +1,1059 @@
+//! `fpli lsp`: a language server, so an editor can check and navigate
+//! PseudoLang as it is typed.
+//!
+//! The server speaks the Language Server Protocol over standard input and output:
+//! JSON-RPC messages, each behind a `Content-Length` header. Everything it knows it
+//! gets the way `fpli check` does -- the lexer, [`parser::parse_all`] and the spans
+//! they record -- so nothing is ever run, and what an editor underlines is exactly
+//! what `fpli run` would refuse. It offers:
+//!
+//! - diagnostics: every syntax error in a file, and every IMPORT that names a file
+//!   that cannot be found, republished whenever the file changes;
+//! - hover: the guide's entry for a built-in, or the signature of a procedure;
+//! - go to definition: of a procedure or class, in the file or one it imports, and
+//!   of an IMPORT, which goes to the file it names;
+//! - completion: keywords, built-ins, and the procedures, classes and names in
+//!   scope;
+//! - document symbols: the file's procedures, classes with their methods, and
+//!   top-level names.
+//!
+//! Every file is analysed afresh on every request. The files a program is made of
+//! are small enough that re-parsing costs less than keeping an index honest.
+
+use crate::error::Span;
+use crate::interpreter::{BUILTIN_NAMES, find_import};
+use crate::json::{Json, read_message, write_message};
+use crate::lexer::{Lexer, Token};
+use crate::parser::{self, AstNode, ParseReport, Spanned};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The guide, which hover and completion quote for the built-ins. Kept in the
+/// binary so that what the editor shows is the documentation of the very version
+/// that will run the program.
+const GUIDE: &str = include_str!("../Pseudolang.md");
+
+/// Words the language reserves, offered as completions.
+const KEYWORDS: &[&str] = &[
+    "AND",
+    "AS",
+    "BREAK",
+    "CASE",
+    "CATCH",
+    "CLASS",
+    "COMMENT",
+    "COMMENTBLOCK",
+    "CONTINUE",
+    "EACH",
+    "ELSE",
+    "EXPORT",
+    "FALSE",
+    "FINALLY",
+    "FOR",
+    "FROM",
+    "IF",
+    "IMPORT",
+    "IN",
+    "MATCH",
+    "MOD",
+    "NAN",
+    "NOT",
+    "NULL",
+    "OR",
+    "PRIVATE",
+    "PROCEDURE",
+    "REPEAT",
+    "RETURN",
+    "STEP",
+    "THROW",
+    "TIMES",
+    "TO",
+    "TRUE",
+    "TRY",
+    "UNTIL",
+    "WHILE",
+];
+
+/// Built-ins the lexer reads as keywords, so they are not in [`BUILTIN_NAMES`],
+/// but which are called like any other and documented like any other.
+const KEYWORD_BUILTINS: &[&str] = &[
+    "APPEND",
+    "CONCAT",
+    "DISPLAY",
+    "DISPLAYINLINE",
+    "EVAL",
+    "INPUT",
+    "INSERT",
+    "LENGTH",
+    "RANDOM",
+    "REMOVE",
+    "SORT",
+    "SUBSTRING",
+    "TONUM",
+    "TOSTRING",
+];
+
+// JSON-RPC's error codes for a message the server could not make sense of.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// The protocol's numbers for the kinds of symbol and completion offered.
+const SYMBOL_MODULE: usize = 2;
+const SYMBOL_CLASS: usize = 5;
+const SYMBOL_METHOD: usize = 6;
+const SYMBOL_FUNCTION: usize = 12;
+const SYMBOL_VARIABLE: usize = 13;
+const COMPLETION_METHOD: usize = 2;
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_VARIABLE: usize = 6;
+const COMPLETION_CLASS: usize = 7;
+const COMPLETION_MODULE: usize = 9;
+const COMPLETION_KEYWORD: usize = 14;
+
+/// Serve the protocol on standard input and output until the editor says to
+/// exit, and return the exit status.
+pub fn run() -> i32 {
+    serve(std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+/// Serve the protocol on `input` and `output`.
+///
+/// The status is 0 after a `shutdown` request then an `exit`, and 1 when the
+/// editor exits or goes away without asking for a shutdown first, as the protocol
+/// asks: that is how an editor tells a server that stopped from one that crashed.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> i32 {
+    let mut server = Server::new();
+    loop {
+        let body = match read_message(&mut input) {
+            Ok(Some(body)) => body,
+            Ok(None) => return i32::from(!server.shut_down),
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                return 1;
+            }
+        };
+        let replies = match Json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(message) => vec![error_response(Json::Null, PARSE_ERROR, message)],
+        };
+        for reply in replies {
+            if write_message(&mut output, &reply).is_err() {
+                return 1;
+            }
+        }
+        if let Some(code) = server.exit_code() {
+            return code;
+        }
+    }
+}
+
+fn response(id: Json, result: Json) -> Json {
+    Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)])
+}
+
+fn error_response(id: Json, code: i64, message: String) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        (
+            "error",
+            Json::object([("code", code.into()), ("message", message.into())]),
+        ),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+/// The state of a session: the files the editor has open, with the text the
+/// editor has for them, which is newer than what is on disk until it is saved.
+#[derive(Default)]
+pub struct Server {
+    open: HashMap<String, String>,
+    shut_down: bool,
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// The status to exit with, once the editor has sent `exit`.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Handle one message and return what to send back: the response to a
+    /// request, and any notifications it causes, such as new diagnostics.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str);
+        let params = message.get("params").unwrap_or(&Json::Null);
+        match (method, message.get("id")) {
+            (Some(method), Some(id)) => vec![self.request(method, id.clone(), params)],
+            (Some(method), None) => self.notify(method, params),
+            // A response to a request the server never makes.
+            (None, _) => Vec::new(),
+        }
+    }
+
+    fn request(&mut self, method: &str, id: Json, params: &Json) -> Json {
+        if self.shut_down {
+            return error_response(
+                id,
+                INVALID_REQUEST,
+                "The server has been shut down".to_string(),
+            );
+        }
+        let result = match method {
+            "initialize" => capabilities(),
+            "shutdown" => {
+                self.shut_down = true;
+                Json::Null
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/documentSymbol" => self.symbols(params),
+            _ => {
+                return error_response(id, METHOD_NOT_FOUND, format!("Unknown method: {}", method));
+            }
+        };
+        response(id, result)
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params
+            .at(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .map(str::to_string);
+        match (method, uri) {
+            ("exit", _) => {
+                self.exit_code = Some(i32::from(!self.shut_down));
+                Vec::new()
+            }
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str);
+                self.open
+                    .insert(uri.clone(), text.unwrap_or_default().to_string());
+                vec![self.diagnostics(&uri)]
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // Whole-document sync, as `initialize` asked for: the last change
+                // carries the full new text.
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                if let Some(text) = text {
+                    self.open.insert(uri.clone(), text.to_string());
+                }
+                vec![self.diagnostics(&uri)]
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.open.remove(&uri);
+                // Clear what was published, or the editor keeps showing it.
+                vec![publish(&uri, Vec::new())]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The text of `uri`: the editor's copy when it has the file open, otherwise
+    /// what is on disk.
+    fn document(&self, uri: &str) -> Option<Document> {
+        let path = uri_to_path(uri);
+        let text = match self.open.get(uri) {
+            Some(text) => text.clone(),
+            None => std::fs::read_to_string(path.as_ref()?).ok()?,
+        };
+        Some(Document::new(&text, path))
+    }
+
+    /// The document a request is about and the offset of its `position`.
+    fn target(&self, params: &Json) -> Option<(String, Document, usize)> {
+        let uri = params.at(&["textDocument", "uri"])?.as_str()?;
+        let doc = self.document(uri)?;
+        let offset = doc.offset(params.get("position")?)?;
+        Some((uri.to_string(), doc, offset))
+    }
+
+    fn load(&self, path: &Path) -> Option<(String, Document)> {
+        let uri = path_to_uri(path);
+        let doc = self.document(&uri)?;
+        Some((uri, doc))
+    }
+
+    /// Every file `doc` brings into scope with a plain IMPORT, and every file those
+    /// import the same way, each once, nearest first. A file imported `AS` a name
+    /// is reached through that name instead; see [`Server::module`].
+    fn imported(&self, doc: &Document) -> Vec<(String, Document)> {
+        let mut seen: HashSet<PathBuf> = doc.path.iter().cloned().collect();
+        let mut pending: VecDeque<PathBuf> = doc.imports(None).into();
+        let mut found = Vec::new();
+        while let Some(path) = pending.pop_front() {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            if let Some((uri, imported)) = self.load(&path) {
+                pending.extend(imported.imports(None));
+                found.push((uri, imported));
+            }
+        }
+        found
+    }
+
+    /// The file `doc` imports as `alias`.
+    fn module(&self, doc: &Document, alias: &str) -> Option<(String, Document)> {
+        let path = doc.imports(Some(alias)).into_iter().next()?;
+        self.load(&path)
+    }
+
+    /// The declaration `name` refers to, with the file declaring it and where in
+    /// that file its name is written.
+    ///
+    /// `lib.name`, where `lib` is a file imported `AS lib`, is looked up in that
+    /// file. `object.name` is taken to be a method of whichever class declares
+    /// one. A plain name is looked up in this file and then in what it imports.
+    fn declaration(
+        &self,
+        uri: &str,
+        doc: &Document,
+        name: &str,
+        start: usize,
+    ) -> Option<(String, Json, Declaration)> {
+        let find = |uri: &str, doc: &Document, visible: bool, methods: bool| {
+            let declarations = if visible {
+                doc.visible_declarations()
+            } else {
+                doc.declarations()
+            };
+            let found = if methods {
+                declarations
+                    .into_iter()
+                    .flat_map(|d| d.children)
+                    .find(|d| d.name == name)
+            } else {
+                declarations
+                    .into_iter()
+                    .find(|d| d.name == name && d.kind != Kind::Variable)
+            };
+            found.map(|d| (uri.to_string(), doc.range(d.name_span), d))
+        };
+        match doc.qualifier(start) {
+            Some(alias) if doc.imports(Some(&alias)).is_empty() => find(uri, doc, false, true)
+                .or_else(|| {
+                    self.imported(doc)
+                        .iter()
+                        .find_map(|(uri, doc)| find(uri, doc, false, true))
+                }),
+            Some(alias) => {
+                let (uri, module) = self.module(doc, &alias)?;
+                find(&uri, &module, true, false)
+            }
+            None => find(uri, doc, false, false).or_else(|| {
+                self.imported(doc)
+                    .iter()
+                    .find_map(|(uri, doc)| find(uri, doc, true, false))
+            }),
+        }
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let Some((uri, doc, offset)) = self.target(params) else {
+            return Json::Null;
+        };
+        let Some((name, span)) = doc.word_at(offset) else {
+            return Json::Null;
+        };
+        // A built-in wins over a procedure of the same name, as it does in a run.
+        let text = if doc.qualifier(span.start).is_none() && is_builtin(&name) {
+            guide_entry(&name)
+        } else {
+            self.declaration(&uri, &doc, &name, span.start)
+                .map(|(_, _, declaration)| format!("```psl\n{}\n```", declaration.signature))
+        };
+        match text {
+            Some(text) => Json::object([
+                (
+                    "contents",
+                    Json::object([("kind", "markdown".into()), ("value", text.into())]),
+                ),
+                ("range", doc.range(span)),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let Some((uri, doc, offset)) = self.target(params) else {
+            return Json::Null;
+        };
+        // On an IMPORT, the file it names, found the way a run would find it.
+        for (path, span) in &doc.parsed.imports {
+            let span = doc.trimmed(*span);
+            if span.start <= offset && offset <= span.end {
+                return match find_import(path, doc.path.as_deref()) {
+                    Ok(resolved) => location(&path_to_uri(&resolved), start_of_file()),
+                    Err(_) => Json::Null,
+                };
+            }
+        }
+        doc.word_at(offset)
+            .and_then(|(name, span)| self.declaration(&uri, &doc, &name, span.start))
+            .map_or(Json::Null, |(uri, range, _)| location(&uri, range))
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let Some((_, doc, offset)) = self.target(params) else {
+            return Json::Null;
+        };
+        let mut items = Vec::new();
+        let start = doc.word_start(offset);
+        match doc.qualifier(start) {
+            // After `lib.`, what the file imported as `lib` shares.
+            Some(alias) => {
+                if let Some((_, module)) = self.module(&doc, &alias) {
+                    items.extend(module.visible_declarations().iter().map(completion_item));
+                }
+            }
+            None => {
+                for keyword in KEYWORDS {
+                    items.push(Json::object([
+                        ("label", (*keyword).into()),
+                        ("kind", COMPLETION_KEYWORD.into()),
+                    ]));
+                }
+                for name in BUILTIN_NAMES.iter().chain(KEYWORD_BUILTINS) {
+                    items.push(builtin_item(name));
+                }
+                items.extend(doc.declarations().iter().map(completion_item));
+                for (_, imported) in self.imported(&doc) {
+                    items.extend(imported.visible_declarations().iter().map(completion_item));
+                }
+            }
+        }
+        // A name declared twice, or in two files, is one completion.
+        let mut labels = HashSet::new();
+        items.retain(|item| labels.insert(item.get("label").cloned().map(|l| l.to_string())));
+        Json::object([("isIncomplete", false.into()), ("items", items.into())])
+    }
+
+    fn symbols(&self, params: &Json) -> Json {
+        let Some(uri) = params.at(&["textDocument", "uri"]).and_then(Json::as_str) else {
+            return Json::Null;
+        };
+        let Some(doc) = self.document(uri) else {
+            return Json::Null;
+        };
+        doc.declarations()
+            .iter()
+            .map(|declaration| symbol(&doc, declaration))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// The `publishDiagnostics` notification for `uri`: its syntax errors, and
+    /// its IMPORTs of files that cannot be found.
+    fn diagnostics(&self, uri: &str) -> Json {
+        let Some(doc) = self.document(uri) else {
+            return publish(uri, Vec::new());
+        };
+        let mut found: Vec<(Span, String)> = doc
+            .parsed
+            .errors
+            .iter()
+            .map(|error| {
+                let span = error.span.unwrap_or(Span::new(0, 0));
+                (doc.placed(span), error.message.clone())
+            })
+            .collect();
+        for (path, span) in &doc.parsed.imports {
+            if let Err(message) = find_import(path, doc.path.as_deref()) {
+                found.push((doc.trimmed(*span), message));
+            }
+        }
+        found.sort_by_key(|(span, _)| span.start);
+        let diagnostics = found
+            .into_iter()
+            .map(|(span, message)| {
+                Json::object([
+                    ("range", doc.range(span)),
+                    ("severity", 1usize.into()),
+                    ("source", "fpli".into()),
+                    ("message", message.into()),
+                ])
+            })
+            .collect();
+        publish(uri, diagnostics)
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object([
+        (
+            "capabilities",
+            Json::object([
+                (
+                    "textDocumentSync",
+                    Json::object([("openClose", true.into()), ("change", 1usize.into())]),
+                ),
+                ("hoverProvider", true.into()),
+                ("definitionProvider", true.into()),
+                (
+                    "completionProvider",
+                    Json::object([("triggerCharacters", vec![Json::from(".")].into())]),
+                ),
+                ("documentSymbolProvider", true.into()),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object([
+                ("name", "fpli".into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+            ]),
+        ),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    notification(
+        "textDocument/publishDiagnostics",
+        Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+    )
+}
+
+fn location(uri: &str, range: Json) -> Json {
+    Json::object([("uri", uri.into()), ("range", range)])
+}
+
+fn start_of_file() -> Json {
+    let start = Json::object([("line", 0usize.into()), ("character", 0usize.into())]);
+    Json::object([("start", start.clone()), ("end", start)])
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTIN_NAMES.contains(&name) || KEYWORD_BUILTINS.contains(&name)
+}
+
+fn builtin_item(name: &str) -> Json {
+    let entry = guide_entry(name);
+    // The first line of the guide's entry is the call written out: `ABS(x)`.
+    let detail = entry
+        .as_deref()
+        .and_then(|entry| entry.lines().next())
+        .and_then(|line| line.strip_prefix('`'))
+        .and_then(|line| line.split('`').next())
+        .filter(|call| call.starts_with(name));
+    let mut fields = vec![("label", name.into()), ("kind", COMPLETION_FUNCTION.into())];
+    if let Some(detail) = detail {
+        fields.push(("detail", detail.into()));
+    }
+    if let Some(entry) = entry {
+        fields.push((
+            "documentation",
+            Json::object([("kind", "markdown".into()), ("value", entry.into())]),
+        ));
+    }
+    Json::object(fields)
+}
+
+fn completion_item(declaration: &Declaration) -> Json {
+    let kind = match declaration.kind {
+        Kind::Procedure => COMPLETION_FUNCTION,
+        Kind::Method => COMPLETION_METHOD,
+        Kind::Class => COMPLETION_CLASS,
+        Kind::Variable => COMPLETION_VARIABLE,
+        Kind::Module => COMPLETION_MODULE,
+    };
+    Json::object([
+        ("label", declaration.name.as_str().into()),
+        ("kind", kind.into()),
+        ("detail", declaration.signature.as_str().into()),
+    ])
+}
+
+fn symbol(doc: &Document, declaration: &Declaration) -> Json {
+    let kind = match declaration.kind {
+        Kind::Procedure => SYMBOL_FUNCTION,
+        Kind::Method => SYMBOL_METHOD,
+        Kind::Class => SYMBOL_CLASS,
+        Kind::Variable => SYMBOL_VARIABLE,
+        Kind::Module => SYMBOL_MODULE,
+    };
+    let children: Vec<Json> = declaration
+        .children
+        .iter()
+        .map(|child| symbol(doc, child))
+        .collect();
+    Json::object([
+        ("name", declaration.name.as_str().into()),
+        ("detail", declaration.signature.as_str().into()),
+        ("kind", kind.into()),
+        ("range", doc.range(declaration.span)),
+        ("selectionRange", doc.range(declaration.name_span)),
+        ("children", children.into()),
+    ])
+}
+
+/// What the guide says about the built-in `name`, as markdown.
+///
+/// The guide introduces a built-in with its call in backticks at the start of a
+/// paragraph -- `` `ABS(x)` `` then what it does, on the same line or in the next
+/// paragraph -- so every such paragraph is its entry. The built-ins that are only
+/// listed in a table get their row, and any left over the first paragraph that
+/// mentions them.
+pub fn guide_entry(name: &str) -> Option<String> {
+    let paragraphs = guide_paragraphs();
+    let call = format!("`{}(", name);
+    let is_call = |line: &&str| line.starts_with('`') && line.ends_with('`');
+
+    let mut entries = Vec::new();
+    for (i, paragraph) in paragraphs.iter().enumerate() {
+        if !paragraph[0].starts_with(&call) {
+            continue;
+        }
+        let mut entry = paragraph.join("\n");
+        if paragraph.iter().all(is_call)
+            && let Some(next) = paragraphs
+                .get(i + 1)
+                .filter(|next| !next[0].starts_with('`'))
+        {
+            entry.push_str("\n\n");
+            entry.push_str(&next.join("\n"));
+        }
+        entries.push(entry);
+    }
+    if !entries.is_empty() {
+        return Some(entries.join("\n\n"));
+    }
+
+    let row = format!("| {}", call);
+    let in_table = paragraphs
+        .iter()
+        .flatten()
+        .find(|line| line.starts_with(&row));
+    if let Some(row) = in_table {
+        let cells: Vec<&str> = row
+            .split('|')
+            .map(str::trim)
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        return Some(format!("{}\n\n{}", cells[0], cells[1..].join(" — ")));
+    }
+
+    let named = format!("`{}`", name);
+    paragraphs
+        .iter()
+        .filter(|paragraph| !paragraph[0].starts_with("```"))
+        .find(|paragraph| {
+            paragraph
+                .iter()
+                .any(|line| line.contains(&call) || line.contains(&named))
+        })
+        .map(|paragraph| paragraph.join("\n"))
+}
+
+/// The guide split into paragraphs, headings left out and each fenced example a
+/// paragraph of its own, fences included.
+fn guide_paragraphs() -> &'static [Vec<&'static str>] {
+    static PARAGRAPHS: OnceLock<Vec<Vec<&'static str>>> = OnceLock::new();
+    PARAGRAPHS.get_or_init(|| {
+        let mut paragraphs = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        let mut in_fence = false;
+        for line in GUIDE.lines() {
+            let fence = line.trim_start().starts_with("```");
+            if in_fence {
+                current.push(line);
+                if fence {
+                    in_fence = false;
+                    paragraphs.push(std::mem::take(&mut current));
+                }
+            } else if fence || line.trim().is_empty() || line.starts_with('#') {
+                if !current.is_empty() {
+                    paragraphs.push(std::mem::take(&mut current));
+                }
+                if fence {
+                    in_fence = true;
+                    current.push(line);
+                }
+            } else {
+                current.push(line);
+            }
+        }
+        if !current.is_empty() {
+            paragraphs.push(current);
+        }
+        paragraphs
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Procedure,
+    Method,
+    Class,
+    Variable,
+    Module,
+}
+
+/// Something a file declares at its top level, or a method of a class it does.
+#[derive(Debug, Clone)]
+struct Declaration {
+    name: String,
+    kind: Kind,
+    /// The declaration's first line, as hover and completion show it:
+    /// `PROCEDURE area(width, height <- 1)`.
+    signature: String,
+    /// The whole declaration.
+    span: Span,
+    /// Just the name, where go to definition puts the cursor.
+    name_span: Span,
+    private: bool,
+    children: Vec<Declaration>,
+}
+
+/// One file, analysed: its text, its tokens, and what the parser made of them.
+struct Document {
+    chars: Vec<char>,
+    /// The offset each line starts at.
+    line_starts: Vec<usize>,
+    tokens: Vec<(Token, Span)>,
+    parsed: ParseReport,
+    /// Where the file is, for the IMPORTs in it to be found from; `None` for a
+    /// file the editor has not saved anywhere yet.
+    path: Option<PathBuf>,
+}
+
+impl Document {
+    fn new(text: &str, path: Option<PathBuf>) -> Document {
+        let chars: Vec<char> = text.chars().collect();
+        let line_starts = std::iter::once(0)
+            .chain(
+                chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        let tokens = Lexer::new(text).tokenize();
+        let parsed = parser::parse_all(tokens.clone(), false);
+        Document {
+            chars,
+            line_starts,
+            tokens,
+            parsed,
+            path,
+        }
+    }
+
+    /// The protocol's position for an offset: a line, and a column counted in
+    /// UTF-16 code units, which is what editors count in.
+    fn position(&self, offset: usize) -> Json {
+        let offset = offset.min(self.chars.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let column: usize = self.chars[self.line_starts[line]..offset]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum();
+        Json::object([("line", line.into()), ("character", column.into())])
+    }
+
+    /// The offset of a protocol position. A column past the end of its line means
+    /// the end of the line.
+    fn offset(&self, position: &Json) -> Option<usize> {
+        let line = position.get("line")?.as_usize()?;
+        let column = position.get("character")?.as_usize()?;
+        let Some(&start) = self.line_starts.get(line) else {
+            return Some(self.chars.len());
+        };
+        let mut offset = start;
+        let mut units = 0;
+        while units < column && self.chars.get(offset).is_some_and(|c| *c != '\n') {
+            units += self.chars[offset].len_utf16();
+            offset += 1;
+        }
+        Some(offset)
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::object([
+            ("start", self.position(span.start)),
+            ("end", self.position(span.end)),
+        ])
+    }
+
+    /// `span` without the line breaks a statement's span can run on over.
+    fn trimmed(&self, span: Span) -> Span {
+        let mut end = span.end.min(self.chars.len());
+        while end > span.start && self.chars[end - 1].is_whitespace() {
+            end -= 1;
+        }
+        Span::new(span.start, end)
+    }
+
+    /// Where to show an error. The parser reports running out of input with an
+    /// empty span at offset 0, which belongs at the end of the file instead.
+    fn placed(&self, span: Span) -> Span {
+        if span.end == 0 {
+            let end = self.trimmed(Span::new(0, self.chars.len())).end;
+            Span::new(end, end)
+        } else {
+            span
+        }
+    }
+
+    fn text(&self, span: Span) -> String {
+        self.chars[span.start.min(self.chars.len())..span.end.min(self.chars.len())]
+            .iter()
+            .collect()
+    }
+
+    /// Where the name the cursor is in or just after begins.
+    fn word_start(&self, offset: usize) -> usize {
+        let mut start = offset.min(self.chars.len());
+        while start > 0 && is_name_char(self.chars[start - 1]) {
+            start -= 1;
+        }
+        start
+    }
+
+    /// The name at `offset`, with its span. A word inside a string or a comment
+    /// is no token of its own, and so no name.
+    fn word_at(&self, offset: usize) -> Option<(String, Span)> {
+        let start = self.word_start(offset);
+        let mut end = start;
+        while self.chars.get(end).copied().is_some_and(is_name_char) {
+            end += 1;
+        }
+        let span = Span::new(start, end);
+        let is_token = self.tokens.iter().any(|(_, token)| token.start == start);
+        (end > start && self.chars[start].is_alphabetic() && is_token)
+            .then(|| (self.text(span), span))
+    }
+
+    /// The name before the dot in `lib.name`, when the name starting at `start`
+    /// has one.
+    fn qualifier(&self, start: usize) -> Option<String> {
+        if start == 0 || self.chars.get(start - 1) != Some(&'.') {
+            return None;
+        }
+        let end = start - 1;
+        let begin = self.word_start(end);
+        (begin < end).then(|| self.text(Span::new(begin, end)))
+    }
+
+    fn statements(&self) -> &[Spanned] {
+        match &self.parsed.program.node {
+            AstNode::Program(statements) => statements,
+            _ => &[],
+        }
+    }
+
+    /// The files this one imports, found the way a run would find them: those
+    /// imported `AS alias`, or those imported under no name for `None`.
+    fn imports(&self, alias: Option<&str>) -> Vec<PathBuf> {
+        self.statements()
+            .iter()
+            .filter_map(|statement| match &statement.node {
+                AstNode::Import(path, name) if name.as_deref() == alias => {
+                    find_import(path, self.path.as_deref()).ok()
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// What this file declares at its top level, in order, each name once.
+    fn declarations(&self) -> Vec<Declaration> {
+        let mut found = Vec::new();
+        for statement in self.statements() {
+            self.declare(statement, false, &mut found);
+        }
+        found
+    }
+
+    /// What an importer of this file gets: what it neither marks PRIVATE nor
+    /// leaves out of an EXPORT.
+    fn visible_declarations(&self) -> Vec<Declaration> {
+        let exported: Option<Vec<&String>> = self
+            .statements()
+            .iter()
+            .filter_map(|statement| match &statement.node {
+                AstNode::Export(names) => Some(names),
+                _ => None,
+            })
+            .fold(None, |all, names| {
+                Some(all.unwrap_or_default().into_iter().chain(names).collect())
+            });
+        self.declarations()
+            .into_iter()
+            .filter(|d| !d.private && d.kind != Kind::Module)
+            .filter(|d| {
+                exported
+                    .as_ref()
+                    .is_none_or(|names| names.contains(&&d.name))
+            })
+            .collect()
+    }
+
+    fn declare(&self, statement: &Spanned, private: bool, found: &mut Vec<Declaration>) {
+        let declaration = match &statement.node {
+            AstNode::Private(inner) => return self.declare(inner, true, found),
+            AstNode::ProcedureDecl(..) => self.procedure(statement, Kind::Procedure),
+            AstNode::ClassDecl(name, body) => {
+                let methods = match &body.node {
+                    AstNode::Block(statements) => statements
+                        .iter()
+                        .filter_map(|method| self.procedure(method, Kind::Method))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                Some(Declaration {
+                    name: name.clone(),
+                    kind: Kind::Class,
+                    signature: format!("CLASS {}", name),
+                    span: self.trimmed(statement.span),
+                    name_span: self.name_span(statement.span, name),
+                    private,
+                    children: methods,
+                })
+            }
+            AstNode::Assignment(target, _) => match &target.node {
+                AstNode::Identifier(name) => Some(Declaration {
+                    name: name.clone(),
+                    kind: Kind::Variable,
+                    signature: name.clone(),
+                    span: self.trimmed(statement.span),
+                    name_span: target.span,
+                    private,
+                    children: Vec::new(),
+                }),
+                _ => None,
+            },
+            AstNode::Import(path, Some(alias)) => Some(Declaration {
+                name: alias.clone(),
+                kind: Kind::Module,
+                signature: format!("IMPORT \"{}\" AS {}", path, alias),
+                span: self.trimmed(statement.span),
+                name_span: self.name_span(statement.span, alias),
+                private,
+                children: Vec::new(),
+            }),
+            _ => None,
+        };
+        // Only a name's first declaration counts: later ones reassign it.
+        if let Some(mut declaration) = declaration
+            && !found.iter().any(|d| d.name == declaration.name)
+        {
+            declaration.private = private;
+            found.push(declaration);
+        }
+    }
+
+    fn procedure(&self, statement: &Spanned, kind: Kind) -> Option<Declaration> {
+        let AstNode::ProcedureDecl(name, params, _) = &statement.node else {
+            return None;
+        };
+        let params: Vec<String> = params
+            .iter()
+            .map(|param| match &param.default {
+                Some(default) => format!("{} <- {}", param.name, self.text(default.span)),
+                None if param.variadic => format!("{}...", param.name),
+                None => param.name.clone(),
+            })
+            .collect();
+        Some(Declaration {
+            name: name.clone(),
+            kind,
+            signature: format!("PROCEDURE {}({})", name, params.join(", ")),
+            span: self.trimmed(statement.span),
+            name_span: self.name_span(statement.span, name),
+            private: false,
+            children: Vec::new(),
+        })
+    }
+
+    /// Where `name` is written in the declaration spanning `within`.
+    fn name_span(&self, within: Span, name: &str) -> Span {
+        self.tokens
+            .iter()
+            .find(|(token, span)| {
+                span.start >= within.start
+                    && span.start < within.end
+                    && matches!(token, Token::Identifier(n) if n == name)
+            })
+            .map_or(within, |(_, span)| *span)
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The `file://` URI of `path`.
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    // `C:/dir` is `file:///C:/dir`.
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    };
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// The path a `file://` URI names; `None` for any other scheme, such as the
+/// `untitled:` of a file the editor has not saved.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Past the host, which is empty or `localhost` for a local file.
+    let rest = &rest[rest.find('/')?..];
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| rest.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // `/C:/dir` is `C:/dir` to Windows.
+    let path = match path.strip_prefix('/') {
+        Some(windows) if cfg!(windows) && windows.get(1..2) == Some(":") => windows.to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
@@ -1,23 +1,58 @@
 use std::fs;
 use std::io::Read;
 
+mod check;
 mod core;
+mod coverage;
+mod dap;
+mod debugger;
 mod error;
+mod format;
+mod golden;
+mod host;
 mod interpreter;
+mod json;
 mod lexer;
+mod limits;
+mod lint;
+mod lsp;
 mod parser;
+mod profile;
+mod repl;
+mod sandbox;
 mod system;
+mod testing;
+mod trace;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
 use clap::{Parser, Subcommand};
 use core::*;
+use interpreter::Observer;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 const HELP_TEMPLATE: &str = r#"PseudoLang Usage:
     fpli [OPTIONS] COMMAND [ARGS]
 
 COMMANDS:
     run <input_file.psl> [PROGRAM_ARGS...]    Execute a PseudoLang program
+    run --step <input_file.psl>               Execute it one statement at a time, in a debugger
+    run --trace <input_file.psl>              Execute it, showing each statement as it runs
+    run --profile <input_file.psl>            Execute it, then report where the time went
+    run --coverage <FILE> <input_file.psl>    Execute it, writing which lines ran to FILE as LCOV
+    run --deny-all <input_file.psl>           Execute it with no access to files, processes or environment
+    run --allow-read=<DIR> <input_file.psl>   Execute it in a sandbox, able to read only inside DIR
+    run --timeout <SECONDS> <input_file.psl>  Execute it, stopping it if it runs for longer
+    repl                                      Start an interactive session
+    check <file.psl>...                       Report syntax errors without running anything
+    lint <file.psl>...                        Warn about likely mistakes without running anything
+    fmt [--check] <file.psl>...               Rewrite files in the canonical layout
+    test [path...]                            Run the test_ procedures in *_test.psl files
+    test --coverage <FILE> [path...]          Run them, writing which lines ran to FILE as LCOV
+    test --golden <dir> [--bless]             Compare programs' output with .expected files
+    lsp                                       Serve the Language Server Protocol on stdin/stdout
+    dap                                       Serve the Debug Adapter Protocol on stdin/stdout
 
 OPTIONS:
     -h, --help       Display this help message
@@ -29,6 +64,20 @@ Examples:
     fpli --debug run source.psl
     fpli run --debug source.psl
     fpli run program.psl --verbose -n 5 output.txt
+    fpli run --step program.psl
+    fpli run --trace --trace-values --trace-file trace.txt program.psl
+    fpli run --count-ops --folded stacks.txt program.psl
+    fpli run --allow-read=data --allow-write=out --allow-env=HOME program.psl
+    fpli run --max-steps 1000000 --timeout 5 --max-memory 64M program.psl
+    fpli repl
+    fpli check program.psl lib/helpers.psl
+    fpli lint program.psl
+    fpli fmt --check program.psl
+    fpli test tests/
+    fpli test --coverage lcov.info tests/
+    fpli test --deny-all --allow-read=submission tests/
+    fpli test --timeout 2 tests/
+    fpli test --golden examples/
 "#;
 
 #[derive(Parser)]
@@ -53,24 +102,345 @@ struct Cli {
     command: Commands,
 }
 
+/// The flags of `run` that attach something to follow the program through its
+/// run. Only one thing can follow a run at a time.
+#[derive(clap::Args)]
+struct Follow {
+    #[arg(
+        long,
+        help = "Stop before the first statement, in the console debugger"
+    )]
+    step: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["step", "profile", "count_ops", "coverage"],
+        help = "Show each statement as it runs, with its file, line and call depth"
+    )]
+    trace: bool,
+    #[arg(
+        long,
+        requires = "trace",
+        help = "Also show assignments, and each procedure's arguments and result"
+    )]
+    trace_values: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "trace",
+        help = "Write the trace to FILE instead of standard error"
+    )]
+    trace_file: Option<String>,
+    #[arg(
+        long,
+        conflicts_with_all = ["step", "count_ops", "coverage"],
+        group = "profiling",
+        help = "Time each procedure and count each line's hits, and report them at the end"
+    )]
+    profile: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["step", "coverage"],
+        group = "profiling",
+        help = "Profile by counting the statements run instead of timing them"
+    )]
+    count_ops: bool,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "profiling",
+        help = "With --profile or --count-ops, write folded stacks for a flamegraph to FILE"
+    )]
+    folded: Option<String>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "step",
+        help = "Write an LCOV report of the lines that ran, and how often, to FILE"
+    )]
+    coverage: Option<String>,
+}
+
+/// The flags of `run` and `test` that put the program in a sandbox. With none
+/// of them it may do everything; with any, only what the `--allow-*` flags give.
+#[derive(clap::Args)]
+struct Sandbox {
+    #[arg(
+        long,
+        help = "Allow no files, processes or environment variables but what --allow-* grants"
+    )]
+    deny_all: bool,
+    #[arg(
+        long,
+        value_name = "PATHS",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ',',
+        help = "Allow reading files, or only those inside the comma-separated PATHS"
+    )]
+    allow_read: Option<Vec<String>>,
+    #[arg(
+        long,
+        value_name = "PATHS",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ',',
+        help = "Allow creating, changing and deleting files, or only inside PATHS"
+    )]
+    allow_write: Option<Vec<String>>,
+    #[arg(
+        long,
+        help = "Allow EXEC and SHELL, and KILL, PROCESSES, PROCESSINFO and WHICH"
+    )]
+    allow_exec: bool,
+    #[arg(
+        long,
+        value_name = "NAMES",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ',',
+        help = "Allow reading and setting environment variables, or only those NAMES"
+    )]
+    allow_env: Option<Vec<String>>,
+}
+
+impl Sandbox {
+    /// What the program may do: everything unless a flag asked for a sandbox.
+    fn permissions(&self) -> sandbox::Permissions {
+        use sandbox::{Grant, Permissions};
+        let sandboxed = self.deny_all
+            || self.allow_read.is_some()
+            || self.allow_write.is_some()
+            || self.allow_exec
+            || self.allow_env.is_some();
+        if !sandboxed {
+            return Permissions::default();
+        }
+        // A flag with no list grants all of its kind; one with a list, only that.
+        let mut permissions = Permissions::none();
+        if let Some(paths) = &self.allow_read {
+            permissions.read = if paths.is_empty() {
+                Grant::All
+            } else {
+                Grant::paths(paths)
+            };
+        }
+        if let Some(paths) = &self.allow_write {
+            permissions.write = if paths.is_empty() {
+                Grant::All
+            } else {
+                Grant::paths(paths)
+            };
+        }
+        permissions.exec = self.allow_exec;
+        if let Some(names) = &self.allow_env {
+            permissions.env = if names.is_empty() {
+                Grant::All
+            } else {
+                Grant::Only(names.clone())
+            };
+        }
+        permissions
+    }
+}
+
+/// The flags of `run` and `test` that limit how much a run may take before it is
+/// stopped. See `limits` for what each one counts.
+#[derive(clap::Args)]
+struct Budget {
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Stop the program once it has run N statements"
+    )]
+    max_steps: Option<u64>,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = limits::parse_seconds,
+        help = "Stop the program once it has run for SECONDS, which may have a fraction"
+    )]
+    timeout: Option<std::time::Duration>,
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = limits::parse_size,
+        help = "Stop the program once its values take about SIZE bytes, or K, M or G"
+    )]
+    max_memory: Option<u64>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Allow procedure calls to nest N deep [default: 1000]"
+    )]
+    max_depth: Option<usize>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Allow EVAL and EXECUTE to nest N deep [default: 32]"
+    )]
+    max_eval_depth: Option<usize>,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Stop the program when any one loop goes round more than N times"
+    )]
+    max_loop_iterations: Option<u64>,
+}
+
+impl Budget {
+    /// The limits the flags set, and the defaults for the rest.
+    fn limits(&self) -> limits::Limits {
+        let defaults = limits::Limits::default();
+        limits::Limits {
+            steps: self.max_steps,
+            timeout: self.timeout,
+            memory: self.max_memory,
+            stack_depth: self.max_depth.unwrap_or(defaults.stack_depth),
+            meta_depth: self.max_eval_depth.unwrap_or(defaults.meta_depth),
+            loop_iterations: self.max_loop_iterations,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     #[command(about = "Execute a PseudoLang program")]
     Run {
         #[arg(help = "Path to a .psl file")]
         input_file: String,
+        #[command(flatten)]
+        follow: Follow,
+        #[command(flatten)]
+        sandbox: Sandbox,
+        #[command(flatten)]
+        budget: Budget,
+    },
+    #[command(about = "Start an interactive session")]
+    Repl,
+    #[command(about = "Report syntax errors in files and their imports without running them")]
+    Check {
+        #[arg(required = true, help = "Paths to .psl files")]
+        files: Vec<String>,
     },
+    #[command(about = "Warn about code that is probably wrong, without running it")]
+    Lint {
+        #[arg(required = true, help = "Paths to .psl files")]
+        files: Vec<String>,
+    },
+    #[command(about = "Rewrite files in the canonical layout, keeping their comments")]
+    Fmt {
+        #[arg(long, help = "Change nothing; fail if a file is not formatted")]
+        check: bool,
+        #[arg(required = true, help = "Paths to .psl files")]
+        files: Vec<String>,
+    },
+    #[command(about = "Run the test_ procedures of every *_test.psl file")]
+    Test {
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with_all = ["Sandbox", "Budget"],
+            help = "Run the programs in DIR and compare their output with their .expected files"
+        )]
+        golden: Option<String>,
+        #[arg(
+            long,
+            requires = "golden",
+            help = "Record what each mismatching program did as what it should do"
+        )]
+        bless: bool,
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "golden",
+            help = "Write an LCOV report of the lines the tests ran to FILE"
+        )]
+        coverage: Option<String>,
+        #[arg(
+            conflicts_with = "golden",
+            help = "Test files, or directories to search; the current directory by default"
+        )]
+        paths: Vec<String>,
+        #[command(flatten)]
+        sandbox: Sandbox,
+        #[command(flatten)]
+        budget: Budget,
+    },
+    #[command(about = "Serve the Language Server Protocol on stdin and stdout, for editors")]
+    Lsp,
+    #[command(about = "Serve the Debug Adapter Protocol on stdin and stdout, for editors")]
+    Dap,
 }
 
+/// Split off what follows the program file of `run`, which belongs to the program.
+///
+/// Only `run` has a program to forward arguments to: every other subcommand that
+/// takes `.psl` files -- `check`, `fmt`, `lint` and `test` -- takes any number of
+/// them, and cutting its command line at the first would hand the rest to nobody.
 fn split_args() -> (Vec<String>, Vec<String>) {
     let all: Vec<String> = std::env::args().collect();
+    let subcommand = all.iter().skip(1).find(|a| !a.starts_with('-'));
+    if subcommand.map(String::as_str) != Some("run") {
+        return (all, vec![]);
+    }
     match all.iter().position(|a| a.ends_with(".psl")) {
         Some(pos) => (all[..=pos].to_vec(), all[pos + 1..].to_vec()),
         None => (all, vec![]),
     }
 }
 
-fn run_program(input_file: &str, debug: bool, program_args: &[String]) -> Result<(), String> {
+/// What follows the program through its run, as the flags of `run` ask: the
+/// console debugger for `--step`, the tracer for `--trace`, the profiler for
+/// `--profile` and `--count-ops`, coverage for `--coverage`.
+fn follower(follow: &Follow) -> Result<Option<Rc<RefCell<dyn Observer>>>, String> {
+    let create = |path: &str, what: &str| {
+        fs::File::create(path).map_err(|e| format!("Error creating {} {}: {}", what, path, e))
+    };
+    if follow.step {
+        return Ok(Some(Rc::new(RefCell::new(debugger::Console::new()))));
+    }
+    if follow.trace {
+        let output: Box<dyn std::io::Write> = match &follow.trace_file {
+            // A line at a time: EXIT ends the process without unwinding, and a
+            // buffered trace would lose its last lines, the ones leading up to it.
+            Some(path) => Box::new(std::io::LineWriter::new(create(path, "trace file")?)),
+            None => Box::new(std::io::stderr()),
+        };
+        return Ok(Some(Rc::new(RefCell::new(trace::Tracer::new(
+            output,
+            follow.trace_values,
+        )))));
+    }
+    if follow.profile || follow.count_ops {
+        let folded: Option<Box<dyn std::io::Write>> = match &follow.folded {
+            Some(path) => Some(Box::new(std::io::BufWriter::new(create(
+                path,
+                "folded stacks file",
+            )?))),
+            None => None,
+        };
+        return Ok(Some(Rc::new(RefCell::new(profile::Profiler::new(
+            Box::new(std::io::stderr()),
+            folded,
+            follow.count_ops,
+        )))));
+    }
+    if let Some(path) = &follow.coverage {
+        // Written once, when the run is over: before EXIT ends the process too.
+        let output = Box::new(std::io::BufWriter::new(create(path, "coverage file")?));
+        return Ok(Some(Rc::new(RefCell::new(coverage::Coverage::new(output)))));
+    }
+    Ok(None)
+}
+
+fn run_program(
+    input_file: &str,
+    debug: bool,
+    observer: Option<Rc<RefCell<dyn Observer>>>,
+    permissions: sandbox::Permissions,
+    limits: limits::Limits,
+    program_args: &[String],
+) -> Result<(), String> {
     if !input_file.ends_with(".psl") {
         return Err(format!(
             "Input file must have .psl extension, got: {}",
@@ -85,13 +455,14 @@ fn run_program(input_file: &str, debug: bool, program_args: &[String]) -> Result
     file.read_to_string(&mut source_code)
         .map_err(|e| format!("Error reading file {}: {}", input_file, e))?;
 
-    match execute_code(
-        &source_code,
-        debug,
-        false,
-        program_args,
-        Some(std::path::Path::new(input_file)),
-    ) {
+    let options = interpreter::RunOptions {
+        args: program_args.to_vec(),
+        script_path: Some(std::path::PathBuf::from(input_file)),
+        observer,
+        permissions,
+        limits,
+    };
+    match execute_code(&source_code, debug, false, options) {
         Ok(_) => Ok(()),
         Err(e) => Err(e),
     }
@@ -106,12 +477,85 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     match cli.command {
-        Commands::Run { ref input_file } => {
-            if let Err(error) = run_program(input_file, cli.debug, &program_args) {
+        Commands::Run {
+            ref input_file,
+            ref follow,
+            ref sandbox,
+            ref budget,
+        } => {
+            let result = follower(follow).and_then(|observer| {
+                let permissions = sandbox.permissions();
+                run_program(
+                    input_file,
+                    cli.debug,
+                    observer,
+                    permissions,
+                    budget.limits(),
+                    &program_args,
+                )
+            });
+            if let Err(error) = result {
                 eprintln!("Error: {}", error);
                 std::process::exit(1);
             }
         }
+        Commands::Check { ref files } => {
+            let code = check::run(files, cli.debug);
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Lint { ref files } => {
+            let code = lint::run(files);
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Fmt { check, ref files } => {
+            let code = format::run(files, check);
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Test {
+            ref golden,
+            bless,
+            ref coverage,
+            ref paths,
+            ref sandbox,
+            ref budget,
+        } => {
+            let code = match golden {
+                Some(dir) => golden::run(dir, bless),
+                None => testing::run(
+                    paths,
+                    coverage.as_deref(),
+                    &sandbox.permissions(),
+                    &budget.limits(),
+                ),
+            };
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Lsp => {
+            let code = lsp::run();
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Dap => {
+            let code = dap::run();
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Commands::Repl => {
+            let code = repl::run();
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
     }
 
     Ok(())
@@ -77,6 +77,11 @@ fn take_children(node: &mut AstNode, out: &mut Vec<Spanned>) {
         | AstNode::ProcedureCall(_, items)
         | AstNode::FormattedString(_, items) => push_all(out, items),
 
+        AstNode::MethodCall(object, _, args) | AstNode::ValueCall(object, args) => {
+            push(out, *object);
+            push_all(out, args);
+        }
+
         AstNode::Dictionary(entries) => {
             for (key, value) in entries {
                 push(out, key);
@@ -93,6 +98,10 @@ fn take_children(node: &mut AstNode, out: &mut Vec<Spanned>) {
         | AstNode::Sort(a)
         | AstNode::ClassDecl(_, a)
         | AstNode::ProcedureDecl(_, _, a)
+        | AstNode::AnonymousProcedure(_, a)
+        | AstNode::FieldAccess(a, _)
+        | AstNode::Throw(a)
+        | AstNode::Private(a)
         | AstNode::Eval(a) => push(out, *a),
 
         AstNode::Display(a) | AstNode::Input(a) => push_all(out, a.map(|b| *b)),
@@ -102,22 +111,35 @@ fn take_children(node: &mut AstNode, out: &mut Vec<Spanned>) {
         | AstNode::BinaryOp(a, _, b)
         | AstNode::RepeatTimes(a, b)
         | AstNode::RepeatUntil(a, b)
-        | AstNode::ForEach(_, a, b)
+        | AstNode::RepeatWhile(a, b)
+        | AstNode::ForEach(_, _, a, b)
         | AstNode::Random(a, b)
         | AstNode::Append(a, b)
         | AstNode::Remove(a, b)
+        | AstNode::FieldAssignment(a, _, b)
         | AstNode::Concat(a, b) => {
             push(out, *a);
             push(out, *b);
         }
 
+        AstNode::Match(subject, cases, default) => {
+            push(out, *subject);
+            for case in cases {
+                push_all(out, case.guard);
+                push(out, case.body);
+            }
+            push_all(out, default.map(|d| *d));
+        }
+
         AstNode::TryCatch {
             try_block: a,
             catch_block: b,
+            finally_block: c,
             ..
         } => {
             push(out, *a);
-            push(out, *b);
+            push_all(out, b.map(|b| *b));
+            push_all(out, c.map(|c| *c));
         }
 
         AstNode::ListAssignment(a, b, c)
@@ -134,6 +156,19 @@ fn take_children(node: &mut AstNode, out: &mut Vec<Spanned>) {
             push_all(out, c.map(|d| *d));
         }
 
+        AstNode::ForRange {
+            from,
+            to,
+            step,
+            body,
+            ..
+        } => {
+            push(out, *from);
+            push(out, *to);
+            push_all(out, step.map(|s| *s));
+            push(out, *body);
+        }
+
         // Leaves: nothing recursive to hand over.
         AstNode::Integer(_)
         | AstNode::Float(_)
@@ -143,8 +178,11 @@ fn take_children(node: &mut AstNode, out: &mut Vec<Spanned>) {
         | AstNode::NaN
         | AstNode::Identifier(_)
         | AstNode::Comment
-        | AstNode::Import(_)
-        | AstNode::RawString(_) => {}
+        | AstNode::Import(..)
+        | AstNode::Export(_)
+        | AstNode::RawString(_)
+        | AstNode::Break
+        | AstNode::Continue => {}
     }
 }
 
@@ -163,6 +201,19 @@ pub enum AstNode {
     Assignment(Box<Spanned>, Box<Spanned>),
     ListAccess(Box<Spanned>, Box<Spanned>),
     ListAssignment(Box<Spanned>, Box<Spanned>, Box<Spanned>),
+    /// `object.field`.
+    FieldAccess(Box<Spanned>, String),
+    /// `object.field <- value`.
+    FieldAssignment(Box<Spanned>, String, Box<Spanned>),
+    /// `object.method(args)`. The receiver is evaluated once and bound to `self`
+    /// inside the method.
+    MethodCall(Box<Spanned>, String, Vec<Spanned>),
+    /// `PROCEDURE (params) { body }` written as an expression. Evaluates to a
+    /// procedure value that closes over the scope it was evaluated in.
+    AnonymousProcedure(Vec<Parameter>, Box<Spanned>),
+    /// A call whose callee is an expression rather than a name: `handlers[1](x)`,
+    /// `makeAdder(1)(2)`. A call through a plain name stays a `ProcedureCall`.
+    ValueCall(Box<Spanned>, Vec<Spanned>),
 
     BinaryOp(Box<Spanned>, BinaryOperator, Box<Spanned>),
     UnaryOp(UnaryOperator, Box<Spanned>),
@@ -170,9 +221,30 @@ pub enum AstNode {
     If(Box<Spanned>, Box<Spanned>, Option<Box<Spanned>>),
     RepeatTimes(Box<Spanned>, Box<Spanned>),
     RepeatUntil(Box<Spanned>, Box<Spanned>),
-    ForEach(String, Box<Spanned>, Box<Spanned>),
+    /// `REPEAT WHILE cond { body }`: the condition, then the body. Tested before
+    /// every iteration, so the body may run zero times.
+    RepeatWhile(Box<Spanned>, Box<Spanned>),
+    /// Leave the innermost loop. Only ever parsed inside a loop body.
+    Break,
+    /// Skip to the next iteration of the innermost loop.
+    Continue,
+    /// `FOR EACH item IN x { body }`. With a second name, `FOR EACH key, value IN`
+    /// a dictionary, or `FOR EACH index, item IN` a list or string.
+    ForEach(String, Option<String>, Box<Spanned>, Box<Spanned>),
+    /// `MATCH subject { CASE pattern IF guard { body } ... ELSE { body } }`: the
+    /// subject, the cases in order, and the ELSE body.
+    Match(Box<Spanned>, Vec<MatchCase>, Option<Box<Spanned>>),
+    /// `FOR var FROM from TO to STEP step { body }`, both bounds inclusive. A
+    /// missing STEP means 1.
+    ForRange {
+        var: String,
+        from: Box<Spanned>,
+        to: Box<Spanned>,
+        step: Option<Box<Spanned>>,
+        body: Box<Spanned>,
+    },
 
-    ProcedureDecl(String, Vec<String>, Box<Spanned>),
+    ProcedureDecl(String, Vec<Parameter>, Box<Spanned>),
     ProcedureCall(String, Vec<Spanned>),
     Return(Box<Spanned>),
 
@@ -195,15 +267,26 @@ pub enum AstNode {
     Block(Vec<Spanned>),
     Program(Vec<Spanned>),
     Comment,
-    Import(String),
+    /// `IMPORT path`, or `IMPORT path AS name` to reach the file's declarations
+    /// through `name` instead of declaring them here.
+    Import(String, Option<String>),
+    /// `EXPORT a, b`: the only top-level names of this file that an importer gets.
+    Export(Vec<String>),
+    /// `PRIVATE` in front of a top-level PROCEDURE, CLASS or assignment to a name:
+    /// the name stays inside the file when it is imported.
+    Private(Box<Spanned>),
 
     RawString(String),
     FormattedString(String, Vec<Spanned>),
     TryCatch {
         try_block: Box<Spanned>,
         error_var: Option<String>,
-        catch_block: Box<Spanned>,
+        /// `None` only when there is a FINALLY: `TRY { } FINALLY { }` cleans up
+        /// without handling the error, which then carries on unwinding.
+        catch_block: Option<Box<Spanned>>,
+        finally_block: Option<Box<Spanned>>,
     },
+    Throw(Box<Spanned>),
     Eval(Box<Spanned>),
 }
 
@@ -224,10 +307,173 @@ impl AstNode {
                 | AstNode::NaN
                 | AstNode::Identifier(_)
                 | AstNode::Comment
-                | AstNode::Import(_)
+                | AstNode::Import(..)
+                | AstNode::Export(_)
                 | AstNode::RawString(_)
+                | AstNode::Break
+                | AstNode::Continue
         )
     }
+
+    /// The nodes directly inside this one, in the order they are written,
+    /// parameter defaults included.
+    ///
+    /// The borrowing counterpart of `take_children`, for passes that read a
+    /// tree without taking it apart: coverage looking for statements, and the
+    /// linter. Exhaustive for the same reason, so a new kind of node cannot hide
+    /// what is inside it from them.
+    pub fn children(&self) -> Vec<&Spanned> {
+        match self {
+            AstNode::List(items)
+            | AstNode::Block(items)
+            | AstNode::Program(items)
+            | AstNode::ProcedureCall(_, items)
+            | AstNode::FormattedString(_, items) => items.iter().collect(),
+
+            AstNode::MethodCall(object, _, args) | AstNode::ValueCall(object, args) => {
+                std::iter::once(&**object).chain(args).collect()
+            }
+
+            AstNode::Dictionary(entries) => entries
+                .iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+
+            AstNode::ProcedureDecl(_, params, body) | AstNode::AnonymousProcedure(params, body) => {
+                params
+                    .iter()
+                    .filter_map(|param| param.default.as_ref())
+                    .chain(std::iter::once(&**body))
+                    .collect()
+            }
+
+            AstNode::UnaryOp(_, a)
+            | AstNode::Return(a)
+            | AstNode::DisplayInline(a)
+            | AstNode::Length(a)
+            | AstNode::ToString(a)
+            | AstNode::ToNum(a)
+            | AstNode::Sort(a)
+            | AstNode::ClassDecl(_, a)
+            | AstNode::FieldAccess(a, _)
+            | AstNode::Throw(a)
+            | AstNode::Private(a)
+            | AstNode::Eval(a) => vec![a],
+
+            AstNode::Display(a) | AstNode::Input(a) => a.iter().map(|a| &**a).collect(),
+
+            AstNode::Assignment(a, b)
+            | AstNode::ListAccess(a, b)
+            | AstNode::BinaryOp(a, _, b)
+            | AstNode::RepeatTimes(a, b)
+            | AstNode::RepeatUntil(a, b)
+            | AstNode::RepeatWhile(a, b)
+            | AstNode::ForEach(_, _, a, b)
+            | AstNode::Random(a, b)
+            | AstNode::Append(a, b)
+            | AstNode::Remove(a, b)
+            | AstNode::FieldAssignment(a, _, b)
+            | AstNode::Concat(a, b) => vec![a, b],
+
+            AstNode::Match(subject, cases, default) => {
+                let mut found = vec![&**subject];
+                for case in cases {
+                    found.extend(&case.guard);
+                    found.push(&case.body);
+                }
+                found.extend(default.as_deref());
+                found
+            }
+
+            AstNode::TryCatch {
+                try_block,
+                catch_block,
+                finally_block,
+                ..
+            } => std::iter::once(&**try_block)
+                .chain(catch_block.as_deref())
+                .chain(finally_block.as_deref())
+                .collect(),
+
+            AstNode::ListAssignment(a, b, c)
+            | AstNode::Insert(a, b, c)
+            | AstNode::Substring(a, b, c) => vec![a, b, c],
+
+            AstNode::If(a, b, c) => std::iter::once(&**a)
+                .chain(std::iter::once(&**b))
+                .chain(c.as_deref())
+                .collect(),
+
+            AstNode::ForRange {
+                from,
+                to,
+                step,
+                body,
+                ..
+            } => std::iter::once(&**from)
+                .chain(std::iter::once(&**to))
+                .chain(step.as_deref())
+                .chain(std::iter::once(&**body))
+                .collect(),
+
+            AstNode::Integer(_)
+            | AstNode::Float(_)
+            | AstNode::String(_)
+            | AstNode::Boolean(_)
+            | AstNode::Null
+            | AstNode::NaN
+            | AstNode::Identifier(_)
+            | AstNode::Comment
+            | AstNode::Import(..)
+            | AstNode::Export(_)
+            | AstNode::RawString(_)
+            | AstNode::Break
+            | AstNode::Continue => Vec::new(),
+        }
+    }
+}
+
+/// One parameter of a PROCEDURE.
+///
+/// A default is an expression bounded by [`MAX_NESTING_DEPTH`] like a pattern
+/// literal, so `take_children` leaves it alone too.
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    /// `name <- value`: what the parameter is when a call leaves it out.
+    /// Evaluated at every such call, after the parameters before it are bound.
+    pub default: Option<Spanned>,
+    /// `name...`: a list of every argument after the ones the other parameters
+    /// took. Only ever the last parameter.
+    pub variadic: bool,
+}
+
+/// One `CASE` of a MATCH.
+#[derive(Debug, Clone)]
+pub struct MatchCase {
+    pub pattern: Pattern,
+    /// `IF condition` after the pattern, tested with the pattern's names bound.
+    pub guard: Option<Spanned>,
+    pub body: Spanned,
+}
+
+/// The shape a MATCH subject is tested against.
+///
+/// Literals are leaf nodes and nesting is bounded by [`MAX_NESTING_DEPTH`], so a
+/// pattern is safe to drop recursively and `take_children` leaves it alone.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// A name: matches anything, and binds it.
+    Binding(String),
+    /// A literal, matched by deep equality.
+    Literal(Spanned),
+    /// `low TO high`, both ends inclusive.
+    Range(Spanned, Spanned),
+    /// `[first, second, rest...]`. Without a rest name the list must have exactly
+    /// as many elements as there are patterns.
+    List(Vec<Pattern>, Option<String>),
+    /// `{"key": pattern, ...}`. Every key must be present; others are ignored.
+    Dictionary(Vec<(Spanned, Pattern)>),
 }
 
 #[derive(Debug, Clone)]
@@ -314,6 +560,19 @@ pub struct Parser {
     current: usize,
     /// Current recursion depth of `parse_expression` / `parse_block`.
     depth: usize,
+    /// How many loop bodies enclose the current position, counted from the
+    /// innermost PROCEDURE body. BREAK and CONTINUE need at least one.
+    loop_depth: usize,
+    /// Whether some loop encloses the innermost PROCEDURE body. Only used to say
+    /// *why* a BREAK there is refused.
+    loop_outside_procedure: bool,
+    /// Set by [`parse_all`]: a statement that fails to parse is recorded in
+    /// `errors` and skipped, instead of ending the parse.
+    recover: bool,
+    errors: Vec<PSLError>,
+    /// Every IMPORT parsed, with its span, for a caller that follows them without
+    /// running anything.
+    imports: Vec<(String, Span)>,
 }
 
 impl Parser {
@@ -322,6 +581,11 @@ impl Parser {
             tokens,
             current: 0,
             depth: 0,
+            loop_depth: 0,
+            loop_outside_procedure: false,
+            recover: false,
+            errors: Vec::new(),
+            imports: Vec::new(),
         }
     }
 
@@ -409,9 +673,22 @@ impl Parser {
             // level no block is open, so nothing would ever consume it and this loop
             // spun forever, appending an empty Block each time until memory ran out.
             if matches!(self.peek(), Some(Token::CloseBrace)) {
-                return Err(self.create_error("Unexpected '}': no block is open here"));
+                let error = self.create_error("Unexpected '}': no block is open here");
+                if !self.recover {
+                    return Err(error);
+                }
+                self.record(error);
+                self.advance();
+                continue;
+            }
+            match self.parse_statement(debug) {
+                Ok(statement) => statements.push(statement),
+                Err(error) if self.recover => {
+                    self.record(error);
+                    self.synchronize(false);
+                }
+                Err(error) => return Err(error),
             }
-            statements.push(self.parse_statement(debug)?);
         }
 
         Self::debug_print(
@@ -442,41 +719,15 @@ impl Parser {
         let start = self.peek_span().start;
 
         match self.peek() {
-            Some(Token::Try) => {
+            Some(Token::Try) => self.parse_try(debug),
+            Some(Token::Throw) => {
                 self.advance();
-                let try_block = self.parse_block(debug)?;
-
-                while matches!(self.peek(), Some(Token::Newline)) {
-                    self.advance();
-                }
-
-                if !self.match_token(&Token::Catch) {
-                    return Err(self.create_error("Expected 'catch' after try block"));
+                // `{` opens a dictionary literal here, as after RETURN.
+                if !self.is_expression_start() && !matches!(self.peek(), Some(Token::OpenBrace)) {
+                    return Err(self.create_error("Expected a value to THROW"));
                 }
-
-                let mut error_var = None;
-                if self.match_token(&Token::OpenParen) {
-                    if let Some(Token::Identifier(name)) = self.advance() {
-                        error_var = Some(name);
-                    } else {
-                        return Err(self.create_error("Expected identifier after 'catch('"));
-                    }
-
-                    if !self.match_token(&Token::CloseParen) {
-                        return Err(self.create_error("Expected ')' after catch variable"));
-                    }
-                }
-
-                let catch_block = self.parse_block(debug)?;
-
-                Ok(self.spanned_from(
-                    AstNode::TryCatch {
-                        try_block: Box::new(try_block),
-                        error_var,
-                        catch_block: Box::new(catch_block),
-                    },
-                    start,
-                ))
+                let value = self.parse_expression(debug)?;
+                Ok(self.spanned_from(AstNode::Throw(Box::new(value)), start))
             }
             Some(Token::ListAppend) => self.parse_builtin(debug, "APPEND", 2, |mut a| {
                 AstNode::Append(Box::new(a.remove(0)), Box::new(a.remove(0)))
@@ -521,97 +772,30 @@ impl Parser {
                     Some(Token::Identifier(name)) => name,
                     _ => return Err(self.create_error("Expected identifier")),
                 };
+                let ident_span = Span::new(start, self.prev_span().end);
 
-                let mut list_accesses = Vec::new();
-                while matches!(self.peek(), Some(Token::OpenBracket)) {
-                    self.advance();
-                    let index = self.parse_expression(debug)?;
-                    if !self.match_token(&Token::CloseBracket) {
-                        return Err(self.create_error("Expected ']'"));
-                    }
-                    list_accesses.push(index);
-                }
+                let base = if self.match_token(&Token::OpenParen) {
+                    let args = self.parse_arguments(debug)?;
+                    self.spanned_from(AstNode::ProcedureCall(identifier, args), start)
+                } else {
+                    Spanned::new(AstNode::Identifier(identifier), ident_span)
+                };
+                // A call in statement position can be indexed too, so that
+                // `f(x)[1]` parses the same way here as it does inside an
+                // expression, and the same chain is what an assignment target is
+                // built from: `a[i][j] <- v` and `p.items[1] <- v` alike.
+                let target = self.parse_postfix(base, start, debug)?;
 
                 match self.peek() {
                     Some(Token::Assign) => {
                         self.advance();
                         let value = self.parse_expression(debug)?;
-
-                        if list_accesses.is_empty() {
-                            let ident_span = Span::new(start, start + identifier.len());
-                            let target = Spanned::new(AstNode::Identifier(identifier), ident_span);
-                            Ok(self.spanned_from(
-                                AstNode::Assignment(Box::new(target), Box::new(value)),
-                                start,
-                            ))
-                        } else {
-                            let ident_span = Span::new(start, start + identifier.len());
-                            let mut current =
-                                Spanned::new(AstNode::Identifier(identifier), ident_span);
-                            for (i, index) in list_accesses.iter().enumerate() {
-                                if i == list_accesses.len() - 1 {
-                                    let node = AstNode::ListAssignment(
-                                        Box::new(current),
-                                        Box::new(index.clone()),
-                                        Box::new(value.clone()),
-                                    );
-                                    return Ok(self.spanned_from(node, start));
-                                } else {
-                                    let access_span = Span::new(start, index.span.end);
-                                    current = Spanned::new(
-                                        AstNode::ListAccess(
-                                            Box::new(current),
-                                            Box::new(index.clone()),
-                                        ),
-                                        access_span,
-                                    );
-                                }
-                            }
-                            unreachable!()
-                        }
+                        self.assignment_to(target, value, start)
                     }
-                    // With indices already collected this is `a[0](x)`, which reads
-                    // like calling what the index produced. The language has no
-                    // callable values, and the two earlier readings were both silent:
-                    // first the indices were dropped and `a` was called, then the
-                    // `(x)` became a separate statement that evaluated to nothing.
-                    Some(Token::OpenParen) if !list_accesses.is_empty() => Err(self.create_error(
-                        "Cannot call an indexed value: PseudoLang has no callable values, so \
-                             write the procedure name directly",
-                    )),
-                    Some(Token::OpenParen) => {
-                        self.advance();
-                        let mut args = Vec::new();
-                        while !self.match_token(&Token::CloseParen) {
-                            if !args.is_empty() && !self.match_token(&Token::Comma) {
-                                return Err(self.create_error("Expected comma between arguments"));
-                            }
-                            args.push(self.parse_expression(debug)?);
-                        }
-                        let call =
-                            self.spanned_from(AstNode::ProcedureCall(identifier, args), start);
-                        // A call in statement position can be indexed too, so
-                        // that `f(x)[1]` parses the same way here as it does
-                        // inside an expression.
-                        self.parse_postfix_index(call, start, debug)
-                    }
-                    _ => {
-                        if list_accesses.is_empty() {
-                            Ok(self.spanned_from(AstNode::Identifier(identifier), start))
-                        } else {
-                            let ident_span = Span::new(start, start + identifier.len());
-                            let mut current =
-                                Spanned::new(AstNode::Identifier(identifier), ident_span);
-                            for index in list_accesses {
-                                let access_span = Span::new(start, index.span.end);
-                                current = Spanned::new(
-                                    AstNode::ListAccess(Box::new(current), Box::new(index)),
-                                    access_span,
-                                );
-                            }
-                            Ok(current)
-                        }
+                    Some(Token::Comma) if matches!(target.node, AstNode::Identifier(_)) => {
+                        self.parse_unpacking(target, start, debug)
                     }
+                    _ => Ok(target),
                 }
             }
             None => {
@@ -623,7 +807,15 @@ impl Parser {
             }
             Some(_) if self.is_expression_start() => {
                 Self::debug_print(debug, "Starting expression parse");
-                self.parse_expression(debug)
+                let expr = self.parse_expression(debug)?;
+                // `[x, [y, z]] <- value`: a list literal in statement position is
+                // only ever an unpacking target.
+                if matches!(expr.node, AstNode::List(_))
+                    && matches!(self.peek(), Some(Token::Assign | Token::Comma))
+                {
+                    return self.parse_unpacking(expr, start, debug);
+                }
+                Ok(expr)
             }
             Some(Token::If) => {
                 Self::debug_print(debug, "Starting if statement parse");
@@ -638,6 +830,9 @@ impl Parser {
                 self.parse_repeat(debug)
             }
             Some(Token::For) => self.parse_foreach(debug),
+            Some(Token::Match) => self.parse_match(debug),
+            Some(Token::Break) => self.parse_loop_exit("BREAK", AstNode::Break),
+            Some(Token::Continue) => self.parse_loop_exit("CONTINUE", AstNode::Continue),
             Some(Token::Class) => self.parse_class(debug),
             Some(Token::Display(_)) => {
                 self.advance();
@@ -660,6 +855,8 @@ impl Parser {
             }),
             Some(Token::Comment) => self.parse_comment(),
             Some(Token::Import) => self.parse_import(),
+            Some(Token::Export) => self.parse_export(),
+            Some(Token::Private) => self.parse_private(debug),
             Some(Token::Return) => {
                 self.advance();
                 if matches!(self.peek(), Some(Token::OpenParen)) {
@@ -676,7 +873,7 @@ impl Parser {
                         Ok(self.spanned_from(AstNode::Return(Box::new(expr)), start))
                     }
                 } else if self.is_expression_start()
-                    || matches!(self.peek(), Some(Token::OpenBrace))
+                    || matches!(self.peek(), Some(Token::OpenBrace | Token::Procedure))
                 {
                     let expr = self.parse_expression(debug)?;
                     Ok(self.spanned_from(AstNode::Return(Box::new(expr)), start))
@@ -955,43 +1152,175 @@ impl Parser {
         }
     }
 
-    /// Consume a run of `[i]` suffixes, wrapping `node` in one [`AstNode::ListAccess`]
-    /// per level.
+    /// Consume a run of `[i]`, `.field` and `.method(args)` suffixes, wrapping
+    /// `node` in one [`AstNode::ListAccess`], [`AstNode::FieldAccess`] or
+    /// [`AstNode::MethodCall`] per level.
     ///
     /// Kept separate from the primary parsers so that indexing composes with
     /// *any* base expression rather than only with a bare identifier:
-    /// `LISTDIR(d)[1]`, `"abc"[1]`, `SORT(xs)[1]` and `f(x)[1][2]` all route
-    /// through here. The interpreter already evaluates a `ListAccess` base as an
-    /// arbitrary expression, so nothing downstream needed changing.
+    /// `LISTDIR(d)[1]`, `"abc"[1]`, `SORT(xs)[1]`, `f(x)[1][2]` and
+    /// `shapes[1].area()` all route through here. The interpreter already
+    /// evaluates a `ListAccess` base as an arbitrary expression, so nothing
+    /// downstream needed changing.
     ///
     /// A `[` on the *next* line cannot be absorbed by mistake, because the lexer
     /// emits `Token::Newline` and this loop stops at it -- so a statement
     /// followed by a list literal still parses as two statements.
-    fn parse_postfix_index(
+    fn parse_postfix(
         &mut self,
         mut node: Spanned,
         start: usize,
         debug: bool,
     ) -> Result<Spanned, PSLError> {
-        while matches!(self.peek(), Some(Token::OpenBracket)) {
-            self.advance();
-            let index = self.parse_expression(debug)?;
-            if !self.match_token(&Token::CloseBracket) {
-                return Err(self.create_error("Expected ']' after index"));
-            }
-            let end = self.prev_span().end;
-            node = Spanned::new(
-                AstNode::ListAccess(Box::new(node), Box::new(index)),
-                Span::new(start, end),
-            );
+        loop {
+            match self.peek() {
+                Some(Token::OpenBracket) => {
+                    self.advance();
+                    let index = self.parse_expression(debug)?;
+                    if !self.match_token(&Token::CloseBracket) {
+                        return Err(self.create_error("Expected ']' after index"));
+                    }
+                    let end = self.prev_span().end;
+                    node = Spanned::new(
+                        AstNode::ListAccess(Box::new(node), Box::new(index)),
+                        Span::new(start, end),
+                    );
+                }
+                Some(Token::Dot) => {
+                    self.advance();
+                    let name = match self.advance() {
+                        Some(Token::Identifier(name)) => name,
+                        _ => {
+                            return Err(
+                                self.create_error("Expected a field or method name after '.'")
+                            );
+                        }
+                    };
+                    node = if self.match_token(&Token::OpenParen) {
+                        let args = self.parse_arguments(debug)?;
+                        self.spanned_from(AstNode::MethodCall(Box::new(node), name, args), start)
+                    } else {
+                        self.spanned_from(AstNode::FieldAccess(Box::new(node), name), start)
+                    };
+                }
+                // `handlers[1](x)` calls what the index produced. This used to be a
+                // parse error, and before that two different silent misreadings:
+                // the indices were dropped and `handlers` was called, or the `(x)`
+                // became a separate statement that evaluated to nothing. The call is
+                // resolved at run time, where a value that is not a procedure is
+                // reported as such.
+                Some(Token::OpenParen) => {
+                    self.advance();
+                    let args = self.parse_arguments(debug)?;
+                    node = self.spanned_from(AstNode::ValueCall(Box::new(node), args), start);
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    /// The arguments of a call, after its `(` has been consumed, up to and
+    /// including the closing `)`.
+    fn parse_arguments(&mut self, debug: bool) -> Result<Vec<Spanned>, PSLError> {
+        let mut args = Vec::new();
+        while !self.match_token(&Token::CloseParen) {
+            if !args.is_empty() && !self.match_token(&Token::Comma) {
+                return Err(self.create_error("Expected comma between arguments"));
+            }
+            args.push(self.parse_expression(debug)?);
+        }
+        Ok(args)
+    }
+
+    /// Turn a parsed postfix chain into the assignment that stores into it.
+    ///
+    /// Only three shapes can be written to: a variable, an element, and a field.
+    /// Anything else -- `f() <- 1`, `p.area() <- 2` -- names a value rather than
+    /// a place.
+    fn assignment_to(
+        &self,
+        target: Spanned,
+        value: Spanned,
+        start: usize,
+    ) -> Result<Spanned, PSLError> {
+        let node = match target.node {
+            AstNode::Identifier(_) => AstNode::Assignment(Box::new(target), Box::new(value)),
+            AstNode::List(_) if Self::is_unpacking_target(&target) => {
+                AstNode::Assignment(Box::new(target), Box::new(value))
+            }
+            AstNode::List(_) => {
+                return Err(PSLError::syntax(
+                    "Invalid assignment target: a list being unpacked into may only hold names and lists of names",
+                    target.span,
+                ));
+            }
+            AstNode::ListAccess(_, _) | AstNode::FieldAccess(_, _) => {
+                // The target's children are moved out, which `Drop for Spanned`
+                // forbids doing by pattern; take them through `mem::replace`.
+                let mut target = target;
+                match std::mem::replace(&mut target.node, AstNode::Null) {
+                    AstNode::ListAccess(base, index) => {
+                        AstNode::ListAssignment(base, index, Box::new(value))
+                    }
+                    AstNode::FieldAccess(object, field) => {
+                        AstNode::FieldAssignment(object, field, Box::new(value))
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => {
+                return Err(PSLError::syntax(
+                    "Invalid assignment target: only a variable, an element or a field can be assigned to",
+                    target.span,
+                ));
+            }
+        };
+        Ok(self.spanned_from(node, start))
+    }
+
+    /// `a, b <- value` or `[a, b] <- value`, from the `,` or `<-` after the first
+    /// target. The names written bare are the same as a list of them, so both
+    /// become an assignment to a list target.
+    fn parse_unpacking(
+        &mut self,
+        first: Spanned,
+        start: usize,
+        debug: bool,
+    ) -> Result<Spanned, PSLError> {
+        let target = if matches!(self.peek(), Some(Token::Comma)) {
+            let mut targets = vec![first];
+            while self.match_token(&Token::Comma) {
+                targets.push(self.parse_primary(debug)?);
+            }
+            self.spanned_from(AstNode::List(targets), start)
+        } else {
+            first
+        };
+        if !self.match_token(&Token::Assign) {
+            return Err(self.create_error("Expected '<-' after the names to unpack into"));
+        }
+        let value = self.parse_expression(debug)?;
+        self.assignment_to(target, value, start)
+    }
+
+    /// Whether `target` is something a list can be unpacked into: a non-empty
+    /// list whose elements are names or, recursively, such lists.
+    fn is_unpacking_target(target: &Spanned) -> bool {
+        match &target.node {
+            AstNode::List(targets) => {
+                !targets.is_empty()
+                    && targets.iter().all(|t| {
+                        matches!(t.node, AstNode::Identifier(_)) || Self::is_unpacking_target(t)
+                    })
+            }
+            _ => false,
         }
-        Ok(node)
     }
 
     fn parse_primary(&mut self, debug: bool) -> Result<Spanned, PSLError> {
         let start = self.peek_span().start;
         let base = self.parse_primary_base(debug)?;
-        self.parse_postfix_index(base, start, debug)
+        self.parse_postfix(base, start, debug)
     }
 
     // skipcq: RS-R1000
@@ -1036,6 +1365,7 @@ impl Parser {
             Some(Token::Sort) => self.parse_builtin(debug, "SORT", 1, |mut a| {
                 AstNode::Sort(Box::new(a.remove(0)))
             }),
+            Some(Token::Procedure) => self.parse_anonymous_procedure(debug),
             Some(Token::Identifier(_)) => {
                 let name = match self.advance() {
                     Some(Token::Identifier(name)) => name,
@@ -1047,15 +1377,9 @@ impl Parser {
                 // The call check comes *before* any indexing. It used to come
                 // after, which meant `a[0](x)` silently threw the index away and
                 // parsed as the call `a(x)`. Indexing a call's result is now the
-                // caller's job (`parse_postfix_index`), so both shapes compose.
+                // caller's job (`parse_postfix`), so both shapes compose.
                 if self.match_token(&Token::OpenParen) {
-                    let mut args = Vec::new();
-                    while !self.match_token(&Token::CloseParen) {
-                        if !args.is_empty() && !self.match_token(&Token::Comma) {
-                            return Err(self.create_error("Expected comma between arguments"));
-                        }
-                        args.push(self.parse_expression(debug)?);
-                    }
+                    let args = self.parse_arguments(debug)?;
                     return Ok(self.spanned_from(AstNode::ProcedureCall(name, args), start));
                 }
 
@@ -1179,21 +1503,64 @@ impl Parser {
     fn parse_foreach(&mut self, debug: bool) -> Result<Spanned, PSLError> {
         let start = self.peek_span().start;
         self.advance();
+        if matches!(self.peek(), Some(Token::Identifier(_))) {
+            return self.parse_for_range(start, debug);
+        }
         if !self.match_token(&Token::Each) {
-            return Err(self.create_error("Expected EACH after FOR"));
+            return Err(self.create_error("Expected EACH or a loop variable after FOR"));
         }
         let var_name = match self.advance() {
             Some(Token::Identifier(name)) => name,
             _ => return Err(self.create_error("Expected identifier after EACH")),
         };
+        let second = if self.match_token(&Token::Comma) {
+            match self.advance() {
+                Some(Token::Identifier(name)) => Some(name),
+                _ => return Err(self.create_error("Expected a second name after ',' in FOR EACH")),
+            }
+        } else {
+            None
+        };
         if !self.match_token(&Token::In) {
             return Err(self.create_error("Expected IN after identifier"));
         }
 
         let list = self.parse_expression(debug)?;
-        let body = self.parse_block(debug)?;
+        let body = self.parse_loop_body(debug)?;
         Ok(self.spanned_from(
-            AstNode::ForEach(var_name, Box::new(list), Box::new(body)),
+            AstNode::ForEach(var_name, second, Box::new(list), Box::new(body)),
+            start,
+        ))
+    }
+
+    /// The rest of `FOR i FROM a TO b STEP s { ... }`, after `FOR`.
+    fn parse_for_range(&mut self, start: usize, debug: bool) -> Result<Spanned, PSLError> {
+        let var = match self.advance() {
+            Some(Token::Identifier(name)) => name,
+            _ => return Err(self.create_error("Expected loop variable after FOR")),
+        };
+        if !self.match_token(&Token::From) {
+            return Err(self.create_error("Expected FROM after the loop variable"));
+        }
+        let from = self.parse_expression(debug)?;
+        if !self.match_token(&Token::To) {
+            return Err(self.create_error("Expected TO after the FROM value"));
+        }
+        let to = self.parse_expression(debug)?;
+        let step = if self.match_token(&Token::Step) {
+            Some(Box::new(self.parse_expression(debug)?))
+        } else {
+            None
+        };
+        let body = self.parse_loop_body(debug)?;
+        Ok(self.spanned_from(
+            AstNode::ForRange {
+                var,
+                from: Box::new(from),
+                to: Box::new(to),
+                step,
+                body: Box::new(body),
+            },
             start,
         ))
     }
@@ -1231,10 +1598,16 @@ impl Parser {
                         break;
                     }
 
-                    let stmt = self.parse_statement(debug)?;
-                    match &stmt.node {
-                        AstNode::Block(v) if v.is_empty() => {}
-                        _ => statements.push(stmt),
+                    match self.parse_statement(debug) {
+                        Ok(stmt) => match &stmt.node {
+                            AstNode::Block(v) if v.is_empty() => {}
+                            _ => statements.push(stmt),
+                        },
+                        Err(error) if self.recover => {
+                            self.record(error);
+                            self.synchronize(true);
+                        }
+                        Err(error) => return Err(error),
                     }
 
                     while matches!(self.peek(), Some(Token::Newline)) {
@@ -1264,7 +1637,34 @@ impl Parser {
         if !self.match_token(&Token::OpenParen) {
             return Err(self.create_error("Expected '(' after procedure name"));
         }
-        let mut params = Vec::new();
+        let params = self.parse_parameters(debug)?;
+        let body = self.parse_procedure_body(debug)?;
+        Ok(self.spanned_from(AstNode::ProcedureDecl(name, params, Box::new(body)), start))
+    }
+
+    /// `PROCEDURE (params) { body }` in expression position: the same shape as a
+    /// declaration with the name left out.
+    fn parse_anonymous_procedure(&mut self, debug: bool) -> Result<Spanned, PSLError> {
+        let start = self.peek_span().start;
+        self.advance();
+        if !self.match_token(&Token::OpenParen) {
+            return Err(self.create_error(
+                "Expected '(' after PROCEDURE: a procedure used as a value has no name",
+            ));
+        }
+        let params = self.parse_parameters(debug)?;
+        let body = self.parse_procedure_body(debug)?;
+        Ok(self.spanned_from(AstNode::AnonymousProcedure(params, Box::new(body)), start))
+    }
+
+    /// A parameter list after its `(` has been consumed, up to and including the
+    /// closing `)`.
+    ///
+    /// Defaults come after every parameter without one, and a variadic parameter
+    /// comes last, so which parameter an argument lands in never depends on
+    /// anything but its position.
+    fn parse_parameters(&mut self, debug: bool) -> Result<Vec<Parameter>, PSLError> {
+        let mut params: Vec<Parameter> = Vec::new();
         while let Some(token) = self.peek() {
             if token == &Token::CloseParen {
                 break;
@@ -1272,16 +1672,35 @@ impl Parser {
             if !params.is_empty() && !self.match_token(&Token::Comma) {
                 return Err(self.create_error("Expected comma between parameters"));
             }
-            match self.advance() {
-                Some(Token::Identifier(param)) => params.push(param),
+            if params.last().is_some_and(|last| last.variadic) {
+                return Err(self.create_error("A variadic parameter must be the last parameter"));
+            }
+            let name = match self.advance() {
+                Some(Token::Identifier(param)) => param,
                 _ => return Err(self.create_error("Expected parameter name")),
+            };
+            let mut param = Parameter {
+                name,
+                default: None,
+                variadic: false,
+            };
+            if matches!(self.peek(), Some(Token::Dot)) {
+                self.parse_ellipsis("a variadic parameter name")?;
+                param.variadic = true;
+            } else if self.match_token(&Token::Assign) {
+                param.default = Some(self.parse_expression(debug)?);
+            } else if params.iter().any(|p| p.default.is_some()) {
+                return Err(self.create_error(&format!(
+                    "Parameter '{}' needs a default: it follows a parameter that has one",
+                    param.name
+                )));
             }
+            params.push(param);
         }
         if !self.match_token(&Token::CloseParen) {
             return Err(self.create_error("Expected ')' after parameters"));
         }
-        let body = self.parse_block(debug)?;
-        Ok(self.spanned_from(AstNode::ProcedureDecl(name, params, Box::new(body)), start))
+        Ok(params)
     }
 
     fn parse_builtin(
@@ -1325,16 +1744,88 @@ impl Parser {
     /// name has no extension, so the two forms agree. A quoted path is still
     /// required for anything with a directory separator or a dot in it, since
     /// those cannot be written as an identifier.
+    ///
+    /// Either form may end in `AS name`.
     fn parse_import(&mut self) -> Result<Spanned, PSLError> {
         let start = self.peek_span().start;
         self.advance();
-        match self.advance() {
-            Some(Token::String(path)) | Some(Token::RawString(path)) => {
-                Ok(self.spanned_from(AstNode::Import(path), start))
+        let path = match self.advance() {
+            Some(Token::String(path)) | Some(Token::RawString(path)) => path,
+            Some(Token::Identifier(name)) => name,
+            _ => {
+                return Err(self.create_error("Expected a module name or quoted path after IMPORT"));
+            }
+        };
+        let alias = if self.match_token(&Token::As) {
+            match self.advance() {
+                Some(Token::Identifier(name)) => Some(name),
+                _ => return Err(self.create_error("Expected a name after AS")),
+            }
+        } else {
+            None
+        };
+        let import = self.spanned_from(AstNode::Import(path.clone(), alias), start);
+        self.imports.push((path, import.span));
+        Ok(import)
+    }
+
+    /// `EXPORT name, name, ...`. Only at the top level of a file: visibility is
+    /// read from a file's text before it runs, so an EXPORT that ran or not
+    /// depending on an IF could not mean anything.
+    fn parse_export(&mut self) -> Result<Spanned, PSLError> {
+        let start = self.peek_span().start;
+        self.require_top_level("EXPORT")?;
+        self.advance();
+        let mut names = Vec::new();
+        loop {
+            match self.advance() {
+                Some(Token::Identifier(name)) => names.push(name),
+                _ => return Err(self.create_error("Expected a name to EXPORT")),
+            }
+            if !self.match_token(&Token::Comma) {
+                break;
             }
-            Some(Token::Identifier(name)) => Ok(self.spanned_from(AstNode::Import(name), start)),
-            _ => Err(self.create_error("Expected a module name or quoted path after IMPORT")),
         }
+        Ok(self.spanned_from(AstNode::Export(names), start))
+    }
+
+    /// `PRIVATE PROCEDURE ...`, `PRIVATE CLASS ...` or `PRIVATE name <- value`, at
+    /// the top level of a file for the same reason as EXPORT.
+    fn parse_private(&mut self, debug: bool) -> Result<Spanned, PSLError> {
+        let start = self.peek_span().start;
+        self.require_top_level("PRIVATE")?;
+        self.advance();
+        if !matches!(
+            self.peek(),
+            Some(Token::Procedure | Token::Class | Token::Identifier(_))
+        ) {
+            return Err(self.create_error(
+                "PRIVATE must be followed by a PROCEDURE, a CLASS or an assignment to a name",
+            ));
+        }
+        let declaration = self.parse_statement(debug)?;
+        match &declaration.node {
+            AstNode::ProcedureDecl(..) | AstNode::ClassDecl(..) => {}
+            AstNode::Assignment(target, _) if matches!(target.node, AstNode::Identifier(_)) => {}
+            _ => {
+                return Err(PSLError::syntax(
+                    "PRIVATE must be followed by a PROCEDURE, a CLASS or an assignment to a name"
+                        .to_string(),
+                    declaration.span,
+                ));
+            }
+        }
+        Ok(self.spanned_from(AstNode::Private(Box::new(declaration)), start))
+    }
+
+    fn require_top_level(&self, keyword: &str) -> Result<(), PSLError> {
+        if self.depth > 0 {
+            return Err(self.create_error(&format!(
+                "{} may only appear at the top level of a file",
+                keyword
+            )));
+        }
+        Ok(())
     }
 
     fn parse_list(&mut self, debug: bool, start: usize) -> Result<Spanned, PSLError> {
@@ -1461,8 +1952,8 @@ impl Parser {
         Self::debug_print(debug, "Starting repeat parse");
         self.advance();
 
-        if self.peek() == Some(&Token::Until) {
-            self.advance();
+        if matches!(self.peek(), Some(Token::Until | Token::While)) {
+            let until = self.advance() == Some(Token::Until);
             let condition = if self.match_token(&Token::OpenParen) {
                 let expr = self.parse_expression(debug)?;
                 if !self.match_token(&Token::CloseParen) {
@@ -1477,23 +1968,372 @@ impl Parser {
                 self.advance();
             }
 
-            let body = self.parse_block(debug)?;
-            Ok(self.spanned_from(
-                AstNode::RepeatUntil(Box::new(body), Box::new(condition)),
-                start,
-            ))
+            let body = self.parse_loop_body(debug)?;
+            let node = if until {
+                AstNode::RepeatUntil(Box::new(body), Box::new(condition))
+            } else {
+                AstNode::RepeatWhile(Box::new(condition), Box::new(body))
+            };
+            Ok(self.spanned_from(node, start))
         } else {
             let times = self.parse_expression(debug)?;
             if !self.match_token(&Token::Times) {
                 return Err(self.create_error("Expected TIMES after repeat count"));
             }
-            let body = self.parse_block(debug)?;
+            let body = self.parse_loop_body(debug)?;
             Ok(self.spanned_from(AstNode::RepeatTimes(Box::new(times), Box::new(body)), start))
         }
     }
 
+    /// Parse a loop body, inside which BREAK and CONTINUE are allowed.
+    fn parse_loop_body(&mut self, debug: bool) -> Result<Spanned, PSLError> {
+        self.loop_depth += 1;
+        let body = self.parse_block(debug);
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parse a procedure body, which starts outside every loop.
+    ///
+    /// A BREAK in a procedure called from a loop cannot end the caller's loop:
+    /// the call might come from anywhere, and the interpreter resolves loop exits
+    /// at the innermost loop *of the running body*. Refusing it here is what
+    /// guarantees a Break interruption never escapes a procedure at run time.
+    fn parse_procedure_body(&mut self, debug: bool) -> Result<Spanned, PSLError> {
+        let saved_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let saved_outside = self.loop_outside_procedure;
+        self.loop_outside_procedure = saved_outside || saved_depth > 0;
+        let body = self.parse_block(debug);
+        self.loop_depth = saved_depth;
+        self.loop_outside_procedure = saved_outside;
+        body
+    }
+
+    /// BREAK or CONTINUE, checked against the enclosing loops.
+    fn parse_loop_exit(&mut self, keyword: &str, node: AstNode) -> Result<Spanned, PSLError> {
+        if self.loop_depth == 0 {
+            let message = if self.loop_outside_procedure {
+                format!(
+                    "{keyword} cannot leave a PROCEDURE: the enclosing loop is outside the procedure body. Use RETURN to leave the procedure instead"
+                )
+            } else {
+                format!("{keyword} can only be used inside a loop")
+            };
+            return Err(self.create_error(&message));
+        }
+        let start = self.peek_span().start;
+        self.advance();
+        Ok(self.spanned_from(node, start))
+    }
+
+    /// `TRY { } CATCH (e) { } FINALLY { }`, where either handler may be left out
+    /// but not both: a TRY with neither would be a block that silently swallows
+    /// nothing and guarantees nothing.
+    fn parse_try(&mut self, debug: bool) -> Result<Spanned, PSLError> {
+        let start = self.peek_span().start;
+        self.advance();
+        let try_block = self.parse_block(debug)?;
+        while matches!(self.peek(), Some(Token::Newline)) {
+            self.advance();
+        }
+
+        let mut error_var = None;
+        let mut catch_block = None;
+        if self.match_token(&Token::Catch) {
+            if self.match_token(&Token::OpenParen) {
+                if let Some(Token::Identifier(name)) = self.advance() {
+                    error_var = Some(name);
+                } else {
+                    return Err(self.create_error("Expected identifier after 'catch('"));
+                }
+
+                if !self.match_token(&Token::CloseParen) {
+                    return Err(self.create_error("Expected ')' after catch variable"));
+                }
+            }
+            catch_block = Some(Box::new(self.parse_block(debug)?));
+        }
+
+        while matches!(self.peek(), Some(Token::Newline)) {
+            self.advance();
+        }
+        let finally_block = if self.match_token(&Token::Finally) {
+            Some(Box::new(self.parse_block(debug)?))
+        } else {
+            None
+        };
+
+        if catch_block.is_none() && finally_block.is_none() {
+            return Err(self.create_error("Expected 'catch' or 'finally' after try block"));
+        }
+
+        Ok(self.spanned_from(
+            AstNode::TryCatch {
+                try_block: Box::new(try_block),
+                error_var,
+                catch_block,
+                finally_block,
+            },
+            start,
+        ))
+    }
+
+    /// `MATCH subject { CASE pattern { } ... ELSE { } }`. ELSE is optional and
+    /// must come last, since no CASE after it could ever run.
+    fn parse_match(&mut self, debug: bool) -> Result<Spanned, PSLError> {
+        let start = self.peek_span().start;
+        self.advance();
+        let subject = self.parse_expression(debug)?;
+        self.skip_newlines();
+        if !self.match_token(&Token::OpenBrace) {
+            return Err(self.create_error("Expected '{' after the MATCH value"));
+        }
+        let mut cases = Vec::new();
+        let mut default = None;
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                Some(Token::CloseBrace) => {
+                    self.advance();
+                    break;
+                }
+                Some(Token::Case) if default.is_none() => {
+                    self.advance();
+                    let pattern = self.nested(|p| p.parse_pattern())?;
+                    let guard = if self.match_token(&Token::If) {
+                        Some(self.parse_expression(debug)?)
+                    } else {
+                        None
+                    };
+                    let body = self.parse_block(debug)?;
+                    cases.push(MatchCase {
+                        pattern,
+                        guard,
+                        body,
+                    });
+                }
+                Some(Token::Else) if default.is_none() => {
+                    self.advance();
+                    default = Some(Box::new(self.parse_block(debug)?));
+                }
+                Some(Token::Case | Token::Else) => {
+                    return Err(self.create_error("ELSE must be the last arm of a MATCH"));
+                }
+                _ => return Err(self.create_error("Expected CASE, ELSE or '}' inside MATCH")),
+            }
+        }
+        if cases.is_empty() {
+            return Err(self.create_error("MATCH needs at least one CASE"));
+        }
+        Ok(self.spanned_from(AstNode::Match(Box::new(subject), cases, default), start))
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, PSLError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.advance();
+                Ok(Pattern::Binding(name))
+            }
+            Some(Token::OpenBracket) => self.parse_list_pattern(),
+            Some(Token::OpenBrace) => self.parse_dictionary_pattern(),
+            _ => {
+                let low = self.parse_pattern_literal()?;
+                if self.match_token(&Token::To) {
+                    let high = self.parse_pattern_literal()?;
+                    Ok(Pattern::Range(low, high))
+                } else {
+                    Ok(Pattern::Literal(low))
+                }
+            }
+        }
+    }
+
+    /// The `...` after a rest pattern's or a variadic parameter's name, which the
+    /// lexer hands over as three dots.
+    fn parse_ellipsis(&mut self, after: &str) -> Result<(), PSLError> {
+        for _ in 0..3 {
+            if !self.match_token(&Token::Dot) {
+                return Err(self.create_error(&format!("Expected '...' after {}", after)));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_list_pattern(&mut self) -> Result<Pattern, PSLError> {
+        self.advance();
+        let mut elements = Vec::new();
+        let mut rest = None;
+        loop {
+            self.skip_newlines();
+            if self.match_token(&Token::CloseBracket) {
+                break;
+            }
+            if rest.is_some() {
+                return Err(self.create_error("A rest pattern must be the last in a list pattern"));
+            }
+            if let (Some((Token::Identifier(name), _)), Some((Token::Dot, _))) = (
+                self.tokens.get(self.current),
+                self.tokens.get(self.current + 1),
+            ) {
+                let name = name.clone();
+                self.advance();
+                self.parse_ellipsis("a rest name")?;
+                rest = Some(name);
+            } else {
+                elements.push(self.nested(|p| p.parse_pattern())?);
+            }
+            self.skip_newlines();
+            if !self.match_token(&Token::Comma) && self.peek() != Some(&Token::CloseBracket) {
+                return Err(self.create_error("Expected ',' or ']' in list pattern"));
+            }
+        }
+        Ok(Pattern::List(elements, rest))
+    }
+
+    fn parse_dictionary_pattern(&mut self) -> Result<Pattern, PSLError> {
+        self.advance();
+        let mut entries = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.match_token(&Token::CloseBrace) {
+                break;
+            }
+            if !matches!(
+                self.peek(),
+                Some(Token::String(_) | Token::Integer(_) | Token::Boolean(_))
+            ) {
+                return Err(self.create_error(
+                    "Expected a string, integer or boolean key in dictionary pattern",
+                ));
+            }
+            let key = self.parse_pattern_literal()?;
+            if !self.match_token(&Token::Colon) {
+                return Err(self.create_error("Expected ':' after key in dictionary pattern"));
+            }
+            self.skip_newlines();
+            entries.push((key, self.nested(|p| p.parse_pattern())?));
+            self.skip_newlines();
+            if !self.match_token(&Token::Comma) && self.peek() != Some(&Token::CloseBrace) {
+                return Err(self.create_error("Expected ',' or '}' in dictionary pattern"));
+            }
+        }
+        Ok(Pattern::Dictionary(entries))
+    }
+
+    /// A literal in a pattern: a number (optionally negative), a string, a
+    /// boolean or NULL. Only literals, so what a CASE matches can be read off
+    /// the page; anything computed belongs in a guard.
+    fn parse_pattern_literal(&mut self) -> Result<Spanned, PSLError> {
+        let start = self.peek_span().start;
+        let negative = self.match_token(&Token::Minus);
+        let node = match (self.peek().cloned(), negative) {
+            (Some(Token::Integer(n)), _) => AstNode::Integer(if negative { -n } else { n }),
+            (Some(Token::Float(f)), _) => AstNode::Float(if negative { -f } else { f }),
+            (Some(Token::String(s)), false) => AstNode::String(s),
+            (Some(Token::Boolean(b)), false) => AstNode::Boolean(b),
+            (Some(Token::Null), false) => AstNode::Null,
+            _ => {
+                return Err(self.create_error(
+                    "Expected a pattern: a literal, a name, a list or a dictionary",
+                ));
+            }
+        };
+        self.advance();
+        Ok(self.spanned_from(node, start))
+    }
+
+    fn skip_newlines(&mut self) {
+        while matches!(self.peek(), Some(Token::Newline)) {
+            self.advance();
+        }
+    }
+
     fn create_error(&self, message: &str) -> PSLError {
-        PSLError::with_span(message, self.peek_span())
+        PSLError::syntax(message, self.peek_span())
+    }
+
+    /// Keep an error found while recovering. An error at the very place of the one
+    /// before it is the same mistake seen again on the way out, not a new one.
+    fn record(&mut self, error: PSLError) {
+        let repeated = self
+            .errors
+            .last()
+            .is_some_and(|last| last.span == error.span);
+        if !repeated {
+            self.errors.push(error);
+        }
+    }
+
+    /// Skip what is left of a statement that failed to parse, so the next one can
+    /// be parsed cleanly.
+    ///
+    /// A statement ends at a line break, but not one inside brackets the failed
+    /// statement opened: `PROCEDURE f(a b) { ... }` is skipped as a whole rather
+    /// than having its body read as top-level statements, each with errors of its
+    /// own. Inside a block the `}` that closes it also ends the statement, and is
+    /// left for the block to consume.
+    fn synchronize(&mut self, in_block: bool) {
+        // The closers still owed for brackets opened since the error. A closer of
+        // the wrong kind belongs to something opened before it, and is skipped.
+        let mut owed: Vec<Token> = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Newline if owed.is_empty() => return,
+                Token::CloseBrace if owed.is_empty() && in_block => return,
+                Token::OpenBrace => owed.push(Token::CloseBrace),
+                Token::OpenBracket => owed.push(Token::CloseBracket),
+                Token::OpenParen => owed.push(Token::CloseParen),
+                Token::CloseBrace | Token::CloseBracket | Token::CloseParen
+                    if owed.last() == Some(token) =>
+                {
+                    owed.pop();
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+}
+
+/// Everything [`parse_all`] found in one file.
+pub struct ParseReport {
+    /// Every syntax error, in the order they appear.
+    pub errors: Vec<PSLError>,
+    /// The path of every IMPORT that parsed, with the span of the IMPORT.
+    pub imports: Vec<(String, Span)>,
+    /// The statements that parsed, with the ones that did not left out: enough
+    /// for an editor to list a half-written file's procedures while it is being
+    /// typed.
+    pub program: Spanned,
+}
+
+/// Parse a whole file, reporting every syntax error instead of stopping at the
+/// first one.
+///
+/// After an error the parser skips to the end of the statement it was in and
+/// carries on, so one pass over a file finds what `parse` would only report one
+/// run at a time. Nothing is evaluated: this is for `fpli check`, `fpli lsp`, and
+/// anything else that wants to know whether a file is well formed without running it.
+pub fn parse_all(tokens: Vec<(Token, Span)>, debug: bool) -> ParseReport {
+    let mut parser = Parser::new(tokens);
+    parser.recover = true;
+    // Recovery records every statement's error, so only a failure outside any
+    // statement could reach here; keep it all the same.
+    let program = match parser.parse_program(debug) {
+        Ok(program) => program,
+        Err(error) => {
+            parser.record(error);
+            Spanned {
+                node: AstNode::Program(Vec::new()),
+                span: Span::new(0, 0),
+            }
+        }
+    };
+    ParseReport {
+        errors: parser.errors,
+        imports: parser.imports,
+        program,
     }
 }
 
@@ -1502,6 +2342,26 @@ pub fn parse(tokens: Vec<(Token, Span)>, debug: bool) -> Result<Spanned, PSLErro
     parser.parse_program(debug)
 }
 
+/// Parse tokens that must make up exactly one expression, surrounded by nothing
+/// but line breaks.
+///
+/// For the REPL: a statement that starts with a name is an assignment or a call,
+/// so `x + 1` is not a program, but typed on its own it is a question whose
+/// answer should be shown.
+pub fn parse_single_expression(
+    tokens: Vec<(Token, Span)>,
+    debug: bool,
+) -> Result<Spanned, PSLError> {
+    let mut parser = Parser::new(tokens);
+    parser.skip_newlines();
+    let expression = parser.parse_expression(debug)?;
+    parser.skip_newlines();
+    if parser.peek().is_some() {
+        return Err(parser.create_error("Unexpected token after the expression"));
+    }
+    Ok(expression)
+}
+
 pub fn parse_with_source(
     tokens: Vec<(Token, Span)>,
     _source: &str,
@@ -0,0 +1,286 @@
+//! `fpli run --profile`: where a program spends its time, per procedure and per
+//! line, reported once the run is over.
+//!
+//! Every procedure call is timed from the moment it is made to the moment it is
+//! over. Its inclusive time is all of that; its exclusive time leaves out what
+//! the procedures it called took in turn. A recursive procedure's inclusive time
+//! counts only its outermost call, so a deep recursion is not counted once per
+//! level. Procedures are told apart by name and by the file that declares them,
+//! so two imported files may each have their own `parse`.
+//!
+//! `--count-ops` counts operations instead of time: one per statement run. The
+//! counts are the same on every run and on every machine, which is what a
+//! lesson about how an algorithm grows needs and what a clock cannot give.
+//!
+//! The report goes to standard error, after the program's output. Folded stacks
+//! -- one line per call path, `<main>;outer;inner 1234`, weighted in
+//! microseconds or operations -- can be written to a file as well, for
+//! flamegraph tools to draw.
+
+use crate::interpreter::{Observer, Paused};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// The time or the operation count at some point in the run, in nanoseconds or
+/// in statements.
+type Reading = u64;
+
+struct Procedure {
+    name: String,
+    file: Option<PathBuf>,
+    calls: u64,
+    inclusive: Reading,
+    exclusive: Reading,
+}
+
+/// A call in progress.
+struct Call {
+    /// Index into [`Profiler::procedures`].
+    procedure: usize,
+    started: Reading,
+    /// Index into [`Profiler::stacks`]: the call path that ends here.
+    stack: usize,
+}
+
+pub struct Profiler {
+    output: Box<dyn Write>,
+    folded: Option<Box<dyn Write>>,
+    /// `None` when counting operations.
+    clock: Option<Instant>,
+    /// Statements run so far.
+    operations: Reading,
+    procedures: Vec<Procedure>,
+    by_name: HashMap<(String, Option<PathBuf>), usize>,
+    /// Every call path seen, with what was spent with it innermost.
+    stacks: Vec<(String, Reading)>,
+    by_path: HashMap<String, usize>,
+    calls: Vec<Call>,
+    /// When the innermost call last started or resumed: what has been spent
+    /// since is its own.
+    mark: Reading,
+    hits: HashMap<(Option<PathBuf>, usize), u64>,
+}
+
+impl Profiler {
+    /// A profiler reporting on `output`, and writing folded stacks to `folded`
+    /// if given. `count_ops` counts statements run instead of time.
+    pub fn new(
+        output: Box<dyn Write>,
+        folded: Option<Box<dyn Write>>,
+        count_ops: bool,
+    ) -> Profiler {
+        let mut profiler = Profiler {
+            output,
+            folded,
+            clock: (!count_ops).then(Instant::now),
+            operations: 0,
+            procedures: Vec::new(),
+            by_name: HashMap::new(),
+            stacks: Vec::new(),
+            by_path: HashMap::new(),
+            calls: Vec::new(),
+            mark: 0,
+            hits: HashMap::new(),
+        };
+        // The program itself, as a call that lasts the whole run.
+        profiler.called("<main>", None);
+        profiler
+    }
+
+    fn now(&self) -> Reading {
+        match self.clock {
+            Some(start) => u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX),
+            None => self.operations,
+        }
+    }
+
+    /// Give what has been spent since the last mark to the innermost call.
+    fn charge(&mut self, now: Reading) {
+        let spent = now - self.mark;
+        self.mark = now;
+        if let Some(call) = self.calls.last() {
+            self.procedures[call.procedure].exclusive += spent;
+            self.stacks[call.stack].1 += spent;
+        }
+    }
+
+    /// A reading as the report shows it.
+    fn show(&self, reading: Reading) -> String {
+        match self.clock {
+            Some(_) => format!("{:.3} ms", reading as f64 / 1e6),
+            None => reading.to_string(),
+        }
+    }
+
+    fn report(&mut self) -> std::io::Result<()> {
+        let mut order: Vec<&Procedure> = self.procedures.iter().collect();
+        order.sort_by(|a, b| {
+            b.exclusive
+                .cmp(&a.exclusive)
+                .then(b.inclusive.cmp(&a.inclusive))
+                .then(a.name.cmp(&b.name))
+        });
+        let rows: Vec<[String; 4]> = order
+            .iter()
+            .map(|procedure| {
+                let name = match procedure.file.as_deref().and_then(Path::file_name) {
+                    Some(file) => format!("{} ({})", procedure.name, file.to_string_lossy()),
+                    None => procedure.name.clone(),
+                };
+                [
+                    name,
+                    procedure.calls.to_string(),
+                    self.show(procedure.inclusive),
+                    self.show(procedure.exclusive),
+                ]
+            })
+            .collect();
+        let unit = match self.clock {
+            Some(_) => "Time",
+            None => "Operations (statements run)",
+        };
+        let mut out = String::new();
+        out.push_str(&format!("{} per procedure:\n", unit));
+        out.push_str(&table(
+            &["Procedure", "Calls", "Inclusive", "Exclusive"],
+            &rows,
+        ));
+
+        let mut lines: Vec<(&(Option<PathBuf>, usize), &u64)> = self.hits.iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let rows: Vec<[String; 2]> = lines
+            .iter()
+            .map(|((file, line), hits)| {
+                let file = file
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .map_or_else(|| "generated source".into(), |f| f.to_string_lossy());
+                [format!("{}:{}", file, line), hits.to_string()]
+            })
+            .collect();
+        out.push_str("\nHits per line:\n");
+        out.push_str(&table(&["Line", "Hits"], &rows));
+        self.output.write_all(out.as_bytes())?;
+        self.output.flush()?;
+
+        if let Some(folded) = &mut self.folded {
+            let mut stacks: Vec<&(String, Reading)> =
+                self.stacks.iter().filter(|(_, spent)| *spent > 0).collect();
+            stacks.sort();
+            for (path, spent) in stacks {
+                // Microseconds: a flamegraph's widths are integers, and whole
+                // nanoseconds would make the numbers needlessly long.
+                let weight = match self.clock {
+                    Some(_) => spent.div_ceil(1000),
+                    None => *spent,
+                };
+                writeln!(folded, "{} {}", path, weight)?;
+            }
+            folded.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// `rows` under `headings`, the first column left-aligned and the rest right.
+fn table<const N: usize>(headings: &[&str; N], rows: &[[String; N]]) -> String {
+    let mut widths: [usize; N] = headings.map(|h| h.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let mut text = String::from("  ");
+        for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+            if i == 0 {
+                text.push_str(&format!("{:<width$}", cell, width = width));
+            } else {
+                text.push_str(&format!("  {:>width$}", cell, width = width));
+            }
+        }
+        text.trim_end().to_string() + "\n"
+    };
+    let mut out = line(headings.to_vec());
+    for row in rows {
+        out.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    out
+}
+
+impl Observer for Profiler {
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+        // A statement's own operation goes to whoever runs it, before a call it
+        // makes is entered.
+        self.operations += 1;
+        let key = (paused.file().map(Path::to_path_buf), paused.line());
+        *self.hits.entry(key).or_insert(0) += 1;
+        None
+    }
+
+    fn follows_calls(&self) -> bool {
+        true
+    }
+
+    fn called(&mut self, name: &str, file: Option<&Path>) {
+        let now = self.now();
+        self.charge(now);
+        let key = (name.to_string(), file.map(Path::to_path_buf));
+        let procedure = match self.by_name.get(&key) {
+            Some(&index) => index,
+            None => {
+                self.procedures.push(Procedure {
+                    name: name.to_string(),
+                    file: key.1.clone(),
+                    calls: 0,
+                    inclusive: 0,
+                    exclusive: 0,
+                });
+                self.by_name.insert(key, self.procedures.len() - 1);
+                self.procedures.len() - 1
+            }
+        };
+        self.procedures[procedure].calls += 1;
+        let path = match self.calls.last() {
+            Some(caller) => format!("{};{}", self.stacks[caller.stack].0, name),
+            None => name.to_string(),
+        };
+        let stack = match self.by_path.get(&path) {
+            Some(&index) => index,
+            None => {
+                self.stacks.push((path.clone(), 0));
+                self.by_path.insert(path, self.stacks.len() - 1);
+                self.stacks.len() - 1
+            }
+        };
+        self.calls.push(Call {
+            procedure,
+            started: now,
+            stack,
+        });
+    }
+
+    fn left(&mut self) {
+        let now = self.now();
+        self.charge(now);
+        let Some(call) = self.calls.pop() else {
+            return;
+        };
+        // Only the outermost of a recursion's calls counts towards inclusive
+        // time; the ones inside it are already part of that.
+        if !self.calls.iter().any(|c| c.procedure == call.procedure) {
+            self.procedures[call.procedure].inclusive += now - call.started;
+        }
+    }
+
+    fn ended(&mut self) {
+        // Whatever is still open -- the program itself, and any call an error or
+        // EXIT cut short -- ends here.
+        while !self.calls.is_empty() {
+            self.left();
+        }
+        let _ = self.report();
+    }
+}
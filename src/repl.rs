@@ -0,0 +1,203 @@
+//! `fpli repl`: an interactive session.
+//!
+//! Every input runs in one [`Session`], so what one line declares the next can
+//! use. An input is read until it is complete -- its braces, brackets and
+//! parentheses balanced and its last statement finished -- which is what lets a
+//! PROCEDURE or an IF be typed over several lines the way it is written in a
+//! file. An error is reported and the session carries on with its state intact.
+
+use crate::interpreter::{Session, SessionOutcome};
+use crate::lexer::{Lexer, Token};
+use crate::parser;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+const PROMPT: &str = "psl> ";
+const CONTINUATION_PROMPT: &str = "...> ";
+
+/// How many of the most recent history lines `:history` shows.
+const HISTORY_SHOWN: usize = 50;
+
+const HELP: &str = "\
+Type PseudoLang statements to run them. A block may span several lines; the
+input runs once its braces are closed. A blank line runs an input that is still
+waiting for more, so its error can be seen. An expression shows its value.
+
+Commands:
+    :vars       List the variables defined so far
+    :procs      List the procedures declared so far
+    :history    Show recent input
+    :reset      Throw away an input that is still waiting for more
+    :help       Show this message
+    :quit       Leave (so does end of input, Ctrl-D)
+:quit and :reset also work part-way through an input.";
+
+/// Run the REPL until end of input, `:quit` or EXIT. Returns the exit status.
+pub fn run() -> i32 {
+    let interactive = io::stdin().is_terminal();
+    let mut session = Session::new(&[]);
+    let mut history = History::open();
+    if interactive {
+        println!(
+            "PseudoLang {} -- type :help for commands, :quit to leave",
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    let mut pending = String::new();
+    loop {
+        if interactive {
+            print!(
+                "{}",
+                if pending.is_empty() {
+                    PROMPT
+                } else {
+                    CONTINUATION_PROMPT
+                }
+            );
+            let _ = io::stdout().flush();
+        }
+        // Read through `Stdin` itself, never a private buffer of our own: INPUT in
+        // the program shares the same stream, and must see the lines after this one.
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                if interactive {
+                    println!();
+                }
+                break;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Error: failed to read input: {}", err);
+                return 1;
+            }
+        }
+
+        let trimmed = line.trim();
+        match trimmed.strip_prefix(':') {
+            // Leaving and starting over work in the middle of an input too,
+            // throwing away what was typed of it: with a bracket left open they
+            // are the only ways out short of Ctrl-C.
+            Some("quit" | "exit" | "q") => break,
+            Some("reset") => {
+                pending.clear();
+                continue;
+            }
+            Some(command) if pending.is_empty() => {
+                run_command(command, &session, &history);
+                continue;
+            }
+            _ => {}
+        }
+        if pending.is_empty() && trimmed.is_empty() {
+            continue;
+        }
+
+        let blank = line.trim().is_empty();
+        pending.push_str(&line);
+        if needs_more(&pending, blank) {
+            continue;
+        }
+        let input = std::mem::take(&mut pending);
+        history.record(&input);
+        match session.run(&input) {
+            Ok(SessionOutcome::Done(Some(value))) => println!("{}", value),
+            Ok(SessionOutcome::Done(None)) => {}
+            Ok(SessionOutcome::Exit(code)) => return code,
+            Err(error) => eprintln!("Error: {}", error),
+        }
+    }
+    0
+}
+
+/// Run a `:command` other than `:quit` and `:reset`, which `run` handles itself.
+fn run_command(command: &str, session: &Session, history: &History) {
+    match command {
+        "vars" => print_names(&session.variable_names(), "No variables defined"),
+        "procs" => print_names(&session.procedure_names(), "No procedures declared"),
+        "history" => {
+            let lines = history.lines();
+            for line in &lines[lines.len().saturating_sub(HISTORY_SHOWN)..] {
+                println!("{}", line);
+            }
+        }
+        "help" => println!("{}", HELP),
+        other => eprintln!("Unknown command :{}. Type :help for the list.", other),
+    }
+}
+
+fn print_names(names: &[String], none: &str) {
+    if names.is_empty() {
+        println!("{}", none);
+    } else {
+        println!("{}", names.join(", "));
+    }
+}
+
+/// Whether `source` is the start of an input rather than the whole of one.
+///
+/// A blank line just typed submits the input as it stands, so its error can be
+/// seen, whatever is still open in it. Otherwise an input with a block still
+/// open needs more, and so does one the parser ran out of rather than meeting a
+/// wrong token in -- `x <-`, or a TRY whose CATCH is on the next line.
+fn needs_more(source: &str, blank_line: bool) -> bool {
+    if blank_line {
+        return false;
+    }
+    let tokens = Lexer::new(source).tokenize();
+    let depth: i64 = tokens
+        .iter()
+        .map(|(token, _)| match token {
+            Token::OpenBrace | Token::OpenBracket | Token::OpenParen => 1,
+            Token::CloseBrace | Token::CloseBracket | Token::CloseParen => -1,
+            _ => 0,
+        })
+        .sum();
+    if depth > 0 {
+        return true;
+    }
+    match parser::parse(tokens, false) {
+        Ok(_) => false,
+        // The parser reports the end of input with an empty span at the start,
+        // which no real token has.
+        Err(error) => error.span.is_none_or(|span| span.end == 0),
+    }
+}
+
+/// Input history, kept one line per line typed in a file under the user's data
+/// directory, so it survives from one session to the next.
+struct History {
+    path: Option<PathBuf>,
+}
+
+impl History {
+    fn open() -> Self {
+        let path = dirs::data_dir().map(|dir| dir.join("pseudolang").join("repl_history"));
+        History { path }
+    }
+
+    /// Append an input. History is a convenience: a data directory that cannot
+    /// be written to must not stop the session, so failures are ignored.
+    fn record(&mut self, input: &str) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = file.write_all(input.trim_end().as_bytes());
+            let _ = file.write_all(b"\n");
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+}
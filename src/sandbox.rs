@@ -0,0 +1,208 @@
+//! `fpli run --deny-all` and the `--allow-*` flags: what a program may reach
+//! beyond its own variables.
+//!
+//! A run starts with every permission, as it always has. Asking for a sandbox
+//! -- `--deny-all`, or any `--allow-*` flag -- starts it with none, and each
+//! flag then grants one back:
+//!
+//! - `--allow-read`: reading files and directories, IMPORT included;
+//! - `--allow-write`: creating, changing, moving and deleting them;
+//! - `--allow-exec`: EXEC and SHELL, and KILL, PROCESSES, PROCESSINFO and
+//!   WHICH, which look at or act on other processes;
+//! - `--allow-env`: reading and changing environment variables.
+//!
+//! Reading and writing can be limited to some directories, `--allow-read=data`,
+//! and the environment to some variables, `--allow-env=HOME,LANG`. A path is
+//! allowed if it is one of the directories or anywhere inside one. Both sides
+//! are compared as absolute paths with their symbolic links followed, so
+//! neither `data/../secret.txt` nor a link inside `data` pointing out of it gets
+//! any further than spelling `secret.txt` would.
+//!
+//! Every builtin that reaches the filesystem goes through `fs_guard` in the
+//! interpreter, and the ones that reach processes and the environment through
+//! `permit`: both ask [`Permissions::check`]. A refusal is an ordinary runtime
+//! error, which names the permission that was missing and which a program can
+//! catch like any other.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Some of a kind of thing, or all of it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Grant<T> {
+    All,
+    /// Only these; nothing at all when empty.
+    Only(Vec<T>),
+}
+
+impl Grant<PathBuf> {
+    /// The directories and files spelled `paths`, and everything inside them.
+    /// Relative paths are taken from the working directory now, so a program
+    /// that changes directory does not move what it was granted.
+    pub fn paths<P: AsRef<Path>>(paths: &[P]) -> Self {
+        Grant::Only(paths.iter().map(|path| resolve(path.as_ref())).collect())
+    }
+
+    fn allows(&self, path: &Path) -> bool {
+        match self {
+            Grant::All => true,
+            Grant::Only(roots) => {
+                let path = resolve(path);
+                roots.iter().any(|root| path.starts_with(root))
+            }
+        }
+    }
+}
+
+impl Grant<String> {
+    fn allows(&self, name: &str) -> bool {
+        match self {
+            Grant::All => true,
+            Grant::Only(names) => names.iter().any(|allowed| allowed == name),
+        }
+    }
+}
+
+/// What a run is allowed to do outside the program itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Permissions {
+    pub read: Grant<PathBuf>,
+    pub write: Grant<PathBuf>,
+    /// Running other programs, and looking at or signalling processes.
+    pub exec: bool,
+    /// Environment variables, by name, for reading and changing alike.
+    pub env: Grant<String>,
+}
+
+impl Default for Permissions {
+    /// Everything: a run that did not ask for a sandbox.
+    fn default() -> Self {
+        Permissions {
+            read: Grant::All,
+            write: Grant::All,
+            exec: true,
+            env: Grant::All,
+        }
+    }
+}
+
+/// What a builtin is about to do, for [`Permissions::check`]. A path is as the
+/// program spelled it, which is how a refusal reports it.
+#[derive(Clone, Debug)]
+pub enum Need {
+    Read(String),
+    Write(String),
+    Exec,
+    Env(String),
+    /// Every environment variable at once: ENVVARS.
+    EveryEnv,
+}
+
+impl Permissions {
+    /// Nothing: `--deny-all`, before any `--allow-*` flag grants something back.
+    pub fn none() -> Self {
+        Permissions {
+            read: Grant::Only(Vec::new()),
+            write: Grant::Only(Vec::new()),
+            exec: false,
+            env: Grant::Only(Vec::new()),
+        }
+    }
+
+    /// Whether the builtin `name` may do what it `need`s, or else the message of
+    /// the error refusing it.
+    pub fn check(&self, name: &str, need: Need) -> Result<(), String> {
+        let (allowed, flag, what) = match need {
+            Need::Read(path) => (
+                self.read.allows(Path::new(&path)),
+                "--allow-read",
+                format!(" to read '{}'", path),
+            ),
+            Need::Write(path) => (
+                self.write.allows(Path::new(&path)),
+                "--allow-write",
+                format!(" to change '{}'", path),
+            ),
+            Need::Exec => (self.exec, "--allow-exec", String::new()),
+            Need::Env(variable) => (
+                self.env.allows(&variable),
+                "--allow-env",
+                format!(" for '{}'", variable),
+            ),
+            Need::EveryEnv => (
+                self.env == Grant::All,
+                "--allow-env",
+                " for every variable".to_string(),
+            ),
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!(
+                "Permission denied: {} needs {}{}",
+                name, flag, what
+            ))
+        }
+    }
+}
+
+/// How many symbolic links [`resolve`] follows in one path before giving up on
+/// it, as the system itself does for a loop of links.
+const MAX_LINKS: usize = 40;
+
+/// `path` made absolute, with its symbolic links followed and its `.` and `..`
+/// worked out, so that two spellings of one place compare equal.
+///
+/// The path is walked one part at a time, and every part that is a link is
+/// replaced by where it points -- a link whose target does not exist yet
+/// included, since writing through it creates that target. Parts that do not
+/// exist are taken as spelled. A path with more than [`MAX_LINKS`] links in it,
+/// or a link that cannot be read, resolves to the empty path, which is inside
+/// no grant.
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = match std::env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+    // The parts still to walk, the next one last.
+    let mut pending: Vec<PathBuf> = parts(&absolute);
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        match part.components().next() {
+            None | Some(Component::CurDir) => {}
+            Some(Component::ParentDir) => {
+                resolved.pop();
+            }
+            Some(Component::Normal(name)) => {
+                let candidate = resolved.join(name);
+                let is_link = std::fs::symlink_metadata(&candidate)
+                    .is_ok_and(|meta| meta.file_type().is_symlink());
+                if !is_link {
+                    resolved = candidate;
+                    continue;
+                }
+                links += 1;
+                let target = match std::fs::read_link(&candidate) {
+                    Ok(target) if links <= MAX_LINKS => target,
+                    _ => return PathBuf::new(),
+                };
+                // A relative target is taken from the directory holding the
+                // link, which is `resolved` as it stands.
+                if target.is_absolute() {
+                    resolved = PathBuf::new();
+                }
+                pending.extend(parts(&target));
+            }
+            Some(root) => resolved.push(root),
+        }
+    }
+    resolved
+}
+
+/// The parts of `path`, last first, as [`resolve`] walks them.
+fn parts(path: &Path) -> Vec<PathBuf> {
+    path.components()
+        .rev()
+        .map(|part| PathBuf::from(part.as_os_str()))
+        .collect()
+}
@@ -0,0 +1,212 @@
+//! `fpli test`: run a project's tests and report which failed.
+//!
+//! A test file is any file named `*_test.psl`, and a test is any procedure
+//! declared at its top level whose name starts with `test_`. Each test runs in an
+//! environment of its own, made by running the file's top level afresh and then
+//! calling the procedure, so tests cannot lean on each other's leftovers and pass
+//! or fail the same whichever of them run. A test passes when it returns; it fails
+//! when an ASSERT does not hold or anything else goes wrong on the way. What a
+//! test prints is held back and shown only when it fails, next to the error.
+//!
+//! With `--coverage FILE` every test runs under one [`Coverage`], and FILE gets
+//! an LCOV report of the lines the tests reached between them.
+
+use crate::coverage::Coverage;
+use crate::error::{ErrorKind, Span};
+use crate::interpreter::{self, Observer, TestOutcome};
+use crate::lexer::Lexer;
+use crate::limits::Limits;
+use crate::parser::{self, AstNode, Spanned};
+use crate::sandbox::Permissions;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The tests `program` declares, in the order it declares them, with where each
+/// is declared.
+pub fn test_procedures(program: &Spanned) -> Vec<(String, Span)> {
+    let AstNode::Program(statements) = &program.node else {
+        return Vec::new();
+    };
+    statements
+        .iter()
+        .filter_map(|statement| match &statement.node {
+            AstNode::ProcedureDecl(name, _, _) if name.starts_with("test_") => {
+                Some((name.clone(), statement.span))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The test files `paths` name: files as given, and every `*_test.psl` under a
+/// directory, in name order. Hidden directories such as `.git` are not searched.
+pub fn discover(paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            collect(&path, &mut files)?;
+        } else if path.extension().is_some_and(|e| e == "psl") {
+            files.push(path);
+        } else if path.exists() {
+            return Err(format!(
+                "Input file must have .psl extension, got: {}",
+                path.display()
+            ));
+        } else {
+            return Err(format!("No such file or directory: {}", path.display()));
+        }
+    }
+    Ok(files)
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Could not read directory {}: {}", dir.display(), e))?;
+    let mut entries: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if path.is_dir() {
+            if !name.starts_with('.') {
+                collect(&path, files)?;
+            }
+        } else if name.ends_with("_test.psl") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// A file as the report names it: `./maths_test.psl`, found by searching the
+/// current directory, reads better without its `./`.
+fn shown(path: &Path) -> String {
+    path.strip_prefix(".").unwrap_or(path).display().to_string()
+}
+
+/// Run the tests in `paths`, or under the current directory when none are
+/// given, print a report, and return the exit status: 0 when every test passed,
+/// 1 otherwise. With `coverage`, also write an LCOV report of the lines the
+/// tests ran to that file. Every test is allowed what `permissions` grant, and
+/// as much as `limits` allow.
+pub fn run(
+    paths: &[String],
+    coverage: Option<&str>,
+    permissions: &Permissions,
+    limits: &Limits,
+) -> i32 {
+    let searched = if paths.is_empty() {
+        vec![".".to_string()]
+    } else {
+        paths.to_vec()
+    };
+    let files = match discover(&searched) {
+        Ok(files) if files.is_empty() => {
+            eprintln!("Error: No test files found. A test file's name ends in _test.psl");
+            return 1;
+        }
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            return 1;
+        }
+    };
+
+    let coverage = match coverage {
+        Some(path) => match std::fs::File::create(path) {
+            Ok(file) => Some(Rc::new(RefCell::new(Coverage::new(Box::new(
+                std::io::BufWriter::new(file),
+            ))))),
+            Err(e) => {
+                eprintln!("Error: Error creating coverage file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => None,
+    };
+
+    let mut passed = 0;
+    let mut failed = 0;
+    // Each failure's name and what to show for it, printed once every test has run.
+    let mut failures: Vec<(String, String)> = Vec::new();
+    for file in &files {
+        let name = shown(file);
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                println!("test {} ... FAILED", name);
+                failed += 1;
+                failures.push((name.clone(), format!("Could not read {}: {}", name, e)));
+                continue;
+            }
+        };
+        let ast = match parser::parse_with_source(Lexer::new(&source).tokenize(), &source, false) {
+            Ok(ast) => ast,
+            Err(error) => {
+                println!("test {} ... FAILED", name);
+                failed += 1;
+                failures.push((name.clone(), error.format(&source)));
+                continue;
+            }
+        };
+        for (test, span) in test_procedures(&ast) {
+            let id = format!("{}::{}", name, test);
+            let observer = coverage
+                .as_ref()
+                .map(|coverage| Rc::clone(coverage) as Rc<RefCell<dyn Observer>>);
+            let TestOutcome { output, error } = interpreter::run_test(
+                &ast,
+                &source,
+                file,
+                &test,
+                span,
+                observer,
+                permissions,
+                limits,
+            );
+            match error {
+                None => {
+                    println!("test {} ... ok", id);
+                    passed += 1;
+                }
+                Some(error) => {
+                    // A failed assertion is the test doing its job; anything else
+                    // means it never got to check what it meant to.
+                    let verdict = if error.kind == ErrorKind::Assertion {
+                        "FAILED"
+                    } else {
+                        "ERROR"
+                    };
+                    println!("test {} ... {}", id, verdict);
+                    failed += 1;
+                    let mut report = output;
+                    if !report.is_empty() && !report.ends_with('\n') {
+                        report.push('\n');
+                    }
+                    report.push_str(&error.format(&source));
+                    failures.push((id, report));
+                }
+            }
+        }
+    }
+
+    if let Some(coverage) = coverage {
+        coverage.borrow_mut().ended();
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (id, report) in &failures {
+            println!("\n---- {} ----\n{}", id, report);
+        }
+    }
+    let verdict = if failed == 0 { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        verdict, passed, failed
+    );
+    i32::from(failed > 0)
+}
@@ -0,0 +1,219 @@
+//! ASSERT, ASSERTEQUAL and ASSERTRAISES, and the per-test runs `fpli test` makes
+//! of a test file.
+
+use super::{Scratch, assert_output, get_error};
+use crate::error::ErrorKind;
+use crate::interpreter::run_test;
+use crate::lexer::Lexer;
+use crate::limits::Limits;
+use crate::parser;
+use crate::sandbox::Permissions;
+use crate::testing::{discover, test_procedures};
+
+#[test]
+fn test_assert_passes_silently() {
+    assert_output(
+        "ASSERT(1 < 2)\nASSERT(TRUE, \"never shown\")\nDISPLAY(\"on\")",
+        "on",
+    );
+}
+
+#[test]
+fn test_a_failed_assert_names_the_line_and_the_message() {
+    let error = get_error("x <- 3\nASSERT(x < 2, \"x is small\")");
+    assert!(error.contains("Line 2"), "got: {error}");
+    assert!(
+        error.contains("Assertion failed: x is small: the condition was FALSE"),
+        "got: {error}"
+    );
+}
+
+#[test]
+fn test_assert_needs_a_boolean() {
+    let error = get_error("ASSERT(1)");
+    assert!(
+        error.contains("ASSERT requires a boolean condition, got integer"),
+        "got: {error}"
+    );
+}
+
+#[test]
+fn test_assertequal_compares_deeply() {
+    assert_output(
+        "ASSERTEQUAL([1, {\"a\": 2.0}], [1, {\"a\": 2}])\nDISPLAY(\"same\")",
+        "same",
+    );
+}
+
+#[test]
+fn test_assertequal_shows_both_values_and_quotes_strings() {
+    let error = get_error("ASSERTEQUAL(1 + 1, \"2\")");
+    assert!(
+        error.contains("Assertion failed: expected \"2\", got 2"),
+        "got: {error}"
+    );
+    let error = get_error("ASSERTEQUAL([1, 2], [2, 1], \"order\")");
+    assert!(
+        error.contains("Assertion failed: order: expected [2, 1], got [1, 2]"),
+        "got: {error}"
+    );
+}
+
+#[test]
+fn test_a_failed_assertion_can_be_caught_and_has_its_own_kind() {
+    assert_output(
+        r#"
+TRY {
+    ASSERTEQUAL(1, 2)
+} CATCH (e) {
+    DISPLAY(e["kind"])
+    DISPLAY(e["message"])
+}
+"#,
+        "assertion\nAssertion failed: expected 2, got 1",
+    );
+}
+
+#[test]
+fn test_assertraises_hands_back_the_error() {
+    assert_output(
+        r#"
+e <- ASSERTRAISES(1 / 0)
+DISPLAY(e["message"])
+DISPLAY(e["kind"])
+t <- ASSERTRAISES(PROCEDURE () { THROW {"code": 7} })
+DISPLAY(t["value"]["code"])
+"#,
+        "Division by zero\nruntime\n7",
+    );
+}
+
+#[test]
+fn test_assertraises_fails_when_nothing_goes_wrong() {
+    let error = get_error("ASSERTRAISES(1 + 1)");
+    assert!(
+        error.contains("Assertion failed: expected an error, but none was raised"),
+        "got: {error}"
+    );
+}
+
+#[test]
+fn test_assertraises_checks_the_message_when_asked() {
+    assert_output("ASSERTRAISES(1 / 0, \"zero\")\nDISPLAY(\"ok\")", "ok");
+    let error = get_error("ASSERTRAISES(1 / 0, \"overflow\")");
+    assert!(
+        error.contains("expected an error containing \"overflow\", got \"Division by zero\""),
+        "got: {error}"
+    );
+}
+
+#[test]
+fn test_assertraises_lets_exit_through() {
+    assert_output("ASSERTRAISES(EXIT(0))\nDISPLAY(\"not reached\")", "");
+}
+
+/// A test's name, the kind and message of what stopped it, and what it printed.
+type Outcome = (String, Option<(ErrorKind, String)>, String);
+
+/// Every test in `source`, run as `fpli test` runs it.
+fn outcomes(source: &str) -> Vec<Outcome> {
+    let scratch = Scratch::new();
+    let path = scratch.write("suite_test.psl", source);
+    let ast = parser::parse_with_source(Lexer::new(source).tokenize(), source, false)
+        .expect("the test file parses");
+    test_procedures(&ast)
+        .into_iter()
+        .map(|(name, span)| {
+            let outcome = run_test(
+                &ast,
+                source,
+                &path,
+                &name,
+                span,
+                None,
+                &Permissions::default(),
+                &Limits::default(),
+            );
+            let error = outcome.error.map(|e| (e.kind, e.message));
+            (name, error, outcome.output)
+        })
+        .collect()
+}
+
+#[test]
+fn test_only_procedures_named_test_are_tests() {
+    let source = "PROCEDURE helper() { }\nPROCEDURE test_one() { }\nPROCEDURE test_two() { }\n";
+    let names: Vec<String> = outcomes(source).into_iter().map(|o| o.0).collect();
+    assert_eq!(names, ["test_one", "test_two"]);
+}
+
+#[test]
+fn test_every_test_starts_from_a_fresh_environment() {
+    let source = r#"
+count <- 0
+PROCEDURE test_first() {
+    count <- count + 1
+    ASSERTEQUAL(count, 1)
+}
+PROCEDURE test_second() {
+    count <- count + 1
+    ASSERTEQUAL(count, 1)
+}
+"#;
+    for (name, error, _) in outcomes(source) {
+        assert!(error.is_none(), "{name} failed: {error:?}");
+    }
+}
+
+#[test]
+fn test_an_outcome_tells_failures_from_errors_and_keeps_the_output() {
+    let source = r#"
+DISPLAY("setup")
+PROCEDURE test_fails() {
+    DISPLAY("checking")
+    ASSERT(FALSE)
+}
+PROCEDURE test_breaks() {
+    x <- [1][5]
+}
+PROCEDURE test_passes() {
+    ASSERT(TRUE)
+}
+"#;
+    let outcomes = outcomes(source);
+    let (_, error, output) = &outcomes[0];
+    assert_eq!(error.as_ref().map(|e| e.0), Some(ErrorKind::Assertion));
+    assert_eq!(output, "setup\nchecking\n");
+    let (_, error, _) = &outcomes[1];
+    assert_eq!(error.as_ref().map(|e| e.0), Some(ErrorKind::Runtime));
+    assert!(outcomes[2].1.is_none());
+}
+
+#[test]
+fn test_exit_ends_a_test_by_its_status() {
+    let source = "PROCEDURE test_zero() { EXIT(0) }\nPROCEDURE test_one() { EXIT(1) }\n";
+    let outcomes = outcomes(source);
+    assert!(outcomes[0].1.is_none());
+    let (_, error, _) = &outcomes[1];
+    assert!(
+        error.as_ref().is_some_and(|e| e.1.contains("EXIT(1)")),
+        "got: {error:?}"
+    );
+}
+
+#[test]
+fn test_discovery_finds_test_files_in_name_order() {
+    let scratch = Scratch::new();
+    scratch.write("b_test.psl", "");
+    scratch.write("a_test.psl", "");
+    scratch.write("helpers.psl", "");
+    scratch.write("nested/c_test.psl", "");
+    scratch.write(".hidden/d_test.psl", "");
+    let root = scratch.path("").display().to_string();
+    let found: Vec<String> = discover(&[root])
+        .expect("discovery succeeds")
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(found, ["a_test.psl", "b_test.psl", "c_test.psl"]);
+}
@@ -0,0 +1,136 @@
+//! `fpli check`: every syntax error in a file and its imports, found without
+//! running anything.
+
+use super::Scratch;
+use crate::check::check_files;
+use crate::lexer::Lexer;
+use crate::parser::parse_all;
+
+/// The messages `parse_all` reports for `source`, with the line each is on.
+fn errors_in(source: &str) -> Vec<(usize, String)> {
+    parse_all(Lexer::new(source).tokenize(), false)
+        .errors
+        .into_iter()
+        .map(|error| {
+            let span = error.span.expect("a syntax error has a span");
+            let line = source[..span.start].matches('\n').count() + 1;
+            (line, error.message)
+        })
+        .collect()
+}
+
+fn lines_of(errors: &[(usize, String)]) -> Vec<usize> {
+    errors.iter().map(|(line, _)| *line).collect()
+}
+
+#[test]
+fn test_a_well_formed_file_has_no_errors() {
+    let errors = errors_in(
+        r#"
+PROCEDURE f(a, b <- 2) {
+    RETURN a + b
+}
+IF f(1) > 2 {
+    DISPLAY("big")
+}
+"#,
+    );
+    assert!(errors.is_empty(), "unexpected errors: {errors:?}");
+}
+
+#[test]
+fn test_every_bad_statement_is_reported_not_only_the_first() {
+    let errors = errors_in("x <- 1\ny <- (2\nDISPLAY(x)\nz <- 3 *\n\nDISPLAY(\"ok\"\n");
+    assert_eq!(errors.len(), 3, "errors: {errors:?}");
+    assert_eq!(errors[0], (2, "Expected ')' after expression".to_string()));
+}
+
+#[test]
+fn test_errors_inside_separate_blocks_are_each_reported() {
+    let errors = errors_in(
+        r#"PROCEDURE first() {
+    a <- )
+    DISPLAY("fine")
+}
+PROCEDURE second() {
+    b <- ]
+}
+"#,
+    );
+    assert_eq!(lines_of(&errors), vec![2, 6], "errors: {errors:?}");
+}
+
+#[test]
+fn test_a_broken_header_skips_its_whole_block() {
+    // The body would parse on its own; reading it as top-level statements would
+    // report a stray '}' as a second error.
+    let errors = errors_in("PROCEDURE f(a b) {\n    DISPLAY(a)\n}\nDISPLAY(1)\n");
+    assert_eq!(errors.len(), 1, "errors: {errors:?}");
+    assert!(errors[0].1.contains("Expected comma between parameters"));
+}
+
+#[test]
+fn test_a_stray_closing_brace_is_reported_and_passed() {
+    let errors = errors_in("DISPLAY(1)\n}\nx <- (\n");
+    assert_eq!(errors.len(), 2, "errors: {errors:?}");
+    assert!(errors[0].1.contains("Unexpected '}'"), "errors: {errors:?}");
+}
+
+#[test]
+fn test_check_follows_imports() {
+    let scratch = Scratch::new();
+    let main = scratch.write("main.psl", "IMPORT \"lib/util.psl\"\nDISPLAY(greet())\n");
+    scratch.write("lib/util.psl", "PROCEDURE greet() {\n    RETURN (\n}\n");
+    let report = check_files(&[main], false);
+    assert_eq!(report.files.len(), 2);
+    assert_eq!(report.errors.len(), 1);
+    let rendered = report.errors[0].format("");
+    assert!(rendered.contains("util.psl: Line 3"), "got: {rendered}");
+}
+
+#[test]
+fn test_a_missing_import_is_reported_at_the_import() {
+    let scratch = Scratch::new();
+    let main = scratch.write("main.psl", "DISPLAY(1)\nIMPORT \"nowhere.psl\"\n");
+    let report = check_files(&[main], false);
+    assert_eq!(report.errors.len(), 1);
+    let rendered = report.errors[0].format("");
+    assert!(
+        rendered.contains("main.psl: Line 2")
+            && rendered.contains("Could not find imported file 'nowhere.psl'"),
+        "got: {rendered}"
+    );
+}
+
+#[test]
+fn test_an_import_cycle_checks_each_file_once() {
+    let scratch = Scratch::new();
+    let a = scratch.write("a.psl", "IMPORT \"b.psl\"\n");
+    scratch.write("b.psl", "IMPORT \"a.psl\"\nIMPORT \"b.psl\"\n");
+    let report = check_files(&[a], false);
+    assert_eq!(report.files.len(), 2);
+    assert!(report.errors.is_empty());
+}
+
+#[test]
+fn test_check_runs_nothing() {
+    let scratch = Scratch::new();
+    let marker = scratch.psl_path("ran.txt");
+    let main = scratch.write(
+        "main.psl",
+        &format!("WRITEFILE(\"{marker}\", \"yes\")\nEXIT(3)\n"),
+    );
+    let report = check_files(&[main], false);
+    assert!(report.errors.is_empty());
+    assert!(!scratch.path("ran.txt").exists());
+}
+
+#[test]
+fn test_an_unclosed_block_is_reported_where_the_file_ends() {
+    let scratch = Scratch::new();
+    let main = scratch.write("main.psl", "DISPLAY(1)\nIF TRUE {\n    DISPLAY(2)\n");
+    let report = check_files(&[main], false);
+    assert_eq!(report.errors.len(), 1);
+    let rendered = report.errors[0].format("");
+    assert!(rendered.contains("Line 3"), "got: {rendered}");
+}
@@ -0,0 +1,217 @@
+//! CLASS tests: construction, fields, methods and `self`.
+//!
+//! Objects are the one value PseudoLang shares instead of copying, so besides
+//! the mechanics these tests pin down that sharing -- a method that changes
+//! `self` changes the caller's object, and so does assigning through a second
+//! name -- and that every other value keeps its copy semantics around them.
+
+use super::{assert_output, get_error};
+
+const POINT: &str = r#"
+CLASS Point {
+    PROCEDURE Point(x, y) {
+        self.x <- x
+        self.y <- y
+    }
+    PROCEDURE norm2() {
+        RETURN self.x * self.x + self.y * self.y
+    }
+    PROCEDURE moveBy(dx, dy) {
+        self.x <- self.x + dx
+        self.y <- self.y + dy
+    }
+}
+"#;
+
+fn with_point(program: &str) -> String {
+    format!("{}{}", POINT, program)
+}
+
+#[test]
+fn test_constructor_sets_fields() {
+    assert_output(
+        &with_point("p <- Point(3, 4)\nDISPLAY(p.x)\nDISPLAY(p.y)"),
+        "3\n4",
+    );
+}
+
+#[test]
+fn test_method_call_sees_self() {
+    assert_output(&with_point("p <- Point(3, 4)\nDISPLAY(p.norm2())"), "25");
+}
+
+#[test]
+fn test_method_mutates_receiver() {
+    assert_output(
+        &with_point("p <- Point(1, 1)\np.moveBy(2, 3)\nDISPLAY(p.x)\nDISPLAY(p.y)"),
+        "3\n4",
+    );
+}
+
+#[test]
+fn test_field_assignment_creates_and_overwrites() {
+    assert_output(
+        &with_point("p <- Point(1, 2)\np.x <- 10\np.label <- \"a\"\nDISPLAY(p)"),
+        "Point{x: 10, y: 2, label: a}",
+    );
+}
+
+#[test]
+fn test_typeof_reports_class_name() {
+    assert_output(&with_point("DISPLAY(TYPEOF(Point(0, 0)))"), "Point");
+}
+
+#[test]
+fn test_objects_are_shared_not_copied() {
+    // The deliberate exception to copy-on-assign: both names reach one object.
+    assert_output(
+        &with_point("p <- Point(1, 2)\nq <- p\nq.x <- 99\nDISPLAY(p.x)"),
+        "99",
+    );
+    assert_output(
+        &with_point(
+            "PROCEDURE reset(pt) {\npt.x <- 0\n}\np <- Point(5, 5)\nreset(p)\nDISPLAY(p.x)",
+        ),
+        "0",
+    );
+}
+
+#[test]
+fn test_list_in_object_field_still_copied_out() {
+    // Reading a list field hands back a copy, like reading any other list.
+    assert_output(
+        r#"
+CLASS Bag {
+    PROCEDURE Bag() {
+        self.items <- [1]
+    }
+}
+b <- Bag()
+copy <- b.items
+APPEND(copy, 2)
+DISPLAY(b.items)
+DISPLAY(copy)"#,
+        "[1]\n[1, 2]",
+    );
+}
+
+#[test]
+fn test_in_place_operations_on_fields() {
+    assert_output(
+        r#"
+CLASS Stack {
+    PROCEDURE Stack() {
+        self.items <- []
+    }
+    PROCEDURE push(x) {
+        APPEND(self.items, x)
+    }
+    PROCEDURE pop() {
+        RETURN REMOVE(self.items, LENGTH(self.items))
+    }
+}
+s <- Stack()
+s.push(1)
+s.push(2)
+s.push(3)
+DISPLAY(s.pop())
+INSERT(s.items, 1, 0)
+s.items[2] <- 10
+DISPLAY(s.items)"#,
+        "3\n[0, 10, 2]",
+    );
+}
+
+#[test]
+fn test_class_without_constructor() {
+    assert_output(
+        "CLASS Box {\nPROCEDURE get() {\nRETURN self.value\n}\n}\nb <- Box()\nb.value <- 7\nDISPLAY(b.get())",
+        "7",
+    );
+    let err = get_error("CLASS Box {\nPROCEDURE get() {\nRETURN 1\n}\n}\nb <- Box(1)");
+    assert!(err.contains("CLASS Box has no constructor"), "{}", err);
+}
+
+#[test]
+fn test_object_equality_is_identity() {
+    assert_output(
+        &with_point("p <- Point(1, 2)\nq <- p\nDISPLAY(p = q)\nDISPLAY(p = Point(1, 2))"),
+        "true\nfalse",
+    );
+}
+
+#[test]
+fn test_methods_are_scoped_to_their_class() {
+    // Two classes may share a method name, and neither method is a bare procedure.
+    let program = r#"
+CLASS Square {
+    PROCEDURE Square(s) {
+        self.s <- s
+    }
+    PROCEDURE area() {
+        RETURN self.s * self.s
+    }
+}
+CLASS Rect {
+    PROCEDURE Rect(w, h) {
+        self.w <- w
+        self.h <- h
+    }
+    PROCEDURE area() {
+        RETURN self.w * self.h
+    }
+}
+shapes <- [Square(2), Rect(2, 3)]
+FOR EACH shape IN shapes {
+    DISPLAY(shape.area())
+}"#;
+    assert_output(program, "4\n6");
+    let err = get_error(&format!("{}\narea()", program));
+    assert!(err.contains("Procedure 'area' not found"), "{}", err);
+}
+
+#[test]
+fn test_call_instantiates_class() {
+    assert_output(
+        &with_point("p <- CALL(\"Point\", [6, 8])\nDISPLAY(p.norm2())"),
+        "100",
+    );
+}
+
+#[test]
+fn test_self_referencing_object_displays() {
+    assert_output(
+        "CLASS Node {\nPROCEDURE Node() {\nself.next <- NULL\n}\n}\nn <- Node()\nn.next <- n\nDISPLAY(n)",
+        "Node{next: Node{...}}",
+    );
+}
+
+#[test]
+fn test_missing_field_error_names_class() {
+    let err = get_error(&with_point("p <- Point(1, 2)\nDISPLAY(p.z)"));
+    assert!(err.contains("Point has no field 'z'"), "{}", err);
+}
+
+#[test]
+fn test_missing_method_error_names_class() {
+    let err = get_error(&with_point("p <- Point(1, 2)\np.scale(2)"));
+    assert!(err.contains("Point has no method 'scale'"), "{}", err);
+}
+
+#[test]
+fn test_field_access_on_non_object_error() {
+    let err = get_error("x <- 5\nDISPLAY(x.y)");
+    assert!(
+        err.contains("Cannot read field 'y': a value of type integer is not an object"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_method_frame_in_stack_trace() {
+    let err = get_error(
+        "CLASS Acc {\nPROCEDURE Acc() {\nself.n <- 0\n}\nPROCEDURE bad() {\nRETURN self.n / 0\n}\n}\na <- Acc()\na.bad()",
+    );
+    assert!(err.contains("Acc.bad"), "{}", err);
+}
@@ -235,3 +235,446 @@ fn test_foreach_on_integer_error() {
         "Expected error for FOR EACH on non-iterable"
     );
 }
+
+// ---------------------------------------------------------------------------
+// BREAK and CONTINUE
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_break_leaves_foreach() {
+    assert_output(
+        "FOR EACH x IN [1, 2, 3, 4]\n{\nIF (x = 3)\n{\nBREAK\n}\nDISPLAY(x)\n}\nDISPLAY(\"done\")",
+        "1\n2\ndone",
+    );
+}
+
+#[test]
+fn test_continue_skips_rest_of_iteration() {
+    assert_output(
+        "FOR EACH x IN [1, 2, 3, 4]\n{\nIF (x MOD 2 = 0)\n{\nCONTINUE\n}\nDISPLAY(x)\n}",
+        "1\n3",
+    );
+}
+
+#[test]
+fn test_break_and_continue_in_repeat_times() {
+    assert_output(
+        "i <- 0\nREPEAT 10 TIMES\n{\ni <- i + 1\nIF (i = 2)\n{\nCONTINUE\n}\nIF (i = 4)\n{\nBREAK\n}\nDISPLAY(i)\n}",
+        "1\n3",
+    );
+}
+
+#[test]
+fn test_continue_in_repeat_until_still_checks_condition() {
+    // Skipping the test would make this loop run forever.
+    assert_output(
+        "n <- 0\nREPEAT UNTIL (n >= 3)\n{\nn <- n + 1\nCONTINUE\n}\nDISPLAY(n)",
+        "3",
+    );
+    assert_output(
+        "n <- 0\nREPEAT UNTIL (FALSE)\n{\nn <- n + 1\nIF (n = 5)\n{\nBREAK\n}\n}\nDISPLAY(n)",
+        "5",
+    );
+}
+
+#[test]
+fn test_break_only_leaves_innermost_loop() {
+    assert_output(
+        "FOR EACH a IN [1, 2]\n{\nFOR EACH b IN [1, 2, 3]\n{\nIF (b = 2)\n{\nBREAK\n}\nDISPLAY(a * 10 + b)\n}\n}",
+        "11\n21",
+    );
+}
+
+#[test]
+fn test_break_is_not_caught_by_try() {
+    assert_output(
+        "FOR EACH x IN [1, 2]\n{\nTRY\n{\nBREAK\n} CATCH (e)\n{\nDISPLAY(\"caught\")\n}\nDISPLAY(x)\n}\nDISPLAY(\"after\")",
+        "after",
+    );
+}
+
+#[test]
+fn test_break_outside_loop_is_a_parse_error() {
+    let err = get_error("DISPLAY(1)\nBREAK");
+    assert!(
+        err.contains("BREAK can only be used inside a loop"),
+        "{}",
+        err
+    );
+    assert!(err.contains("Line 2"), "{}", err);
+    let err = get_error("IF (TRUE)\n{\nCONTINUE\n}");
+    assert!(err.contains("CONTINUE can only be used inside"), "{}", err);
+}
+
+#[test]
+fn test_break_cannot_cross_a_procedure_boundary() {
+    let err = get_error("REPEAT 3 TIMES\n{\nPROCEDURE stop()\n{\nBREAK\n}\nstop()\n}");
+    assert!(err.contains("BREAK cannot leave a PROCEDURE"), "{}", err);
+    let err = get_error("FOR EACH x IN [1]\n{\nf <- PROCEDURE () { CONTINUE }\n}");
+    assert!(err.contains("CONTINUE cannot leave a PROCEDURE"), "{}", err);
+    // A loop inside the procedure is fine.
+    assert_output(
+        "PROCEDURE first(xs)\n{\nFOR EACH x IN xs\n{\nIF (x > 1)\n{\nBREAK\n}\nDISPLAY(x)\n}\n}\nFOR EACH i IN [1]\n{\nfirst([1, 2, 3])\n}",
+        "1",
+    );
+}
+
+// ---------------------------------------------------------------------------
+// FOR ... FROM ... TO ... STEP and REPEAT WHILE
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_for_range_counts_inclusively() {
+    assert_output("FOR i FROM 1 TO 4\n{\nDISPLAY(i)\n}", "1\n2\n3\n4");
+}
+
+#[test]
+fn test_for_range_with_step() {
+    assert_output("FOR i FROM 0 TO 10 STEP 4\n{\nDISPLAY(i)\n}", "0\n4\n8");
+    assert_output("FOR i FROM 10 TO 1 STEP -4\n{\nDISPLAY(i)\n}", "10\n6\n2");
+}
+
+#[test]
+fn test_for_range_runs_zero_times_when_bounds_are_reversed() {
+    assert_output(
+        "FOR i FROM 3 TO 1\n{\nDISPLAY(i)\n}\nDISPLAY(\"done\")",
+        "done",
+    );
+}
+
+#[test]
+fn test_for_range_over_floats() {
+    assert_output(
+        "FOR x FROM 0 TO 1 STEP 0.25\n{\nDISPLAY(x)\n}",
+        "0\n0.25\n0.5\n0.75\n1",
+    );
+    // Values are computed as from + k * step, so ten steps of 0.1 still land on 1.
+    assert_output(
+        "n <- 0\nFOR x FROM 0 TO 1 STEP 0.1\n{\nn <- n + 1\n}\nDISPLAY(n)",
+        "11",
+    );
+}
+
+#[test]
+fn test_for_range_beyond_i64() {
+    assert_output(
+        "FOR i FROM 9223372036854775807 TO 9223372036854775809\n{\nDISPLAY(i)\n}",
+        "9223372036854775807\n9223372036854775808\n9223372036854775809",
+    );
+}
+
+#[test]
+fn test_for_range_variable_persists_like_foreach() {
+    assert_output(
+        "FOR i FROM 1 TO 3\n{\nlast <- i\n}\nDISPLAY(i)\nDISPLAY(last)",
+        "3\n3",
+    );
+}
+
+#[test]
+fn test_for_range_with_break_and_continue() {
+    assert_output(
+        "FOR i FROM 1 TO 100\n{\nIF (i = 2)\n{\nCONTINUE\n}\nIF (i > 3)\n{\nBREAK\n}\nDISPLAY(i)\n}",
+        "1\n3",
+    );
+}
+
+#[test]
+fn test_for_range_step_zero_error() {
+    let err = get_error("FOR i FROM 1 TO 3 STEP 0\n{\nDISPLAY(i)\n}");
+    assert!(err.contains("FOR STEP must not be zero"), "{}", err);
+}
+
+#[test]
+fn test_for_range_non_numeric_bound_error() {
+    let err = get_error("FOR i FROM 1 TO \"ten\"\n{\nDISPLAY(i)\n}");
+    assert!(
+        err.contains("FOR TO value must be a number, found string"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_repeat_while_checks_before_each_iteration() {
+    assert_output(
+        "n <- 0\nREPEAT WHILE (n < 3)\n{\nn <- n + 1\n}\nDISPLAY(n)",
+        "3",
+    );
+    assert_output(
+        "REPEAT WHILE (FALSE)\n{\nDISPLAY(\"never\")\n}\nDISPLAY(\"done\")",
+        "done",
+    );
+}
+
+#[test]
+fn test_repeat_while_non_boolean_error() {
+    let err = get_error("REPEAT WHILE (1)\n{\nDISPLAY(1)\n}");
+    assert!(
+        err.contains("REPEAT WHILE condition must evaluate to boolean"),
+        "{}",
+        err
+    );
+}
+
+// ---------------------------------------------------------------------------
+// MATCH and CASE
+// ---------------------------------------------------------------------------
+
+/// Dispatch over each input, so one program exercises every arm.
+fn classify(cases: &str, inputs: &str) -> String {
+    format!(
+        "FOR EACH v IN {inputs}\n{{\nMATCH v\n{{\n{cases}\n}}\n}}",
+        inputs = inputs,
+        cases = cases
+    )
+}
+
+#[test]
+fn test_match_literals_and_else() {
+    assert_output(
+        &classify(
+            "CASE 0 { DISPLAY(\"zero\") }\n\
+             CASE \"go\" { DISPLAY(\"string\") }\n\
+             CASE TRUE { DISPLAY(\"true\") }\n\
+             CASE NULL { DISPLAY(\"null\") }\n\
+             CASE -1.5 { DISPLAY(\"negative\") }\n\
+             ELSE { DISPLAY(\"other\") }",
+            "[0, \"go\", TRUE, NULL, -1.5, 7]",
+        ),
+        "zero\nstring\ntrue\nnull\nnegative\nother",
+    );
+}
+
+#[test]
+fn test_match_runs_only_the_first_matching_case() {
+    assert_output(
+        "MATCH 1\n{\nCASE 1 { DISPLAY(\"first\") }\nCASE 1 { DISPLAY(\"second\") }\n}",
+        "first",
+    );
+}
+
+#[test]
+fn test_match_without_a_matching_case_does_nothing() {
+    assert_output(
+        "MATCH 5\n{\nCASE 1 { DISPLAY(\"one\") }\n}\nDISPLAY(\"after\")",
+        "after",
+    );
+}
+
+#[test]
+fn test_match_literal_uses_deep_equality() {
+    assert_output(
+        &classify(
+            "CASE 2 { DISPLAY(\"two\") }\nELSE { DISPLAY(\"no\") }",
+            "[2.0, \"2\"]",
+        ),
+        "two\nno",
+    );
+}
+
+#[test]
+fn test_match_ranges() {
+    assert_output(
+        &classify(
+            "CASE -10 TO -1 { DISPLAY(\"negative\") }\n\
+             CASE 0 TO 9.5 { DISPLAY(\"small\") }\n\
+             CASE \"a\" TO \"m\" { DISPLAY(\"early\") }\n\
+             ELSE { DISPLAY(\"other\") }",
+            "[-3, 0, 9.5, 10, \"dog\", \"zebra\", [1]]",
+        ),
+        "negative\nsmall\nsmall\nother\nearly\nother\nother",
+    );
+}
+
+#[test]
+fn test_match_name_binds_in_the_current_scope() {
+    assert_output(
+        "MATCH 42\n{\nCASE n { DISPLAY(n) }\n}\nDISPLAY(n + 1)",
+        "42\n43",
+    );
+}
+
+#[test]
+fn test_match_list_patterns() {
+    assert_output(
+        &classify(
+            "CASE [] { DISPLAY(\"empty\") }\n\
+             CASE [x] { DISPLAY(\"one \" + TOSTRING(x)) }\n\
+             CASE [0, y] { DISPLAY(\"zero then \" + TOSTRING(y)) }\n\
+             CASE [first, rest...] { DISPLAY(TOSTRING(first) + \" then \" + TOSTRING(LENGTH(rest))) }\n\
+             ELSE { DISPLAY(\"not a list\") }",
+            "[[], [5], [0, 9], [1, 2, 3], 4]",
+        ),
+        "empty\none 5\nzero then 9\n1 then 2\nnot a list",
+    );
+}
+
+#[test]
+fn test_match_rest_may_be_empty() {
+    assert_output(
+        "MATCH [1]\n{\nCASE [head, tail...] { DISPLAY(tail) }\n}",
+        "[]",
+    );
+}
+
+#[test]
+fn test_match_nested_list_and_dictionary_patterns() {
+    assert_output(
+        &classify(
+            "CASE {\"cmd\": \"move\", \"to\": [x, y]} { DISPLAY(\"move \" + TOSTRING(x) + \",\" + TOSTRING(y)) }\n\
+             CASE {\"cmd\": \"say\", \"text\": text} { DISPLAY(\"say \" + text) }\n\
+             CASE {\"cmd\": other} { DISPLAY(\"unknown \" + other) }\n\
+             ELSE { DISPLAY(\"not a command\") }",
+            "[{\"cmd\": \"move\", \"to\": [1, 2], \"extra\": TRUE}, {\"cmd\": \"say\", \"text\": \"hi\"}, {\"cmd\": \"jump\"}, {\"to\": [1, 2]}, \"cmd\"]",
+        ),
+        "move 1,2\nsay hi\nunknown jump\nnot a command\nnot a command",
+    );
+}
+
+#[test]
+fn test_match_guards() {
+    assert_output(
+        &classify(
+            "CASE [a, b] IF a = b { DISPLAY(\"pair\") }\n\
+             CASE [a, b] { DISPLAY(\"unequal\") }\n\
+             CASE n IF n < 0 { DISPLAY(\"negative\") }\n\
+             CASE n { DISPLAY(\"plain\") }",
+            "[-1, [2, 2], [2, 3], 5]",
+        ),
+        "negative\npair\nunequal\nplain",
+    );
+}
+
+#[test]
+fn test_match_failed_guard_unbinds_its_names() {
+    assert_output(
+        "x <- \"outer\"\n\
+         MATCH 1\n{\n\
+         CASE x IF FALSE { DISPLAY(\"never\") }\n\
+         CASE y IF FALSE { DISPLAY(\"never\") }\n\
+         ELSE { DISPLAY(x) }\n}\n\
+         DISPLAY(ISDEFINED(\"y\"))",
+        "outer\nfalse",
+    );
+}
+
+#[test]
+fn test_match_guard_must_be_boolean() {
+    let err = get_error("MATCH 1\n{\nCASE n IF n { DISPLAY(n) }\n}");
+    assert!(
+        err.contains("A CASE guard must be a boolean, found integer"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_match_body_can_leave_a_procedure_or_loop() {
+    assert_output(
+        "PROCEDURE sign(n)\n{\nMATCH n\n{\nCASE 0 { RETURN \"zero\" }\nELSE { RETURN \"nonzero\" }\n}\n}\n\
+         DISPLAY(sign(0))\nDISPLAY(sign(3))\n\
+         FOR EACH i IN [1, 2, 3]\n{\nMATCH i\n{\nCASE 2 { BREAK }\nELSE { DISPLAY(i) }\n}\n}",
+        "zero\nnonzero\n1",
+    );
+}
+
+#[test]
+fn test_match_parse_errors() {
+    let cases = [
+        ("MATCH 1\n{\n}", "MATCH needs at least one CASE"),
+        (
+            "MATCH 1\n{\nELSE { }\nCASE 1 { }\n}",
+            "ELSE must be the last arm of a MATCH",
+        ),
+        (
+            "MATCH 1\n{\nDISPLAY(1)\n}",
+            "Expected CASE, ELSE or '}' inside MATCH",
+        ),
+        (
+            "MATCH [1]\n{\nCASE [a..., b] { }\n}",
+            "A rest pattern must be the last in a list pattern",
+        ),
+        ("MATCH 1\n{\nCASE 1 + 1 { }\n}", "Expected '{'"),
+        (
+            "MATCH 1\n{\nCASE (1) { }\n}",
+            "Expected a pattern: a literal, a name, a list or a dictionary",
+        ),
+    ];
+    for (source, expected) in cases {
+        let err = get_error(source);
+        assert!(err.contains(expected), "{}: {}", source, err);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// FOR EACH with two names
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_foreach_key_and_value_over_a_dictionary() {
+    assert_output(
+        r#"
+        ages <- {"ada": 36, "alan": 41}
+        FOR EACH name, age IN ages
+        {
+            DISPLAY(name + " " + TOSTRING(age))
+        }
+        "#,
+        "ada 36\nalan 41",
+    );
+}
+
+#[test]
+fn test_foreach_index_and_item_over_a_list_and_a_string() {
+    assert_output(
+        r#"
+        FOR EACH i, fruit IN ["apple", "pear"]
+        {
+            DISPLAY(TOSTRING(i) + ". " + fruit)
+        }
+        FOR EACH i, c IN "ok"
+        {
+            DISPLAY(TOSTRING(i) + c)
+        }
+        "#,
+        "1. apple\n2. pear\n1o\n2k",
+    );
+}
+
+#[test]
+fn test_foreach_with_two_names_supports_break_and_continue() {
+    assert_output(
+        r#"
+        FOR EACH i, n IN [5, 6, 7, 8]
+        {
+            IF (i = 2)
+            {
+                CONTINUE
+            }
+            IF (n = 8)
+            {
+                BREAK
+            }
+            DISPLAY(n)
+        }
+        "#,
+        "5\n7",
+    );
+}
+
+#[test]
+fn test_foreach_with_two_names_on_an_integer_is_an_error() {
+    let err = get_error("FOR EACH i, x IN 5\n{\n}");
+    assert!(
+        err.contains("FOR EACH requires list, string, or dictionary"),
+        "{}",
+        err
+    );
+    let err = get_error("FOR EACH i, IN [1]\n{\n}");
+    assert!(
+        err.contains("Expected a second name after ',' in FOR EACH"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,190 @@
+//! `fpli run --coverage` and `fpli test --coverage`: the LCOV report, driven
+//! through `run_observed` and `run_test`. Each file's path is cut down to its
+//! name before comparing, as the scratch directory differs from run to run.
+
+use super::{Scratch, Shared};
+use crate::coverage::Coverage;
+use crate::interpreter::{self, Observer};
+use crate::lexer::Lexer;
+use crate::limits::Limits;
+use crate::parser;
+use crate::sandbox::Permissions;
+use crate::testing::test_procedures;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The report with every `SF:` path cut down to the file's name.
+fn named(report: &str) -> String {
+    report
+        .lines()
+        .map(|line| match line.strip_prefix("SF:") {
+            Some(path) => format!("SF:{}\n", path.rsplit(['/', '\\']).next().unwrap_or(path)),
+            None => format!("{}\n", line),
+        })
+        .collect()
+}
+
+/// The report from running `main` in a directory holding `files` as well.
+fn coverage(main: &str, files: &[(&str, &str)]) -> String {
+    let scratch = Scratch::new();
+    for (name, text) in files {
+        scratch.write(name, text);
+    }
+    let path = scratch.write("main.psl", main);
+    let tokens = Lexer::new(main).tokenize();
+    let ast = parser::parse_with_source(tokens, main, false).expect("the program parses");
+    let report = Shared::default();
+    let coverage = Coverage::new(Box::new(report.clone()));
+    let _ = interpreter::run_observed(
+        &ast,
+        main,
+        &[],
+        Some(path),
+        Rc::new(RefCell::new(coverage)) as Rc<RefCell<dyn Observer>>,
+    );
+    named(&report.text())
+}
+
+#[test]
+fn test_lines_that_never_ran_are_reported_with_no_hits() {
+    let report = coverage(
+        "// Counting up
+PROCEDURE unused()
+{
+    DISPLAY(\"never\")
+}
+x <- 0
+REPEAT 3 TIMES
+{
+    x <- x + 1
+}
+IF (x > 100)
+{
+    DISPLAY(\"big\")
+}
+",
+        &[],
+    );
+    assert_eq!(
+        report,
+        "TN:
+SF:main.psl
+DA:2,1
+DA:4,0
+DA:6,1
+DA:7,1
+DA:9,3
+DA:11,1
+DA:13,0
+LF:7
+LH:5
+end_of_record
+"
+    );
+}
+
+#[test]
+fn test_imported_files_get_records_of_their_own() {
+    let report = coverage(
+        "IMPORT \"lib.psl\"\nDISPLAY(double(2))\nDISPLAY(EVAL(\"1 + 1\"))\n",
+        &[(
+            "lib.psl",
+            "PROCEDURE double(n)
+{
+    RETURN n * 2
+}
+
+CLASS Point
+{
+    PROCEDURE Point(x)
+    {
+        self.x <- x
+    }
+}
+",
+        )],
+    );
+    // EVAL's source has no file, and no record.
+    assert_eq!(
+        report,
+        "TN:
+SF:main.psl
+DA:1,1
+DA:2,1
+DA:3,1
+LF:3
+LH:3
+end_of_record
+TN:
+SF:lib.psl
+DA:1,1
+DA:3,1
+DA:6,1
+DA:10,0
+LF:4
+LH:3
+end_of_record
+"
+    );
+}
+
+#[test]
+fn test_one_report_covers_every_test_run() {
+    let scratch = Scratch::new();
+    let source = "PROCEDURE sign(n)
+{
+    IF (n < 0)
+    {
+        RETURN -1
+    }
+    RETURN 1
+}
+PROCEDURE test_negative()
+{
+    ASSERTEQUAL(sign(-2), -1)
+}
+PROCEDURE test_positive()
+{
+    ASSERTEQUAL(sign(2), 1)
+}
+";
+    let path = scratch.write("sign_test.psl", source);
+    let ast = parser::parse_with_source(Lexer::new(source).tokenize(), source, false)
+        .expect("the tests parse");
+    let report = Shared::default();
+    let coverage = Rc::new(RefCell::new(Coverage::new(Box::new(report.clone()))));
+    for (test, span) in test_procedures(&ast) {
+        let observer = Rc::clone(&coverage) as Rc<RefCell<dyn Observer>>;
+        let outcome = interpreter::run_test(
+            &ast,
+            source,
+            &path,
+            &test,
+            span,
+            Some(observer),
+            &Permissions::default(),
+            &Limits::default(),
+        );
+        assert!(outcome.error.is_none(), "{} failed", test);
+    }
+    // Nothing is written until the last test is over.
+    assert_eq!(report.text(), "");
+    coverage.borrow_mut().ended();
+    assert_eq!(
+        named(&report.text()),
+        "TN:
+SF:sign_test.psl
+DA:1,2
+DA:3,2
+DA:5,1
+DA:7,1
+DA:9,2
+DA:11,1
+DA:13,2
+DA:15,1
+LF:8
+LH:8
+end_of_record
+"
+    );
+}
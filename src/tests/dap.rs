@@ -0,0 +1,649 @@
+use super::{Scratch, Shared};
+use crate::dap;
+use crate::interpreter::{self, Frame, Observed, Observer, Paused};
+use crate::json::{Json, read_message};
+use crate::lexer::Lexer;
+use crate::parser;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+/// A scope's name, and its variables as `(name, rendered value)`.
+type Shown = (String, Vec<(String, String)>);
+
+/// An observer that writes down where the run went, and can end it at a given
+/// statement.
+#[derive(Default)]
+struct Recorder {
+    /// The line, depth and frames of every statement reached.
+    reached: Vec<(usize, usize, Vec<Frame>)>,
+    /// Each scope of the innermost frame, with its variables rendered, at every
+    /// statement.
+    scopes: Vec<Vec<Shown>>,
+    /// End the run with this status at this many statements.
+    exit_at: Option<(usize, i32)>,
+    input: Vec<String>,
+    printed: String,
+}
+
+impl Observer for Recorder {
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+        self.printed.push_str(&paused.take_output());
+        self.reached
+            .push((paused.line(), paused.depth(), paused.frames()));
+        self.scopes.push(
+            paused
+                .scopes(0)
+                .into_iter()
+                .map(|scope| {
+                    let variables = scope
+                        .variables
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.render()))
+                        .collect();
+                    (scope.name.to_string(), variables)
+                })
+                .collect(),
+        );
+        match self.exit_at {
+            Some((at, status)) if self.reached.len() == at => Some(status),
+            _ => None,
+        }
+    }
+
+    fn read_line(&mut self) -> Option<Result<String, String>> {
+        if self.input.is_empty() {
+            Some(Err("No input left".to_string()))
+        } else {
+            Some(Ok(self.input.remove(0)))
+        }
+    }
+}
+
+fn observe(source: &str, path: &Path, recorder: Recorder) -> (Recorder, Observed) {
+    let tokens = Lexer::new(source).tokenize();
+    let ast = parser::parse_with_source(tokens, source, false).expect("the program parses");
+    let recorder = Rc::new(RefCell::new(recorder));
+    let observed = interpreter::run_observed(
+        &ast,
+        source,
+        &[],
+        Some(path.to_path_buf()),
+        Rc::clone(&recorder) as Rc<RefCell<dyn Observer>>,
+    );
+    let recorder = Rc::try_unwrap(recorder)
+        .ok()
+        .expect("the run lets go of its observer")
+        .into_inner();
+    (recorder, observed)
+}
+
+#[test]
+fn every_statement_is_reached_with_its_line_and_depth() {
+    let scratch = Scratch::new();
+    let source =
+        "// set up\nPROCEDURE twice(n)\n{\n    RETURN n * 2\n}\nx <- twice(4)\nDISPLAY(x)\n";
+    let path = scratch.write("main.psl", source);
+    let (recorder, observed) = observe(source, &path, Recorder::default());
+    let lines: Vec<(usize, usize)> = recorder
+        .reached
+        .iter()
+        .map(|(line, depth, _)| (*line, *depth))
+        .collect();
+    // The comment and the empty block the parser closes a program with are
+    // never stopped on.
+    assert_eq!(lines, vec![(2, 0), (6, 0), (4, 1), (7, 0)]);
+    assert_eq!(observed.output, "8\n");
+    assert_eq!(observed.status.ok(), Some(0));
+}
+
+#[test]
+fn frames_name_the_procedure_and_the_call_in_progress() {
+    let scratch = Scratch::new();
+    let source =
+        "PROCEDURE inner()\n{\n    DISPLAY(1)\n}\nPROCEDURE outer()\n{\n    inner()\n}\nouter()\n";
+    let path = scratch.write("main.psl", source);
+    let (recorder, _) = observe(source, &path, Recorder::default());
+    let (_, depth, frames) = &recorder.reached[4];
+    assert_eq!(*depth, 2);
+    let shown: Vec<(&str, usize, usize)> = frames
+        .iter()
+        .map(|f| (f.name.as_str(), f.line, f.column))
+        .collect();
+    assert_eq!(
+        shown,
+        vec![("inner", 3, 5), ("outer", 7, 5), ("<main>", 9, 1)]
+    );
+    let file = std::fs::canonicalize(&path).unwrap();
+    assert!(
+        frames
+            .iter()
+            .all(|f| f.file.as_deref() == Some(file.as_path()))
+    );
+}
+
+#[test]
+fn frames_in_an_imported_file_are_in_that_file() {
+    let scratch = Scratch::new();
+    scratch.write("lib.psl", "PROCEDURE helper()\n{\n    RETURN 1\n}\n");
+    let source = "IMPORT \"lib\"\ny <- helper()\n";
+    let path = scratch.write("main.psl", source);
+    let (recorder, _) = observe(source, &path, Recorder::default());
+    let lib = std::fs::canonicalize(scratch.path("lib.psl")).unwrap();
+    let main = std::fs::canonicalize(&path).unwrap();
+    // The import's own top level, then the call into it.
+    let (line, depth, frames) = &recorder.reached[1];
+    assert_eq!((*line, *depth), (1, 0));
+    assert_eq!(frames[0].file.as_deref(), Some(lib.as_path()));
+    let (_, _, frames) = recorder.reached.last().unwrap();
+    assert_eq!(frames[0].name, "helper");
+    assert_eq!(
+        (frames[0].file.as_deref(), frames[0].line),
+        (Some(lib.as_path()), 3)
+    );
+    assert_eq!(
+        (frames[1].file.as_deref(), frames[1].line),
+        (Some(main.as_path()), 2)
+    );
+}
+
+#[test]
+fn scopes_show_locals_then_globals() {
+    let scratch = Scratch::new();
+    let source =
+        "total <- 10\nPROCEDURE add(n)\n{\n    sum <- total + n\n    RETURN sum\n}\nadd(5)\n";
+    let path = scratch.write("main.psl", source);
+    let (recorder, _) = observe(source, &path, Recorder::default());
+    let at_return = &recorder.scopes[recorder.scopes.len() - 1];
+    assert_eq!(at_return[0].0, "Locals");
+    assert_eq!(
+        at_return[0].1,
+        vec![
+            ("n".to_string(), "5".to_string()),
+            ("sum".to_string(), "15".to_string())
+        ]
+    );
+    assert_eq!(at_return.last().unwrap().0, "Globals");
+    assert!(
+        at_return
+            .last()
+            .unwrap()
+            .1
+            .contains(&("total".to_string(), "10".to_string()))
+    );
+}
+
+#[test]
+fn values_render_with_their_type_and_expand() {
+    let scratch = Scratch::new();
+    let source = "CLASS Point\n{\n    PROCEDURE Point(x)\n    {\n        self.x <- x\n    }\n}\nvalue <- [\"a\", {\"k\": 1}, Point(3)]\nDISPLAY(value)\n";
+    let path = scratch.write("main.psl", source);
+    let tokens = Lexer::new(source).tokenize();
+    let ast = parser::parse_with_source(tokens, source, false).unwrap();
+
+    struct Expand(Vec<String>);
+    impl Observer for Expand {
+        fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+            if let Some(value) = paused.lookup(0, "value") {
+                self.0
+                    .push(format!("{} {}", value.type_name(), value.render()));
+                for (name, child) in value.children() {
+                    let grandchildren: Vec<String> = child
+                        .children()
+                        .into_iter()
+                        .map(|(name, value)| format!("{}={}", name, value.render()))
+                        .collect();
+                    self.0.push(format!(
+                        "{} {} {} {:?}",
+                        name,
+                        child.type_name(),
+                        child.render(),
+                        grandchildren
+                    ));
+                }
+            }
+            None
+        }
+    }
+    let expand = Rc::new(RefCell::new(Expand(Vec::new())));
+    interpreter::run_observed(&ast, source, &[], Some(path), expand.clone());
+    assert_eq!(
+        expand.borrow().0,
+        vec![
+            r#"list [a, {k: 1}, Point{x: 3}]"#,
+            r#"[1] string "a" []"#,
+            r#"[2] dictionary {k: 1} ["\"k\"=1"]"#,
+            r#"[3] object Point{x: 3} ["\"x\"=3"]"#,
+        ]
+    );
+}
+
+#[test]
+fn an_observer_can_end_the_run() {
+    let scratch = Scratch::new();
+    let source = "DISPLAY(1)\nDISPLAY(2)\nDISPLAY(3)\n";
+    let path = scratch.write("main.psl", source);
+    let recorder = Recorder {
+        exit_at: Some((3, 4)),
+        ..Recorder::default()
+    };
+    let (recorder, observed) = observe(source, &path, recorder);
+    assert_eq!(observed.status.ok(), Some(4));
+    assert_eq!(format!("{}{}", recorder.printed, observed.output), "1\n2\n");
+}
+
+#[test]
+fn input_reads_what_the_observer_supplies() {
+    let scratch = Scratch::new();
+    let source = "name <- INPUT(\"Name? \")\nDISPLAY(\"Hello, \" + name)\nagain <- INPUT()\n";
+    let path = scratch.write("main.psl", source);
+    let recorder = Recorder {
+        input: vec!["Ada".to_string()],
+        ..Recorder::default()
+    };
+    let (recorder, observed) = observe(source, &path, recorder);
+    // The prompt and what was typed go with the program's output, as they would
+    // appear on a terminal.
+    assert_eq!(
+        format!("{}{}", recorder.printed, observed.output),
+        "Name? Ada\nHello, Ada\n"
+    );
+    let error = observed.status.unwrap_err();
+    assert_eq!(error.message, "No input left");
+}
+
+/// Serve a whole session of `requests`, numbered in order, and return every
+/// message the adapter sent.
+fn session(requests: &[(&str, &str)]) -> Vec<Json> {
+    let (sender, inbox) = std::sync::mpsc::channel();
+    for (seq, (command, arguments)) in requests.iter().enumerate() {
+        sender
+            .send(format!(
+                r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#,
+                seq + 1,
+                command,
+                arguments
+            ))
+            .unwrap();
+    }
+    drop(sender);
+    let output = Shared::default();
+    assert_eq!(dap::serve(inbox, output.clone()), 0);
+    let bytes = output.0.borrow().clone();
+    let mut reader = std::io::Cursor::new(bytes);
+    let mut messages = Vec::new();
+    while let Some(body) = read_message(&mut reader).unwrap() {
+        messages.push(Json::parse(&body).unwrap());
+    }
+    messages
+}
+
+/// The reply to the `seq`th request.
+fn reply(messages: &[Json], seq: usize) -> &Json {
+    messages
+        .iter()
+        .find(|m| m.get("request_seq").and_then(Json::as_usize) == Some(seq))
+        .unwrap_or_else(|| panic!("no reply to request {} in {:?}", seq, messages))
+}
+
+/// Each event's name, with the reason of a stop and the text of output.
+fn events(messages: &[Json]) -> Vec<String> {
+    messages
+        .iter()
+        .filter(|m| m.get("type").and_then(Json::as_str) == Some("event"))
+        .map(|m| {
+            let name = m.get("event").and_then(Json::as_str).unwrap().to_string();
+            let detail = m
+                .at(&["body", "reason"])
+                .or_else(|| m.at(&["body", "output"]))
+                .and_then(Json::as_str)
+                .map(str::to_string)
+                .or_else(|| m.at(&["body", "exitCode"]).map(|c| c.to_string()));
+            match detail {
+                Some(detail) => format!("{} {}", name, detail),
+                None => name,
+            }
+        })
+        .collect()
+}
+
+/// The (name, line) of every frame in a `stackTrace` reply.
+fn frames(reply: &Json) -> Vec<(String, usize)> {
+    reply
+        .at(&["body", "stackFrames"])
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|f| {
+            (
+                f.get("name").and_then(Json::as_str).unwrap().to_string(),
+                f.get("line").and_then(Json::as_usize).unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn launch(path: &Path, extra: &str) -> String {
+    format!(
+        r#"{{"program":{}{}}}"#,
+        Json::from(path.display().to_string()),
+        extra
+    )
+}
+
+fn breakpoints(path: &Path, lines: &[usize]) -> String {
+    let lines: Vec<String> = lines
+        .iter()
+        .map(|l| format!(r#"{{"line":{}}}"#, l))
+        .collect();
+    format!(
+        r#"{{"source":{{"path":{}}},"breakpoints":[{}]}}"#,
+        Json::from(path.display().to_string()),
+        lines.join(",")
+    )
+}
+
+#[test]
+fn a_session_stops_at_a_breakpoint_and_shows_the_stack() {
+    let scratch = Scratch::new();
+    let path = scratch.write(
+        "main.psl",
+        "PROCEDURE square(n)\n{\n    result <- n * n\n    RETURN result\n}\nDISPLAY(square(3))\nDISPLAY(\"end\")\n",
+    );
+    let messages = session(&[
+        ("initialize", "{}"),
+        ("launch", &launch(&path, "")),
+        ("setBreakpoints", &breakpoints(&path, &[4])),
+        ("configurationDone", "{}"),
+        ("stackTrace", r#"{"threadId":1}"#),
+        ("continue", r#"{"threadId":1}"#),
+        ("disconnect", "{}"),
+    ]);
+    assert_eq!(
+        events(&messages),
+        vec![
+            "initialized",
+            "stopped breakpoint",
+            "output 9\n",
+            "output end\n",
+            "exited 0",
+            "terminated"
+        ]
+    );
+    assert!(
+        reply(&messages, 1)
+            .at(&["body", "supportsConfigurationDoneRequest"])
+            .is_some()
+    );
+    assert_eq!(
+        frames(reply(&messages, 5)),
+        vec![("square".to_string(), 4), ("<main>".to_string(), 6)]
+    );
+    assert_eq!(
+        reply(&messages, 5)
+            .at(&["body", "stackFrames"])
+            .unwrap()
+            .as_array()
+            .unwrap()[0]
+            .at(&["source", "name"])
+            .and_then(Json::as_str),
+        Some("main.psl")
+    );
+    assert_eq!(reply(&messages, 7).get("success"), Some(&Json::Bool(true)));
+}
+
+#[test]
+fn a_breakpoint_statement_stops_the_session_after_it() {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", "x <- 1\nBREAKPOINT()\nx <- 2\n");
+    let messages = session(&[
+        ("launch", &launch(&path, "")),
+        ("configurationDone", "{}"),
+        ("stackTrace", r#"{"threadId":1}"#),
+        ("continue", r#"{"threadId":1}"#),
+    ]);
+    assert_eq!(
+        events(&messages),
+        vec!["stopped breakpoint", "exited 0", "terminated"]
+    );
+    assert_eq!(frames(reply(&messages, 3)), vec![("<main>".to_string(), 3)]);
+}
+
+#[test]
+fn stepping_goes_in_over_and_out_of_procedures() {
+    let scratch = Scratch::new();
+    let path = scratch.write(
+        "main.psl",
+        "PROCEDURE inc(n)\n{\n    m <- n + 1\n    RETURN m\n}\na <- inc(1)\nb <- inc(a)\nc <- b\n",
+    );
+    let trace = r#"{"threadId":1}"#;
+    let messages = session(&[
+        ("launch", &launch(&path, r#","stopOnEntry":true"#)),
+        ("configurationDone", "{}"),
+        ("next", trace),       // from the declaration to line 6
+        ("stepIn", trace),     // into inc, line 3
+        ("stackTrace", trace), // 5
+        ("stepOut", trace),    // back to line 7
+        ("stackTrace", trace), // 7
+        ("next", trace),       // over the call, to line 8
+        ("stackTrace", trace), // 9
+        ("continue", trace),
+    ]);
+    assert_eq!(
+        events(&messages)[..5],
+        [
+            "stopped entry",
+            "stopped step",
+            "stopped step",
+            "stopped step",
+            "stopped step"
+        ]
+    );
+    assert_eq!(
+        frames(reply(&messages, 5)),
+        vec![("inc".to_string(), 3), ("<main>".to_string(), 6)]
+    );
+    assert_eq!(frames(reply(&messages, 7)), vec![("<main>".to_string(), 7)]);
+    assert_eq!(frames(reply(&messages, 9)), vec![("<main>".to_string(), 8)]);
+}
+
+#[test]
+fn a_statement_on_the_same_line_as_a_stop_is_part_of_it() {
+    let scratch = Scratch::new();
+    let path = scratch.write(
+        "main.psl",
+        "x <- 1\nIF (x = 1) { DISPLAY(x) }\nDISPLAY(2)\n",
+    );
+    let messages = session(&[
+        ("setBreakpoints", &breakpoints(&path, &[2])),
+        ("launch", &launch(&path, "")),
+        ("configurationDone", "{}"),
+        ("next", r#"{"threadId":1}"#),
+        ("stackTrace", r#"{"threadId":1}"#),
+        ("continue", r#"{"threadId":1}"#),
+    ]);
+    assert_eq!(
+        events(&messages),
+        vec![
+            "stopped breakpoint",
+            "output 1\n",
+            "stopped step",
+            "output 2\n",
+            "exited 0",
+            "terminated"
+        ]
+    );
+    assert_eq!(frames(reply(&messages, 5)), vec![("<main>".to_string(), 3)]);
+}
+
+#[test]
+fn scopes_variables_and_evaluate_show_values() {
+    let scratch = Scratch::new();
+    let path = scratch.write(
+        "main.psl",
+        "scores <- {\"ada\": [3, 4]}\nPROCEDURE show(label)\n{\n    DISPLAY(label)\n}\nshow(\"hi\")\n",
+    );
+    let messages = session(&[
+        ("setBreakpoints", &breakpoints(&path, &[4])),
+        ("launch", &launch(&path, "")),
+        ("configurationDone", "{}"),
+        ("scopes", r#"{"frameId":0}"#),
+        ("variables", r#"{"variablesReference":1}"#),
+        ("variables", r#"{"variablesReference":2}"#),
+        ("variables", r#"{"variablesReference":3}"#),
+        ("evaluate", r#"{"expression":"label","frameId":0}"#),
+        ("evaluate", r#"{"expression":"nothing","frameId":0}"#),
+        ("continue", "{}"),
+        ("variables", r#"{"variablesReference":1}"#),
+    ]);
+    let scopes: Vec<&str> = reply(&messages, 4)
+        .at(&["body", "scopes"])
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|s| s.get("name").and_then(Json::as_str).unwrap())
+        .collect();
+    assert_eq!(scopes, vec!["Locals", "Globals"]);
+    let variables = |seq: usize| -> Vec<String> {
+        reply(&messages, seq)
+            .at(&["body", "variables"])
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .map(|v| {
+                format!(
+                    "{}={} {}",
+                    v.get("name").and_then(Json::as_str).unwrap(),
+                    v.get("value").and_then(Json::as_str).unwrap(),
+                    v.get("variablesReference")
+                        .and_then(Json::as_usize)
+                        .unwrap()
+                )
+            })
+            .collect()
+    };
+    assert_eq!(variables(5), vec![r#"label="hi" 0"#]);
+    assert!(
+        variables(6).contains(&r#"scores={ada: [3, 4]} 3"#.to_string()),
+        "{:?}",
+        variables(6)
+    );
+    assert_eq!(variables(7), vec![r#""ada"=[3, 4] 4"#]);
+    assert_eq!(
+        reply(&messages, 8)
+            .at(&["body", "result"])
+            .and_then(Json::as_str),
+        Some(r#""hi""#)
+    );
+    assert_eq!(reply(&messages, 9).get("success"), Some(&Json::Bool(false)));
+    // Once the program has moved on, the old references mean nothing.
+    assert_eq!(
+        reply(&messages, 11).get("success"),
+        Some(&Json::Bool(false))
+    );
+}
+
+#[test]
+fn input_reads_the_launch_stdin() {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", "n <- INPUT()\nDISPLAY(n + \"!\")\n");
+    let messages = session(&[
+        ("launch", &launch(&path, r#","stdin":"yes\n""#)),
+        ("configurationDone", "{}"),
+    ]);
+    assert_eq!(
+        events(&messages),
+        vec!["output yes\n", "output yes!\n", "exited 0", "terminated"]
+    );
+}
+
+#[test]
+fn a_failing_program_reports_its_error_and_status() {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", "DISPLAY(1)\nDISPLAY(missing)\n");
+    let messages = session(&[
+        ("launch", &launch(&path, r#","args":["a"]"#)),
+        ("configurationDone", "{}"),
+    ]);
+    let events = events(&messages);
+    assert_eq!(events[0], "output 1\n");
+    assert!(
+        events[1].contains("Undefined variable: missing"),
+        "{:?}",
+        events
+    );
+    assert_eq!(events[2..], ["exited 1", "terminated"]);
+    let categories: Vec<&str> = messages
+        .iter()
+        .filter_map(|m| m.at(&["body", "category"]).and_then(Json::as_str))
+        .collect();
+    assert_eq!(categories, vec!["stdout", "stderr"]);
+}
+
+#[test]
+fn a_program_that_does_not_parse_fails_to_launch() {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", "x <- (1\n");
+    let missing = scratch.path("missing.psl");
+    let messages = session(&[
+        ("launch", &launch(&path, "")),
+        ("launch", &launch(&missing, "")),
+        ("launch", "{}"),
+        ("configurationDone", "{}"),
+    ]);
+    for seq in 1..=3 {
+        assert_eq!(
+            reply(&messages, seq).get("success"),
+            Some(&Json::Bool(false))
+        );
+    }
+    assert!(
+        reply(&messages, 2)
+            .get("message")
+            .and_then(Json::as_str)
+            .unwrap()
+            .starts_with("Error reading file")
+    );
+    assert!(events(&messages).is_empty(), "{:?}", events(&messages));
+}
+
+#[test]
+fn requests_that_need_a_stop_are_refused_without_one() {
+    let messages = session(&[
+        ("stackTrace", r#"{"threadId":1}"#),
+        ("threads", "{}"),
+        ("frobnicate", "{}"),
+    ]);
+    assert_eq!(
+        reply(&messages, 1).get("message").and_then(Json::as_str),
+        Some("The program is not paused")
+    );
+    assert_eq!(
+        reply(&messages, 2)
+            .at(&["body", "threads"])
+            .map(|t| t.to_string()),
+        Some(r#"[{"id":1,"name":"main"}]"#.to_string())
+    );
+    assert_eq!(
+        reply(&messages, 3).get("message").and_then(Json::as_str),
+        Some("Unsupported request 'frobnicate'")
+    );
+}
+
+#[test]
+fn disconnecting_while_stopped_ends_the_program() {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", "DISPLAY(1)\nDISPLAY(2)\n");
+    let messages = session(&[
+        ("setBreakpoints", &breakpoints(&path, &[2])),
+        ("launch", &launch(&path, "")),
+        ("configurationDone", "{}"),
+        // Held until the stop, and the disconnect behind it: a disconnect that
+        // arrived while the program ran would end it before it got there.
+        ("threads", "{}"),
+        ("stackTrace", r#"{"threadId":1}"#),
+        ("disconnect", "{}"),
+    ]);
+    assert_eq!(events(&messages), vec!["output 1\n", "stopped breakpoint"]);
+    assert_eq!(reply(&messages, 6).get("success"), Some(&Json::Bool(true)));
+}
@@ -0,0 +1,174 @@
+//! The console debugger, driven through `run_observed` with its commands
+//! scripted and everything it shows -- and everything the program prints --
+//! written down in one transcript. BREAKPOINT() and `fpli run --step` on a real
+//! terminal are covered by the integration suite.
+
+use super::{Scratch, Shared, get_error, run_test};
+use crate::debugger::Console;
+use crate::interpreter::{self, Observer};
+use crate::lexer::Lexer;
+use crate::parser;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Run `source` in the console debugger, answering its prompts with `commands`.
+/// Returns the transcript, with what was left uncaptured at the end appended,
+/// and the run's exit status.
+fn debug(source: &str, commands: &[&str]) -> (String, i32) {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", source);
+    let tokens = Lexer::new(source).tokenize();
+    let ast = parser::parse_with_source(tokens, source, false).expect("the program parses");
+    let mut commands: VecDeque<String> = commands.iter().map(|c| format!("{}\n", c)).collect();
+    let transcript = Shared::default();
+    let console = Console::with_io(
+        Box::new(move |line| {
+            let next = commands.pop_front().unwrap_or_default();
+            line.push_str(&next);
+            Ok(next.len())
+        }),
+        Box::new(transcript.clone()),
+        Box::new(transcript.clone()),
+    );
+    let observed = interpreter::run_observed(
+        &ast,
+        source,
+        &[],
+        Some(path),
+        Rc::new(RefCell::new(console)) as Rc<RefCell<dyn Observer>>,
+    );
+    let status = observed.status.expect("the program runs");
+    let mut text = transcript.text();
+    text.push_str(&observed.output);
+    (text, status)
+}
+
+const PROGRAM: &str = "PROCEDURE inc(n)
+{
+    m <- n + 1
+    RETURN m
+}
+a <- inc(1)
+DISPLAY(a)
+";
+
+#[test]
+fn test_stepping_in_and_out_shows_each_line() {
+    let (transcript, status) = debug(PROGRAM, &["s", "s", "s", "o", "c"]);
+    assert_eq!(status, 0);
+    assert_eq!(
+        transcript,
+        "Stopped at main.psl:1, in <main>
+   1 | PROCEDURE inc(n)
+(fpli) Stopped at main.psl:6, in <main>
+   6 | a <- inc(1)
+(fpli) Stopped at main.psl:3, in inc
+   3 |     m <- n + 1
+(fpli) Stopped at main.psl:4, in inc
+   4 |     RETURN m
+(fpli) Stopped at main.psl:7, in <main>
+   7 | DISPLAY(a)
+(fpli) 2
+"
+    );
+}
+
+#[test]
+fn test_next_steps_over_a_call_and_an_empty_line_repeats_it() {
+    let (transcript, _) = debug(PROGRAM, &["n", "", "c"]);
+    assert!(transcript.contains("   6 | a <- inc(1)"), "{}", transcript);
+    assert!(transcript.contains("   7 | DISPLAY(a)"), "{}", transcript);
+    assert!(!transcript.contains("in inc"), "{}", transcript);
+}
+
+#[test]
+fn test_print_evaluates_in_the_scope_stopped_in() {
+    let (transcript, _) = debug(
+        PROGRAM,
+        &["s", "s", "p n * 10", "p [n, \"x\"]", "p missing", "p", "c"],
+    );
+    assert!(transcript.contains("(fpli) 10\n"), "{}", transcript);
+    assert!(transcript.contains("(fpli) [1, x]\n"), "{}", transcript);
+    assert!(
+        transcript.contains("(fpli) Error: Undefined variable: missing\n"),
+        "{}",
+        transcript
+    );
+    assert!(
+        transcript.contains("(fpli) Usage: print EXPRESSION\n"),
+        "{}",
+        transcript
+    );
+}
+
+#[test]
+fn test_what_an_expression_prints_comes_before_its_value() {
+    let source = "PROCEDURE loud(x)\n{\n    DISPLAY(\"called\")\n    RETURN x\n}\ny <- 1\n";
+    let (transcript, _) = debug(source, &["s", "p loud(5)", "c"]);
+    assert!(transcript.contains("(fpli) called\n5\n"), "{}", transcript);
+}
+
+#[test]
+fn test_vars_and_where_show_the_scopes_and_the_stack() {
+    let (transcript, _) = debug(PROGRAM, &["s", "s", "v", "w", "c"]);
+    assert!(
+        transcript.contains("(fpli) Locals:\n  n = 1\nGlobals:\n"),
+        "{}",
+        transcript
+    );
+    assert!(
+        transcript.contains("(fpli)   #0 inc at main.psl:3\n  #1 <main> at main.psl:6\n"),
+        "{}",
+        transcript
+    );
+}
+
+#[test]
+fn test_quit_ends_the_run_with_a_failure() {
+    let (transcript, status) = debug(PROGRAM, &["q"]);
+    assert_eq!(status, 1);
+    assert!(!transcript.contains("2\n"), "{}", transcript);
+}
+
+#[test]
+fn test_unknown_commands_and_help_keep_the_program_stopped() {
+    let (transcript, _) = debug("x <- 1\n", &["frobnicate", "h", "c"]);
+    assert!(
+        transcript.contains("Unknown command 'frobnicate'. Type 'help' for the list."),
+        "{}",
+        transcript
+    );
+    assert!(transcript.contains("  q, quit"), "{}", transcript);
+    assert_eq!(transcript.matches("Stopped at").count(), 1);
+}
+
+#[test]
+fn test_a_breakpoint_stops_a_program_that_was_continued() {
+    let source = "DISPLAY(1)\nBREAKPOINT()\nDISPLAY(2)\nDISPLAY(3)\n";
+    let (transcript, _) = debug(source, &["c", "c"]);
+    assert!(
+        transcript.ends_with(
+            "(fpli) 1\nStopped at main.psl:3, in <main>\n   3 | DISPLAY(2)\n(fpli) 2\n3\n"
+        ),
+        "{}",
+        transcript
+    );
+}
+
+#[test]
+fn test_running_out_of_commands_lets_the_program_finish() {
+    let (transcript, status) = debug("DISPLAY(1)\nBREAKPOINT()\nDISPLAY(2)\n", &[]);
+    assert_eq!(status, 0);
+    assert!(transcript.ends_with("1\n2\n"), "{}", transcript);
+    assert_eq!(transcript.matches("Stopped at").count(), 1);
+}
+
+#[test]
+fn test_breakpoint_does_nothing_in_a_captured_run() {
+    assert_eq!(
+        run_test("DISPLAY(1)\nBREAKPOINT()\nDISPLAY(2)\n").unwrap(),
+        "1\n2"
+    );
+    assert!(get_error("BREAKPOINT(1)").contains("BREAKPOINT takes no arguments"));
+}
@@ -8,11 +8,11 @@ use crate::lexer::Lexer;
 use crate::parser;
 
 /// Blocks that are syntax templates rather than programs, marked by a placeholder.
-fn is_a_template(source: &str) -> bool {
+pub(super) fn is_a_template(source: &str) -> bool {
     source.contains("<statement") || source.contains("...procs") || source.contains("<first")
 }
 
-fn psl_blocks(markdown: &str) -> Vec<(usize, String)> {
+pub(super) fn psl_blocks(markdown: &str) -> Vec<(usize, String)> {
     let mut blocks = Vec::new();
     let mut lines = markdown.lines().enumerate();
     while let Some((number, line)) = lines.next() {
@@ -0,0 +1,193 @@
+//! `Interpreter`, PseudoLang embedded in a Rust program: state kept from one
+//! call to the next, values passed each way, and the errors a host gets back.
+
+use crate::error::{ErrorKind, PSLError};
+use crate::host::Value;
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::sandbox::Permissions;
+
+const RULES: &str = "threshold <- 10
+PROCEDURE score(order)
+{
+    points <- order[\"items\"] * 2
+    IF (order[\"member\"])
+    {
+        points <- points + threshold
+    }
+    RETURN points
+}
+";
+
+/// What a call that has to succeed came to.
+fn done<T>(result: Result<T, PSLError>) -> T {
+    result.unwrap_or_else(|error| panic!("{}", error.message))
+}
+
+fn order(items: i64, member: bool) -> Value {
+    Value::Dictionary(vec![
+        ("items".into(), items.into()),
+        ("member".into(), member.into()),
+    ])
+}
+
+#[test]
+fn test_state_is_kept_between_calls() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(done(interpreter.run(RULES)), String::new());
+    assert_eq!(
+        done(interpreter.call("score", &[order(3, false)])),
+        Value::from(6)
+    );
+    assert_eq!(
+        done(interpreter.call("score", &[order(3, true)])),
+        Value::from(16)
+    );
+
+    interpreter.set("threshold", 100).expect("a usable name");
+    assert_eq!(
+        done(interpreter.call("score", &[order(1, true)])),
+        Value::from(102)
+    );
+    assert_eq!(done(interpreter.eval("threshold + 1")), Value::from(101));
+    assert_eq!(interpreter.get("threshold"), Some(Value::from(100)));
+    assert_eq!(interpreter.get("points"), None);
+
+    // An assignment or a DISPLAY has no value; what it printed waits.
+    assert_eq!(done(interpreter.eval("DISPLAY(threshold)")), Value::Unit);
+    assert_eq!(done(interpreter.eval("seen <- 1")), Value::Unit);
+    assert_eq!(interpreter.take_output(), "100\n");
+    assert_eq!(done(interpreter.run("DISPLAY(seen)")), "1\n".to_string());
+}
+
+#[test]
+fn test_values_cross_both_ways() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .set("names", vec!["ada", "alan"])
+        .expect("a list of strings");
+    let joined = interpreter
+        .eval("{\"first\": names[1], \"count\": LENGTH(names), \"ratio\": 1.0 / 4, \"none\": NULL}")
+        .expect("evaluates");
+    assert_eq!(joined.get("first").and_then(Value::as_str), Some("ada"));
+    assert_eq!(joined.get("count").and_then(Value::as_i64), Some(2));
+    assert_eq!(joined.get("ratio").and_then(Value::as_f64), Some(0.25));
+    assert_eq!(joined.get("none"), Some(&Value::Null));
+
+    // An object comes back shared: handed to a procedure that changes it, it
+    // is changed for the program too.
+    interpreter
+        .run(
+            "CLASS Counter
+{
+    PROCEDURE Counter()
+    {
+        self.count <- 0
+    }
+}
+counter <- Counter()
+PROCEDURE bump(c)
+{
+    c.count <- c.count + 1
+}
+",
+        )
+        .expect("runs");
+    let counter = interpreter.get("counter").expect("defined");
+    let Value::Other(shared) = &counter else {
+        panic!("an object is not copied out: {:?}", counter);
+    };
+    assert_eq!(shared.type_name(), "object");
+    for _ in 0..2 {
+        interpreter
+            .call("bump", std::slice::from_ref(&counter))
+            .expect("bumps");
+    }
+    assert_eq!(done(interpreter.eval("counter.count")), Value::from(2));
+
+    let error = interpreter
+        .set("table", Value::Dictionary(vec![(1.5.into(), "x".into())]))
+        .expect_err("a float key");
+    assert_eq!(
+        error.message,
+        "Dictionary keys must be strings, integers, or booleans"
+    );
+    let error = interpreter.set("IF", true).expect_err("a keyword");
+    assert!(
+        error
+            .message
+            .starts_with("'IF' is not a usable variable name")
+    );
+}
+
+#[test]
+fn test_errors_come_back_structured() {
+    let mut interpreter = Interpreter::new();
+    interpreter.run(RULES).expect("runs");
+
+    // A runtime error in a procedure loaded earlier points at its own line.
+    let error = interpreter
+        .call("score", &[Value::Dictionary(Vec::new())])
+        .expect_err("no items");
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(
+        error.format(interpreter.source()),
+        "Line 4, Column 15: Key not found: items
+    points <- order[\"items\"] * 2
+              ^
+  in score (line 11)"
+    );
+    assert!(interpreter.source().ends_with("score({})\n"));
+
+    // A syntax error carries the source that did not parse, which never runs.
+    let error = interpreter.eval("x <- (1 +").expect_err("unbalanced");
+    assert_eq!(error.kind, ErrorKind::Syntax);
+    assert!(
+        error.format("").starts_with("Line 1, Column"),
+        "{}",
+        error.format("")
+    );
+    assert_eq!(interpreter.get("x"), None);
+
+    let error = interpreter.call("missing", &[]).expect_err("undeclared");
+    assert_eq!(error.message, "Procedure 'missing' not found");
+
+    let error = interpreter.eval("THROW(\"rejected\")").expect_err("thrown");
+    assert_eq!(error.message, "rejected");
+
+    // EXIT ends the call and not the interpreter.
+    assert_eq!(done(interpreter.eval("EXIT(0)")), Value::Unit);
+    let error = interpreter.eval("EXIT(3)").expect_err("a failing status");
+    assert_eq!(error.message, "The program ended with EXIT(3)");
+    assert_eq!(done(interpreter.eval("threshold")), Value::from(10));
+}
+
+#[test]
+fn test_a_restricted_interpreter_gives_every_call_its_limits() {
+    let limits = Limits {
+        steps: Some(50),
+        ..Limits::default()
+    };
+    let mut interpreter = Interpreter::restricted(Permissions::none(), limits);
+    interpreter
+        .run("PROCEDURE spin(n)\n{\n    REPEAT n TIMES\n    {\n        x <- 1\n    }\n}\n")
+        .expect("declares");
+    // Forty statements each time, which fifty covers however often it is called.
+    for _ in 0..5 {
+        interpreter
+            .call("spin", &[Value::from(39)])
+            .expect("in budget");
+    }
+    let error = interpreter
+        .call("spin", &[Value::from(1000)])
+        .expect_err("over budget");
+    assert_eq!(error.kind, ErrorKind::Limit);
+
+    let error = interpreter
+        .eval("READFILE(\"Cargo.toml\")")
+        .expect_err("no reading");
+    assert_eq!(
+        error.message,
+        "Permission denied: READFILE needs --allow-read to read 'Cargo.toml'"
+    );
+}
@@ -21,7 +21,7 @@ fn test_try_catch() {
                 x <- 1 / 0
                 DISPLAY("After error")
             } CATCH (err) {
-                DISPLAY("Caught error: " + err)
+                DISPLAY("Caught error: " + err["message"])
             }
             "#,
         "Before error\nCaught error: Division by zero",
@@ -45,7 +45,7 @@ fn test_try_catch() {
                 list <- [1, 2, 3]
                 DISPLAY(list[4])
             } CATCH (err) {
-                DISPLAY("List error: " + err)
+                DISPLAY("List error: " + err["message"])
             }
             "#,
         "List error: List index out of bounds: 4 (size: 3)",
@@ -420,7 +420,7 @@ fn test_parser_error_unexpected_token() {
 #[test]
 fn test_try_catch_error_message_only() {
     assert_output(
-        "TRY {\n    x <- 1 / 0\n} CATCH (e) {\n    DISPLAY(e)\n}",
+        "TRY {\n    x <- 1 / 0\n} CATCH (e) {\n    DISPLAY(e[\"message\"])\n}",
         "Division by zero",
     );
 }
@@ -428,7 +428,7 @@ fn test_try_catch_error_message_only() {
 #[test]
 fn test_try_catch_list_error_message() {
     assert_output(
-        "TRY {\n    list <- [1]\n    DISPLAY(list[5])\n} CATCH (e) {\n    DISPLAY(e)\n}",
+        "TRY {\n    list <- [1]\n    DISPLAY(list[5])\n} CATCH (e) {\n    DISPLAY(e[\"message\"])\n}",
         "List index out of bounds: 5 (size: 1)",
     );
 }
@@ -436,7 +436,7 @@ fn test_try_catch_list_error_message() {
 #[test]
 fn test_try_catch_undefined_var_message() {
     assert_output(
-        "TRY {\n    DISPLAY(noSuchVar)\n} CATCH (e) {\n    DISPLAY(e)\n}",
+        "TRY {\n    DISPLAY(noSuchVar)\n} CATCH (e) {\n    DISPLAY(e[\"message\"])\n}",
         "Undefined variable: noSuchVar",
     );
 }
@@ -448,7 +448,7 @@ fn test_try_catch_in_procedure() {
              TRY {\n\
                  RETURN(a / b)\n\
              } CATCH (e) {\n\
-                 RETURN(e)\n\
+                 RETURN(e[\"message\"])\n\
              }\n\
          }\n\
          DISPLAY(safe_div(10, 0))",
@@ -608,9 +608,14 @@ fn test_not_boolean_error() {
 }
 
 #[test]
-fn test_class_decl_not_implemented() {
-    let err = get_error("CLASS Foo\n{\nPROCEDURE bar()\n{\nDISPLAY(1)\n}\n}");
-    assert!(err.contains("not yet implemented"), "{}", err);
+fn test_class_body_statement_error() {
+    let err = get_error("CLASS Foo\n{\nx <- 1\nPROCEDURE bar()\n{\nDISPLAY(1)\n}\n}");
+    assert!(
+        err.contains("Only PROCEDURE declarations may appear in the body of CLASS Foo"),
+        "{}",
+        err
+    );
+    assert!(err.contains("Line 3"), "Should report line 3: {}", err);
 }
 
 // ---------------------------------------------------------------------------
@@ -952,3 +957,264 @@ fn test_catch_output_ordering_across_procedure_boundary() {
         "start\na1\nc1\na2\nc2\na3\nc3\nend",
     );
 }
+
+// ---------------------------------------------------------------------------
+// Structured errors, THROW and FINALLY
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_caught_error_carries_kind_and_location() {
+    assert_output(
+        "x <- 1\n\
+         TRY {\n\
+         \x20   y <- x / 0\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(e[\"kind\"])\n\
+         \x20   DISPLAY(e[\"line\"])\n\
+         \x20   DISPLAY(e[\"column\"])\n\
+         \x20   DISPLAY(e[\"file\"])\n\
+         \x20   DISPLAY(e[\"value\"])\n\
+         }",
+        "runtime\n3\n10\nNULL\nNULL",
+    );
+}
+
+#[test]
+fn test_caught_error_stack_names_each_call() {
+    assert_output(
+        "PROCEDURE inner() {\n\
+         \x20   RETURN 1 / 0\n\
+         }\n\
+         PROCEDURE outer() {\n\
+         \x20   RETURN inner()\n\
+         }\n\
+         TRY {\n\
+         \x20   outer()\n\
+         } CATCH (e) {\n\
+         \x20   FOR EACH frame IN e[\"stack\"] {\n\
+         \x20       DISPLAY(frame[\"name\"] + \" \" + TOSTRING(frame[\"line\"]))\n\
+         \x20   }\n\
+         }",
+        "outer 8\ninner 5",
+    );
+}
+
+#[test]
+fn test_caught_eval_parse_error_is_a_syntax_error() {
+    assert_output(
+        "TRY {\n    EVAL(\"1 +\")\n} CATCH (e) {\n    DISPLAY(e[\"kind\"])\n}",
+        "syntax",
+    );
+}
+
+#[test]
+fn test_throw_any_value() {
+    assert_output(
+        "TRY {\n\
+         \x20   THROW [1, 2]\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(e[\"message\"])\n\
+         \x20   DISPLAY(e[\"kind\"])\n\
+         \x20   DISPLAY(e[\"value\"][1])\n\
+         \x20   DISPLAY(e[\"line\"])\n\
+         }",
+        "[1, 2]\nthrown\n1\n2",
+    );
+}
+
+#[test]
+fn test_throw_dictionary_names_message_and_kind() {
+    assert_output(
+        "TRY {\n\
+         \x20   THROW {\"kind\": \"ValueError\", \"message\": \"negative\", \"got\": -1}\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(e[\"kind\"] + \": \" + e[\"message\"])\n\
+         \x20   DISPLAY(e[\"value\"][\"got\"])\n\
+         }",
+        "ValueError: negative\n-1",
+    );
+}
+
+#[test]
+fn test_throw_object_names_message_and_kind() {
+    assert_output(
+        "CLASS NotFound {\n\
+         \x20   PROCEDURE NotFound(name) {\n\
+         \x20       self.kind <- \"NotFound\"\n\
+         \x20       self.message <- name + \" is missing\"\n\
+         \x20   }\n\
+         }\n\
+         TRY {\n\
+         \x20   THROW NotFound(\"config\")\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(e[\"kind\"] + \": \" + e[\"message\"])\n\
+         \x20   DISPLAY(TYPEOF(e[\"value\"]))\n\
+         }",
+        "NotFound: config is missing\nNotFound",
+    );
+}
+
+#[test]
+fn test_rethrowing_a_caught_error_keeps_message_and_kind() {
+    assert_output(
+        "TRY {\n\
+         \x20   TRY {\n\
+         \x20       x <- [][3]\n\
+         \x20   } CATCH (inner) {\n\
+         \x20       THROW inner\n\
+         \x20   }\n\
+         } CATCH (outer) {\n\
+         \x20   DISPLAY(outer[\"kind\"])\n\
+         \x20   DISPLAY(outer[\"message\"])\n\
+         }",
+        "runtime\nList index out of bounds: 3 (size: 0)",
+    );
+}
+
+#[test]
+fn test_uncaught_throw_is_reported_like_an_error() {
+    let err = get_error("DISPLAY(\"a\")\nTHROW \"custom failure\"");
+    assert!(err.contains("Line 2, Column 1: custom failure"), "{}", err);
+}
+
+#[test]
+fn test_throw_requires_a_value() {
+    let err = get_error("THROW\nDISPLAY(1)");
+    assert!(err.contains("Expected a value to THROW"), "{}", err);
+}
+
+#[test]
+fn test_finally_runs_after_try_and_after_catch() {
+    assert_output(
+        "TRY {\n\
+         \x20   DISPLAY(\"try\")\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(\"catch\")\n\
+         } FINALLY {\n\
+         \x20   DISPLAY(\"finally\")\n\
+         }\n\
+         TRY {\n\
+         \x20   x <- 1 / 0\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(\"catch\")\n\
+         }\n\
+         FINALLY\n\
+         {\n\
+         \x20   DISPLAY(\"finally\")\n\
+         }",
+        "try\nfinally\ncatch\nfinally",
+    );
+}
+
+#[test]
+fn test_finally_runs_on_return() {
+    assert_output(
+        "PROCEDURE f() {\n\
+         \x20   TRY {\n\
+         \x20       RETURN \"returned\"\n\
+         \x20   } FINALLY {\n\
+         \x20       DISPLAY(\"cleanup\")\n\
+         \x20   }\n\
+         }\n\
+         DISPLAY(f())",
+        "cleanup\nreturned",
+    );
+}
+
+#[test]
+fn test_finally_without_catch_lets_the_error_through() {
+    let err = get_error(
+        "TRY {\n\
+         \x20   x <- 1 / 0\n\
+         } FINALLY {\n\
+         \x20   DISPLAY(\"cleanup\")\n\
+         }\n\
+         DISPLAY(\"not reached\")",
+    );
+    assert!(err.contains("Division by zero"), "{}", err);
+    assert_output(
+        "TRY {\n\
+         \x20   TRY {\n\
+         \x20       THROW \"inner\"\n\
+         \x20   } FINALLY {\n\
+         \x20       DISPLAY(\"cleanup\")\n\
+         \x20   }\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(e[\"message\"])\n\
+         }",
+        "cleanup\ninner",
+    );
+}
+
+#[test]
+fn test_finally_runs_when_the_catch_block_fails() {
+    assert_output(
+        "TRY {\n\
+         \x20   TRY {\n\
+         \x20       x <- 1 / 0\n\
+         \x20   } CATCH (e) {\n\
+         \x20       THROW \"handler failed\"\n\
+         \x20   } FINALLY {\n\
+         \x20       DISPLAY(\"cleanup\")\n\
+         \x20   }\n\
+         } CATCH (e) {\n\
+         \x20   DISPLAY(e[\"message\"])\n\
+         }",
+        "cleanup\nhandler failed",
+    );
+}
+
+#[test]
+fn test_finally_runs_on_exit() {
+    assert_output(
+        "TRY {\n\
+         \x20   EXIT()\n\
+         } FINALLY {\n\
+         \x20   DISPLAY(\"cleanup\")\n\
+         }\n\
+         DISPLAY(\"not reached\")",
+        "cleanup",
+    );
+}
+
+#[test]
+fn test_finally_runs_on_break_and_continue() {
+    assert_output(
+        "FOR EACH i IN [1, 2, 3] {\n\
+         \x20   TRY {\n\
+         \x20       IF i = 1 {\n\
+         \x20           CONTINUE\n\
+         \x20       }\n\
+         \x20       BREAK\n\
+         \x20   } FINALLY {\n\
+         \x20       DISPLAY(i)\n\
+         \x20   }\n\
+         }",
+        "1\n2",
+    );
+}
+
+#[test]
+fn test_an_interrupted_finally_replaces_the_outcome() {
+    assert_output(
+        "PROCEDURE f() {\n\
+         \x20   TRY {\n\
+         \x20       x <- 1 / 0\n\
+         \x20   } FINALLY {\n\
+         \x20       RETURN \"from finally\"\n\
+         \x20   }\n\
+         }\n\
+         DISPLAY(f())",
+        "from finally",
+    );
+}
+
+#[test]
+fn test_try_needs_catch_or_finally() {
+    let err = get_error("TRY {\n    x <- 1\n}\nDISPLAY(x)");
+    assert!(
+        err.contains("Expected 'catch' or 'finally' after try block"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,244 @@
+//! `fpli fmt`: the canonical layout, comments kept, and formatting that changes
+//! neither what a program means nor, done twice, anything at all.
+
+use super::assert_output;
+use super::docs::{is_a_template, psl_blocks};
+use crate::format::format_source;
+use crate::lexer::Lexer;
+use crate::parser;
+
+fn fmt(source: &str) -> String {
+    format_source(source).unwrap_or_else(|e| panic!("did not format: {}", e.format(source)))
+}
+
+/// The parsed program with every span taken out, so that two layouts of the same
+/// program compare equal.
+fn shape(source: &str) -> String {
+    let ast = parser::parse(Lexer::new(source).tokenize(), false)
+        .unwrap_or_else(|e| panic!("did not parse: {}\n{source}", e.format(source)));
+    let mut debug = format!("{:?}", ast);
+    while let Some(at) = debug.find(", span: Span {") {
+        let close = at + debug[at..].find('}').expect("a span closes");
+        debug.replace_range(at..=close, "");
+    }
+    debug
+}
+
+/// Formatting `source` must keep its meaning and be finished in one pass.
+fn assert_stable(name: &str, source: &str) {
+    let once = fmt(source);
+    assert_eq!(
+        shape(source),
+        shape(&once),
+        "formatting changed the program {name}:\n{once}"
+    );
+    assert_eq!(
+        once,
+        fmt(&once),
+        "formatting {name} a second time changed it"
+    );
+}
+
+#[test]
+fn test_every_documented_example_formats_to_the_same_program() {
+    let guide = include_str!("../../Pseudolang.md");
+    for (line, source) in psl_blocks(guide) {
+        if !is_a_template(&source) {
+            assert_stable(&format!("at Pseudolang.md:{line}"), &source);
+        }
+    }
+}
+
+#[test]
+fn test_every_fixture_program_formats_to_the_same_program() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut pending = vec![dir];
+    let mut checked = 0;
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).expect("read fixture dir") {
+            let path = entry.expect("fixture entry").path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|e| e == "psl") {
+                let source = std::fs::read_to_string(&path).expect("read fixture");
+                assert_stable(&path.display().to_string(), &source);
+                checked += 1;
+            }
+        }
+    }
+    assert!(checked > 5, "only {checked} fixtures found");
+}
+
+#[test]
+fn test_braces_go_on_their_own_lines_and_bodies_are_indented() {
+    assert_eq!(
+        fmt("IF x > 1 {\n  DISPLAY(x)\n} ELSE {\n DISPLAY(0) }\n"),
+        "IF x > 1\n{\n    DISPLAY(x)\n}\nELSE\n{\n    DISPLAY(0)\n}\n"
+    );
+}
+
+#[test]
+fn test_redundant_parentheses_are_removed() {
+    assert_eq!(
+        fmt("PROCEDURE f(a)\n{\nRETURN (a)\n}\nIF (a = 1) { DISPLAY((a + 1) * 2) }\n"),
+        "PROCEDURE f(a)\n{\n    RETURN a\n}\nIF a = 1\n{\n    DISPLAY((a + 1) * 2)\n}\n"
+    );
+}
+
+#[test]
+fn test_parentheses_the_grammar_needs_are_kept() {
+    assert_eq!(fmt("x <- a - (b - c)\n"), "x <- a - (b - c)\n");
+    assert_eq!(fmt("x <- NOT (a AND b)\n"), "x <- NOT (a AND b)\n");
+    assert_eq!(fmt("x <- (1 + 2)[1]\n"), "x <- (1 + 2)[1]\n");
+    // A condition or value that merely starts with a parenthesised part is wrapped
+    // whole, or IF and RETURN would end it at the first ')'.
+    assert_eq!(
+        fmt("IF ((a + b) * 2 > c) { DISPLAY(1) }\n"),
+        "IF ((a + b) * 2 > c)\n{\n    DISPLAY(1)\n}\n"
+    );
+}
+
+#[test]
+fn test_spacing_is_normalised() {
+    assert_eq!(
+        fmt("x<-[1,2,  3]\ny <- {\"a\":1}\nDISPLAY(f(x,y))\n"),
+        "x <- [1, 2, 3]\ny <- {\"a\": 1}\nDISPLAY(f(x, y))\n"
+    );
+}
+
+#[test]
+fn test_every_kind_of_comment_is_kept() {
+    let source = "\
+COMMENT first
+// second
+# third
+COMMENTBLOCK
+  fourth
+COMMENTBLOCK
+DISPLAY(1)
+";
+    assert_eq!(fmt(source), source);
+}
+
+#[test]
+fn test_a_comment_after_a_statement_stays_on_its_line() {
+    assert_eq!(
+        fmt("x <- 1    // one\nIF x = 1 {\n      DISPLAY(x)  COMMENT shown\n}\n"),
+        "x <- 1  // one\nIF x = 1\n{\n    DISPLAY(x)  COMMENT shown\n}\n"
+    );
+}
+
+#[test]
+fn test_comments_inside_blocks_keep_their_place() {
+    let source = "\
+PROCEDURE f()
+{
+    # before
+    DISPLAY(1)
+    # between
+    DISPLAY(2)
+    # at the end
+}
+";
+    assert_eq!(fmt(source), source);
+}
+
+#[test]
+fn test_a_comment_inside_an_expression_moves_before_its_statement() {
+    assert_eq!(fmt("xs <- [1, # one\n  2]\n"), "# one\nxs <- [1, 2]\n");
+}
+
+#[test]
+fn test_blank_lines_are_kept_but_not_doubled() {
+    assert_eq!(
+        fmt("a <- 1\n\n\n\nb <- 2\nc <- 3\n\n"),
+        "a <- 1\n\nb <- 2\nc <- 3\n"
+    );
+}
+
+#[test]
+fn test_strings_and_numbers_are_copied_as_written() {
+    let source = "\
+a <- \"tab\\there \\\"quoted\\\"\"
+b <- r\"C:\\raw\"
+c <- f\"{a} and {b}\"
+d <- 1.50
+e <- \"\"\"two
+  lines\"\"\"
+";
+    assert_eq!(fmt(source), source);
+}
+
+#[test]
+fn test_a_long_list_is_written_one_element_per_line() {
+    let names: Vec<String> = (1..=12)
+        .map(|i| format!("\"element number {i}\""))
+        .collect();
+    let formatted = fmt(&format!("xs <- [{}]\n", names.join(", ")));
+    assert!(formatted.starts_with("xs <- [\n    \"element number 1\",\n"));
+    assert!(formatted.ends_with("    \"element number 12\"\n]\n"));
+}
+
+#[test]
+fn test_else_if_chains_stay_flat() {
+    assert_eq!(
+        fmt("IF a {\nDISPLAY(1)\n} ELSE IF b {\nDISPLAY(2)\n} ELSE {\nDISPLAY(3)\n}\n"),
+        "IF a\n{\n    DISPLAY(1)\n}\nELSE IF b\n{\n    DISPLAY(2)\n}\nELSE\n{\n    DISPLAY(3)\n}\n"
+    );
+}
+
+#[test]
+fn test_every_statement_form_survives_formatting() {
+    assert_stable(
+        "the kitchen sink",
+        r#"
+IMPORT helpers
+IMPORT "lib/table.psl" AS table
+EXPORT main, Point
+PRIVATE secret <- 1
+CLASS Point { PROCEDURE init(x, y) { self.x <- x
+self.y <- y } }
+PROCEDURE main(first, second <- first * 2, rest...) {
+  a, [b, c] <- [1, [2, 3]]
+  FOR EACH i, item IN rest { IF item = NULL { CONTINUE } }
+  FOR i FROM 1 TO 10 STEP 2 { REPEAT 2 TIMES { BREAK } }
+  REPEAT UNTIL (a > 3) { a <- a + 1 }
+  REPEAT WHILE a < 6 { a <- a + 1 }
+  MATCH a { CASE 1 TO 3 IF b > 0 { DISPLAY("low") } CASE [x, more...] { } CASE {"k": v} { } ELSE { } }
+  TRY { THROW {"message": "m"} } CATCH (e) { DISPLAYINLINE(e["message"]) } FINALLY { DISPLAY() }
+  handler <- PROCEDURE (x) { RETURN -x }
+  DISPLAY(handler(2) MOD 3 NOT= 1 OR NOT TRUE)
+  p <- Point(1, 2)
+  p.x <- p.move(1).x
+  table.rows()[1] <- LENGTH(SORT(INSERT(APPEND(xs, 1), 1, 2)))
+  RETURN
+}
+"#,
+    );
+}
+
+#[test]
+fn test_formatted_code_runs_the_same() {
+    let source = "PROCEDURE f(n) { IF (n <= 1) { RETURN (1) } RETURN (n * f(n - 1)) }\nDISPLAY(f(5))  # 120\n";
+    assert_output(source, "120");
+    assert_output(&fmt(source), "120");
+}
+
+#[test]
+fn test_a_file_that_does_not_parse_is_left_to_its_error() {
+    let error = format_source("x <- (1\n").unwrap_err();
+    assert!(
+        error.message.contains("Expected ')'"),
+        "got: {}",
+        error.message
+    );
+}
+
+#[test]
+fn test_a_comment_after_a_block_stays_after_it() {
+    let source = "IF a\n{\n    DISPLAY(1)\n}\n\n# next   \nDISPLAY(2)\n";
+    assert_eq!(
+        fmt(source),
+        "IF a\n{\n    DISPLAY(1)\n}\n\n# next\nDISPLAY(2)\n"
+    );
+}
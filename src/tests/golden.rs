@@ -0,0 +1,178 @@
+//! `fpli test --golden`: finding cases, judging what they did, and the diff shown
+//! when the output is wrong. Running them is a child process, and is covered by
+//! the integration suite.
+
+use super::Scratch;
+use crate::golden::{Ran, bless, discover, mismatches, stderr_mismatch, unified_diff};
+
+fn ran(status: i32, stdout: &str) -> Ran {
+    Ran {
+        status: Some(status),
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+    }
+}
+
+#[test]
+fn test_a_diff_shows_changed_lines_with_context() {
+    let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10";
+    let actual = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10";
+    assert_eq!(
+        unified_diff(expected, actual),
+        "--- expected\n+++ actual\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8"
+    );
+}
+
+#[test]
+fn test_distant_changes_get_hunks_of_their_own() {
+    let expected: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+    let mut actual = expected.clone();
+    actual[0] = "one".to_string();
+    actual[19] = "twenty".to_string();
+    let diff = unified_diff(&expected.join("\n"), &actual.join("\n"));
+    let headers: Vec<&str> = diff.lines().filter(|l| l.starts_with("@@")).collect();
+    assert_eq!(headers, ["@@ -1,4 +1,4 @@", "@@ -17,4 +17,4 @@"]);
+}
+
+#[test]
+fn test_a_diff_of_added_and_missing_lines() {
+    assert_eq!(
+        unified_diff("a\nb", "a\nb\nc"),
+        "--- expected\n+++ actual\n@@ -1,2 +1,3 @@\n a\n b\n+c"
+    );
+    assert_eq!(
+        unified_diff("a", ""),
+        "--- expected\n+++ actual\n@@ -1 +0,0 @@\n-a"
+    );
+}
+
+#[test]
+fn test_a_long_output_with_one_change_still_gets_a_diff() {
+    let expected: Vec<String> = (1..=50_000).map(|n| n.to_string()).collect();
+    let mut actual = expected.clone();
+    actual[24_999] = "changed".to_string();
+    let diff = unified_diff(&expected.join("\n"), &actual.join("\n"));
+    assert!(
+        diff.contains("@@ -24997,7 +24997,7 @@\n 24997\n 24998\n 24999\n-25000\n+changed\n"),
+        "{}",
+        diff
+    );
+}
+
+#[test]
+fn test_a_large_mismatch_shows_only_its_first_difference() {
+    // Far past what a line-by-line table could hold without gigabytes.
+    let expected: Vec<String> = (1..=40_000).map(|n| n.to_string()).collect();
+    let mut actual: Vec<String> = (1..=60_000).map(|n| format!("line {}", n)).collect();
+    actual[0] = "1".to_string();
+    assert_eq!(
+        unified_diff(&expected.join("\n"), &actual.join("\n")),
+        "--- expected\n+++ actual\nToo many lines differ to diff (40000 expected, 60000 printed); \
+         the first difference is at line 2:\n-2\n+line 2"
+    );
+}
+
+#[test]
+fn test_cases_are_found_with_their_fixtures() {
+    let scratch = Scratch::new();
+    scratch.write("cases/echo.psl", "DISPLAY(INPUT())\n");
+    scratch.write("cases/echo.expected", "hi\n");
+    scratch.write("cases/echo.stdin", "hi\n");
+    scratch.write("cases/echo.args", "--loud  twice\n");
+    scratch.write("cases/echo.exit", "3\n");
+    scratch.write("cases/helpers.psl", "PROCEDURE f() { }\n");
+    scratch.write("cases/multi/main.psl", "IMPORT \"lib.psl\"\n");
+    scratch.write("cases/multi/lib.psl", "");
+    scratch.write("cases/multi/expected", "");
+    scratch.write("cases/nested/deeper.psl", "DISPLAY(1)\n");
+    scratch.write("cases/nested/deeper.expected", "1\n");
+
+    let cases = discover(&scratch.path("cases")).expect("cases load");
+    let names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["echo", "multi", "nested/deeper"]);
+
+    let echo = &cases[0];
+    assert_eq!(echo.args, ["--loud", "twice"]);
+    assert_eq!(echo.stdin.as_deref(), Some("hi\n"));
+    assert_eq!(echo.exit, 3);
+    assert_eq!(cases[1].entry, "main.psl");
+    assert_eq!(cases[2].exit, 0);
+}
+
+#[test]
+fn test_header_comments_set_up_a_case_and_files_override_them() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "fails.psl",
+        "# ARGS: -n 3\n# STDIN: first\n# STDIN: second\n# EXIT: 1\n# STDERR: boom\nDISPLAY(1)\n# EXIT: 9\n",
+    );
+    scratch.write("fails.expected", "");
+    scratch.write("fails.args", "--other\n");
+
+    let case = &discover(&scratch.path("")).expect("cases load")[0];
+    assert_eq!(case.args, ["--other"]);
+    assert_eq!(case.stdin.as_deref(), Some("first\nsecond\n"));
+    assert_eq!(case.exit, 1);
+    assert_eq!(case.stderr_contains.as_deref(), Some("boom"));
+}
+
+#[test]
+fn test_a_bad_exit_status_file_is_an_error() {
+    let scratch = Scratch::new();
+    scratch.write("x.psl", "");
+    scratch.write("x.expected", "");
+    scratch.write("x.exit", "one\n");
+    let error = discover(&scratch.path("")).unwrap_err();
+    assert!(
+        error.contains("the exit status must be a number"),
+        "got: {error}"
+    );
+}
+
+#[test]
+fn test_mismatches_ignore_trailing_blank_lines_and_line_endings() {
+    let scratch = Scratch::new();
+    scratch.write("x.psl", "");
+    scratch.write("x.expected", "a\r\nb\r\n\r\n");
+    let case = &discover(&scratch.path("")).expect("cases load")[0];
+    assert!(mismatches(case, &ran(0, "a\nb")).is_empty());
+
+    let found = mismatches(case, &ran(2, "a\nc\n"));
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0], "exit status 2, expected 0");
+    assert!(found[1].contains("-b\n+c"), "got: {}", found[1]);
+}
+
+#[test]
+fn test_standard_error_is_checked_only_when_asked() {
+    let scratch = Scratch::new();
+    scratch.write("x.psl", "# STDERR: Division by zero\n");
+    scratch.write("x.expected", "");
+    let case = &discover(&scratch.path("")).expect("cases load")[0];
+    let mut outcome = ran(1, "");
+    outcome.stderr = "Error: Line 1: Division by zero".to_string();
+    assert!(stderr_mismatch(case, &outcome).is_none());
+    outcome.stderr = "Error: something else".to_string();
+    assert!(stderr_mismatch(case, &outcome).is_some());
+}
+
+#[test]
+fn test_blessing_records_the_output_and_a_changed_status() {
+    let scratch = Scratch::new();
+    scratch.write("x.psl", "");
+    scratch.write("x.expected", "old\n");
+    scratch.write("y.psl", "");
+    scratch.write("y.expected", "old\n");
+    let cases = discover(&scratch.path("")).expect("cases load");
+
+    bless(&cases[0], &ran(4, "new\n")).expect("bless x");
+    bless(&cases[1], &ran(0, "new\n")).expect("bless y");
+    let read = |name: &str| std::fs::read_to_string(scratch.path(name)).unwrap();
+    assert_eq!(read("x.expected"), "new\n");
+    assert_eq!(read("x.exit"), "4\n");
+    assert_eq!(read("y.expected"), "new\n");
+    assert!(!scratch.path("y.exit").exists());
+
+    let reloaded = discover(&scratch.path("")).expect("cases reload");
+    assert!(mismatches(&reloaded[0], &ran(4, "new\n")).is_empty());
+}
@@ -229,17 +229,33 @@ fn test_multiline_string_literals_parse() {
 }
 
 #[test]
-fn test_calling_an_indexed_value_is_refused() {
+fn test_calling_an_indexed_value() {
     // Two silent readings preceded this: first the indices were dropped and `a` was
     // called as a procedure, then the `(9)` became a separate statement evaluating to
     // nothing. Either way `handlers[1](arg)` looked like a call and did not call.
+    assert_output(
+        r#"
+        PROCEDURE double(x)
+        {
+            RETURN x * 2
+        }
+        handlers <- [double]
+        DISPLAY(handlers[1](9))
+        "#,
+        "18",
+    );
+    // What the index produced is checked when called, not dropped.
     let err = get_error(
         r#"
         a <- [1, 2]
         a[1](9)
         "#,
     );
-    assert!(err.contains("Cannot call an indexed value"), "{}", err);
+    assert!(
+        err.contains("Cannot call a value of type integer: only procedures can be called"),
+        "{}",
+        err
+    );
 }
 
 #[test]
@@ -0,0 +1,264 @@
+//! The limits of a run: what each one counts, the error that ends a run going
+//! over it, and that nothing in the program can catch that error.
+
+use crate::error::{ErrorKind, PSLError};
+use crate::interpreter::{self, OutputMode, RunOptions};
+use crate::lexer::Lexer;
+use crate::limits::{self, Limits};
+use crate::parser;
+use std::time::{Duration, Instant};
+
+/// Run `source` allowed only as much as `limits`.
+fn run(source: &str, limits: Limits) -> Result<String, PSLError> {
+    let ast = parser::parse_with_source(Lexer::new(source).tokenize(), source, false)
+        .expect("the program parses");
+    interpreter::run_with_mode(
+        ast,
+        source,
+        OutputMode::Capture,
+        false,
+        RunOptions {
+            limits,
+            ..RunOptions::default()
+        },
+    )
+    .map(|output| output.trim_end().to_string())
+}
+
+fn run_limited(source: &str, limits: Limits) -> Result<String, String> {
+    run(source, limits).map_err(|e| e.message)
+}
+
+/// The message of the error that stopped `source`, which has to be a limit's.
+fn stopped(source: &str, limits: Limits) -> String {
+    let error = run(source, limits).expect_err("the run goes over its limit");
+    assert_eq!(error.kind, ErrorKind::Limit, "{}", error.message);
+    error.message
+}
+
+#[test]
+fn test_the_step_budget_counts_statements() {
+    let source = "// Comments are not counted.
+total <- 0
+REPEAT 3 TIMES
+{
+    total <- total + 1
+}
+DISPLAY(total)";
+    // Five statements at the top level and in the loop, the loop's three times.
+    let budget = |steps| Limits {
+        steps: Some(steps),
+        ..Limits::default()
+    };
+    assert_eq!(run_limited(source, budget(6)), Ok("3".to_string()));
+    assert_eq!(
+        stopped(source, budget(5)),
+        "Step limit exceeded: the program ran 5 statements (--max-steps)"
+    );
+}
+
+#[test]
+fn test_a_limit_is_not_caught_and_finally_does_not_run() {
+    // Were FINALLY to run, its EXIT(0) would end the run as a success.
+    let source = "TRY
+{
+    REPEAT WHILE(TRUE)
+    {
+        x <- 1
+    }
+} CATCH (err)
+{
+    DISPLAY(\"caught\")
+} FINALLY
+{
+    EXIT(0)
+}";
+    let limits = Limits {
+        steps: Some(100),
+        ..Limits::default()
+    };
+    assert!(stopped(source, limits).starts_with("Step limit exceeded"));
+
+    let source = "PROCEDURE down(n)
+{
+    RETURN down(n + 1)
+}
+ASSERTRAISES(PROCEDURE () { down(1) })";
+    let limits = Limits {
+        stack_depth: 20,
+        ..Limits::default()
+    };
+    assert_eq!(
+        stopped(source, limits),
+        "Stack overflow: maximum recursion depth exceeded (limit: 20, --max-depth)"
+    );
+}
+
+#[test]
+fn test_the_loop_limit_applies_to_every_loop_each_time_it_runs() {
+    let limits = Limits {
+        loop_iterations: Some(3),
+        ..Limits::default()
+    };
+    for source in [
+        "REPEAT 4 TIMES\n{\n}",
+        "FOR i FROM 1 TO 4\n{\n}",
+        "FOR EACH c IN \"four\"\n{\n}",
+        "n <- 0\nREPEAT UNTIL(n = 4)\n{\n    n <- n + 1\n}",
+    ] {
+        assert_eq!(
+            stopped(source, limits.clone()),
+            "Maximum loop iterations exceeded (limit: 3, --max-loop-iterations)",
+            "{}",
+            source
+        );
+    }
+    // Nine turns in all, but no loop goes round more than three times at once.
+    let source = "count <- 0
+REPEAT 3 TIMES
+{
+    FOR EACH item IN [1, 2, 3]
+    {
+        count <- count + 1
+    }
+}
+DISPLAY(count)";
+    assert_eq!(run_limited(source, limits), Ok("9".to_string()));
+}
+
+#[test]
+fn test_loops_are_not_limited_by_default() {
+    assert_eq!(
+        run_limited(
+            "n <- 0\nREPEAT UNTIL(n = 1000001)\n{\n    n <- n + 1\n}\nDISPLAY(n)",
+            Limits::default()
+        ),
+        Ok("1000001".to_string())
+    );
+}
+
+#[test]
+fn test_the_timeout_stops_a_loop_with_nothing_in_it() {
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(200)),
+        ..Limits::default()
+    };
+    assert_eq!(
+        stopped("REPEAT WHILE(TRUE)\n{\n}", limits),
+        "Time limit exceeded: the program ran for more than 0.2 seconds (--timeout)"
+    );
+}
+
+#[test]
+fn test_sleep_does_not_outlast_the_timeout() {
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(200)),
+        ..Limits::default()
+    };
+    let started = Instant::now();
+    assert!(stopped("SLEEP(30)\nDISPLAY(\"woke\")", limits).starts_with("Time limit exceeded"));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn test_the_memory_cap_stops_a_growing_value() {
+    let limits = Limits {
+        memory: Some(1 << 20),
+        ..Limits::default()
+    };
+    let source = "items <- []
+REPEAT WHILE(TRUE)
+{
+    APPEND(items, \"a hundred bytes of text, or thereabouts, added to the list on every turn of the loop\")
+}";
+    let message = stopped(source, limits.clone());
+    assert!(
+        message.starts_with("Memory limit exceeded: the program's values take about "),
+        "{}",
+        message
+    );
+    assert!(
+        message.ends_with(", more than 1048576 (--max-memory)"),
+        "{}",
+        message
+    );
+
+    // Values held by a caller count, though the closure it calls runs in a scope
+    // of its own: neither list alone is over the cap, but the two together are.
+    let source = "more <- PROCEDURE ()
+{
+    mine <- []
+    REPEAT 6000 TIMES
+    {
+        APPEND(mine, \"text that only this closure's own scope holds, a hundred bytes at a time, or so\")
+    }
+    RETURN LENGTH(mine)
+}
+PROCEDURE fill()
+{
+    kept <- []
+    REPEAT 6000 TIMES
+    {
+        APPEND(kept, \"text that only the caller's scope holds, a hundred bytes at a time, more or less\")
+    }
+    RETURN more()
+}
+DISPLAY(fill())";
+    assert!(stopped(source, limits.clone()).starts_with("Memory limit exceeded"));
+    let roomier = Limits {
+        memory: Some(4 << 20),
+        ..Limits::default()
+    };
+    assert_eq!(run_limited(source, roomier), Ok("6000".to_string()));
+
+    // A dictionary shared a thousand times over is counted once.
+    let source = "big <- {}
+FOR i FROM 1 TO 5000
+{
+    big[i] <- i
+}
+copies <- []
+REPEAT 1000 TIMES
+{
+    APPEND(copies, big)
+}
+DISPLAY(LENGTH(copies))";
+    assert_eq!(run_limited(source, limits), Ok("1000".to_string()));
+}
+
+#[test]
+fn test_eval_nesting_can_be_limited() {
+    let limits = Limits {
+        meta_depth: 2,
+        ..Limits::default()
+    };
+    assert_eq!(
+        run_limited("DISPLAY(EVAL(\"EVAL(\\\"1 + 1\\\")\"))", limits.clone()),
+        Ok("2".to_string())
+    );
+    let message = stopped(
+        "DISPLAY(EVAL(\"EVAL(\\\"EVAL(\\\\\\\"1\\\\\\\")\\\")\"))",
+        limits,
+    );
+    assert!(
+        message.starts_with("Maximum EVAL nesting depth exceeded (limit: 2, --max-eval-depth)"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_sizes_and_seconds_are_read_the_way_the_flags_take_them() {
+    assert_eq!(limits::parse_size("4096"), Ok(4096));
+    assert_eq!(limits::parse_size("64K"), Ok(64 << 10));
+    assert_eq!(limits::parse_size("2m"), Ok(2 << 20));
+    assert_eq!(limits::parse_size("1G"), Ok(1 << 30));
+    assert!(limits::parse_size("12T").is_err());
+    assert!(limits::parse_size("lots").is_err());
+    assert_eq!(
+        limits::parse_seconds("1.5"),
+        Ok(Duration::from_millis(1500))
+    );
+    assert!(limits::parse_seconds("-1").is_err());
+    assert!(limits::parse_seconds("soon").is_err());
+}
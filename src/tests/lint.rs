@@ -0,0 +1,258 @@
+//! `fpli lint`: each rule, where it stays quiet, and suppression comments.
+
+use super::Scratch;
+use crate::lint::lint_source;
+use std::path::Path;
+
+/// The code and line of every warning for `source`, in order.
+fn lints_at(source: &str, path: Option<&Path>) -> Vec<(&'static str, usize)> {
+    lint_source(source, path)
+        .unwrap_or_else(|errors| panic!("does not parse: {}", errors[0].message))
+        .iter()
+        .map(|lint| {
+            let span = lint.error.span.expect("every warning has a place");
+            (lint.code, crate::error::resolve_span(source, &span).0)
+        })
+        .collect()
+}
+
+fn lints(source: &str) -> Vec<(&'static str, usize)> {
+    lints_at(source, None)
+}
+
+#[test]
+fn test_a_procedure_named_like_a_builtin() {
+    assert_eq!(
+        lints("PROCEDURE TRIM(s)\n{\n    RETURN s\n}\nDISPLAY(TRIM(\" a \"))\n"),
+        [("shadowed-builtin", 1)]
+    );
+}
+
+#[test]
+fn test_reads_before_any_assignment() {
+    let source = "DISPLAY(total)
+total <- 0
+REPEAT 3 TIMES
+{
+    DISPLAY(step)
+    step <- 1
+}
+PROCEDURE show()
+{
+    DISPLAY(nowhere)
+    DISPLAY(total)
+}
+show()
+";
+    assert_eq!(
+        lints(source),
+        [
+            ("read-before-assignment", 1),
+            ("read-before-assignment", 5),
+            ("read-before-assignment", 10),
+        ]
+    );
+}
+
+#[test]
+fn test_names_bound_other_ways_are_not_read_before_assignment() {
+    let source = "PROCEDURE caller()
+{
+    depth <- 1
+    callee()
+}
+PROCEDURE callee()
+{
+    DISPLAY(depth)
+}
+CLASS Point
+{
+    PROCEDURE Point(x)
+    {
+        self.x <- x
+    }
+}
+FOR EACH i, item IN [1]
+{
+    DISPLAY(i + item)
+}
+MATCH [1, 2]
+{
+    CASE [first, rest...] { DISPLAY(first + LENGTH(rest)) }
+}
+TRY
+{
+    THROW(\"x\")
+} CATCH (err)
+{
+    DISPLAY(err)
+}
+DISPLAY(ARGCOUNT)
+DISPLAY(Point(1).x)
+add <- PROCEDURE (n) { RETURN n + offset }
+offset <- 1
+DISPLAY(add(1))
+caller()
+";
+    assert_eq!(lints(source), []);
+}
+
+#[test]
+fn test_assignments_never_read() {
+    let source = "unused <- 1
+used <- 2
+DISPLAY(used)
+[quotient, remainder] <- [3, 2]
+DISPLAY(remainder)
+PROCEDURE work()
+{
+    scratch <- 1
+    scratch <- 2
+}
+work()
+";
+    assert_eq!(
+        lints(source),
+        [
+            ("unused-assignment", 1),
+            ("unused-assignment", 4),
+            ("unused-assignment", 8),
+        ]
+    );
+}
+
+#[test]
+fn test_exported_variables_are_read_by_importers() {
+    assert_eq!(lints("EXPORT limit\nlimit <- 10\n"), []);
+}
+
+#[test]
+fn test_code_after_return_or_exit_is_unreachable() {
+    let source = "PROCEDURE sign(n)
+{
+    IF (n < 0)
+    {
+        RETURN -1
+    }
+    ELSE
+    {
+        RETURN 1
+    }
+    DISPLAY(\"never\")
+    DISPLAY(\"nor this\")
+}
+DISPLAY(sign(2))
+EXIT(0)
+// Comments are not code.
+DISPLAY(\"never either\")
+";
+    assert_eq!(
+        lints(source),
+        [("unreachable-code", 11), ("unreachable-code", 17)]
+    );
+}
+
+#[test]
+fn test_calls_with_the_wrong_number_of_arguments() {
+    let source = "PROCEDURE area(w, h <- 1)
+{
+    RETURN w * h
+}
+PROCEDURE total(first, rest...)
+{
+    RETURN first + LENGTH(rest)
+}
+CLASS Point
+{
+    PROCEDURE Point(x, y)
+    {
+        self.x <- x
+        self.y <- y
+    }
+}
+DISPLAY(area(1) + area(1, 2) + area(1, 2, 3))
+DISPLAY(total() + total(1, 2, 3))
+DISPLAY(Point(1).x)
+";
+    let found = lint_source(source, None).expect("parses");
+    let messages: Vec<&str> = found.iter().map(|l| l.error.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Procedure 'area' expects 1 to 2 arguments, but is called with 3",
+            "Procedure 'total' expects at least 1 argument, but is called with 0",
+            "Procedure 'Point' expects 2 arguments, but is called with 1",
+        ]
+    );
+}
+
+#[test]
+fn test_assignments_to_parameters() {
+    let source = "PROCEDURE scale(n)
+{
+    n <- n * 2
+    twice <- PROCEDURE () { n <- 1 RETURN n }
+    RETURN twice() + n
+}
+DISPLAY(scale(1))
+";
+    assert_eq!(
+        lints(source),
+        [("shadowed-parameter", 3), ("shadowed-parameter", 4)]
+    );
+}
+
+#[test]
+fn test_allow_comments_silence_their_line_or_the_next() {
+    let source = "a <- 1 // lint: allow(unused-assignment)
+// lint: allow(read-before-assignment, unused-assignment)
+b <- missing
+c <- 3 // lint: allow(read-before-assignment)
+";
+    assert_eq!(lints(source), [("unused-assignment", 4)]);
+}
+
+#[test]
+fn test_names_reached_by_name_turn_the_variable_rules_off() {
+    assert_eq!(lints("SETVAR(\"x\", 1)\nDISPLAY(x)\ny <- 2\n"), []);
+    assert_eq!(lints("DISPLAY(EVAL(\"x <- 1\"))\nDISPLAY(x)\n"), []);
+}
+
+#[test]
+fn test_imports_declare_names_and_signatures() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "lib.psl",
+        "IMPORT \"more.psl\"\nlimit <- 3\nPROCEDURE clamp(n)\n{\n    RETURN MIN(n, limit)\n}\n",
+    );
+    scratch.write("more.psl", "PROCEDURE twice(n)\n{\n    RETURN n * 2\n}\n");
+    let main = "IMPORT \"lib.psl\"\nDISPLAY(clamp(limit, 1) + twice(2))\n";
+    let path = scratch.write("main.psl", main);
+    assert_eq!(lints_at(main, Some(&path)), [("argument-count", 2)]);
+
+    // An import that cannot be found could declare anything.
+    let main = "IMPORT \"missing.psl\"\nDISPLAY(anything)\n";
+    let path = scratch.write("other.psl", main);
+    assert_eq!(lints_at(main, Some(&path)), []);
+}
+
+#[test]
+fn test_a_warning_in_a_formatted_string_points_at_the_string() {
+    let source = "DISPLAY(f\"total: {missing}\")\n";
+    let found = lint_source(source, None).expect("parses");
+    assert_eq!(found.len(), 1);
+    assert_eq!(
+        found[0].format(),
+        "Line 1, Column 9: 'missing' is read before any assignment to it [read-before-assignment]
+    DISPLAY(f\"total: {missing}\")
+            ^"
+    );
+}
+
+#[test]
+fn test_a_file_that_does_not_parse_gets_its_syntax_errors() {
+    let errors = lint_source("PROCEDURE (\n", None)
+        .err()
+        .expect("syntax errors instead of warnings");
+    assert_eq!(errors[0].message, "Expected procedure name");
+}
@@ -1,4 +1,4 @@
-use super::assert_output;
+use super::{assert_output, get_error};
 
 #[test]
 fn test_list_operations() {
@@ -400,3 +400,127 @@ fn test_sort_mixed_types_does_not_crash() {
     assert_output("DISPLAY(SORT([\"b\", \"a\", \"c\"]))", "[a, b, c]");
     assert_output("DISPLAY(SORT([]))", "[]");
 }
+
+// ---------------------------------------------------------------------------
+// Unpacking a list into names
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_unpacking_a_returned_list() {
+    assert_output(
+        r#"
+        PROCEDURE divmod(a, b)
+        {
+            RETURN [(a - a MOD b) / b, a MOD b]
+        }
+        q, r <- divmod(17, 5)
+        DISPLAY(q)
+        DISPLAY(r)
+        "#,
+        "3\n2",
+    );
+}
+
+#[test]
+fn test_unpacking_swaps_without_a_temporary() {
+    assert_output(
+        "a <- 1\nb <- 2\na, b <- [b, a]\nDISPLAY(a)\nDISPLAY(b)",
+        "2\n1",
+    );
+}
+
+#[test]
+fn test_unpacking_nested_lists() {
+    assert_output(
+        r#"
+        [x, [y, z]] <- [1, [2, 3]]
+        DISPLAY(x + y + z)
+        name, [first, last] <- ["point", [10, 20]]
+        DISPLAY(name)
+        DISPLAY(last - first)
+        [only] <- [[4, 5]]
+        DISPLAY(only)
+        "#,
+        "6\npoint\n10\n[4, 5]",
+    );
+}
+
+#[test]
+fn test_unpacking_the_wrong_number_of_elements_is_an_error() {
+    let err = get_error("a, b <- [1, 2, 3]");
+    assert!(
+        err.contains("Cannot unpack a list of 3 elements into 2 names"),
+        "{}",
+        err
+    );
+    let err = get_error("[a, [b, c]] <- [1, [2]]");
+    assert!(
+        err.contains("Cannot unpack a list of 1 element into 2 names"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_unpacking_something_other_than_a_list_is_an_error() {
+    let err = get_error("a, b <- 5");
+    assert!(
+        err.contains(
+            "Cannot unpack a value of type integer into 2 names: only a list can be unpacked"
+        ),
+        "{}",
+        err
+    );
+    let err = get_error("a, [b, c] <- [1, {\"b\": 2}]");
+    assert!(
+        err.contains("Cannot unpack a value of type dictionary into 2 names"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_a_failed_unpacking_assigns_nothing() {
+    assert_output(
+        r#"
+        a <- "before"
+        TRY
+        {
+            a, [b, c] <- [1, [2]]
+        } CATCH (e)
+        {
+            DISPLAY(a)
+            DISPLAY(ISDEFINED("b"))
+        }
+        "#,
+        "before\nfalse",
+    );
+}
+
+#[test]
+fn test_only_names_can_be_unpacked_into() {
+    let err = get_error("a, b[1] <- [1, 2]");
+    assert!(
+        err.contains("a list being unpacked into may only hold names and lists of names"),
+        "{}",
+        err
+    );
+    let err = get_error("[a, 1] <- [1, 2]");
+    assert!(
+        err.contains("may only hold names and lists of names"),
+        "{}",
+        err
+    );
+    let err = get_error("[] <- []");
+    assert!(
+        err.contains("may only hold names and lists of names"),
+        "{}",
+        err
+    );
+    let err = get_error("a, b");
+    assert!(
+        err.contains("Expected '<-' after the names to unpack into"),
+        "{}",
+        err
+    );
+}
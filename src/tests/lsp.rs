@@ -0,0 +1,525 @@
+//! `fpli lsp`: the JSON it reads and writes, and the server's answers to the
+//! requests an editor sends, driven message by message through `Server::handle`.
+//! The framing on standard input and output is covered by the integration suite.
+
+use super::Scratch;
+use crate::interpreter::BUILTIN_NAMES;
+use crate::json::Json;
+use crate::lsp::{Server, guide_entry, path_to_uri, serve, uri_to_path};
+
+const URI: &str = "file:///project/main.psl";
+
+fn message(text: &str) -> Json {
+    Json::parse(text).expect("test message is valid JSON")
+}
+
+fn open(server: &mut Server, uri: &str, text: &str) -> Vec<Json> {
+    server.handle(&Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/didOpen".into()),
+        (
+            "params",
+            Json::object([(
+                "textDocument",
+                Json::object([
+                    ("uri", uri.into()),
+                    ("languageId", "pseudolang".into()),
+                    ("version", 1usize.into()),
+                    ("text", text.into()),
+                ]),
+            )]),
+        ),
+    ]))
+}
+
+/// The result of a request about `line`:`character` of `uri`.
+fn ask(server: &mut Server, method: &str, uri: &str, line: usize, character: usize) -> Json {
+    let replies = server.handle(&Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("id", 7usize.into()),
+        ("method", method.into()),
+        (
+            "params",
+            Json::object([
+                ("textDocument", Json::object([("uri", uri.into())])),
+                (
+                    "position",
+                    Json::object([("line", line.into()), ("character", character.into())]),
+                ),
+            ]),
+        ),
+    ]));
+    assert_eq!(replies.len(), 1, "one response: {:?}", replies);
+    assert_eq!(replies[0].get("id"), Some(&Json::from(7usize)));
+    replies[0].get("result").cloned().expect("a result")
+}
+
+/// A server with `text` open as [`URI`].
+fn server_with(text: &str) -> Server {
+    let mut server = Server::new();
+    open(&mut server, URI, text);
+    server
+}
+
+/// The diagnostics a notification publishes, as (line, character, message).
+fn diagnostics(notification: &Json) -> Vec<(usize, usize, String)> {
+    assert_eq!(
+        notification.get("method").and_then(Json::as_str),
+        Some("textDocument/publishDiagnostics")
+    );
+    notification
+        .at(&["params", "diagnostics"])
+        .and_then(Json::as_array)
+        .expect("a diagnostics list")
+        .iter()
+        .map(|d| {
+            let start = d.at(&["range", "start"]).unwrap();
+            (
+                start.get("line").and_then(Json::as_usize).unwrap(),
+                start.get("character").and_then(Json::as_usize).unwrap(),
+                d.get("message").and_then(Json::as_str).unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn labels(completion: &Json) -> Vec<String> {
+    completion
+        .get("items")
+        .and_then(Json::as_array)
+        .expect("completion items")
+        .iter()
+        .map(|item| {
+            item.get("label")
+                .and_then(Json::as_str)
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+fn start_of(location: &Json) -> (String, usize, usize) {
+    let start = location.at(&["range", "start"]).expect("a range");
+    (
+        location
+            .get("uri")
+            .and_then(Json::as_str)
+            .unwrap()
+            .to_string(),
+        start.get("line").and_then(Json::as_usize).unwrap(),
+        start.get("character").and_then(Json::as_usize).unwrap(),
+    )
+}
+
+#[test]
+fn test_json_reads_back_what_it_writes() {
+    let text =
+        r#"{"id":1,"ok":true,"none":null,"list":[1.5,-2,"tab\there \"quoted\" \\ é"],"empty":{}}"#;
+    let value = message(text);
+    assert_eq!(value.to_string(), text);
+    assert_eq!(value.get("id").and_then(Json::as_usize), Some(1));
+    assert_eq!(
+        value
+            .at(&["list"])
+            .and_then(Json::as_array)
+            .map(<[Json]>::len),
+        Some(3)
+    );
+}
+
+#[test]
+fn test_json_unicode_escapes_are_decoded() {
+    assert_eq!(message(r#""café 😀""#), Json::from("café 😀"));
+    assert_eq!(Json::from("\u{1}").to_string(), r#""\u0001""#);
+}
+
+#[test]
+fn test_json_rejects_what_is_not_json() {
+    for bad in ["", "{", "[1,]", "{\"a\" 1}", "nul", "\"open", "1 2"] {
+        assert!(Json::parse(bad).is_err(), "{:?} should not parse", bad);
+    }
+}
+
+#[test]
+fn test_file_uris_round_trip_through_paths() {
+    let path = std::path::Path::new("/home/me/my project/café.psl");
+    let uri = path_to_uri(path);
+    assert_eq!(uri, "file:///home/me/my%20project/caf%C3%A9.psl");
+    assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+    assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+}
+
+#[test]
+fn test_initialize_answers_with_the_capabilities() {
+    let mut server = Server::new();
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+    ));
+    let capabilities = replies[0].at(&["result", "capabilities"]).unwrap();
+    for provider in [
+        "hoverProvider",
+        "definitionProvider",
+        "documentSymbolProvider",
+    ] {
+        assert_eq!(
+            capabilities.get(provider),
+            Some(&Json::Bool(true)),
+            "{}",
+            provider
+        );
+    }
+    assert!(capabilities.get("completionProvider").is_some());
+    assert_eq!(replies[0].get("id"), Some(&Json::from(1usize)));
+}
+
+#[test]
+fn test_an_unknown_request_is_an_error_and_an_unknown_notification_is_ignored() {
+    let mut server = Server::new();
+    let replies = server.handle(&message(r#"{"jsonrpc":"2.0","id":"x","method":"no/such"}"#));
+    assert_eq!(
+        replies[0].at(&["error", "code"]).and_then(Json::as_f64),
+        Some(-32601.0)
+    );
+    assert_eq!(replies[0].get("id"), Some(&Json::from("x")));
+    assert!(
+        server
+            .handle(&message(r#"{"jsonrpc":"2.0","method":"$/whatever"}"#))
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_exit_after_shutdown_succeeds_and_without_it_fails() {
+    let mut server = Server::new();
+    server.handle(&message(r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#));
+    server.handle(&message(r#"{"jsonrpc":"2.0","method":"exit"}"#));
+    assert_eq!(server.exit_code(), Some(0));
+
+    let mut server = Server::new();
+    assert_eq!(server.exit_code(), None);
+    server.handle(&message(r#"{"jsonrpc":"2.0","method":"exit"}"#));
+    assert_eq!(server.exit_code(), Some(1));
+}
+
+#[test]
+fn test_opening_a_file_publishes_every_syntax_error() {
+    let mut server = Server::new();
+    let published = open(&mut server, URI, "x <- 1\ny <- (2 +\nDISPLAY(x)\nz <- ]\n");
+    let found = diagnostics(&published[0]);
+    assert_eq!(found.len(), 2, "{:?}", found);
+    // Each is reported at the token the parser could not use.
+    assert_eq!(found[0].0, 2);
+    assert_eq!(found[1].0, 3);
+}
+
+#[test]
+fn test_a_fix_clears_the_diagnostics_and_closing_clears_them_too() {
+    let mut server = Server::new();
+    assert_eq!(
+        diagnostics(&open(&mut server, URI, "x <- (1\n")[0]).len(),
+        1
+    );
+    let changed = server.handle(&message(&format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{}","version":2}},"contentChanges":[{{"text":"x <- (1)\n"}}]}}}}"#,
+        URI
+    )));
+    assert!(diagnostics(&changed[0]).is_empty());
+    let closed = server.handle(&message(&format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/didClose","params":{{"textDocument":{{"uri":"{}"}}}}}}"#,
+        URI
+    )));
+    assert!(diagnostics(&closed[0]).is_empty());
+}
+
+#[test]
+fn test_an_unclosed_block_is_reported_at_the_end_of_the_file() {
+    let mut server = Server::new();
+    let published = open(
+        &mut server,
+        URI,
+        "x <- 1\nIF (x = 1)\n{\n    DISPLAY(x)\n\n",
+    );
+    let found = diagnostics(&published[0]);
+    assert_eq!(found.len(), 1, "{:?}", found);
+    assert_eq!(found[0].0, 3);
+}
+
+#[test]
+fn test_positions_count_utf16_code_units() {
+    // The error is reported at the end of the line, past an emoji that is one
+    // character but two UTF-16 code units: offset 12 in the line, but column 13
+    // for the editor.
+    let mut server = Server::new();
+    let published = open(&mut server, URI, "s <- \"😀\" + ]\n");
+    let found = diagnostics(&published[0]);
+    assert_eq!((found[0].0, found[0].1), (0, 13));
+}
+
+#[test]
+fn test_an_import_that_cannot_be_found_is_a_diagnostic() {
+    let scratch = Scratch::new();
+    scratch.write("lib.psl", "PROCEDURE helper()\n{\n}\n");
+    let uri = path_to_uri(&scratch.path("main.psl"));
+    let mut server = Server::new();
+    let published = open(&mut server, &uri, "IMPORT \"lib\"\nIMPORT \"missing\"\n");
+    let found = diagnostics(&published[0]);
+    assert_eq!(found.len(), 1, "{:?}", found);
+    assert_eq!(found[0].0, 1);
+    assert!(found[0].2.contains("missing"), "{}", found[0].2);
+}
+
+#[test]
+fn test_every_builtin_has_documentation_to_show() {
+    for name in BUILTIN_NAMES {
+        let entry = guide_entry(name).unwrap_or_else(|| panic!("no guide entry for {}", name));
+        assert!(
+            entry.contains(name),
+            "the entry for {} is about something else",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_hovering_over_a_builtin_shows_its_guide_entry() {
+    let mut server = server_with("x <- ABS(-3)\nDISPLAY(x)\n");
+    let hover = ask(&mut server, "textDocument/hover", URI, 0, 6);
+    let text = hover
+        .at(&["contents", "value"])
+        .and_then(Json::as_str)
+        .unwrap();
+    assert!(text.starts_with("`ABS(x)`"), "{}", text);
+    assert!(text.contains("absolute value"), "{}", text);
+    let hover = ask(&mut server, "textDocument/hover", URI, 1, 2);
+    assert!(hover.at(&["contents", "value"]).is_some());
+}
+
+#[test]
+fn test_hovering_over_a_procedure_shows_its_signature() {
+    let mut server = server_with(
+        "PROCEDURE area(w, h <- 1, rest...)\n{\n    RETURN w * h\n}\nDISPLAY(area(2))\n",
+    );
+    let hover = ask(&mut server, "textDocument/hover", URI, 4, 9);
+    assert_eq!(
+        hover.at(&["contents", "value"]).and_then(Json::as_str),
+        Some("```psl\nPROCEDURE area(w, h <- 1, rest...)\n```")
+    );
+}
+
+#[test]
+fn test_hovering_over_nothing_in_particular_shows_nothing() {
+    let mut server = server_with("x <- \"ABS\" # ABS\n");
+    assert_eq!(
+        ask(&mut server, "textDocument/hover", URI, 0, 7),
+        Json::Null
+    );
+    assert_eq!(
+        ask(&mut server, "textDocument/hover", URI, 0, 14),
+        Json::Null
+    );
+    assert_eq!(
+        ask(&mut server, "textDocument/hover", URI, 0, 0),
+        Json::Null
+    );
+}
+
+#[test]
+fn test_definition_of_a_procedure_in_the_same_file() {
+    let mut server =
+        server_with("x <- 1\nPROCEDURE greet(name)\n{\n    DISPLAY(name)\n}\ngreet(\"a\")\n");
+    let location = ask(&mut server, "textDocument/definition", URI, 5, 2);
+    assert_eq!(start_of(&location), (URI.to_string(), 1, 10));
+}
+
+#[test]
+fn test_definition_of_a_procedure_in_an_imported_file() {
+    let scratch = Scratch::new();
+    let lib = scratch.write(
+        "lib/strings.psl",
+        "COMMENT helpers\nPROCEDURE shout(s)\n{\n    RETURN UPPERCASE(s)\n}\n",
+    );
+    let uri = path_to_uri(&scratch.path("main.psl"));
+    let mut server = Server::new();
+    open(
+        &mut server,
+        &uri,
+        "IMPORT \"lib/strings\"\nDISPLAY(shout(\"hi\"))\n",
+    );
+    let location = ask(&mut server, "textDocument/definition", &uri, 1, 9);
+    let lib_uri = path_to_uri(&std::fs::canonicalize(lib).unwrap());
+    assert_eq!(start_of(&location), (lib_uri.clone(), 1, 10));
+
+    // And on the IMPORT itself, the file it names.
+    let location = ask(&mut server, "textDocument/definition", &uri, 0, 10);
+    assert_eq!(start_of(&location), (lib_uri, 0, 0));
+}
+
+#[test]
+fn test_definition_through_an_import_alias() {
+    let scratch = Scratch::new();
+    let lib = scratch.write("maths.psl", "PROCEDURE twice(n)\n{\n    RETURN n * 2\n}\n");
+    let uri = path_to_uri(&scratch.path("main.psl"));
+    let mut server = Server::new();
+    open(
+        &mut server,
+        &uri,
+        "IMPORT \"maths\" AS m\nDISPLAY(m.twice(2))\n",
+    );
+    let location = ask(&mut server, "textDocument/definition", &uri, 1, 12);
+    let lib_uri = path_to_uri(&std::fs::canonicalize(lib).unwrap());
+    assert_eq!(start_of(&location), (lib_uri, 0, 10));
+}
+
+#[test]
+fn test_definition_of_a_method() {
+    let mut server = server_with(
+        "CLASS Point\n{\n    PROCEDURE norm()\n    {\n        RETURN 0\n    }\n}\np <- Point()\nDISPLAY(p.norm())\n",
+    );
+    let location = ask(&mut server, "textDocument/definition", URI, 8, 11);
+    assert_eq!(start_of(&location), (URI.to_string(), 2, 14));
+    let location = ask(&mut server, "textDocument/definition", URI, 7, 6);
+    assert_eq!(start_of(&location), (URI.to_string(), 0, 6));
+}
+
+#[test]
+fn test_completion_offers_keywords_builtins_and_procedures_in_scope() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "lib.psl",
+        "PROCEDURE shared()\n{\n}\nPRIVATE PROCEDURE hidden()\n{\n}\n",
+    );
+    let uri = path_to_uri(&scratch.path("main.psl"));
+    let mut server = Server::new();
+    open(
+        &mut server,
+        &uri,
+        "IMPORT \"lib\"\ncount <- 0\nPROCEDURE local()\n{\n}\n\n",
+    );
+    let offered = labels(&ask(&mut server, "textDocument/completion", &uri, 5, 0));
+    for expected in [
+        "REPEAT",
+        "PROCEDURE",
+        "DISPLAY",
+        "SQRT",
+        "local",
+        "count",
+        "shared",
+    ] {
+        assert!(
+            offered.iter().any(|l| l == expected),
+            "{} not offered",
+            expected
+        );
+    }
+    assert!(
+        !offered.iter().any(|l| l == "hidden"),
+        "a PRIVATE procedure was offered"
+    );
+    assert_eq!(
+        offered.iter().filter(|l| *l == "local").count(),
+        1,
+        "offered twice"
+    );
+}
+
+#[test]
+fn test_completion_after_an_alias_offers_what_that_file_exports() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "shapes.psl",
+        "EXPORT area\nPROCEDURE area(r)\n{\n}\nPROCEDURE helper()\n{\n}\n",
+    );
+    let uri = path_to_uri(&scratch.path("main.psl"));
+    let mut server = Server::new();
+    open(
+        &mut server,
+        &uri,
+        "IMPORT \"shapes\" AS shapes\nx <- shapes.\n",
+    );
+    let offered = labels(&ask(&mut server, "textDocument/completion", &uri, 1, 12));
+    assert_eq!(offered, ["area"]);
+}
+
+#[test]
+fn test_document_symbols_list_procedures_classes_and_names() {
+    let mut server = server_with(
+        "limit <- 10\nPROCEDURE go()\n{\n}\nCLASS Box\n{\n    PROCEDURE open()\n    {\n    }\n}\nlimit <- 11\n",
+    );
+    let replies = server.handle(&message(&format!(
+        r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/documentSymbol","params":{{"textDocument":{{"uri":"{}"}}}}}}"#,
+        URI
+    )));
+    let symbols = replies[0].get("result").and_then(Json::as_array).unwrap();
+    let summary: Vec<(String, usize)> = symbols
+        .iter()
+        .map(|s| {
+            (
+                s.get("name").and_then(Json::as_str).unwrap().to_string(),
+                s.get("kind").and_then(Json::as_usize).unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("limit".to_string(), 13),
+            ("go".to_string(), 12),
+            ("Box".to_string(), 5)
+        ]
+    );
+    let methods = symbols[2].get("children").and_then(Json::as_array).unwrap();
+    assert_eq!(methods[0].get("name"), Some(&Json::from("open")));
+    assert_eq!(
+        symbols[2]
+            .at(&["range", "end", "line"])
+            .and_then(Json::as_usize),
+        Some(9)
+    );
+}
+
+#[test]
+fn test_symbols_survive_a_syntax_error_elsewhere_in_the_file() {
+    let mut server = server_with("PROCEDURE ok()\n{\n}\nx <- (\nPROCEDURE also()\n{\n}\n");
+    let replies = server.handle(&message(&format!(
+        r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/documentSymbol","params":{{"textDocument":{{"uri":"{}"}}}}}}"#,
+        URI
+    )));
+    let symbols = replies[0].get("result").and_then(Json::as_array).unwrap();
+    assert!(
+        symbols
+            .iter()
+            .any(|s| s.get("name") == Some(&Json::from("ok")))
+    );
+}
+
+#[test]
+fn test_serve_reads_and_writes_framed_messages() {
+    let framed = |body: &str| format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let input = [
+        framed(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+        framed(r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#),
+        framed(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    let code = serve(input.as_bytes(), &mut output);
+    assert_eq!(code, 0);
+    let output = String::from_utf8(output).unwrap();
+    let bodies: Vec<&str> = output.split("Content-Length: ").skip(1).collect();
+    assert_eq!(bodies.len(), 2, "{}", output);
+    for body in bodies {
+        let (length, body) = body.split_once("\r\n\r\n").unwrap();
+        assert_eq!(length.parse::<usize>().unwrap(), body.len());
+        assert!(Json::parse(body).is_ok(), "{}", body);
+    }
+}
+
+#[test]
+fn test_serve_answers_a_malformed_message_with_a_parse_error() {
+    let body = "{not json";
+    let input = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let mut output = Vec::new();
+    assert_eq!(serve(input.as_bytes(), &mut output), 1);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("-32700"), "{}", output);
+}
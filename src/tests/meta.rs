@@ -145,7 +145,7 @@ fn test_execute_requires_a_string() {
 #[test]
 fn test_self_referential_execute_hits_the_recursion_guard() {
     // Without a stack frame per nested EXECUTE this recursion would bypass
-    // MAX_STACK_DEPTH entirely and abort the process on a real stack overflow.
+    // the recursion limit entirely and abort the process on a real stack overflow.
     let err = get_error(
         r#"
         code <- "EXECUTE(code)"
@@ -1,31 +1,49 @@
 use crate::interpreter;
 use crate::lexer::Lexer;
 use crate::parser;
+use std::cell::RefCell;
+use std::io::Write;
 use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 mod algorithms;
 mod aliasing;
 mod arithmetic;
+mod assertions;
 mod basics;
+mod check;
+mod classes;
 mod cli_args;
 mod control_flow;
+mod coverage;
+mod dap;
+mod debugger;
 mod dictionaries;
 mod docs;
+mod embedding;
 mod error_handling;
 mod file_io;
+mod format;
+mod golden;
 mod indexing;
+mod limits;
+mod lint;
 mod lists;
+mod lsp;
 mod meta;
 mod modules;
 mod parsing;
 mod paths;
 mod procedures;
+mod profile;
 mod recursion_limits;
+mod sandbox;
 mod semantics;
 mod stdlib;
 mod strings;
 mod system;
+mod trace;
 mod unicode;
 
 /// A scratch directory that removes itself when the test ends.
@@ -78,6 +96,28 @@ impl Drop for Scratch {
     }
 }
 
+/// A writer whose bytes stay readable after whatever it was handed to -- a debug
+/// adapter, a debugger, a tracer -- has taken ownership of it.
+#[derive(Clone, Default)]
+pub struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Shared {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).expect("UTF-8 output")
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub fn run_test(input: &str) -> Result<String, String> {
     let mut lexer = Lexer::new(input);
     let tokens = lexer.tokenize();
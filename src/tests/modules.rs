@@ -4,7 +4,7 @@
 //! script through [`run_test_at`](super::run_test_at), because the whole point of
 //! the resolution rules is that they depend on where the importing file lives.
 
-use super::{Scratch, assert_output_at, get_error_at, run_test_at};
+use super::{Scratch, assert_output_at, get_error, get_error_at, run_test_at};
 
 #[test]
 fn test_import_brings_in_procedures_and_variables() {
@@ -957,9 +957,10 @@ fn test_module_builtins_take_no_arguments() {
 
 #[test]
 fn test_a_caught_import_failure_reports_the_plain_message() {
-    // The error variable holds the failure, not a rendered multi-line diagnostic.
+    // The message is the failure, not a rendered multi-line diagnostic, and the
+    // location is in the imported file rather than the importing one.
     let scratch = Scratch::new();
-    scratch.write("bad.psl", "x <- 1 / 0");
+    scratch.write("bad.psl", "\n\nx <- 1 / 0");
     let main = scratch.write("main.psl", "");
     assert_output_at(
         r#"
@@ -968,11 +969,13 @@ fn test_a_caught_import_failure_reports_the_plain_message() {
             IMPORT "bad.psl"
         } CATCH (e)
         {
-            DISPLAY(e)
+            DISPLAY(e["message"])
+            DISPLAY(e["line"])
+            DISPLAY(ENDSWITH(e["file"], "bad.psl"))
         }
         "#,
         &main,
-        "Division by zero",
+        "Division by zero\n3\ntrue",
     );
 }
 
@@ -1006,3 +1009,613 @@ fn test_retrying_a_failed_import_reruns_its_top_level() {
         "first failed\nsecond failed\n2",
     );
 }
+
+// ---------------------------------------------------------------------------
+// IMPORT ... AS and name collisions
+// ---------------------------------------------------------------------------
+
+/// Two libraries that both declare `parse`, with a helper and a top-level
+/// variable each, so a test can tell whose code ran.
+fn two_parsers(scratch: &Scratch) {
+    scratch.write(
+        "csv.psl",
+        r#"
+        SEPARATOR <- ","
+        PROCEDURE describe()
+        {
+            RETURN "csv split on " + SEPARATOR
+        }
+        PROCEDURE parse(text)
+        {
+            RETURN describe()
+        }
+        "#,
+    );
+    scratch.write(
+        "tsv.psl",
+        r#"
+        SEPARATOR <- "tab"
+        PROCEDURE describe()
+        {
+            RETURN "tsv split on " + SEPARATOR
+        }
+        PROCEDURE parse(text)
+        {
+            RETURN describe()
+        }
+        "#,
+    );
+}
+
+#[test]
+fn test_import_as_reaches_procedures_and_variables_through_the_name() {
+    let scratch = Scratch::new();
+    two_parsers(&scratch);
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "csv.psl" AS csv
+        IMPORT "tsv" AS tsv
+        DISPLAY(csv.parse("a,b"))
+        DISPLAY(tsv.parse("a	b"))
+        DISPLAY(csv.SEPARATOR)
+        DISPLAY(TYPEOF(csv))
+        DISPLAY(csv)
+        "#,
+        &main,
+        "csv split on ,\ntsv split on tab\n,\nmodule\n<module csv.psl>",
+    );
+}
+
+#[test]
+fn test_import_as_declares_nothing_in_the_importing_program() {
+    let scratch = Scratch::new();
+    two_parsers(&scratch);
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at(
+        r#"
+        IMPORT "csv.psl" AS csv
+        DISPLAY(ISDEFINED("SEPARATOR"))
+        parse("x")
+        "#,
+        &main,
+    );
+    assert!(err.contains("Procedure 'parse' not found"), "{}", err);
+}
+
+#[test]
+fn test_a_module_procedure_runs_in_its_module_not_its_caller() {
+    // Every other named procedure sees its caller's variables; one from a module
+    // sees the module's, so a caller's `SEPARATOR` cannot leak in.
+    let scratch = Scratch::new();
+    two_parsers(&scratch);
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        SEPARATOR <- "mine"
+        PROCEDURE describe()
+        {
+            RETURN "the program's describe"
+        }
+        IMPORT "csv.psl" AS csv
+        DISPLAY(csv.parse(""))
+        f <- csv.parse
+        DISPLAY(f(""))
+        DISPLAY(describe())
+        "#,
+        &main,
+        "csv split on ,\ncsv split on ,\nthe program's describe",
+    );
+}
+
+#[test]
+fn test_a_module_is_loaded_once_and_shared_by_every_name() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "counter.psl",
+        r#"
+        DISPLAY("loading")
+        PROCEDURE name()
+        {
+            RETURN "counter"
+        }
+        "#,
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "counter.psl" AS a
+        IMPORT "counter.psl" AS b
+        DISPLAY(b.name())
+        DISPLAY(a = b)
+        DISPLAY(LENGTH(MODULES()))
+        "#,
+        &main,
+        "loading\ncounter\ntrue\n1",
+    );
+}
+
+#[test]
+fn test_a_module_class_is_instantiated_through_the_module() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "shapes.psl",
+        r#"
+        UNIT <- "cm"
+        PROCEDURE label(n)
+        {
+            RETURN TOSTRING(n) + UNIT
+        }
+        CLASS Square
+        {
+            PROCEDURE Square(side)
+            {
+                self.side <- side
+            }
+            PROCEDURE describe()
+            {
+                RETURN label(self.side)
+            }
+        }
+        "#,
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "shapes.psl" AS shapes
+        sq <- shapes.Square(3)
+        DISPLAY(sq.describe())
+        DISPLAY(TYPEOF(sq))
+        "#,
+        &main,
+        "3cm\nSquare",
+    );
+}
+
+#[test]
+fn test_a_module_can_import_its_own_modules() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "lib/inner.psl",
+        "PROCEDURE greet(name)\n{\nRETURN \"hello \" + name\n}",
+    );
+    scratch.write(
+        "lib/outer.psl",
+        "IMPORT \"inner.psl\" AS inner\nPROCEDURE welcome()\n{\nRETURN inner.greet(\"outer\")\n}",
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "lib/outer.psl" AS outer
+        DISPLAY(outer.welcome())
+        DISPLAY(outer.inner.greet("main"))
+        "#,
+        &main,
+        "hello outer\nhello main",
+    );
+}
+
+#[test]
+fn test_a_plain_import_inside_a_module_stays_in_the_module() {
+    let scratch = Scratch::new();
+    scratch.write("util.psl", "PROCEDURE helper()\n{\nRETURN \"util\"\n}");
+    scratch.write(
+        "lib.psl",
+        "IMPORT \"util.psl\"\nPROCEDURE run()\n{\nRETURN helper()\n}",
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "lib.psl" AS lib
+        DISPLAY(lib.run())
+        DISPLAY(ISDEFINED("helper"))
+        IMPORT "util.psl"
+        DISPLAY(helper())
+        "#,
+        &main,
+        "util\nfalse\nutil",
+    );
+}
+
+#[test]
+fn test_a_missing_module_member_is_an_error() {
+    let scratch = Scratch::new();
+    two_parsers(&scratch);
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at("IMPORT \"csv.psl\" AS csv\nDISPLAY(csv.nothing)", &main);
+    assert!(
+        err.contains("Module csv.psl has no variable or procedure 'nothing'"),
+        "{}",
+        err
+    );
+    let err = get_error_at("IMPORT \"csv.psl\" AS csv\ncsv.SEPARATOR()", &main);
+    assert!(
+        err.contains("Cannot call 'SEPARATOR': it holds a value of type string"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_the_entry_script_cannot_be_imported_as_a_module() {
+    let scratch = Scratch::new();
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at("IMPORT \"main.psl\" AS me", &main);
+    assert!(err.contains("it is the script being run"), "{}", err);
+}
+
+#[test]
+fn test_a_failed_module_can_be_imported_again() {
+    let scratch = Scratch::new();
+    scratch.write("flaky.psl", "DISPLAY(\"running\")\nx <- 1 / 0");
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        REPEAT 2 TIMES
+        {
+            TRY
+            {
+                IMPORT "flaky.psl" AS flaky
+            } CATCH (e)
+            {
+                DISPLAY(e["message"])
+            }
+        }
+        DISPLAY(ISDEFINED("flaky"))
+        "#,
+        &main,
+        "running\nDivision by zero\nrunning\nDivision by zero\nfalse",
+    );
+}
+
+#[test]
+fn test_flat_imports_that_declare_the_same_procedure_are_refused() {
+    let scratch = Scratch::new();
+    two_parsers(&scratch);
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at(
+        r#"
+        IMPORT "csv.psl"
+        IMPORT "tsv.psl"
+        "#,
+        &main,
+    );
+    assert!(
+        err.contains("IMPORT \"tsv.psl\" clashes with names already in use"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("procedure 'describe' is already declared by"),
+        "{}",
+        err
+    );
+    assert!(err.contains("csv.psl"), "{}", err);
+    assert!(err.contains("Import one of the files with AS"), "{}", err);
+}
+
+#[test]
+fn test_a_refused_import_leaves_the_first_declaration_in_place() {
+    let scratch = Scratch::new();
+    scratch.write("one.psl", "PROCEDURE pick()\n{\nRETURN \"one\"\n}");
+    scratch.write("two.psl", "PROCEDURE pick()\n{\nRETURN \"two\"\n}");
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "one.psl"
+        TRY
+        {
+            IMPORT "two.psl"
+        } CATCH (e)
+        {
+            DISPLAY("refused")
+        }
+        DISPLAY(pick())
+        "#,
+        &main,
+        "refused\none",
+    );
+}
+
+#[test]
+fn test_a_refused_import_runs_none_of_the_file() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "lib.psl",
+        "DISPLAY(\"lib ran\")\nshared <- \"lib\"\nPROCEDURE pick()\n{\nRETURN \"lib\"\n}",
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        shared <- "main"
+        PROCEDURE pick()
+        {
+            RETURN "main"
+        }
+        TRY
+        {
+            IMPORT "lib.psl"
+        } CATCH (e)
+        {
+            DISPLAY("refused")
+        }
+        DISPLAY(shared)
+        DISPLAY(pick())
+        DISPLAY(MODULES())
+        "#,
+        &main,
+        "refused\nmain\nmain\n[]",
+    );
+}
+
+#[test]
+fn test_a_clash_declared_only_as_the_file_runs_is_still_refused() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "lib.psl",
+        "IF (TRUE)\n{\nPROCEDURE pick()\n{\nRETURN \"lib\"\n}\n}",
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        PROCEDURE pick()
+        {
+            RETURN "main"
+        }
+        TRY
+        {
+            IMPORT "lib.psl"
+        } CATCH (e)
+        {
+            DISPLAY("refused")
+        }
+        DISPLAY(pick())
+        "#,
+        &main,
+        "refused\nmain",
+    );
+}
+
+#[test]
+fn test_an_import_may_not_redeclare_a_procedure_of_the_program() {
+    let scratch = Scratch::new();
+    scratch.write("lib.psl", "CLASS Point\n{\n}");
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at(
+        r#"
+        CLASS Point
+        {
+        }
+        IMPORT "lib.psl"
+        "#,
+        &main,
+    );
+    assert!(
+        err.contains("CLASS Point is already declared by"),
+        "{}",
+        err
+    );
+    assert!(err.contains("main.psl"), "{}", err);
+}
+
+// ---------------------------------------------------------------------------
+// EXPORT and PRIVATE
+// ---------------------------------------------------------------------------
+
+/// A library that EXPORTs one procedure and one variable, and keeps the helper
+/// and the variable the helper reads to itself.
+const GREETER: &str = r#"
+EXPORT greet, VERSION
+VERSION <- "1.0"
+prefix <- "hello "
+PROCEDURE helper(name)
+{
+    RETURN prefix + name
+}
+PROCEDURE greet(name)
+{
+    RETURN helper(name)
+}
+"#;
+
+#[test]
+fn test_a_plain_import_declares_only_the_exported_names() {
+    let scratch = Scratch::new();
+    scratch.write("greeter.psl", GREETER);
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "greeter.psl"
+        DISPLAY(greet("you"))
+        DISPLAY(VERSION)
+        DISPLAY(ISDEFINED("prefix"))
+        DISPLAY(PROCEDURES())
+        DISPLAY(VARIABLES())
+        "#,
+        &main,
+        "hello you\n1.0\nfalse\n[greet]\n[ARGCOUNT, ARGS, POSITIONALS, VERSION]",
+    );
+    let err = get_error_at("IMPORT \"greeter.psl\"\nhelper(\"you\")", &main);
+    assert!(err.contains("Procedure 'helper' not found"), "{}", err);
+}
+
+#[test]
+fn test_private_declarations_stay_in_their_file() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "tally.psl",
+        r#"
+        PRIVATE step <- 10
+        PRIVATE PROCEDURE scale(n)
+        {
+            RETURN n * step
+        }
+        PRIVATE CLASS Hidden
+        {
+        }
+        PROCEDURE tally(n)
+        {
+            RETURN scale(n)
+        }
+        "#,
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        step <- 1
+        IMPORT "tally.psl"
+        DISPLAY(tally(4))
+        DISPLAY(step)
+        DISPLAY(PROCEDURES())
+        "#,
+        &main,
+        "40\n1\n[tally]",
+    );
+    let err = get_error_at("IMPORT \"tally.psl\"\nx <- Hidden()", &main);
+    assert!(err.contains("'Hidden' not found"), "{}", err);
+}
+
+#[test]
+fn test_a_private_name_cannot_be_reached_through_a_module() {
+    let scratch = Scratch::new();
+    scratch.write("greeter.psl", GREETER);
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "greeter.psl" AS g
+        DISPLAY(g.greet("module"))
+        TRY
+        {
+            g.helper("x")
+        } CATCH (e)
+        {
+            DISPLAY(e["message"])
+        }
+        TRY
+        {
+            DISPLAY(g.prefix)
+        } CATCH (e)
+        {
+            DISPLAY(e["message"])
+        }
+        "#,
+        &main,
+        "hello module\n'helper' is private to greeter.psl\n'prefix' is private to greeter.psl",
+    );
+}
+
+#[test]
+fn test_a_file_with_exports_runs_once_for_plain_and_named_imports() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "once.psl",
+        "EXPORT name\nDISPLAY(\"loading\")\nPROCEDURE name()\n{\nRETURN \"once\"\n}",
+    );
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        IMPORT "once.psl" AS o
+        IMPORT "once.psl"
+        DISPLAY(name())
+        DISPLAY(o.name())
+        DISPLAY(LENGTH(MODULES()))
+        "#,
+        &main,
+        "loading\nonce\nonce\n1",
+    );
+}
+
+#[test]
+fn test_exported_names_still_clash_with_another_flat_import() {
+    let scratch = Scratch::new();
+    scratch.write("greeter.psl", GREETER);
+    scratch.write("other.psl", "PROCEDURE greet(name)\n{\nRETURN name\n}");
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at("IMPORT \"other.psl\"\nIMPORT \"greeter.psl\"", &main);
+    assert!(
+        err.contains("procedure 'greet' is already declared by"),
+        "{}",
+        err
+    );
+    // The helper is private, so it cannot clash with anything.
+    assert_output_at(
+        r#"
+        PROCEDURE helper()
+        {
+            RETURN "mine"
+        }
+        IMPORT "greeter.psl"
+        DISPLAY(helper())
+        DISPLAY(greet("too"))
+        "#,
+        &main,
+        "mine\nhello too",
+    );
+}
+
+#[test]
+fn test_exporting_an_undeclared_name_is_an_error() {
+    let scratch = Scratch::new();
+    scratch.write("typo.psl", "EXPORT greet\nPROCEDURE gret()\n{\n}");
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at("IMPORT \"typo.psl\"", &main);
+    assert!(
+        err.contains("EXPORT names 'greet', but the file declares no variable, procedure or CLASS of that name"),
+        "{}",
+        err
+    );
+    assert!(err.contains("typo.psl"), "{}", err);
+}
+
+#[test]
+fn test_a_name_cannot_be_both_exported_and_private() {
+    let scratch = Scratch::new();
+    scratch.write("both.psl", "EXPORT x\nPRIVATE x <- 1");
+    let main = scratch.write("main.psl", "");
+    let err = get_error_at("IMPORT \"both.psl\" AS both", &main);
+    assert!(err.contains("'x' is both EXPORTed and PRIVATE"), "{}", err);
+}
+
+#[test]
+fn test_export_and_private_are_top_level_only() {
+    let err = get_error("IF TRUE\n{\nEXPORT x\n}");
+    assert!(
+        err.contains("EXPORT may only appear at the top level of a file"),
+        "{}",
+        err
+    );
+    let err = get_error("PROCEDURE f()\n{\nPRIVATE y <- 1\n}");
+    assert!(
+        err.contains("PRIVATE may only appear at the top level of a file"),
+        "{}",
+        err
+    );
+    let err = get_error("PRIVATE DISPLAY(1)");
+    assert!(
+        err.contains("PRIVATE must be followed by a PROCEDURE, a CLASS or an assignment to a name"),
+        "{}",
+        err
+    );
+    let err = get_error("PRIVATE x[1] <- 2");
+    assert!(err.contains("PRIVATE must be followed by"), "{}", err);
+}
+
+#[test]
+fn test_export_and_private_change_nothing_in_the_program_being_run() {
+    let scratch = Scratch::new();
+    let main = scratch.write("main.psl", "");
+    assert_output_at(
+        r#"
+        EXPORT shown
+        PRIVATE hidden <- 2
+        shown <- 1
+        PRIVATE PROCEDURE f()
+        {
+            RETURN hidden + shown
+        }
+        DISPLAY(f())
+        "#,
+        &main,
+        "3",
+    );
+}
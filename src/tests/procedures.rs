@@ -375,3 +375,347 @@ fn test_return_from_loop() {
         "3",
     );
 }
+
+// ---------------------------------------------------------------------------
+// Procedures as values
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_declared_procedure_as_argument() {
+    assert_output(
+        r#"
+            PROCEDURE square(x)
+            {
+                RETURN x * x
+            }
+            PROCEDURE apply(f, x)
+            {
+                RETURN f(x)
+            }
+            DISPLAY(apply(square, 4))
+            DISPLAY(TYPEOF(square))
+            DISPLAY(square)
+        "#,
+        "16\nprocedure\n<procedure square>",
+    );
+}
+
+#[test]
+fn test_anonymous_procedure_captures_defining_scope() {
+    assert_output(
+        r#"
+            PROCEDURE makeAdder(n)
+            {
+                RETURN PROCEDURE (x) { RETURN x + n }
+            }
+            addThree <- makeAdder(3)
+            addTen <- makeAdder(10)
+            DISPLAY(addThree(1))
+            DISPLAY(addTen(1))
+            DISPLAY(makeAdder(100)(1))
+        "#,
+        "4\n11\n101",
+    );
+}
+
+#[test]
+fn test_closure_reads_captured_variables_live() {
+    assert_output(
+        r#"
+            base <- 1
+            f <- PROCEDURE () { RETURN base }
+            base <- 2
+            DISPLAY(f())
+        "#,
+        "2",
+    );
+}
+
+#[test]
+fn test_closure_does_not_see_callers_variables() {
+    // A closure sees where it was written, not who calls it.
+    let err = get_error(
+        r#"
+            f <- PROCEDURE () { RETURN secret }
+            PROCEDURE caller()
+            {
+                secret <- 1
+                RETURN f()
+            }
+            DISPLAY(caller())
+        "#,
+    );
+    assert!(err.contains("Undefined variable: secret"), "{}", err);
+}
+
+#[test]
+fn test_call_any_expression_yielding_a_procedure() {
+    assert_output(
+        r#"
+            handlers <- {"neg": PROCEDURE (x) { RETURN -x }}
+            fs <- [PROCEDURE (x) { RETURN x + 1 }]
+            DISPLAY(handlers["neg"](5))
+            DISPLAY(fs[1](5))
+        "#,
+        "-5\n6",
+    );
+}
+
+#[test]
+fn test_procedure_variable_wins_over_declaration() {
+    assert_output(
+        r#"
+            PROCEDURE f(x)
+            {
+                RETURN "global f"
+            }
+            PROCEDURE apply(f, x)
+            {
+                RETURN f(x)
+            }
+            DISPLAY(apply(PROCEDURE (y) { RETURN y * 10 }, 4))
+            f <- PROCEDURE (x) { RETURN "variable" }
+            DISPLAY(f(1))
+        "#,
+        "40\nvariable",
+    );
+}
+
+#[test]
+fn test_a_non_procedure_variable_does_not_hide_a_declaration() {
+    assert_output(
+        r#"
+            PROCEDURE total(a, b)
+            {
+                RETURN a + b
+            }
+            total <- 0
+            DISPLAY(total(2, 4))
+        "#,
+        "6",
+    );
+}
+
+#[test]
+fn test_procedure_values_share_the_recursion_guard() {
+    let err = get_error(
+        r#"
+            loop <- PROCEDURE (n) { RETURN loop(n + 1) }
+            loop(1)
+        "#,
+    );
+    assert!(err.contains("maximum recursion depth exceeded"), "{}", err);
+    assert!(err.contains("<anonymous>"), "{}", err);
+}
+
+#[test]
+fn test_calling_a_non_procedure_variable() {
+    let err = get_error("x <- 5\nx(1)");
+    assert!(
+        err.contains("Cannot call 'x': it holds a value of type integer, not a procedure"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_procedure_value_equality() {
+    assert_output(
+        r#"
+            PROCEDURE f()
+            {
+                RETURN 1
+            }
+            g <- f
+            DISPLAY(g = f)
+            DISPLAY(PROCEDURE () { RETURN 1 } = PROCEDURE () { RETURN 1 })
+        "#,
+        "true\nfalse",
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Arity, defaults and variadic parameters
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_a_call_with_too_many_arguments_is_an_error() {
+    let err = get_error("PROCEDURE add(a, b)\n{\nRETURN a + b\n}\nadd(1, 2, 3)");
+    assert!(
+        err.contains("Procedure 'add' expects 2 arguments, but was called with 3"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_a_call_with_too_few_arguments_is_an_error() {
+    let err = get_error("PROCEDURE show(x)\n{\nDISPLAY(x)\n}\nshow()");
+    assert!(
+        err.contains("Procedure 'show' expects 1 argument, but was called with 0"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_arity_applies_to_every_kind_of_call() {
+    let err = get_error("PROCEDURE f(a)\n{\n}\nCALL(\"f\", [1, 2])");
+    assert!(
+        err.contains("Procedure 'f' expects 1 argument, but was called with 2"),
+        "{}",
+        err
+    );
+    let err = get_error("g <- PROCEDURE (a, b) { RETURN a }\ng(1)");
+    assert!(
+        err.contains("Procedure '<anonymous>' expects 2 arguments, but was called with 1"),
+        "{}",
+        err
+    );
+    let err = get_error(
+        r#"
+        CLASS Point
+        {
+            PROCEDURE Point(x, y)
+            {
+                self.x <- x
+            }
+            PROCEDURE moveBy(dx)
+            {
+                self.x <- self.x + dx
+            }
+        }
+        p <- Point(1, 2)
+        p.moveBy()
+        "#,
+    );
+    assert!(
+        err.contains("Procedure 'Point.moveBy' expects 1 argument, but was called with 0"),
+        "{}",
+        err
+    );
+    let err = get_error("CLASS Point\n{\nPROCEDURE Point(x)\n{\n}\n}\np <- Point()");
+    assert!(
+        err.contains("Procedure 'Point.Point' expects 1 argument"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_default_parameters() {
+    assert_output(
+        r#"
+        PROCEDURE greet(name, greeting <- "Hello", mark <- "!")
+        {
+            RETURN greeting + ", " + name + mark
+        }
+        DISPLAY(greet("Ada"))
+        DISPLAY(greet("Ada", "Hi"))
+        DISPLAY(greet("Ada", "Hi", "?"))
+        "#,
+        "Hello, Ada!\nHi, Ada!\nHi, Ada?",
+    );
+    let err = get_error("PROCEDURE f(a, b <- 1)\n{\n}\nf()");
+    assert!(
+        err.contains("Procedure 'f' expects 1 to 2 arguments, but was called with 0"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_a_default_is_evaluated_at_each_call_and_sees_earlier_parameters() {
+    assert_output(
+        r#"
+        PROCEDURE collect(item, into <- [])
+        {
+            APPEND(into, item)
+            RETURN into
+        }
+        DISPLAY(collect(1))
+        DISPLAY(collect(2))
+        PROCEDURE box(width, height <- width)
+        {
+            RETURN width * height
+        }
+        DISPLAY(box(3))
+        DISPLAY(box(3, 4))
+        "#,
+        "[1]\n[2]\n9\n12",
+    );
+}
+
+#[test]
+fn test_an_error_in_a_default_is_reported_inside_the_procedure() {
+    let err = get_error("PROCEDURE f(a <- 1 / 0)\n{\n}\nf()");
+    assert!(
+        err.contains("Line 1, Column 18: Division by zero"),
+        "{}",
+        err
+    );
+    assert!(err.contains("in f (line 4)"), "{}", err);
+}
+
+#[test]
+fn test_variadic_parameters() {
+    assert_output(
+        r#"
+        PROCEDURE tag(name, rest...)
+        {
+            RETURN name + ":" + TOSTRING(rest)
+        }
+        DISPLAY(tag("a"))
+        DISPLAY(tag("a", 1))
+        DISPLAY(tag("a", 1, [2], "three"))
+        all <- PROCEDURE (items...) { RETURN LENGTH(items) }
+        DISPLAY(all())
+        DISPLAY(CALL("tag", ["b", 4, 5]))
+        "#,
+        "a:[]\na:[1]\na:[1, [2], three]\n0\nb:[4, 5]",
+    );
+    let err = get_error("PROCEDURE f(a, rest...)\n{\n}\nf()");
+    assert!(
+        err.contains("Procedure 'f' expects at least 1 argument, but was called with 0"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_defaults_and_a_variadic_parameter_together() {
+    assert_output(
+        r#"
+        PROCEDURE f(a, b <- "b", rest...)
+        {
+            RETURN a + b + TOSTRING(LENGTH(rest))
+        }
+        DISPLAY(f("a"))
+        DISPLAY(f("a", "x"))
+        DISPLAY(f("a", "x", 1, 2))
+        "#,
+        "ab0\nax0\nax2",
+    );
+}
+
+#[test]
+fn test_parameter_list_rules() {
+    let err = get_error("PROCEDURE f(a <- 1, b)\n{\n}");
+    assert!(
+        err.contains("Parameter 'b' needs a default: it follows a parameter that has one"),
+        "{}",
+        err
+    );
+    let err = get_error("PROCEDURE f(rest..., b)\n{\n}");
+    assert!(
+        err.contains("A variadic parameter must be the last parameter"),
+        "{}",
+        err
+    );
+    let err = get_error("PROCEDURE f(rest..)\n{\n}");
+    assert!(
+        err.contains("Expected '...' after a variadic parameter name"),
+        "{}",
+        err
+    );
+}
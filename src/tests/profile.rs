@@ -0,0 +1,113 @@
+//! `fpli run --profile` and `--count-ops`: the profiler, driven through
+//! `run_observed`. Operation counts are the same on every run, so the reports
+//! here are checked whole; timings only for their shape.
+
+use super::{Scratch, Shared};
+use crate::interpreter::{self, Observer};
+use crate::lexer::Lexer;
+use crate::parser;
+use crate::profile::Profiler;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The report and folded stacks from profiling `main` in a directory holding
+/// `files` as well.
+fn profile(main: &str, files: &[(&str, &str)], count_ops: bool) -> (String, String) {
+    let scratch = Scratch::new();
+    for (name, text) in files {
+        scratch.write(name, text);
+    }
+    let path = scratch.write("main.psl", main);
+    let tokens = Lexer::new(main).tokenize();
+    let ast = parser::parse_with_source(tokens, main, false).expect("the program parses");
+    let report = Shared::default();
+    let folded = Shared::default();
+    let profiler = Profiler::new(
+        Box::new(report.clone()),
+        Some(Box::new(folded.clone())),
+        count_ops,
+    );
+    let _ = interpreter::run_observed(
+        &ast,
+        main,
+        &[],
+        Some(path),
+        Rc::new(RefCell::new(profiler)) as Rc<RefCell<dyn Observer>>,
+    );
+    (report.text(), folded.text())
+}
+
+const FIB: &str = "PROCEDURE fib(n)
+{
+    IF (n < 2)
+    {
+        RETURN n
+    }
+    RETURN fib(n - 1) + fib(n - 2)
+}
+DISPLAY(fib(3))
+";
+
+#[test]
+fn test_operations_are_counted_per_procedure_and_per_line() {
+    let (report, _) = profile(FIB, &[], true);
+    assert_eq!(
+        report,
+        "Operations (statements run) per procedure:
+  Procedure       Calls  Inclusive  Exclusive
+  fib (main.psl)      5         10         10
+  <main>              1         12          2
+
+Hits per line:
+  Line        Hits
+  main.psl:3     5
+  main.psl:5     3
+  main.psl:7     2
+  main.psl:1     1
+  main.psl:9     1
+"
+    );
+}
+
+#[test]
+fn test_folded_stacks_give_each_call_path_its_own_count() {
+    let (_, folded) = profile(FIB, &[], true);
+    assert_eq!(
+        folded,
+        "<main> 2\n<main>;fib 2\n<main>;fib;fib 4\n<main>;fib;fib;fib 4\n"
+    );
+}
+
+#[test]
+fn test_procedures_are_told_apart_by_the_file_declaring_them() {
+    let main = "IMPORT \"a\" AS a\nIMPORT \"b\" AS b\na.go()\nb.go()\nb.go()\n";
+    let go = "PROCEDURE go()\n{\n    x <- 1\n}\n";
+    let (report, _) = profile(main, &[("a.psl", go), ("b.psl", go)], true);
+    assert!(report.contains("  b.psl.go (b.psl)      2"), "{}", report);
+    assert!(report.contains("  a.psl.go (a.psl)      1"), "{}", report);
+    assert!(report.contains("  b.psl:3        2"), "{}", report);
+}
+
+#[test]
+fn test_a_call_cut_short_by_an_error_still_counts() {
+    let main = "PROCEDURE fail()\n{\n    x <- 1\n    y <- 1 / 0\n}\nfail()\n";
+    let (report, _) = profile(main, &[], true);
+    assert!(
+        report.contains("  fail (main.psl)      1          2          2"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_timings_are_shown_in_milliseconds() {
+    let (report, folded) = profile(FIB, &[], false);
+    assert!(report.starts_with("Time per procedure:\n"), "{}", report);
+    let fib = report
+        .lines()
+        .find(|line| line.trim_start().starts_with("fib"))
+        .unwrap();
+    assert!(fib.ends_with(" ms"), "{}", fib);
+    assert!(fib.contains("  5  "), "{}", fib);
+    assert!(folded.lines().all(|line| line.starts_with("<main>")));
+}
@@ -0,0 +1,265 @@
+//! The permissions of a sandboxed run: what each grant lets through, how far a
+//! path grant reaches, and the error a refusal raises. Every path is absolute,
+//! as the tests share one working directory with each other.
+
+use super::Scratch;
+use crate::interpreter::{self, OutputMode, RunOptions};
+use crate::lexer::Lexer;
+use crate::parser;
+use crate::sandbox::{Grant, Permissions};
+use std::path::Path;
+
+/// Run `source` as though loaded from `script`, allowed only `permissions`.
+fn run_allowed(
+    source: &str,
+    script: Option<&Path>,
+    permissions: Permissions,
+) -> Result<String, String> {
+    let ast = parser::parse_with_source(Lexer::new(source).tokenize(), source, false)
+        .map_err(|e| e.format(source))?;
+    interpreter::run_with_mode(
+        ast,
+        source,
+        OutputMode::Capture,
+        false,
+        RunOptions {
+            script_path: script.map(Path::to_path_buf),
+            permissions,
+            ..RunOptions::default()
+        },
+    )
+    .map(|output| output.trim_end().to_string())
+    .map_err(|e| e.message)
+}
+
+/// Permission to read inside `dir` and nothing else.
+fn reading(dir: &Path) -> Permissions {
+    Permissions {
+        read: Grant::paths(&[dir]),
+        ..Permissions::none()
+    }
+}
+
+#[test]
+fn test_nothing_outside_the_program_is_allowed_under_deny_all() {
+    let scratch = Scratch::new();
+    let file = scratch.write("notes.txt", "hello");
+    let shown = file.display();
+    let spelled = scratch.psl_path("notes.txt");
+    for (call, message) in [
+        (
+            format!("READFILE(\"{}\")", spelled),
+            format!("READFILE needs --allow-read to read '{}'", shown),
+        ),
+        (
+            format!("DELETEFILE(\"{}\")", spelled),
+            format!("DELETEFILE needs --allow-write to change '{}'", shown),
+        ),
+        (
+            "SHELL(\"echo hi\")".to_string(),
+            "SHELL needs --allow-exec".to_string(),
+        ),
+        (
+            "PROCESSES()".to_string(),
+            "PROCESSES needs --allow-exec".to_string(),
+        ),
+        ("KILL(1)".to_string(), "KILL needs --allow-exec".to_string()),
+        (
+            "GETENV(\"HOME\", \"\")".to_string(),
+            "GETENV needs --allow-env for 'HOME'".to_string(),
+        ),
+    ] {
+        let error = run_allowed(&call, None, Permissions::none()).expect_err(&call);
+        assert_eq!(error, format!("Permission denied: {}", message));
+    }
+    assert!(scratch.path("notes.txt").exists());
+}
+
+#[test]
+fn test_a_refusal_can_be_caught() {
+    let source = "TRY
+{
+    EXEC(\"ls\")
+} CATCH (err)
+{
+    DISPLAY(err[\"message\"])
+}
+DISPLAY(\"carried on\")";
+    assert_eq!(
+        run_allowed(source, None, Permissions::none()),
+        Ok("Permission denied: EXEC needs --allow-exec\ncarried on".to_string())
+    );
+}
+
+#[test]
+fn test_reading_is_allowed_inside_the_granted_directory_only() {
+    let scratch = Scratch::new();
+    scratch.write("data/scores.txt", "90");
+    scratch.write("answers.txt", "secret");
+    let data = scratch.path("data");
+    let inside = scratch.psl_path("data/scores.txt");
+    assert_eq!(
+        run_allowed(
+            &format!("DISPLAY(READFILE(\"{}\"))", inside),
+            None,
+            reading(&data)
+        ),
+        Ok("90".to_string())
+    );
+    // `..` cannot climb out of the grant.
+    let climbing = scratch.psl_path("data/../answers.txt");
+    let error = run_allowed(&format!("READFILE(\"{}\")", climbing), None, reading(&data))
+        .expect_err("outside the grant");
+    assert!(error.contains("needs --allow-read"), "{}", error);
+    // Nor is reading the same as writing.
+    let error = run_allowed(
+        &format!("WRITEFILE(\"{}\", \"100\")", inside),
+        None,
+        reading(&data),
+    )
+    .expect_err("read-only");
+    assert!(error.contains("WRITEFILE needs --allow-write"), "{}", error);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_a_link_out_of_the_granted_directory_is_followed() {
+    let scratch = Scratch::new();
+    let secret = scratch.write("answers.txt", "secret");
+    std::fs::create_dir_all(scratch.path("data")).expect("create data");
+    std::os::unix::fs::symlink(&secret, scratch.path("data/link.txt")).expect("symlink");
+    let error = run_allowed(
+        &format!("READFILE(\"{}\")", scratch.psl_path("data/link.txt")),
+        None,
+        reading(&scratch.path("data")),
+    )
+    .expect_err("the link leads outside");
+    assert!(error.contains("needs --allow-read"), "{}", error);
+}
+
+/// Permission to write inside `dir` and nothing else.
+fn writing(dir: &Path) -> Permissions {
+    Permissions {
+        write: Grant::paths(&[dir]),
+        ..Permissions::none()
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_a_dangling_link_out_of_the_granted_directory_is_followed() {
+    // Writing through a link to a file that does not exist yet creates that
+    // file, so the link has to be followed even though its target is missing.
+    let scratch = Scratch::new();
+    std::fs::create_dir_all(scratch.path("data")).expect("create data");
+    std::fs::create_dir_all(scratch.path("outside")).expect("create outside");
+    std::os::unix::fs::symlink("../outside/pwned.txt", scratch.path("data/link")).expect("symlink");
+    let error = run_allowed(
+        &format!("WRITEFILE(\"{}\", \"x\")", scratch.psl_path("data/link")),
+        None,
+        writing(&scratch.path("data")),
+    )
+    .expect_err("the link leads outside");
+    assert!(error.contains("WRITEFILE needs --allow-write"), "{}", error);
+    assert!(!scratch.path("outside/pwned.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_a_link_to_a_directory_out_of_the_grant_is_followed() {
+    let scratch = Scratch::new();
+    std::fs::create_dir_all(scratch.path("data")).expect("create data");
+    std::fs::create_dir_all(scratch.path("outside")).expect("create outside");
+    std::os::unix::fs::symlink(scratch.path("outside"), scratch.path("data/dir")).expect("symlink");
+    let error = run_allowed(
+        &format!(
+            "WRITEFILE(\"{}\", \"x\")",
+            scratch.psl_path("data/dir/new.txt")
+        ),
+        None,
+        writing(&scratch.path("data")),
+    )
+    .expect_err("the link leads outside");
+    assert!(error.contains("WRITEFILE needs --allow-write"), "{}", error);
+    assert!(!scratch.path("outside/new.txt").exists());
+    // A file that stays inside the grant is still allowed.
+    assert_eq!(
+        run_allowed(
+            &format!("WRITEFILE(\"{}\", \"x\")", scratch.psl_path("data/new.txt")),
+            None,
+            writing(&scratch.path("data")),
+        ),
+        Ok(String::new())
+    );
+}
+
+#[test]
+fn test_copying_reads_one_path_and_writes_the_other() {
+    let scratch = Scratch::new();
+    scratch.write("in/source.txt", "text");
+    std::fs::create_dir_all(scratch.path("out")).expect("create out");
+    let permissions = Permissions {
+        read: Grant::paths(&[scratch.path("in")]),
+        write: Grant::paths(&[scratch.path("out")]),
+        ..Permissions::none()
+    };
+    let copy = |from: &str, to: &str| {
+        run_allowed(
+            &format!(
+                "DISPLAY(COPYFILE(\"{}\", \"{}\"))",
+                scratch.psl_path(from),
+                scratch.psl_path(to)
+            ),
+            None,
+            permissions.clone(),
+        )
+    };
+    assert_eq!(copy("in/source.txt", "out/copy.txt"), Ok("4".to_string()));
+    let error = copy("in/source.txt", "in/copy.txt").expect_err("not writable");
+    assert!(error.contains("COPYFILE needs --allow-write"), "{}", error);
+    let error = copy("out/copy.txt", "out/again.txt").expect_err("not readable");
+    assert!(error.contains("COPYFILE needs --allow-read"), "{}", error);
+}
+
+#[test]
+fn test_environment_variables_can_be_granted_by_name() {
+    let permissions = Permissions {
+        env: Grant::Only(vec!["PSL_SANDBOX_ALLOWED".to_string()]),
+        ..Permissions::none()
+    };
+    assert_eq!(
+        run_allowed(
+            "DISPLAY(GETENV(\"PSL_SANDBOX_ALLOWED\", \"unset\"))",
+            None,
+            permissions.clone()
+        ),
+        Ok("unset".to_string())
+    );
+    for (call, refused) in [
+        (
+            "UNSETENV(\"PATH\")",
+            "UNSETENV needs --allow-env for 'PATH'",
+        ),
+        ("ENVVARS()", "ENVVARS needs --allow-env for every variable"),
+    ] {
+        let error = run_allowed(call, None, permissions.clone()).expect_err(call);
+        assert_eq!(error, format!("Permission denied: {}", refused));
+    }
+}
+
+#[test]
+fn test_an_import_needs_to_read_the_file() {
+    let scratch = Scratch::new();
+    scratch.write(
+        "lib/helpers.psl",
+        "PROCEDURE twice(n)\n{\n    RETURN n * 2\n}\n",
+    );
+    let main = scratch.write("main.psl", "");
+    let source = "IMPORT \"lib/helpers.psl\"\nDISPLAY(twice(2))";
+    assert_eq!(
+        run_allowed(source, Some(&main), reading(&scratch.path("lib"))),
+        Ok("4".to_string())
+    );
+    let error = run_allowed(source, Some(&main), Permissions::none()).expect_err("denied");
+    assert!(error.starts_with("Permission denied: IMPORT needs --allow-read to read '"));
+}
@@ -261,7 +261,7 @@ fn the_error_variable_restores_a_name_it_shadowed() {
             x <- 1 / 0
         } CATCH (err)
         {
-            DISPLAY(err NOT= "mine")
+            DISPLAY(err["message"] NOT= "mine")
         }
         DISPLAY(err)
         "#,
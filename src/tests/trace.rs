@@ -0,0 +1,123 @@
+//! `fpli run --trace`: the tracer, driven through `run_observed` with its trace
+//! written to a buffer. The flags, and the trace going to standard error or a
+//! file, are covered by the integration suite.
+
+use super::{Scratch, Shared};
+use crate::interpreter::{self, Observer};
+use crate::lexer::Lexer;
+use crate::parser;
+use crate::trace::Tracer;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The trace of running `source` from `main.psl`, and what the program printed.
+fn trace(source: &str, values: bool) -> (String, String) {
+    let scratch = Scratch::new();
+    let path = scratch.write("main.psl", source);
+    let tokens = Lexer::new(source).tokenize();
+    let ast = parser::parse_with_source(tokens, source, false).expect("the program parses");
+    let written = Shared::default();
+    let tracer = Tracer::new(Box::new(written.clone()), values);
+    let observed = interpreter::run_observed(
+        &ast,
+        source,
+        &[],
+        Some(path),
+        Rc::new(RefCell::new(tracer)) as Rc<RefCell<dyn Observer>>,
+    );
+    observed.status.expect("the program runs");
+    (written.text(), observed.output)
+}
+
+const RECURSIVE: &str = "PROCEDURE sum(n)
+{
+    IF (n = 0)
+    {
+        RETURN 0
+    }
+    RETURN n + sum(n - 1)
+}
+DISPLAY(sum(1))
+";
+
+#[test]
+fn test_each_statement_is_traced_with_its_line_and_depth() {
+    let (trace, output) = trace(RECURSIVE, false);
+    assert_eq!(
+        trace,
+        "main.psl:1 [0] PROCEDURE sum(n)
+main.psl:9 [0] DISPLAY(sum(1))
+main.psl:3 [1]   IF (n = 0)
+main.psl:7 [1]   RETURN n + sum(n - 1)
+main.psl:3 [2]     IF (n = 0)
+main.psl:5 [2]     RETURN 0
+"
+    );
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn test_values_add_calls_with_their_arguments_and_results() {
+    let (trace, _) = trace(RECURSIVE, true);
+    assert_eq!(
+        trace,
+        "main.psl:1 [0] PROCEDURE sum(n)
+main.psl:9 [0] DISPLAY(sum(1))
+main.psl:9 [1]   -> sum(n = 1)
+main.psl:3 [1]   IF (n = 0)
+main.psl:7 [1]   RETURN n + sum(n - 1)
+main.psl:7 [2]     -> sum(n = 0)
+main.psl:3 [2]     IF (n = 0)
+main.psl:5 [2]     RETURN 0
+main.psl:5 [2]     <- sum returned 0
+main.psl:7 [1]   <- sum returned 1
+"
+    );
+}
+
+#[test]
+fn test_values_add_assignments_and_loop_variables() {
+    let source = "s <- \"a\"\ns <- s + \"b\"\nFOR i FROM 1 TO 2\n{\n    DISPLAY(i)\n}\nFOR EACH k, v IN {\"x\": [1]}\n{\n}\nfirst, rest <- [1, 2]\n";
+    let (trace, output) = trace(source, true);
+    let values: Vec<&str> = trace
+        .lines()
+        .filter_map(|line| line.split_once("] ").map(|(_, text)| text))
+        .filter(|text| text.contains(" = "))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            "s = \"a\"",
+            "s = \"ab\"",
+            "i = 1",
+            "i = 2",
+            "k = \"x\"",
+            "v = [1]",
+            "first = 1",
+            "rest = 2"
+        ]
+    );
+    assert_eq!(output, "1\n2\n");
+}
+
+#[test]
+fn test_without_values_there_are_no_assignments() {
+    let (trace, _) = trace("x <- 1\nx <- x + 1\n", false);
+    assert_eq!(trace, "main.psl:1 [0] x <- 1\nmain.psl:2 [0] x <- x + 1\n");
+}
+
+#[test]
+fn test_a_method_and_a_procedure_value_are_calls_too() {
+    let source = "CLASS Box\n{\n    PROCEDURE Box(w)\n    {\n        self.w <- w\n    }\n    PROCEDURE twice()\n    {\n        RETURN self.w * 2\n    }\n}\nb <- Box(3)\nf <- PROCEDURE (x) { RETURN x }\ny <- f(b.twice())\n";
+    let (trace, _) = trace(source, true);
+    for expected in [
+        "main.psl:12 [1]   -> Box.Box(w = 3)",
+        // The constructor gives the object, not what its body ended with.
+        "main.psl:5 [1]   <- Box.Box returned Box{w: 3}",
+        "main.psl:9 [1]   <- Box.twice returned 6",
+        "main.psl:14 [1]   -> <anonymous>(x = 6)",
+        "main.psl:14 [0] y = 6",
+    ] {
+        assert!(trace.contains(expected), "{:?} in {}", expected, trace);
+    }
+}
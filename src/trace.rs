@@ -0,0 +1,112 @@
+//! `fpli run --trace`: every statement written down as it runs, with where it is
+//! and how deep in the call stack, for following the path a program takes
+//! through its loops and calls.
+//!
+//! ```text
+//! main.psl:9 [0] total <- sum(2)
+//! main.psl:9 [1]   -> sum(n = 2)
+//! main.psl:3 [1]   IF (n = 0)
+//! main.psl:7 [1]   RETURN n + sum(n - 1)
+//! main.psl:7 [2]     -> sum(n = 1)
+//! ```
+//!
+//! Each line is indented by its depth as well as numbered with it, so a
+//! recursion reads as a staircase. With `--trace-values` the trace also shows
+//! each assignment and loop variable as it is bound, and each procedure's
+//! arguments and result; without it, only the statements.
+//!
+//! The trace goes to standard error, or to a file, so it never mixes with what
+//! the program prints on standard output. It is the same [`Observer`] hook the
+//! debuggers use, one that never stops the program.
+
+use crate::error::resolve_span;
+use crate::interpreter::{Inspected, Observer, Paused};
+use std::io::Write;
+
+pub struct Tracer {
+    output: Box<dyn Write>,
+    values: bool,
+    /// The `file:line` of the statement last reached at each depth, the top
+    /// level first: where an assignment or a call at that depth happens.
+    places: Vec<String>,
+}
+
+impl Tracer {
+    /// A tracer writing to `output`; `values` adds assignments, arguments and
+    /// results to the statements.
+    pub fn new(output: Box<dyn Write>, values: bool) -> Tracer {
+        Tracer {
+            output,
+            values,
+            places: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, place: usize, depth: usize, text: &str) {
+        let place = self
+            .places
+            .get(place)
+            .or(self.places.last())
+            .map_or("", String::as_str);
+        let _ = writeln!(
+            self.output,
+            "{} [{}] {}{}",
+            place,
+            depth,
+            "  ".repeat(depth),
+            text
+        );
+    }
+}
+
+impl Observer for Tracer {
+    fn before_statement(&mut self, paused: &Paused) -> Option<i32> {
+        paused.flush_output();
+        let (line, _, text) = resolve_span(paused.source(), &paused.span());
+        let file = paused.file().and_then(|file| file.file_name()).map_or_else(
+            || "generated source".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let depth = paused.depth();
+        let place = format!("{}:{}", file, line);
+        self.places.truncate(depth);
+        // A depth with no statement of its own -- a procedure called from a
+        // default parameter value -- is at its caller's place.
+        while self.places.len() < depth {
+            self.places.push(place.clone());
+        }
+        self.places.push(place);
+        self.write(depth, depth, text.trim());
+        None
+    }
+
+    fn follows_values(&self) -> bool {
+        self.values
+    }
+
+    fn assigned(&mut self, depth: usize, name: &str, value: &Inspected) {
+        self.write(depth, depth, &format!("{} = {}", name, value.render()));
+    }
+
+    fn entered(&mut self, depth: usize, name: &str, arguments: &[(String, Inspected)]) {
+        let arguments: Vec<String> = arguments
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value.render()))
+            .collect();
+        // Nothing in the procedure has run yet, so this is the call's place.
+        let caller = depth.saturating_sub(1);
+        self.write(
+            caller,
+            depth,
+            &format!("-> {}({})", name, arguments.join(", ")),
+        );
+    }
+
+    fn returned(&mut self, depth: usize, name: &str, value: &Inspected) {
+        self.write(
+            depth,
+            depth,
+            &format!("<- {} returned {}", name, value.render()),
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! `fpli check`: syntax errors reported from the command line, with an exit
+//! status an editor or a pre-commit hook can act on.
+
+use crate::harness::Invocation;
+
+#[test]
+fn a_clean_file_passes_with_status_zero() {
+    Invocation::new(&["check", "main.psl"])
+        .file("main.psl", "DISPLAY(\"hi\")\n")
+        .run()
+        .success()
+        .stdout_is("Checked 1 file: no errors");
+}
+
+#[test]
+fn nothing_is_run() {
+    let run = Invocation::new(&["check", "main.psl"])
+        .file(
+            "main.psl",
+            "DISPLAY(\"ran\")\nWRITEFILE(\"out.txt\", \"x\")\nname <- INPUT()\n",
+        )
+        .run();
+    run.success().stdout_excludes("ran");
+    assert!(!run.file_exists("out.txt"));
+}
+
+#[test]
+fn every_error_is_reported_with_a_failing_status() {
+    Invocation::new(&["check", "main.psl"])
+        .file("main.psl", "a <- (1\nDISPLAY(a)\nb <- ]\nc <- 3\n")
+        .run()
+        .code(1)
+        .stderr_contains("main.psl: Line 1")
+        .stderr_contains("main.psl: Line 3")
+        .stderr_contains("Checked 1 file: found 2 errors");
+}
+
+#[test]
+fn errors_in_an_imported_file_are_reported() {
+    Invocation::new(&["check", "main.psl"])
+        .file("main.psl", "IMPORT \"lib/util.psl\"\n")
+        .file("lib/util.psl", "x <- )\n")
+        .run()
+        .code(1)
+        .stderr_contains("util.psl: Line 1")
+        .stderr_contains("Checked 2 files: found 1 error");
+}
+
+#[test]
+fn several_files_can_be_checked_at_once() {
+    Invocation::new(&["check", "one.psl", "two.psl"])
+        .file("one.psl", "DISPLAY(1)\n")
+        .file("two.psl", "DISPLAY(2\n")
+        .run()
+        .code(1)
+        .stderr_contains("two.psl: Line 1")
+        .stderr_contains("Checked 2 files: found 1 error");
+}
+
+#[test]
+fn a_missing_file_is_an_error() {
+    Invocation::new(&["check", "absent.psl"])
+        .run()
+        .code(1)
+        .stderr_contains("Could not read absent.psl");
+}
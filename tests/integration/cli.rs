@@ -240,3 +240,10 @@ fn a_unicode_program_round_trips_through_the_terminal() {
     .stdout_contains("héllo wörld — ünïcode")
     .stdout_contains("21");
 }
+
+#[test]
+fn help_lists_the_repl_subcommand() {
+    let (code, stdout, _) = raw(&["--help"]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("repl"), "unexpected help: {:?}", stdout);
+}
@@ -0,0 +1,103 @@
+//! `fpli run --coverage` and `fpli test --coverage`: an LCOV file written once
+//! the program or the tests are over.
+
+use crate::harness::Invocation;
+
+const PROGRAM: &str = "IMPORT \"lib.psl\"
+FOR i FROM 1 TO 3
+{
+    DISPLAY(square(i))
+}
+";
+
+const LIB: &str = "PROCEDURE square(n)
+{
+    RETURN n * n
+}
+PROCEDURE cube(n)
+{
+    RETURN n * n * n
+}
+";
+
+#[test]
+fn a_run_writes_a_record_for_the_program_and_each_import() {
+    let run = Invocation::new(&["run", "--coverage", "lcov.info", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .file("lib.psl", LIB)
+        .run();
+    run.success();
+    assert_eq!(run.stdout, "1\n4\n9\n");
+    let report = run.file("lcov.info");
+    let records: Vec<&str> = report.split_inclusive("end_of_record\n").collect();
+    assert_eq!(records.len(), 2, "{}", report);
+    assert!(
+        records[0].contains("main.psl\nDA:1,1\nDA:2,1\nDA:4,3\nLF:3\nLH:3\n"),
+        "{}",
+        report
+    );
+    assert!(
+        records[1].contains("lib.psl\nDA:1,1\nDA:3,3\nDA:5,1\nDA:7,0\nLF:4\nLH:3\n"),
+        "{}",
+        report
+    );
+    // Absolute paths, which report generators resolve no matter where they run.
+    for record in records {
+        let path = record.lines().find_map(|line| line.strip_prefix("SF:"));
+        assert!(
+            path.is_some_and(|path| std::path::Path::new(path).is_absolute()),
+            "{}",
+            record
+        );
+    }
+}
+
+#[test]
+fn the_report_survives_an_exit() {
+    let run = Invocation::new(&["run", "--coverage", "lcov.info", "main.psl"])
+        .file("main.psl", "DISPLAY(1)\nEXIT(3)\nDISPLAY(2)\n")
+        .run();
+    run.code(3);
+    assert!(
+        run.file("lcov.info")
+            .ends_with("DA:1,1\nDA:2,1\nDA:3,0\nLF:3\nLH:2\nend_of_record\n"),
+        "{}",
+        run.file("lcov.info")
+    );
+}
+
+#[test]
+fn tests_share_one_report() {
+    let run = Invocation::new(&["test", "--coverage", "lcov.info"])
+        .file("lib.psl", LIB)
+        .file(
+            "lib_test.psl",
+            "IMPORT \"lib.psl\"
+PROCEDURE test_square()
+{
+    ASSERTEQUAL(square(3), 9)
+}
+PROCEDURE test_cube()
+{
+    ASSERTEQUAL(cube(2), 8)
+}
+",
+        )
+        .run();
+    run.success();
+    let report = run.file("lcov.info");
+    assert!(
+        report.contains("lib.psl\nDA:1,2\nDA:3,1\nDA:5,2\nDA:7,1\nLF:4\nLH:4\n"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn coverage_and_a_trace_cannot_follow_the_same_run() {
+    Invocation::new(&["run", "--coverage", "lcov.info", "--trace", "main.psl"])
+        .file("main.psl", "DISPLAY(1)\n")
+        .run()
+        .failed()
+        .stderr_contains("cannot be used with");
+}
@@ -0,0 +1,122 @@
+//! `fpli dap`, driven the way an editor drives it: requests framed with
+//! `Content-Length` headers on its standard input, responses and events read back
+//! from its standard output.
+//!
+//! A whole session goes in at once, so a request meant for a stop has to reach
+//! the adapter before the program gets there. The adapter answers requests in
+//! the order they came and holds one that needs the program stopped until it is,
+//! which is what lets these sessions be written down in advance.
+
+use crate::harness::{Invocation, Run};
+
+/// `requests`, numbered in order and framed as the protocol frames them.
+fn framed(requests: &[(&str, &str)]) -> String {
+    requests
+        .iter()
+        .enumerate()
+        .map(|(seq, (command, arguments))| {
+            let body = format!(
+                r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#,
+                seq + 1,
+                command,
+                arguments
+            );
+            format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+        })
+        .collect()
+}
+
+/// The bodies of the messages the adapter wrote, checking each one's length. The
+/// harness has already turned the `\r\n` ending the header into `\n`.
+fn messages(run: &Run) -> Vec<String> {
+    run.stdout
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|message| {
+            let (length, body) = message
+                .split_once("\n\n")
+                .unwrap_or_else(|| panic!("no blank line after the header: {:?}", message));
+            assert_eq!(length.parse::<usize>().unwrap(), body.len(), "{:?}", body);
+            body.to_string()
+        })
+        .collect()
+}
+
+const PROGRAM: &str = "IMPORT \"shapes\"
+side <- 3
+DISPLAY(area(side))
+DISPLAY(\"done\")
+";
+
+const SHAPES: &str = "PROCEDURE area(n)
+{
+    squared <- n * n
+    RETURN squared
+}
+";
+
+#[test]
+fn a_breakpoint_in_an_imported_file_stops_the_program_there() {
+    let run = Invocation::new(&["dap"])
+        .file("main.psl", PROGRAM)
+        .file("shapes.psl", SHAPES)
+        .stdin(&framed(&[
+            ("initialize", r#"{"adapterID":"fpli"}"#),
+            ("launch", r#"{"program":"main.psl"}"#),
+            (
+                "setBreakpoints",
+                r#"{"source":{"path":"shapes.psl"},"breakpoints":[{"line":4}]}"#,
+            ),
+            ("configurationDone", "{}"),
+            ("stackTrace", r#"{"threadId":1}"#),
+            ("evaluate", r#"{"expression":"squared","frameId":0}"#),
+            ("continue", r#"{"threadId":1}"#),
+            ("disconnect", "{}"),
+        ]))
+        .run();
+    run.success();
+    let messages = messages(&run);
+    let find = |needle: &str| {
+        messages
+            .iter()
+            .position(|m| m.contains(needle))
+            .unwrap_or_else(|| panic!("no {} in {:?}", needle, messages))
+    };
+    let stopped = find(r#""reason":"breakpoint""#);
+    let trace = find(r#""command":"stackTrace""#);
+    assert!(stopped < trace);
+    assert!(
+        messages[trace].contains(r#""name":"area","line":4"#),
+        "{}",
+        messages[trace]
+    );
+    assert!(messages[trace].contains(r#""name":"shapes.psl""#));
+    assert!(messages[trace].contains(r#""name":"<main>","line":3"#));
+    assert!(messages[find(r#""command":"evaluate""#)].contains(r#""result":"9""#));
+    assert!(find(r#""output":"9\n""#) > stopped);
+    assert!(find(r#""exitCode":0"#) < find(r#""event":"terminated""#));
+}
+
+#[test]
+fn a_piped_session_runs_the_program_to_the_end() {
+    let run = Invocation::new(&["dap"])
+        .file(
+            "main.psl",
+            "name <- INPUT(\"Who? \")\nDISPLAY(\"Hi \" + name)\nEXIT(3)\n",
+        )
+        .stdin(&framed(&[
+            ("initialize", "{}"),
+            ("launch", r#"{"program":"main.psl","stdin":"Ada"}"#),
+            ("configurationDone", "{}"),
+        ]))
+        .run();
+    run.success();
+    let messages = messages(&run).join("\n");
+    assert!(
+        messages.contains(r#""output":"Who? Ada\n""#),
+        "{}",
+        messages
+    );
+    assert!(messages.contains(r#""output":"Hi Ada\n""#), "{}", messages);
+    assert!(messages.contains(r#""exitCode":3"#), "{}", messages);
+}
@@ -0,0 +1,66 @@
+//! The console debugger, with its commands and the program's input piped in
+//! together on standard input, the way `fpli run --step prog.psl < script` would
+//! take them. What the debugger shows goes to standard error; standard output
+//! holds only what the program printed.
+
+use crate::harness::Invocation;
+
+const PROGRAM: &str = "PROCEDURE double(n)
+{
+    result <- n * 2
+    RETURN result
+}
+name <- INPUT(\"Name? \")
+BREAKPOINT()
+x <- double(3)
+DISPLAY(name)
+DISPLAY(x)
+";
+
+#[test]
+fn a_breakpoint_stops_the_program_and_input_still_reads_stdin() {
+    let run = Invocation::new(&["run", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .stdin("Ann\np name\ns\nv\nw\np n + 1\nc\n")
+        .run();
+    run.success();
+    assert_eq!(run.stdout, "Name? Ann\n6\n");
+    for shown in [
+        "Stopped at main.psl:8, in <main>\n   8 | x <- double(3)\n",
+        "(fpli) \"Ann\"\n",
+        "Stopped at main.psl:3, in double\n   3 |     result <- n * 2\n",
+        "Locals:\n  n = 3\n",
+        "  #0 double at main.psl:3\n  #1 <main> at main.psl:8\n",
+        "(fpli) 4\n",
+    ] {
+        assert!(run.stderr.contains(shown), "{:?} in {}", shown, run.stderr);
+    }
+}
+
+#[test]
+fn step_stops_before_the_first_statement_and_quit_ends_the_run() {
+    let run = Invocation::new(&["run", "--step", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .stdin("n\n\nAnn\nq\n")
+        .run();
+    run.code(1);
+    assert_eq!(run.stdout, "Name? ");
+    assert!(
+        run.stderr.starts_with(
+            "Stopped at main.psl:1, in <main>\n   1 | PROCEDURE double(n)\n(fpli) Stopped at main.psl:6"
+        ),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("   7 | BREAKPOINT()"), "{}", run.stderr);
+}
+
+#[test]
+fn running_out_of_commands_lets_the_program_finish() {
+    let run = Invocation::new(&["run", "--step", "main.psl"])
+        .file("main.psl", "DISPLAY(1)\nDISPLAY(2)\n")
+        .stdin("s\n")
+        .run();
+    run.success();
+    assert_eq!(run.stdout, "1\n2\n");
+}
@@ -0,0 +1,45 @@
+//! `fpli fmt`: files rewritten in place, and `--check` for a CI step that fails
+//! on an unformatted file without touching it.
+
+use crate::harness::Invocation;
+
+const MESSY: &str = "IF (x > 1) {  DISPLAY(x)   // big\n}\n";
+const TIDY: &str = "IF x > 1\n{\n    DISPLAY(x)  // big\n}\n";
+
+#[test]
+fn a_file_is_rewritten_in_place() {
+    let run = Invocation::new(&["fmt", "main.psl"])
+        .file("main.psl", MESSY)
+        .run();
+    run.success().stdout_contains("Formatted main.psl");
+    assert_eq!(run.file("main.psl"), TIDY);
+}
+
+#[test]
+fn check_fails_on_an_unformatted_file_and_leaves_it_alone() {
+    let run = Invocation::new(&["fmt", "--check", "main.psl"])
+        .file("main.psl", MESSY)
+        .run();
+    run.code(1).stdout_contains("main.psl is not formatted");
+    assert_eq!(run.file("main.psl"), MESSY);
+}
+
+#[test]
+fn check_passes_on_a_formatted_file() {
+    Invocation::new(&["fmt", "--check", "main.psl"])
+        .file("main.psl", TIDY)
+        .run()
+        .success()
+        .stdout_is_empty();
+}
+
+#[test]
+fn a_file_that_does_not_parse_is_reported_and_not_written() {
+    let run = Invocation::new(&["fmt", "main.psl", "other.psl"])
+        .file("main.psl", "x <- (1\n")
+        .file("other.psl", MESSY)
+        .run();
+    run.code(1).stderr_contains("main.psl: Line 1");
+    assert_eq!(run.file("main.psl"), "x <- (1\n");
+    assert_eq!(run.file("other.psl"), TIDY);
+}
@@ -0,0 +1,91 @@
+//! `fpli test --golden`: programs run as children and their output compared with
+//! what was recorded, mismatches shown as a diff, and `--bless` to re-record.
+
+use crate::harness::Invocation;
+use std::path::Path;
+
+#[test]
+fn the_crates_own_fixtures_pass() {
+    let programs = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    Invocation::new(&["test", "--golden", &programs.to_string_lossy()])
+        .run()
+        .success()
+        .stdout_contains("golden library ... ok")
+        .stdout_contains("golden stdin_driven ... ok")
+        .stdout_contains(" 0 failed");
+}
+
+#[test]
+fn stdin_args_and_status_come_from_files_beside_the_program() {
+    Invocation::new(&["test", "--golden", "cases"])
+        .file(
+            "cases/greet.psl",
+            "DISPLAY(INPUT() + \" \" + POSITIONALS[1])\nEXIT(3)\n",
+        )
+        .file("cases/greet.stdin", "hello\n")
+        .file("cases/greet.args", "world\n")
+        .file("cases/greet.exit", "3\n")
+        .file("cases/greet.expected", "hello world\n")
+        .run()
+        .success()
+        .stdout_contains("golden greet ... ok");
+}
+
+#[test]
+fn a_mismatch_fails_with_a_diff() {
+    Invocation::new(&["test", "--golden", "cases"])
+        .file("cases/count.psl", "DISPLAY(1)\nDISPLAY(2)\nDISPLAY(3)\n")
+        .file("cases/count.expected", "1\ntwo\n3\n")
+        .run()
+        .code(1)
+        .stdout_contains("golden count ... FAILED")
+        .stdout_contains("--- expected\n+++ actual\n@@ -1,3 +1,3 @@\n 1\n-two\n+2\n 3")
+        .stdout_contains("test result: FAILED. 0 passed; 1 failed");
+}
+
+#[test]
+fn a_program_runs_in_a_copy_and_leaves_its_directory_alone() {
+    let run = Invocation::new(&["test", "--golden", "cases"])
+        .file(
+            "cases/writer.psl",
+            "WRITEFILE(\"out.txt\", \"x\")\nDISPLAY(\"wrote\")\n",
+        )
+        .file("cases/writer.expected", "wrote\n")
+        .run();
+    run.success();
+    assert!(!run.file_exists("cases/out.txt"));
+}
+
+#[test]
+fn bless_records_what_the_programs_did() {
+    let run = Invocation::new(&["test", "--golden", "cases", "--bless"])
+        .file("cases/changed.psl", "DISPLAY(\"new\")\nEXIT(2)\n")
+        .file("cases/changed.expected", "old\n")
+        .file("cases/same.psl", "DISPLAY(1)\n")
+        .file("cases/same.expected", "1\n")
+        .run();
+    run.success()
+        .stdout_contains("golden changed ... blessed")
+        .stdout_contains("golden same ... ok")
+        .stdout_contains("1 passed; 1 blessed; 0 failed");
+    assert_eq!(run.file("cases/changed.expected"), "new\n");
+    assert_eq!(run.file("cases/changed.exit"), "2\n");
+    assert!(!run.file_exists("cases/same.exit"));
+}
+
+#[test]
+fn bless_needs_golden() {
+    Invocation::new(&["test", "--bless"])
+        .run()
+        .failed()
+        .stderr_contains("--golden");
+}
+
+#[test]
+fn a_directory_without_cases_is_an_error() {
+    Invocation::new(&["test", "--golden", "cases"])
+        .file("cases/lib.psl", "x <- 1\n")
+        .run()
+        .code(1)
+        .stderr_contains("No cases found in cases");
+}
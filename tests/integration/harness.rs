@@ -233,6 +233,84 @@ impl Program {
     }
 }
 
+/// `fpli` with arguments of the test's choosing -- a subcommand other than
+/// `run` -- started in a scratch directory holding the files the test wrote.
+///
+/// ```ignore
+/// Invocation::new(&["repl"]).stdin("1 + 1\n").run().success().stdout_is("2");
+/// ```
+pub struct Invocation {
+    args: Vec<String>,
+    files: Vec<(String, String)>,
+    stdin: Option<String>,
+    env_set: Vec<(String, std::ffi::OsString)>,
+    timeout: Duration,
+}
+
+impl Invocation {
+    pub fn new(args: &[&str]) -> Self {
+        Invocation {
+            args: args.iter().map(|a| a.to_string()).collect(),
+            files: Vec::new(),
+            stdin: None,
+            env_set: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Write a file, relative to the scratch directory the command runs in.
+    pub fn file(mut self, relative_path: &str, contents: &str) -> Self {
+        self.files
+            .push((relative_path.to_string(), contents.to_string()));
+        self
+    }
+
+    pub fn stdin(mut self, text: &str) -> Self {
+        self.stdin = Some(text.to_string());
+        self
+    }
+
+    /// Set a variable in the child's environment. A value containing `{dir}` has
+    /// it replaced by the scratch directory, for paths the test cannot know yet.
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env_set
+            .push((name.into(), std::ffi::OsString::from(value)));
+        self
+    }
+
+    pub fn run(self) -> Run {
+        let dir = ScratchDir::new();
+        for (name, contents) in &self.files {
+            write_file(&dir.path().join(name), contents);
+        }
+        let mut command = Command::new(FPLI);
+        command.current_dir(dir.path()).args(&self.args);
+        for (name, value) in &self.env_set {
+            let value = value
+                .to_string_lossy()
+                .replace("{dir}", &dir.path().to_string_lossy());
+            command.env(name, value);
+        }
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let child = command
+            .spawn()
+            .unwrap_or_else(|e| panic!("could not start {}: {}", FPLI, e));
+        let raw = wait_with_timeout(child, self.stdin, self.timeout);
+        Run {
+            dir,
+            command_line: format!("fpli {}", self.args.join(" ")),
+            status: raw.status,
+            timed_out: raw.timed_out,
+            stdout: normalise(&raw.stdout),
+            stderr: normalise(&raw.stderr),
+            elapsed: raw.elapsed,
+        }
+    }
+}
+
 fn write_file(path: &Path, contents: &str) {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent).expect("create parent dir");
@@ -0,0 +1,90 @@
+//! `fpli run` and `fpli test` with limits: `--max-steps`, `--timeout`,
+//! `--max-memory` and the depth and loop limits.
+
+use crate::harness::Invocation;
+
+const RUNAWAY: &str = "DISPLAY(\"started\")
+TRY
+{
+    REPEAT WHILE(TRUE)
+    {
+        x <- 1
+    }
+} CATCH (err)
+{
+    DISPLAY(\"caught\")
+}
+";
+
+#[test]
+fn a_timeout_ends_a_runaway_program_that_tries_to_catch_it() {
+    Invocation::new(&["run", "--timeout", "0.3", "main.psl"])
+        .file("main.psl", RUNAWAY)
+        .run()
+        .code(1)
+        .stdout_is("started\n")
+        .stderr_contains(
+            "Time limit exceeded: the program ran for more than 0.3 seconds (--timeout)",
+        );
+}
+
+#[test]
+fn the_step_budget_and_the_recursion_limit_are_flags() {
+    Invocation::new(&["run", "--max-steps", "50", "main.psl"])
+        .file("main.psl", RUNAWAY)
+        .run()
+        .code(1)
+        .stdout_is("started\n")
+        .stderr_contains("Line 6, Column 9: Step limit exceeded: the program ran 50 statements");
+    Invocation::new(&["run", "--max-depth", "10", "main.psl"])
+        .file(
+            "main.psl",
+            "PROCEDURE down(n)\n{\n    RETURN down(n + 1)\n}\ndown(1)\n",
+        )
+        .run()
+        .code(1)
+        .stderr_contains("maximum recursion depth exceeded (limit: 10, --max-depth)");
+}
+
+#[test]
+fn each_test_has_the_limits_to_itself() {
+    Invocation::new(&["test", "--timeout", "0.5", "--max-memory", "1M"])
+        .file(
+            "grade_test.psl",
+            "PROCEDURE test_quick()
+{
+    ASSERTEQUAL(1 + 1, 2)
+}
+PROCEDURE test_endless()
+{
+    REPEAT WHILE(TRUE)
+    {
+    }
+}
+PROCEDURE test_hungry()
+{
+    text <- \"x\"
+    REPEAT 30 TIMES
+    {
+        text <- text + text
+    }
+}
+",
+        )
+        .run()
+        .code(1)
+        .stdout_contains("test grade_test.psl::test_quick ... ok")
+        .stdout_contains("test grade_test.psl::test_endless ... ERROR")
+        .stdout_contains("Time limit exceeded")
+        .stdout_contains("test grade_test.psl::test_hungry ... ERROR")
+        .stdout_contains("Memory limit exceeded");
+}
+
+#[test]
+fn a_size_that_is_not_one_is_refused() {
+    Invocation::new(&["run", "--max-memory", "lots", "main.psl"])
+        .file("main.psl", "DISPLAY(1)\n")
+        .run()
+        .code(2)
+        .stderr_contains("'lots' is not a size: use a number of bytes, or K, M or G");
+}
@@ -0,0 +1,54 @@
+//! `fpli lint`: warnings rendered like errors, and an exit status CI can act on.
+
+use crate::harness::Invocation;
+
+#[test]
+fn a_clean_file_passes_with_status_zero() {
+    Invocation::new(&["lint", "main.psl"])
+        .file("main.psl", "name <- \"Ada\"\nDISPLAY(name)\n")
+        .run()
+        .success()
+        .stdout_is("Linted 1 file: no warnings");
+}
+
+#[test]
+fn warnings_show_their_place_and_code_and_fail_the_run() {
+    let run = Invocation::new(&["lint", "main.psl"])
+        .file(
+            "main.psl",
+            "PROCEDURE half(n)\n{\n    RETURN n / 2\n    DISPLAY(n)\n}\nDISPLAY(half(1, 2))\n",
+        )
+        .run();
+    run.code(1);
+    assert_eq!(
+        run.stderr,
+        "Warning: main.psl: Line 4, Column 5: This is never run: the RETURN before it always leaves [unreachable-code]
+    DISPLAY(n)
+    ^
+Warning: main.psl: Line 6, Column 9: Procedure 'half' expects 1 argument, but is called with 2 [argument-count]
+    DISPLAY(half(1, 2))
+            ^
+Linted 1 file: found 2 warnings
+"
+    );
+}
+
+#[test]
+fn nothing_is_run() {
+    let run = Invocation::new(&["lint", "main.psl"])
+        .file("main.psl", "WRITEFILE(\"out.txt\", \"x\")\n")
+        .run();
+    run.success();
+    assert!(!run.file_exists("out.txt"));
+}
+
+#[test]
+fn a_file_that_does_not_parse_reports_its_errors() {
+    Invocation::new(&["lint", "good.psl", "bad.psl"])
+        .file("good.psl", "x <- 1\nDISPLAY(x)\n")
+        .file("bad.psl", "a <- (1\n")
+        .run()
+        .code(1)
+        .stderr_contains("bad.psl: Line 1")
+        .stderr_contains("Linted 2 files: found 1 error");
+}
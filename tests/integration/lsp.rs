@@ -0,0 +1,136 @@
+//! `fpli lsp`, driven the way an editor drives it: JSON-RPC messages framed with
+//! `Content-Length` headers, written to its standard input, with the replies read
+//! back from its standard output.
+
+use crate::harness::{Invocation, Run};
+
+/// `messages`, each framed as the protocol frames it.
+fn framed(messages: &[&str]) -> String {
+    messages
+        .iter()
+        .map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body))
+        .collect()
+}
+
+/// The bodies of the messages the server wrote, checking each one's length. The
+/// harness has already turned the `\r\n` ending the header into `\n`; a body
+/// never holds a raw line break, which JSON escapes.
+fn replies(run: &Run) -> Vec<String> {
+    run.stdout
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|message| {
+            let (length, body) = message
+                .split_once("\n\n")
+                .unwrap_or_else(|| panic!("no blank line after the header: {:?}", message));
+            assert_eq!(length.parse::<usize>().unwrap(), body.len(), "{:?}", body);
+            body.to_string()
+        })
+        .collect()
+}
+
+const INITIALIZE: &str =
+    r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}"#;
+const INITIALIZED: &str = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+const SHUTDOWN: &str = r#"{"jsonrpc":"2.0","id":99,"method":"shutdown"}"#;
+const EXIT: &str = r#"{"jsonrpc":"2.0","method":"exit"}"#;
+
+#[test]
+fn a_session_from_initialize_to_exit() {
+    let open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"untitled:main","languageId":"pseudolang","version":1,"text":"x <- (1\nDISPLAY(x)\n"}}}"#;
+    let run = Invocation::new(&["lsp"])
+        .stdin(&framed(&[INITIALIZE, INITIALIZED, open, SHUTDOWN, EXIT]))
+        .run();
+    run.success();
+    let replies = replies(&run);
+    assert_eq!(replies.len(), 3, "{:?}", replies);
+    assert!(replies[0].contains(r#""id":1"#) && replies[0].contains("hoverProvider"));
+    assert!(replies[1].contains("publishDiagnostics"), "{}", replies[1]);
+    assert!(replies[1].contains(r#""line":1"#), "{}", replies[1]);
+    assert_eq!(replies[2], r#"{"jsonrpc":"2.0","id":99,"result":null}"#);
+}
+
+#[test]
+fn exiting_without_a_shutdown_fails() {
+    Invocation::new(&["lsp"])
+        .stdin(&framed(&[INITIALIZE, EXIT]))
+        .run()
+        .code(1);
+}
+
+#[test]
+fn the_editor_going_away_ends_the_server() {
+    Invocation::new(&["lsp"])
+        .stdin(&framed(&[INITIALIZE]))
+        .run()
+        .code(1);
+}
+
+#[test]
+fn hover_completion_and_symbols_answer_requests() {
+    let open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"untitled:main","languageId":"pseudolang","version":1,"text":"PROCEDURE twice(n)\n{\n    RETURN n * 2\n}\nDISPLAY(SQRT(twice(8)))\n"}}}"#;
+    let at = |id: usize, method: &str, line: usize, character: usize| {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"textDocument/{}","params":{{"textDocument":{{"uri":"untitled:main"}},"position":{{"line":{},"character":{}}}}}}}"#,
+            id, method, line, character
+        )
+    };
+    let hover = at(2, "hover", 4, 9);
+    let definition = at(3, "definition", 4, 14);
+    let completion = at(4, "completion", 4, 0);
+    let symbols = r#"{"jsonrpc":"2.0","id":5,"method":"textDocument/documentSymbol","params":{"textDocument":{"uri":"untitled:main"}}}"#;
+    let run = Invocation::new(&["lsp"])
+        .stdin(&framed(&[
+            INITIALIZE,
+            open,
+            &hover,
+            &definition,
+            &completion,
+            symbols,
+            SHUTDOWN,
+            EXIT,
+        ]))
+        .run();
+    run.success();
+    let replies = replies(&run);
+    let reply = |id: usize| {
+        replies
+            .iter()
+            .find(|r| r.contains(&format!(r#""id":{},"#, id)))
+            .unwrap_or_else(|| panic!("no reply to {} in {:?}", id, replies))
+    };
+    assert!(reply(2).contains("square root"), "{}", reply(2));
+    assert!(
+        reply(3).contains(r#""start":{"line":0,"character":10}"#),
+        "{}",
+        reply(3)
+    );
+    assert!(reply(4).contains(r#""label":"twice""#), "{}", reply(4));
+    assert!(reply(4).contains(r#""label":"REPEAT""#), "{}", reply(4));
+    assert!(reply(5).contains(r#""name":"twice""#), "{}", reply(5));
+}
+
+#[test]
+fn going_to_an_import_opens_the_file_it_names() {
+    // An unsaved file has no directory of its own, so its IMPORTs are found in the
+    // working directory, as a run started there would find them.
+    let open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"untitled:main","languageId":"pseudolang","version":1,"text":"IMPORT \"lib\"\nIMPORT \"gone\"\n"}}}"#;
+    let definition = r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{"textDocument":{"uri":"untitled:main"},"position":{"line":0,"character":9}}}"#;
+    let run = Invocation::new(&["lsp"])
+        .file("lib.psl", "PROCEDURE helper()\n{\n}\n")
+        .stdin(&framed(&[INITIALIZE, open, definition, SHUTDOWN, EXIT]))
+        .run();
+    run.success();
+    let replies = replies(&run);
+    assert!(
+        replies[1].contains("Could not find imported file 'gone'"),
+        "{}",
+        replies[1]
+    );
+    assert!(!replies[1].contains("'lib'"), "{}", replies[1]);
+    assert!(
+        replies[2].contains(r#"/lib.psl","range""#),
+        "{}",
+        replies[2]
+    );
+}
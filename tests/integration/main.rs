@@ -11,11 +11,25 @@
 
 mod harness;
 
+mod check;
 mod cli;
+mod coverage;
+mod dap;
+mod debugger;
 mod exit_status;
+mod fmt;
+mod golden;
 mod input;
+mod limits;
+mod lint;
+mod lsp;
 mod multi_file;
 mod process_state;
+mod profile;
 mod programs;
+mod repl;
+mod sandbox;
 mod sleep;
 mod streaming;
+mod testing;
+mod trace;
@@ -239,6 +239,23 @@ fn a_parse_error_in_a_library_names_that_library_not_the_entry() {
         .stderr_contains("broken.psl");
 }
 
+#[test]
+fn an_import_clash_points_at_the_import_in_the_importing_file() {
+    // The IMPORT's span is in the entry file; shown against lib.psl's text it
+    // would land on whatever line of the library sits at the same offset. The
+    // clash is found before lib.psl runs, so none of its DISPLAYs happen either.
+    Program::new("PROCEDURE shared()\n{\n}\nIMPORT \"lib.psl\"\n")
+        .file(
+            "lib.psl",
+            "DISPLAY(1)\nDISPLAY(2)\nDISPLAY(3)\nPROCEDURE shared()\n{\n}\n",
+        )
+        .run()
+        .code(1)
+        .stdout_is_empty()
+        .stderr_contains("Line 4, Column 1: IMPORT \"lib.psl\" clashes")
+        .stderr_contains("    IMPORT \"lib.psl\"\n    ^");
+}
+
 #[test]
 fn a_three_deep_import_chain_resolves_each_link_against_its_own_directory() {
     Program::new(
@@ -0,0 +1,70 @@
+//! `fpli run --profile` and `--count-ops`: the report on standard error once the
+//! program is over, and folded stacks in a file.
+
+use crate::harness::Invocation;
+
+const PROGRAM: &str = "PROCEDURE square(n)
+{
+    RETURN n * n
+}
+FOR i FROM 1 TO 3
+{
+    DISPLAY(square(i))
+}
+";
+
+#[test]
+fn the_report_follows_the_program_output_on_stderr() {
+    let run = Invocation::new(&["run", "--profile", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .run();
+    run.success();
+    assert_eq!(run.stdout, "1\n4\n9\n");
+    assert!(
+        run.stderr.starts_with("Time per procedure:\n"),
+        "{}",
+        run.stderr
+    );
+    assert!(
+        run.stderr.contains("  square (main.psl)      3"),
+        "{}",
+        run.stderr
+    );
+    assert!(
+        run.stderr.contains("  main.psl:3     3\n"),
+        "{}",
+        run.stderr
+    );
+}
+
+#[test]
+fn counted_operations_and_folded_stacks_survive_an_exit() {
+    let run = Invocation::new(&["run", "--count-ops", "--folded", "stacks.txt", "main.psl"])
+        .file("main.psl", &format!("{}EXIT(4)\nDISPLAY(0)\n", PROGRAM))
+        .run();
+    run.code(4);
+    assert!(
+        run.stderr.starts_with(
+            "Operations (statements run) per procedure:
+  Procedure          Calls  Inclusive  Exclusive
+  <main>                 1          9          6
+  square (main.psl)      3          3          3
+"
+        ),
+        "{}",
+        run.stderr
+    );
+    assert_eq!(
+        std::fs::read_to_string(run.path("stacks.txt")).unwrap(),
+        "<main> 6\n<main>;square 3\n"
+    );
+}
+
+#[test]
+fn folded_stacks_need_a_profile() {
+    Invocation::new(&["run", "--folded", "stacks.txt", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .run()
+        .failed()
+        .stderr_contains("--profile");
+}
@@ -1,7 +1,8 @@
 //! Fixture runner: every program under `tests/programs/` is executed and its
 //! output compared against a recorded expectation.
 //!
-//! Adding one needs no Rust: drop two files in `tests/programs/`. Use a
+//! Adding one needs no Rust: drop two files in `tests/programs/`. The same layout
+//! runs without cargo, too: `fpli test --golden tests/programs`. Use a
 //! hand-written test in the other modules when you need stdin, timing, arguments,
 //! a particular working directory, or to inspect the filesystem afterwards.
 //!
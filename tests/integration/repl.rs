@@ -0,0 +1,149 @@
+//! `fpli repl`, fed through a standard input pipe the way a user would type.
+//!
+//! With stdin not a terminal the REPL prints no banner and no prompts, so stdout
+//! holds only what the session itself displayed.
+
+use crate::harness::Invocation;
+
+/// A REPL whose history goes to the scratch directory, never the real user's.
+fn repl(input: &str) -> Invocation {
+    Invocation::new(&["repl"])
+        .env("XDG_DATA_HOME", "{dir}/data")
+        .env("HOME", "{dir}")
+        .stdin(input)
+}
+
+#[test]
+fn state_carries_from_one_input_to_the_next() {
+    repl("x <- 5\ny <- x * 2\nDISPLAY(y)\n")
+        .run()
+        .success()
+        .stdout_is("10");
+}
+
+#[test]
+fn an_expression_shows_its_value() {
+    repl("x <- 4\nx + 1\n[1, 2]\n\"hi\"\n")
+        .run()
+        .success()
+        .stdout_is("5\n[1, 2]\nhi");
+}
+
+#[test]
+fn an_assignment_shows_nothing() {
+    repl("x <- 4\n").run().success().stdout_is_empty();
+}
+
+#[test]
+fn a_procedure_can_be_typed_over_several_lines() {
+    repl("PROCEDURE double(n)\n{\n    RETURN n * 2\n}\ndouble(21)\n")
+        .run()
+        .success()
+        .stdout_is("42");
+}
+
+#[test]
+fn a_try_waits_for_its_catch_on_the_next_line() {
+    repl("TRY { THROW \"boom\" }\nCATCH (e) { DISPLAY(e[\"message\"]) }\n")
+        .run()
+        .success()
+        .stdout_is("boom");
+}
+
+#[test]
+fn an_error_is_reported_and_the_session_carries_on() {
+    repl("x <- 1\ny <- missing + 1\nDISPLAY(x)\n")
+        .run()
+        .success()
+        .stdout_is("1")
+        .stderr_contains("Error:")
+        .stderr_contains("missing");
+}
+
+#[test]
+fn a_parse_error_does_not_end_the_session() {
+    repl("x <- )\n\nDISPLAY(\"still here\")\n")
+        .run()
+        .success()
+        .stdout_is("still here")
+        .stderr_contains("Error:");
+}
+
+#[test]
+fn a_blank_line_runs_an_input_with_a_bracket_left_open() {
+    repl("DISPLAY((1)\n\nDISPLAY(2)\n")
+        .run()
+        .success()
+        .stdout_is("2")
+        .stderr_contains("Error:");
+}
+
+#[test]
+fn quit_leaves_in_the_middle_of_an_input() {
+    repl("DISPLAY((1)\n:quit\nDISPLAY(2)\n")
+        .run()
+        .success()
+        .stdout_is_empty()
+        .stderr_is_empty();
+}
+
+#[test]
+fn reset_throws_away_the_input_waiting_for_more() {
+    repl("x <- 1\nPROCEDURE broken(\n:reset\nDISPLAY(x)\n")
+        .run()
+        .success()
+        .stdout_is("1")
+        .stderr_is_empty();
+}
+
+#[test]
+fn vars_and_procs_list_what_the_session_declared() {
+    repl("b <- 2\na <- 1\nPROCEDURE greet() { DISPLAY(\"hi\") }\n:vars\n:procs\n")
+        .run()
+        .success()
+        .stdout_contains("a, b")
+        .stdout_contains("greet");
+}
+
+#[test]
+fn exit_ends_the_session_with_its_status() {
+    repl("EXIT(3)\nDISPLAY(\"not reached\")\n")
+        .run()
+        .code(3)
+        .stdout_is_empty();
+}
+
+#[test]
+fn quit_ends_the_session_cleanly() {
+    repl(":quit\nDISPLAY(\"not reached\")\n")
+        .run()
+        .success()
+        .stdout_is_empty();
+}
+
+#[test]
+fn input_reads_the_line_after_the_one_that_called_it() {
+    repl("name <- INPUT()\nworld\nDISPLAY(CONCAT(\"hello, \", name))\n")
+        .run()
+        .success()
+        .stdout_is("hello, world");
+}
+
+#[test]
+fn an_unknown_command_is_reported_on_stderr() {
+    repl(":frobnicate\n")
+        .run()
+        .success()
+        .stderr_contains("Unknown command :frobnicate");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn history_is_kept_under_the_data_directory() {
+    let run = repl("x <- 1\nDISPLAY(x)\n").run();
+    run.success();
+    assert_eq!(
+        run.file("data/pseudolang/repl_history"),
+        "x <- 1\nDISPLAY(x)\n"
+    );
+}
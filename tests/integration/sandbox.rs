@@ -0,0 +1,76 @@
+//! `fpli run` and `fpli test` in a sandbox: `--deny-all` and the `--allow-*`
+//! flags, relative paths taken from where `fpli` was started.
+
+use crate::harness::Invocation;
+
+const GRADER: &str = "DISPLAY(READFILE(\"data/input.txt\"))
+WRITEFILE(\"out/result.txt\", \"done\")
+TRY
+{
+    DELETETREE(\"data\")
+} CATCH (err)
+{
+    DISPLAY(err[\"message\"])
+}
+SHELL(\"echo escaped\")
+";
+
+#[test]
+fn a_program_gets_only_what_the_flags_allow() {
+    let run = Invocation::new(&["run", "--allow-read=data", "--allow-write=out", "main.psl"])
+        .file("main.psl", GRADER)
+        .file("data/input.txt", "42")
+        .file("out/.keep", "")
+        .run();
+    run.code(1)
+        .stdout_is("42\nPermission denied: DELETETREE needs --allow-write to change 'data'\n")
+        .stderr_contains("Line 10, Column 1: Permission denied: SHELL needs --allow-exec");
+    assert_eq!(run.file("out/result.txt"), "done");
+    assert!(run.file_exists("data/input.txt"));
+}
+
+#[test]
+fn deny_all_refuses_even_reading() {
+    Invocation::new(&["run", "--deny-all", "main.psl"])
+        .file("main.psl", GRADER)
+        .file("data/input.txt", "42")
+        .run()
+        .code(1)
+        .stdout_is_empty()
+        .stderr_contains("Permission denied: READFILE needs --allow-read to read 'data/input.txt'");
+}
+
+#[test]
+fn without_sandbox_flags_everything_is_allowed() {
+    Invocation::new(&["run", "main.psl"])
+        .file(
+            "main.psl",
+            "WRITEFILE(\"x.txt\", \"1\")\nDISPLAY(READFILE(\"x.txt\"))\n",
+        )
+        .run()
+        .success()
+        .stdout_is("1\n");
+}
+
+#[test]
+fn tests_run_in_the_sandbox_too() {
+    Invocation::new(&["test", "--deny-all", "--allow-env=PSL_SANDBOX_GRADE"])
+        .file(
+            "grade_test.psl",
+            "PROCEDURE test_env()
+{
+    ASSERTEQUAL(GETENV(\"PSL_SANDBOX_GRADE\"), \"A\")
+}
+PROCEDURE test_files()
+{
+    DISPLAY(LISTDIR(\".\"))
+}
+",
+        )
+        .env("PSL_SANDBOX_GRADE", "A")
+        .run()
+        .code(1)
+        .stdout_contains("test grade_test.psl::test_env ... ok")
+        .stdout_contains("test grade_test.psl::test_files ... ERROR")
+        .stdout_contains("Permission denied: LISTDIR needs --allow-read to read '.'");
+}
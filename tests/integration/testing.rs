@@ -0,0 +1,90 @@
+//! `fpli test`: test files found and run, failures shown with their output, and
+//! an exit status CI can act on.
+
+use crate::harness::Invocation;
+
+const PASSING: &str = "\
+PROCEDURE test_adds()
+{
+    ASSERTEQUAL(1 + 1, 2)
+}
+";
+
+const FAILING: &str = "\
+PROCEDURE test_adds()
+{
+    ASSERTEQUAL(1 + 1, 2)
+}
+
+PROCEDURE test_subtracts()
+{
+    DISPLAY(\"subtracting\")
+    ASSERTEQUAL(3 - 1, 1)
+}
+";
+
+#[test]
+fn passing_tests_exit_zero() {
+    Invocation::new(&["test"])
+        .file("maths_test.psl", PASSING)
+        .run()
+        .success()
+        .stdout_contains("test maths_test.psl::test_adds ... ok")
+        .stdout_contains("test result: ok. 1 passed; 0 failed");
+}
+
+#[test]
+fn a_failure_is_shown_with_its_output_and_fails_the_run() {
+    Invocation::new(&["test"])
+        .file("maths_test.psl", FAILING)
+        .run()
+        .code(1)
+        .stdout_contains("test maths_test.psl::test_adds ... ok")
+        .stdout_contains("test maths_test.psl::test_subtracts ... FAILED")
+        .stdout_contains("---- maths_test.psl::test_subtracts ----\nsubtracting\nLine 9")
+        .stdout_contains("Assertion failed: expected 1, got 2")
+        .stdout_contains("test result: FAILED. 1 passed; 1 failed");
+}
+
+#[test]
+fn an_error_that_is_not_an_assertion_is_reported_as_one() {
+    Invocation::new(&["test"])
+        .file(
+            "maths_test.psl",
+            "PROCEDURE test_divides()\n{\n    x <- 1 / 0\n}\n",
+        )
+        .run()
+        .code(1)
+        .stdout_contains("test maths_test.psl::test_divides ... ERROR")
+        .stdout_contains("Division by zero");
+}
+
+#[test]
+fn only_named_files_and_directories_are_searched() {
+    Invocation::new(&["test", "unit"])
+        .file("unit/maths_test.psl", PASSING)
+        .file("slow_test.psl", FAILING)
+        .file("unit/helpers.psl", "x <- )\n")
+        .run()
+        .success()
+        .stdout_excludes("slow_test")
+        .stdout_contains("1 passed; 0 failed");
+}
+
+#[test]
+fn a_test_file_that_does_not_parse_fails_the_run() {
+    Invocation::new(&["test"])
+        .file("broken_test.psl", "PROCEDURE test_x(\n")
+        .run()
+        .code(1)
+        .stdout_contains("test broken_test.psl ... FAILED");
+}
+
+#[test]
+fn finding_no_test_files_is_an_error() {
+    Invocation::new(&["test"])
+        .file("main.psl", PASSING)
+        .run()
+        .code(1)
+        .stderr_contains("No test files found");
+}
@@ -0,0 +1,81 @@
+//! `fpli run --trace`: the trace on standard error or in a file, apart from what
+//! the program prints on standard output.
+
+use crate::harness::Invocation;
+
+const PROGRAM: &str = "PROCEDURE double(n)
+{
+    RETURN n * 2
+}
+FOR i FROM 1 TO 2
+{
+    DISPLAY(double(i))
+}
+";
+
+#[test]
+fn the_trace_goes_to_stderr_and_the_output_to_stdout() {
+    let run = Invocation::new(&["run", "--trace", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .run();
+    run.success();
+    assert_eq!(run.stdout, "2\n4\n");
+    assert_eq!(
+        run.stderr,
+        "main.psl:1 [0] PROCEDURE double(n)
+main.psl:5 [0] FOR i FROM 1 TO 2
+main.psl:7 [0] DISPLAY(double(i))
+main.psl:3 [1]   RETURN n * 2
+main.psl:7 [0] DISPLAY(double(i))
+main.psl:3 [1]   RETURN n * 2
+"
+    );
+}
+
+#[test]
+fn values_show_assignments_calls_and_results() {
+    let run = Invocation::new(&["run", "--trace", "--trace-values", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .run();
+    run.success();
+    for expected in [
+        "main.psl:5 [0] i = 1\n",
+        "main.psl:7 [1]   -> double(n = 1)\n",
+        "main.psl:3 [1]   <- double returned 2\n",
+    ] {
+        assert!(
+            run.stderr.contains(expected),
+            "{:?} in {}",
+            expected,
+            run.stderr
+        );
+    }
+}
+
+#[test]
+fn a_trace_file_is_kept_up_to_the_exit() {
+    let run = Invocation::new(&["run", "--trace", "--trace-file", "trace.txt", "main.psl"])
+        .file("main.psl", "DISPLAY(1)\nEXIT(3)\nDISPLAY(2)\n")
+        .run();
+    run.code(3);
+    assert_eq!(run.stdout, "1\n");
+    assert_eq!(run.stderr, "");
+    assert_eq!(
+        std::fs::read_to_string(run.path("trace.txt")).unwrap(),
+        "main.psl:1 [0] DISPLAY(1)\nmain.psl:2 [0] EXIT(3)\n"
+    );
+}
+
+#[test]
+fn trace_options_need_the_trace_and_it_cannot_be_stepped() {
+    Invocation::new(&["run", "--trace-values", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .run()
+        .failed()
+        .stderr_contains("--trace");
+    Invocation::new(&["run", "--trace", "--step", "main.psl"])
+        .file("main.psl", PROGRAM)
+        .run()
+        .failed()
+        .stderr_contains("cannot be used with");
+}